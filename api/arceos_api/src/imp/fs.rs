@@ -1,10 +1,15 @@
-use alloc::string::String;
-use axerrno::AxResult;
-use axfs::fops::{Directory, File};
+use alloc::{string::String, sync::Arc};
+use axerrno::{AxResult, ax_err};
+use axfs::fops::{Directory, File, PAGE_SIZE, PageCache};
+use axhal::mem::{PhysAddr, VirtAddr, virt_to_phys};
+use axhal::paging::{MappingFlags, PageSize};
+use axmm::MmapFile;
+use memory_addr::VirtAddrRange;
 
 pub use axfs::fops::DirEntry as AxDirEntry;
 pub use axfs::fops::FileAttr as AxFileAttr;
 pub use axfs::fops::FilePerm as AxFilePerm;
+pub use axfs::fops::FileTimes as AxFileTimes;
 pub use axfs::fops::FileType as AxFileType;
 pub use axfs::fops::OpenOptions as AxOpenOptions;
 pub use axio::SeekFrom as AxSeekFrom;
@@ -18,6 +23,27 @@ pub struct AxFileHandle(File);
 /// A handle to an opened directory.
 pub struct AxDirHandle(Directory);
 
+/// The page cache of a file mapped by [`ax_mmap_file`].
+///
+/// The pages are mapped while the address space is locked, so they are
+/// loaded in advance and only pinned here, without any I/O.
+struct MappedFile(Arc<PageCache>);
+
+impl MmapFile for MappedFile {
+    fn get_page(&self, index: u64, write: bool) -> Option<PhysAddr> {
+        let page = self.0.pin_page(index, write)?;
+        Some(virt_to_phys(VirtAddr::from(page.as_ptr() as usize)))
+    }
+
+    fn put_page(&self, index: u64) {
+        self.0.unmap_page(index)
+    }
+
+    fn sync(&self, start: u64, end: u64) -> AxResult {
+        self.0.sync_pages(start, end)
+    }
+}
+
 pub fn ax_open_file(path: &str, opts: &AxOpenOptions) -> AxResult<AxFileHandle> {
     Ok(AxFileHandle(File::open(path, opts)?))
 }
@@ -58,6 +84,14 @@ pub fn ax_file_attr(file: &AxFileHandle) -> AxResult<AxFileAttr> {
     file.0.get_attr()
 }
 
+pub fn ax_set_file_perm(file: &AxFileHandle, perm: AxFilePerm) -> AxResult {
+    file.0.set_perm(perm)
+}
+
+pub fn ax_file_times(file: &AxFileHandle) -> AxResult<AxFileTimes> {
+    file.0.times()
+}
+
 pub fn ax_read_dir(dir: &mut AxDirHandle, dirents: &mut [AxDirEntry]) -> AxResult<usize> {
     dir.0.read_dir(dirents)
 }
@@ -78,6 +112,26 @@ pub fn ax_rename(old: &str, new: &str) -> AxResult {
     axfs::api::rename(old, new)
 }
 
+pub fn ax_symlink(target: &str, link: &str) -> AxResult {
+    axfs::api::symlink(target, link)
+}
+
+pub fn ax_read_link(path: &str) -> AxResult<String> {
+    axfs::api::read_link(path)
+}
+
+pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr> {
+    axfs::api::symlink_metadata(path).map(|m| *m.raw_metadata())
+}
+
+pub fn ax_symlink_times(path: &str) -> AxResult<AxFileTimes> {
+    axfs::api::symlink_metadata(path).map(|m| *m.raw_times())
+}
+
+pub fn ax_set_perm(path: &str, perm: AxFilePerm) -> AxResult {
+    axfs::api::set_permissions(path, perm)
+}
+
 pub fn ax_current_dir() -> AxResult<String> {
     axfs::api::current_dir()
 }
@@ -85,3 +139,83 @@ pub fn ax_current_dir() -> AxResult<String> {
 pub fn ax_set_current_dir(path: &str) -> AxResult {
     axfs::api::set_current_dir(path)
 }
+
+pub fn ax_mount(source: &str, target: &str, fstype: &str) -> AxResult {
+    axfs::api::mount(source, target, fstype)
+}
+
+pub fn ax_bind_mount(source: &str, target: &str) -> AxResult {
+    axfs::api::bind_mount(source, target)
+}
+
+pub fn ax_umount(target: &str, detach: bool) -> AxResult {
+    axfs::api::umount(target, detach)
+}
+
+pub fn ax_sync() -> AxResult {
+    axfs::api::sync()
+}
+
+pub fn ax_mmap_file(
+    file: &AxFileHandle,
+    len: usize,
+    offset: u64,
+    writable: bool,
+    shared: bool,
+) -> AxResult<usize> {
+    if len == 0 || offset % PAGE_SIZE as u64 != 0 {
+        return ax_err!(InvalidInput);
+    }
+    let size = len.next_multiple_of(PAGE_SIZE);
+    let first = offset / PAGE_SIZE as u64;
+    let last = first + (size / PAGE_SIZE) as u64;
+    let cache = file.0.page_cache(writable && shared)?;
+    let release = |end: u64| (first..end).for_each(|index| cache.unmap_page(index));
+
+    // read the pages in advance, not to access the file with the address
+    // space locked
+    for index in first..last {
+        if let Err(e) = cache.map_page(index, false) {
+            release(index);
+            return Err(e);
+        }
+    }
+    let mut flags = MappingFlags::READ;
+    if writable {
+        flags |= MappingFlags::WRITE;
+    }
+    let result = {
+        let mut aspace = axmm::kernel_aspace().lock();
+        let limit = VirtAddrRange::from_start_size(aspace.base(), aspace.size());
+        match aspace.find_free_area(aspace.base(), size, limit, PageSize::Size4K) {
+            Some(start) => {
+                let file = Arc::new(MappedFile(cache.clone()));
+                aspace
+                    .map_file(start, size, flags, file, first, shared, true)
+                    .map(|_| start.as_usize())
+            }
+            None => ax_err!(NoMemory),
+        }
+    };
+    release(last);
+    result
+}
+
+pub fn ax_munmap(addr: usize, len: usize) -> AxResult {
+    let (start, size) = (VirtAddr::from(addr), len.next_multiple_of(PAGE_SIZE));
+    let mut aspace = axmm::kernel_aspace().lock();
+    if len == 0 || !aspace.is_file_mapped(start, size) {
+        return ax_err!(InvalidInput, "not mapped by `ax_mmap_file`");
+    }
+    aspace.unmap(start, size)
+}
+
+pub fn ax_msync(addr: usize, len: usize) -> AxResult {
+    let (start, size) = (VirtAddr::from(addr), len.next_multiple_of(PAGE_SIZE));
+    // written back without the address space locked
+    let files = axmm::kernel_aspace().lock().shared_files(start, size)?;
+    for (file, first, last) in files {
+        file.sync(first, last)?;
+    }
+    Ok(())
+}
@@ -51,4 +51,5 @@ pub use self::task::*;
 pub use self::time::*;
 
 pub use axhal::misc::terminate as ax_terminate;
+pub use axhal::random::fill_bytes as ax_fill_random;
 pub use axio::PollState as AxPollState;
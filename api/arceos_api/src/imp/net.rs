@@ -1,7 +1,8 @@
 use crate::io::AxPollState;
 use axerrno::AxResult;
-use axnet::{UdpSocket, TcpSocket};
+use axnet::{IcmpSocket, RawSocket, UdpSocket, TcpSocket};
 use core::net::{IpAddr, SocketAddr};
+use core::time::Duration;
 
 /// A handle to a TCP socket.
 pub struct AxTcpSocketHandle(TcpSocket);
@@ -9,6 +10,12 @@ pub struct AxTcpSocketHandle(TcpSocket);
 /// A handle to a UDP socket.
 pub struct AxUdpSocketHandle(UdpSocket);
 
+/// A handle to an ICMP socket.
+pub struct AxIcmpSocketHandle(IcmpSocket);
+
+/// A handle to a raw IP socket.
+pub struct AxRawSocketHandle(RawSocket);
+
 ////////////////////////////////////////////////////////////////////////////////
 // TCP socket
 ////////////////////////////////////////////////////////////////////////////////
@@ -30,6 +37,48 @@ pub fn ax_tcp_set_nonblocking(socket: &AxTcpSocketHandle, nonblocking: bool) ->
     Ok(())
 }
 
+pub fn ax_tcp_set_nodelay(socket: &AxTcpSocketHandle, nodelay: bool) -> AxResult {
+    socket.0.set_nodelay(nodelay);
+    Ok(())
+}
+
+pub fn ax_tcp_nodelay(socket: &AxTcpSocketHandle) -> AxResult<bool> {
+    Ok(socket.0.nodelay())
+}
+
+pub fn ax_tcp_set_keep_alive(socket: &AxTcpSocketHandle, interval: Option<Duration>) -> AxResult {
+    socket.0.set_keep_alive(interval)
+}
+
+pub fn ax_tcp_keep_alive(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.keep_alive())
+}
+
+pub fn ax_tcp_set_linger(socket: &AxTcpSocketHandle, linger: Option<Duration>) -> AxResult {
+    socket.0.set_linger(linger);
+    Ok(())
+}
+
+pub fn ax_tcp_linger(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.linger())
+}
+
+pub fn ax_tcp_set_recv_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_recv_timeout(timeout)
+}
+
+pub fn ax_tcp_recv_timeout(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.recv_timeout())
+}
+
+pub fn ax_tcp_set_send_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_send_timeout(timeout)
+}
+
+pub fn ax_tcp_send_timeout(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.send_timeout())
+}
+
 pub fn ax_tcp_connect(socket: &AxTcpSocketHandle, addr: SocketAddr) -> AxResult {
     socket.0.connect(addr)
 }
@@ -38,8 +87,8 @@ pub fn ax_tcp_bind(socket: &AxTcpSocketHandle, addr: SocketAddr) -> AxResult {
     socket.0.bind(addr)
 }
 
-pub fn ax_tcp_listen(socket: &AxTcpSocketHandle, _backlog: usize) -> AxResult {
-    socket.0.listen()
+pub fn ax_tcp_listen(socket: &AxTcpSocketHandle, backlog: usize) -> AxResult {
+    socket.0.listen(backlog)
 }
 
 pub fn ax_tcp_accept(socket: &AxTcpSocketHandle) -> AxResult<(AxTcpSocketHandle, SocketAddr)> {
@@ -85,6 +134,22 @@ pub fn ax_udp_set_nonblocking(socket: &AxUdpSocketHandle, nonblocking: bool) ->
     Ok(())
 }
 
+pub fn ax_udp_set_recv_timeout(socket: &AxUdpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_recv_timeout(timeout)
+}
+
+pub fn ax_udp_recv_timeout(socket: &AxUdpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.recv_timeout())
+}
+
+pub fn ax_udp_set_send_timeout(socket: &AxUdpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_send_timeout(timeout)
+}
+
+pub fn ax_udp_send_timeout(socket: &AxUdpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.send_timeout())
+}
+
 pub fn ax_udp_bind(socket: &AxUdpSocketHandle, addr: SocketAddr) -> AxResult {
     socket.0.bind(addr)
 }
@@ -117,6 +182,64 @@ pub fn ax_udp_poll(socket: &AxUdpSocketHandle) -> AxResult<AxPollState> {
     socket.0.poll()
 }
 
+////////////////////////////////////////////////////////////////////////////////
+// ICMP socket
+////////////////////////////////////////////////////////////////////////////////
+
+pub fn ax_icmp_socket() -> AxIcmpSocketHandle {
+    AxIcmpSocketHandle(IcmpSocket::new())
+}
+
+pub fn ax_icmp_ident(socket: &AxIcmpSocketHandle) -> AxResult<u16> {
+    socket.0.ident()
+}
+
+pub fn ax_icmp_set_nonblocking(socket: &AxIcmpSocketHandle, nonblocking: bool) -> AxResult {
+    socket.0.set_nonblocking(nonblocking);
+    Ok(())
+}
+
+pub fn ax_icmp_bind(socket: &AxIcmpSocketHandle, ident: u16) -> AxResult {
+    socket.0.bind(ident)
+}
+
+pub fn ax_icmp_send_to(socket: &AxIcmpSocketHandle, buf: &[u8], addr: IpAddr) -> AxResult<usize> {
+    socket.0.send_to(buf, addr)
+}
+
+pub fn ax_icmp_recv_from(socket: &AxIcmpSocketHandle, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+    socket.0.recv_from(buf)
+}
+
+pub fn ax_icmp_poll(socket: &AxIcmpSocketHandle) -> AxResult<AxPollState> {
+    socket.0.poll()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Raw socket
+////////////////////////////////////////////////////////////////////////////////
+
+pub fn ax_raw_socket(protocol: u8) -> AxRawSocketHandle {
+    AxRawSocketHandle(RawSocket::new(protocol))
+}
+
+pub fn ax_raw_set_nonblocking(socket: &AxRawSocketHandle, nonblocking: bool) -> AxResult {
+    socket.0.set_nonblocking(nonblocking);
+    Ok(())
+}
+
+pub fn ax_raw_send(socket: &AxRawSocketHandle, buf: &[u8]) -> AxResult<usize> {
+    socket.0.send(buf)
+}
+
+pub fn ax_raw_recv_from(socket: &AxRawSocketHandle, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+    socket.0.recv_from(buf)
+}
+
+pub fn ax_raw_poll(socket: &AxRawSocketHandle) -> AxResult<AxPollState> {
+    socket.0.poll()
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Miscellaneous
 ////////////////////////////////////////////////////////////////////////////////
@@ -129,3 +252,27 @@ pub fn ax_poll_interfaces() -> AxResult {
     axnet::poll_interfaces();
     Ok(())
 }
+
+#[cfg(feature = "net-capture")]
+pub fn ax_net_capture_start(snaplen: usize, ring_size: usize, to_console: bool) {
+    let mode = if to_console {
+        axnet::CaptureMode::Console
+    } else {
+        axnet::CaptureMode::Ring
+    };
+    axnet::start_capture(axnet::CaptureConfig {
+        mode,
+        snaplen,
+        ring_size,
+    });
+}
+
+#[cfg(feature = "net-capture")]
+pub fn ax_net_capture_stop() {
+    axnet::stop_capture();
+}
+
+#[cfg(feature = "net-capture")]
+pub fn ax_net_capture_pcap() -> alloc::vec::Vec<u8> {
+    axnet::capture_to_pcap()
+}
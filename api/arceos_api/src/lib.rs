@@ -32,6 +32,9 @@ pub mod sys {
     define_api! {
         /// Shutdown the whole system and all CPUs.
         pub fn ax_terminate() -> !;
+        /// Fills the buffer with cryptographically secure random bytes from
+        /// the kernel random number generator.
+        pub fn ax_fill_random(buf: &mut [u8]);
     }
 }
 
@@ -190,6 +193,7 @@ pub mod fs {
         pub type AxFileAttr;
         pub type AxFileType;
         pub type AxFilePerm;
+        pub type AxFileTimes;
         pub type AxDirEntry;
         pub type AxSeekFrom;
         #[cfg(feature = "myfs")]
@@ -236,6 +240,10 @@ pub mod fs {
         pub fn ax_seek_file(file: &mut AxFileHandle, pos: AxSeekFrom) -> AxResult<u64>;
         /// Returns attributes of the file.
         pub fn ax_file_attr(file: &AxFileHandle) -> AxResult<AxFileAttr>;
+        /// Changes the permissions of the file.
+        pub fn ax_set_file_perm(file: &AxFileHandle, perm: AxFilePerm) -> AxResult;
+        /// Returns the access, modification and change times of the file.
+        pub fn ax_file_times(file: &AxFileHandle) -> AxResult<AxFileTimes>;
 
         /// Reads directory entries starts from the current position into the
         /// given buffer, returns the number of entries read.
@@ -255,23 +263,71 @@ pub mod fs {
         ///
         /// It will delete the original file if `old` already exists.
         pub fn ax_rename(old: &str, new: &str) -> AxResult;
+        /// Creates a symbolic link `link` pointing to `target`.
+        pub fn ax_symlink(target: &str, link: &str) -> AxResult;
+        /// Returns the target of the symbolic link at `path`.
+        pub fn ax_read_link(path: &str) -> AxResult<alloc::string::String>;
+        /// Returns attributes of the file at `path`, without following the
+        /// symbolic link at the last component.
+        pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr>;
+        /// Returns the times of the file at `path`, without following the
+        /// symbolic link at the last component.
+        pub fn ax_symlink_times(path: &str) -> AxResult<AxFileTimes>;
+        /// Changes the permissions of the file or directory at `path`.
+        pub fn ax_set_perm(path: &str, perm: AxFilePerm) -> AxResult;
 
         /// Returns the current working directory.
         pub fn ax_current_dir() -> AxResult<alloc::string::String>;
         /// Changes the current working directory to the specified path.
         pub fn ax_set_current_dir(path: &str) -> AxResult;
+
+        /// Mounts a new filesystem of type `fstype` on the directory `target`.
+        pub fn ax_mount(source: &str, target: &str, fstype: &str) -> AxResult;
+        /// Mounts the directory `source` on the directory `target`.
+        pub fn ax_bind_mount(source: &str, target: &str) -> AxResult;
+        /// Unmounts the filesystem mounted on `target`.
+        ///
+        /// If `detach` is set, a busy filesystem is detached from the directory
+        /// tree and cleaned up later, instead of returning an error.
+        pub fn ax_umount(target: &str, detach: bool) -> AxResult;
+        /// Writes all cached pages of files and cached blocks back to the block
+        /// devices.
+        pub fn ax_sync() -> AxResult;
+
+        /// Maps `len` bytes of the file from `offset` into memory, returns the
+        /// starting address.
+        ///
+        /// `offset` must be a multiple of the page size. Changes to a `shared`
+        /// mapping are written back to the file by [`ax_msync`], while those to
+        /// a private mapping are not.
+        pub fn ax_mmap_file(
+            file: &AxFileHandle,
+            len: usize,
+            offset: u64,
+            writable: bool,
+            shared: bool,
+        ) -> AxResult<usize>;
+        /// Unmaps the memory mapped by [`ax_mmap_file`], the whole range must be
+        /// mapped by it.
+        pub fn ax_munmap(addr: usize, len: usize) -> AxResult;
+        /// Writes the changes to the shared file mappings in the memory range
+        /// back to the files.
+        pub fn ax_msync(addr: usize, len: usize) -> AxResult;
     }
 }
 
-/// Networking primitives for TCP/UDP communication.
+/// Networking primitives for TCP/UDP/ICMP communication.
 pub mod net {
     use crate::{AxResult, io::AxPollState};
     use core::net::{IpAddr, SocketAddr};
+    use core::time::Duration;
 
     define_api_type! {
         @cfg "net";
         pub type AxTcpSocketHandle;
         pub type AxUdpSocketHandle;
+        pub type AxIcmpSocketHandle;
+        pub type AxRawSocketHandle;
     }
 
     define_api! {
@@ -287,13 +343,37 @@ pub mod net {
         pub fn ax_tcp_peer_addr(socket: &AxTcpSocketHandle) -> AxResult<SocketAddr>;
         /// Moves this TCP socket into or out of nonblocking mode.
         pub fn ax_tcp_set_nonblocking(socket: &AxTcpSocketHandle, nonblocking: bool) -> AxResult;
+        /// Enables or disables the Nagle's algorithm (`TCP_NODELAY`).
+        pub fn ax_tcp_set_nodelay(socket: &AxTcpSocketHandle, nodelay: bool) -> AxResult;
+        /// Returns whether the Nagle's algorithm is disabled.
+        pub fn ax_tcp_nodelay(socket: &AxTcpSocketHandle) -> AxResult<bool>;
+        /// Enables keep-alive with the given interval, or disables it if `None`.
+        pub fn ax_tcp_set_keep_alive(socket: &AxTcpSocketHandle, interval: Option<Duration>) -> AxResult;
+        /// Returns the keep-alive interval, or `None` if keep-alive is disabled.
+        pub fn ax_tcp_keep_alive(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>>;
+        /// Sets the linger timeout (`SO_LINGER`) used when the socket is shut down.
+        pub fn ax_tcp_set_linger(socket: &AxTcpSocketHandle, linger: Option<Duration>) -> AxResult;
+        /// Returns the linger timeout of the TCP socket.
+        pub fn ax_tcp_linger(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>>;
+        /// Sets the timeout of blocking receiving operations, `None` means
+        /// blocking indefinitely.
+        pub fn ax_tcp_set_recv_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult;
+        /// Returns the timeout of receiving operations on the TCP socket.
+        pub fn ax_tcp_recv_timeout(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>>;
+        /// Sets the timeout of blocking sending operations, `None` means
+        /// blocking indefinitely.
+        pub fn ax_tcp_set_send_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult;
+        /// Returns the timeout of sending operations on the TCP socket.
+        pub fn ax_tcp_send_timeout(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>>;
 
         /// Connects the TCP socket to the given address and port.
         pub fn ax_tcp_connect(handle: &AxTcpSocketHandle, addr: SocketAddr) -> AxResult;
         /// Binds the TCP socket to the given address and port.
         pub fn ax_tcp_bind(socket: &AxTcpSocketHandle, addr: SocketAddr) -> AxResult;
         /// Starts listening on the bound address and port.
-        pub fn ax_tcp_listen(socket: &AxTcpSocketHandle, _backlog: usize) -> AxResult;
+        ///
+        /// At most `backlog` established connections can wait to be accepted.
+        pub fn ax_tcp_listen(socket: &AxTcpSocketHandle, backlog: usize) -> AxResult;
         /// Accepts a new connection on the TCP socket.
         ///
         /// This function will block the calling thread until a new TCP connection
@@ -320,6 +400,16 @@ pub mod net {
         pub fn ax_udp_peer_addr(socket: &AxUdpSocketHandle) -> AxResult<SocketAddr>;
         /// Moves this UDP socket into or out of nonblocking mode.
         pub fn ax_udp_set_nonblocking(socket: &AxUdpSocketHandle, nonblocking: bool) -> AxResult;
+        /// Sets the timeout of blocking receiving operations, `None` means
+        /// blocking indefinitely.
+        pub fn ax_udp_set_recv_timeout(socket: &AxUdpSocketHandle, timeout: Option<Duration>) -> AxResult;
+        /// Returns the timeout of receiving operations on the UDP socket.
+        pub fn ax_udp_recv_timeout(socket: &AxUdpSocketHandle) -> AxResult<Option<Duration>>;
+        /// Sets the timeout of blocking sending operations, `None` means
+        /// blocking indefinitely.
+        pub fn ax_udp_set_send_timeout(socket: &AxUdpSocketHandle, timeout: Option<Duration>) -> AxResult;
+        /// Returns the timeout of sending operations on the UDP socket.
+        pub fn ax_udp_send_timeout(socket: &AxUdpSocketHandle) -> AxResult<Option<Duration>>;
 
         /// Binds the UDP socket to the given address and port.
         pub fn ax_udp_bind(socket: &AxUdpSocketHandle, addr: SocketAddr) -> AxResult;
@@ -346,6 +436,42 @@ pub mod net {
         /// Returns whether the UDP socket is readable or writable.
         pub fn ax_udp_poll(socket: &AxUdpSocketHandle) -> AxResult<AxPollState>;
 
+        // ICMP socket
+
+        /// Creates a new ICMP socket.
+        pub fn ax_icmp_socket() -> AxIcmpSocketHandle;
+        /// Returns the ICMP identifier the socket is bound to.
+        pub fn ax_icmp_ident(socket: &AxIcmpSocketHandle) -> AxResult<u16>;
+        /// Moves this ICMP socket into or out of nonblocking mode.
+        pub fn ax_icmp_set_nonblocking(socket: &AxIcmpSocketHandle, nonblocking: bool) -> AxResult;
+
+        /// Binds the ICMP socket to the given identifier, 0 means generating
+        /// one automatically.
+        pub fn ax_icmp_bind(socket: &AxIcmpSocketHandle, ident: u16) -> AxResult;
+        /// Sends an ICMP message to the given address. The identifier of echo
+        /// requests is replaced by the one the socket is bound to.
+        pub fn ax_icmp_send_to(socket: &AxIcmpSocketHandle, buf: &[u8], addr: IpAddr) -> AxResult<usize>;
+        /// Receives a single ICMP message on the socket. On success, returns
+        /// the number of bytes read and the origin.
+        pub fn ax_icmp_recv_from(socket: &AxIcmpSocketHandle, buf: &mut [u8]) -> AxResult<(usize, IpAddr)>;
+        /// Returns whether the ICMP socket is readable or writable.
+        pub fn ax_icmp_poll(socket: &AxIcmpSocketHandle) -> AxResult<AxPollState>;
+
+        // Raw socket
+
+        /// Creates a new raw socket for the given IP protocol number.
+        pub fn ax_raw_socket(protocol: u8) -> AxRawSocketHandle;
+        /// Moves this raw socket into or out of nonblocking mode.
+        pub fn ax_raw_set_nonblocking(socket: &AxRawSocketHandle, nonblocking: bool) -> AxResult;
+
+        /// Sends an IP packet, including the IPv4 header, on the raw socket.
+        pub fn ax_raw_send(socket: &AxRawSocketHandle, buf: &[u8]) -> AxResult<usize>;
+        /// Receives a single IP packet, including the IPv4 header, on the raw
+        /// socket. On success, returns the number of bytes read and the origin.
+        pub fn ax_raw_recv_from(socket: &AxRawSocketHandle, buf: &mut [u8]) -> AxResult<(usize, IpAddr)>;
+        /// Returns whether the raw socket is readable or writable.
+        pub fn ax_raw_poll(socket: &AxRawSocketHandle) -> AxResult<AxPollState>;
+
         // Miscellaneous
 
         /// Resolves the host name to a list of IP addresses.
@@ -356,6 +482,22 @@ pub mod net {
         /// packets to the NIC.
         pub fn ax_poll_interfaces() -> AxResult;
     }
+
+    define_api! {
+        @cfg "net-capture";
+
+        /// Starts capturing the frames received and transmitted by the NIC.
+        ///
+        /// At most `snaplen` bytes are captured from each frame. If
+        /// `to_console` is true, the frames are streamed over the console as
+        /// hex encoded pcap data, otherwise they are kept in a ring buffer of
+        /// `ring_size` bytes.
+        pub fn ax_net_capture_start(snaplen: usize, ring_size: usize, to_console: bool);
+        /// Stops capturing frames.
+        pub fn ax_net_capture_stop();
+        /// Returns the frames in the capture ring buffer as a pcap file.
+        pub fn ax_net_capture_pcap() -> alloc::vec::Vec<u8>;
+    }
 }
 
 /// Graphics manipulation operations.
@@ -41,9 +41,12 @@ typedef struct {{
             "ssize_t",
             "off_t",
             "mode_t",
+            "uid_t",
+            "gid_t",
             "sock.*",
             "fd_set",
             "timeval",
+            "linger",
             "pthread_t",
             "pthread_attr_t",
             "pthread_mutex_t",
@@ -53,6 +56,7 @@ typedef struct {{
             "clockid_t",
             "rlimit",
             "aibuf",
+            "flock",
         ];
         let allow_vars = [
             "CLOCK_.*",
@@ -60,6 +64,10 @@ typedef struct {{
             "AF_.*",
             "SOCK_.*",
             "IPPROTO_.*",
+            "SOL_.*",
+            "SO_.*",
+            "TCP_.*",
+            "SHUT_.*",
             "FD_.*",
             "F_.*",
             "_SC_.*",
@@ -68,6 +76,12 @@ typedef struct {{
             "RLIMIT_.*",
             "EAI_.*",
             "MAXADDRS",
+            "MS_.*",
+            "MNT_.*",
+            "[RWX]_OK",
+            "UTIME_.*",
+            "AT_SYMLINK_NOFOLLOW",
+            "LOCK_.*",
         ];
 
         #[derive(Debug)]
@@ -1,9 +1,12 @@
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_long};
+use core::time::Duration;
 
-use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use axerrno::{AxError, LinuxError, LinuxResult};
+use axfs::api::AccessMode;
+use axfs::fops::{FilePerm, FileTimes, LockKind, OpenOptions, TimeUpdate};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
 
@@ -57,21 +60,10 @@ impl FileLike for File {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.perm().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        let inner = self.inner.lock();
+        let owner = inner.owner()?;
+        let attr = inner.get_attr()?;
+        Ok(attr_to_stat(&attr, owner.uid, owner.gid, &inner.times()?))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -90,8 +82,34 @@ impl FileLike for File {
     }
 }
 
-/// Convert open flags to [`OpenOptions`].
-fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
+/// Convert file attributes, the owner and the times to [`ctypes::stat`].
+fn attr_to_stat(
+    metadata: &axfs::fops::FileAttr,
+    uid: u32,
+    gid: u32,
+    times: &FileTimes,
+) -> ctypes::stat {
+    let ty = metadata.file_type() as u8;
+    let perm = metadata.perm().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    ctypes::stat {
+        st_ino: 1,
+        st_nlink: 1,
+        st_mode,
+        st_uid: uid,
+        st_gid: gid,
+        st_size: metadata.size() as _,
+        st_blocks: metadata.blocks() as _,
+        st_blksize: 512,
+        st_atim: times.accessed.into(),
+        st_mtim: times.modified.into(),
+        st_ctim: times.changed.into(),
+        ..Default::default()
+    }
+}
+
+/// Convert open flags and the mode of new files to [`OpenOptions`].
+fn flags_to_options(flags: c_int, mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
     let mut options = OpenOptions::new();
     match flags & 0b11 {
@@ -118,6 +136,7 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     if flags & ctypes::O_DIRECTORY != 0 {
         options.directory(true);
     }
+    options.mode(mode);
     options
 }
 
@@ -130,8 +149,10 @@ pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) ->
     debug!("sys_open <= {:?} {:#o} {:#o}", filename, flags, mode);
     syscall_body!(sys_open, {
         add_file_or_directory_fd(
-            axfs::fops::File::open,
-            axfs::fops::Directory::open_dir,
+            |path, options| axfs::fops::File::open(path, options).map_err(|e| path_err(path, e)),
+            |path, options| {
+                axfs::fops::Directory::open_dir(path, options).map_err(|e| path_err(path, e))
+            },
             filename?,
             &flags_to_options(flags, mode),
         )
@@ -165,9 +186,20 @@ pub fn sys_openat(
     }
 
     match Directory::from_fd(dirfd).and_then(|dir| {
+        let loop_err = |e| path_err(&format!("{}/{}", dir.path, filename), e);
         add_file_or_directory_fd(
-            |filename, options| dir.inner.lock().open_file_at(filename, options),
-            |filename, options| dir.inner.lock().open_dir_at(filename, options),
+            |filename, options| {
+                dir.inner
+                    .lock()
+                    .open_file_at(filename, options)
+                    .map_err(loop_err)
+            },
+            |filename, options| {
+                dir.inner
+                    .lock()
+                    .open_dir_at(filename, options)
+                    .map_err(loop_err)
+            },
             filename,
             &flags_to_options(flags, mode),
         )
@@ -239,8 +271,9 @@ pub unsafe fn sys_stat(path: *const c_char, buf: *mut ctypes::stat) -> c_int {
         }
         let mut options = OpenOptions::new();
         options.read(true);
-        let file = axfs::fops::File::open(path?, &options)?;
-        let st = File::new(file, path?.to_string()).stat()?;
+        let path = path?;
+        let file = axfs::fops::File::open(path, &options).map_err(|e| path_err(path, e))?;
+        let st = File::new(file, path.to_string()).stat()?;
         unsafe { *buf = st };
         Ok(0)
     })
@@ -271,11 +304,380 @@ pub unsafe fn sys_lstat(path: *const c_char, buf: *mut ctypes::stat) -> ctypes::
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        unsafe { *buf = Default::default() }; // TODO
+        let path = path?;
+        let md = axfs::api::symlink_metadata(path).map_err(|e| path_err(path, e))?;
+        let st = attr_to_stat(md.raw_metadata(), md.uid(), md.gid(), md.raw_times());
+        unsafe { *buf = st };
+        Ok(0)
+    })
+}
+
+/// Maps the errors of resolving `path`, which are `ELOOP` if there are too
+/// many levels of symbolic links.
+fn path_err(path: &str, e: AxError) -> LinuxError {
+    match e {
+        AxError::InvalidData if axfs::api::is_symlink_loop(path) => LinuxError::ELOOP,
+        e => e.into(),
+    }
+}
+
+/// Maps the errors of changing permissions and owners, which are `EPERM` if
+/// it's not allowed.
+fn change_err(e: AxError) -> LinuxError {
+    match e {
+        AxError::PermissionDenied | AxError::Unsupported => LinuxError::EPERM,
+        e => e.into(),
+    }
+}
+
+/// Maps the errors of changing permissions and owners of the file `path`,
+/// see [`change_err`] and [`path_err`].
+fn change_path_err(path: &str, e: AxError) -> LinuxError {
+    match e {
+        AxError::InvalidData => path_err(path, e),
+        e => change_err(e),
+    }
+}
+
+/// Check whether the current user can access the file `path` with `mode`,
+/// which is `F_OK` or any of `R_OK`, `W_OK` and `X_OK`.
+///
+/// Return 0 if the access is allowed.
+pub fn sys_access(path: *const c_char, mode: c_int) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_access <= {:?} {:#o}", path, mode);
+    syscall_body!(sys_access, {
+        let mode = mode as u32;
+        if mode & !(ctypes::R_OK | ctypes::W_OK | ctypes::X_OK) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut access = AccessMode::empty();
+        if mode & ctypes::R_OK != 0 {
+            access |= AccessMode::READ;
+        }
+        if mode & ctypes::W_OK != 0 {
+            access |= AccessMode::WRITE;
+        }
+        if mode & ctypes::X_OK != 0 {
+            access |= AccessMode::EXECUTE;
+        }
+        let path = path?;
+        axfs::api::access(path, access).map_err(|e| path_err(path, e))?;
+        Ok(0)
+    })
+}
+
+/// Change the permissions of the file `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_chmod(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_chmod <= {:?} {:#o}", path, mode);
+    syscall_body!(sys_chmod, {
+        // diff: the set-user-ID, set-group-ID and sticky bits are ignored, and
+        // `EPERM` instead of `EACCES` if a directory in the path can't be searched
+        let perm = FilePerm::from_bits_truncate(mode as u16);
+        let path = path?;
+        axfs::api::set_permissions(path, perm).map_err(|e| change_path_err(path, e))?;
+        Ok(0)
+    })
+}
+
+/// Change the permissions of the file or directory opened as `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    debug!("sys_fchmod <= {} {:#o}", fd, mode);
+    syscall_body!(sys_fchmod, {
+        // diff: the set-user-ID, set-group-ID and sticky bits are ignored
+        let perm = FilePerm::from_bits_truncate(mode as u16);
+        let f = get_file_like(fd)?.into_any();
+        let res = match f.downcast::<File>() {
+            Ok(file) => file.inner.lock().set_perm(perm),
+            // diff: `EINVAL` for sockets and pipes
+            Err(f) => Directory::from_any(f)?.inner.lock().set_perm(perm),
+        };
+        res.map_err(change_err)?;
+        Ok(0)
+    })
+}
+
+/// Change the owner and group of the file `path`, `-1` leaves it unchanged.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_chown(path: *const c_char, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_chown <= {:?} {} {}", path, owner, group);
+    syscall_body!(sys_chown, {
+        let uid = (owner != ctypes::uid_t::MAX).then_some(owner);
+        let gid = (group != ctypes::gid_t::MAX).then_some(group);
+        let path = path?;
+        axfs::api::chown(path, uid, gid).map_err(|e| change_path_err(path, e))?;
+        Ok(0)
+    })
+}
+
+/// Convert the new access and modification times of `utimensat` and
+/// `futimens`, which are the current time if `times` is null.
+unsafe fn times_to_updates(times: *const ctypes::timespec) -> LinuxResult<[TimeUpdate; 2]> {
+    if times.is_null() {
+        return Ok([TimeUpdate::Now; 2]);
+    }
+    let times = unsafe { core::slice::from_raw_parts(times, 2) };
+    let mut updates = [TimeUpdate::Omit; 2];
+    for (update, ts) in updates.iter_mut().zip(times) {
+        *update = match ts.tv_nsec {
+            n if n == ctypes::UTIME_NOW as c_long => TimeUpdate::Now,
+            n if n == ctypes::UTIME_OMIT as c_long => TimeUpdate::Omit,
+            n if !(0..1_000_000_000).contains(&n) || ts.tv_sec < 0 => {
+                return Err(LinuxError::EINVAL);
+            }
+            _ => TimeUpdate::At(Duration::from(*ts)),
+        };
+    }
+    Ok(updates)
+}
+
+/// Change the access and modification times of the file `path` relative to
+/// the directory `dirfd`, to the current time if `times` is null.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub unsafe fn sys_utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_utimensat <= {} {:?} {:#x}", dirfd, path, flags);
+    syscall_body!(sys_utimensat, {
+        // diff: a null `path` to change the times of `dirfd` is not supported
+        let path = path?;
+        if flags as u32 & !ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let follow = flags as u32 & ctypes::AT_SYMLINK_NOFOLLOW == 0;
+        let [accessed, modified] = unsafe { times_to_updates(times)? };
+        let (res, path) = if path.starts_with('/') || dirfd == AT_FDCWD as _ {
+            let res = if follow {
+                axfs::api::set_times(path, accessed, modified)
+            } else {
+                axfs::api::set_symlink_times(path, accessed, modified)
+            };
+            (res, path.into())
+        } else {
+            let dir = Directory::from_fd(dirfd)?;
+            let res = dir
+                .inner
+                .lock()
+                .set_times_at(path, follow, accessed, modified);
+            (res, format!("{}/{}", dir.path, path))
+        };
+        // diff: `EPERM` instead of `EACCES` if a directory in the path can't
+        // be searched, or the times can't be set to the current time
+        res.map_err(|e| change_path_err(&path, e))?;
+        Ok(0)
+    })
+}
+
+/// Change the access and modification times of the file or directory opened
+/// as `fd`, to the current time if `times` is null.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub unsafe fn sys_futimens(fd: c_int, times: *const ctypes::timespec) -> c_int {
+    debug!("sys_futimens <= {}", fd);
+    syscall_body!(sys_futimens, {
+        let [accessed, modified] = unsafe { times_to_updates(times)? };
+        let f = get_file_like(fd)?.into_any();
+        let res = match f.downcast::<File>() {
+            Ok(file) => file.inner.lock().set_times(accessed, modified),
+            // diff: `EINVAL` for sockets and pipes
+            Err(f) => Directory::from_any(f)?
+                .inner
+                .lock()
+                .set_times(accessed, modified),
+        };
+        res.map_err(change_err)?;
+        Ok(0)
+    })
+}
+
+/// Maps the errors of locking, which is `EDEADLK` if waiting for the lock
+/// would block forever, and `EBADF` if the file is not opened for the lock.
+fn lock_err(e: AxError, wait: bool) -> LinuxError {
+    match e {
+        AxError::WouldBlock if wait => LinuxError::EDEADLK,
+        AxError::PermissionDenied => LinuxError::EBADF,
+        e => e.into(),
+    }
+}
+
+/// Apply or remove a whole-file advisory lock on the file opened as `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_flock(fd: c_int, operation: c_int) -> c_int {
+    debug!("sys_flock <= {} {:#x}", fd, operation);
+    syscall_body!(sys_flock, {
+        let operation = operation as u32;
+        let wait = operation & ctypes::LOCK_NB == 0;
+        let kind = match operation & !ctypes::LOCK_NB {
+            ctypes::LOCK_SH => Some(LockKind::Shared),
+            ctypes::LOCK_EX => Some(LockKind::Exclusive),
+            ctypes::LOCK_UN => None,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        // diff: `EINVAL` for directories
+        let file = File::from_fd(fd)?;
+        // the file is kept open by `file` without being locked while waiting
+        let locks = file.inner.lock().locks();
+        locks.flock(kind, wait).map_err(|e| lock_err(e, wait))?;
+        Ok(0)
+    })
+}
+
+/// Get, set or wait for a byte-range lock described by `fl` on the file
+/// opened as `fd`, for the `F_GETLK`, `F_SETLK` and `F_SETLKW` commands of
+/// `fcntl`.
+pub(crate) fn fcntl_lock(fd: c_int, cmd: u32, fl: *mut ctypes::flock) -> LinuxResult<c_int> {
+    if fl.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let fl = unsafe { &mut *fl };
+    // diff: `EINVAL` for directories
+    let file = File::from_fd(fd)?;
+    // the file is kept open by `file` without being locked while waiting
+    let (base, locks) = {
+        let mut inner = file.inner.lock();
+        let base = match fl.l_whence {
+            0 => 0,
+            1 => inner.seek(SeekFrom::Current(0))?,
+            2 => inner.get_attr()?.size(),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        (base, inner.locks())
+    };
+    let start = (base as i64)
+        .checked_add(fl.l_start as i64)
+        .ok_or(LinuxError::EOVERFLOW)?;
+    // `None` to the end of the file however it grows
+    let (start, end) = match fl.l_len as i64 {
+        0 => (Some(start), None),
+        len if len > 0 => (Some(start), Some(start.saturating_add(len))),
+        len => (start.checked_add(len), Some(start)),
+    };
+    let start = match start {
+        Some(start) if start >= 0 => start as u64,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let end = end.map_or(u64::MAX, |end| end as u64);
+    let kind = match fl.l_type as u32 {
+        ctypes::F_RDLCK => Some(LockKind::Shared),
+        ctypes::F_WRLCK => Some(LockKind::Exclusive),
+        ctypes::F_UNLCK if cmd != ctypes::F_GETLK => None,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    if cmd == ctypes::F_GETLK {
+        match locks.test_lock(kind.unwrap(), start, end) {
+            Some(lock) => {
+                fl.l_type = match lock.kind {
+                    LockKind::Shared => ctypes::F_RDLCK,
+                    LockKind::Exclusive => ctypes::F_WRLCK,
+                } as _;
+                fl.l_whence = 0;
+                fl.l_start = lock.start as _;
+                fl.l_len = match lock.end {
+                    u64::MAX => 0,
+                    end => (end - lock.start) as _,
+                };
+                // diff: the locks are owned by the opened files like Linux's
+                // `F_OFD_SETLK`, as there is only one process
+                fl.l_pid = -1;
+            }
+            None => fl.l_type = ctypes::F_UNLCK as _,
+        }
+        return Ok(0);
+    }
+    let wait = cmd == ctypes::F_SETLKW;
+    locks
+        .lock_range(kind, start, end, wait)
+        .map_err(|e| lock_err(e, wait))?;
+    Ok(0)
+}
+
+/// Set the file mode creation mask of the current process to `mask`.
+///
+/// Return the previous mask, it always succeeds.
+pub fn sys_umask(mask: ctypes::mode_t) -> ctypes::mode_t {
+    debug!("sys_umask <= {:#o}", mask);
+    let mask = FilePerm::from_bits_truncate(mask as u16);
+    axfs::api::set_umask(mask).bits() as _
+}
+
+/// Create a directory `path` with the permissions `mode`, without the bits in
+/// the file mode creation mask.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_mkdir(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_mkdir <= {:?} {:#o}", path, mode);
+    syscall_body!(sys_mkdir, {
+        let path = path?;
+        // diff: the sticky bit is ignored
+        axfs::api::DirBuilder::new()
+            .mode(mode as u32)
+            .create(path)
+            .map_err(|e| path_err(path, e))?;
         Ok(0)
     })
 }
 
+/// Create a symbolic link `linkpath` pointing to `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    syscall_body!(sys_symlink, {
+        let target = char_ptr_to_str(target)?;
+        let linkpath = char_ptr_to_str(linkpath)?;
+        debug!(
+            "sys_symlink <= target: {:?}, linkpath: {:?}",
+            target, linkpath
+        );
+        // diff: `EPERM` if the filesystem does not support symbolic links
+        axfs::api::symlink(target, linkpath).map_err(|e| match e {
+            AxError::Unsupported => LinuxError::EPERM,
+            e => path_err(linkpath, e),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Read the target of the symbolic link `path` into `buf`, without the
+/// terminating null byte.
+///
+/// Return the number of bytes placed in `buf`, the target is truncated if
+/// `buf` is too small.
+pub unsafe fn sys_readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: usize,
+) -> ctypes::ssize_t {
+    let path = char_ptr_to_str(path);
+    debug!("sys_readlink <= {:?} {:#x} {}", path, buf as usize, bufsiz);
+    syscall_body!(sys_readlink, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        } else if bufsiz == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let path = path?;
+        let target = axfs::api::read_link(path).map_err(|e| path_err(path, e))?;
+        let len = target.len().min(bufsiz);
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        dst.copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
 /// Get the path of the current directory.
 pub fn sys_getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
     debug!("sys_getcwd <= {:#x} {}", buf as usize, size);
@@ -305,7 +707,61 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
         let old_path = char_ptr_to_str(old)?;
         let new_path = char_ptr_to_str(new)?;
         debug!("sys_rename <= old: {:?}, new: {:?}", old_path, new_path);
-        axfs::api::rename(old_path, new_path)?;
+        axfs::api::rename(old_path, new_path).map_err(|e| match e {
+            AxError::InvalidData if axfs::api::is_symlink_loop(new_path) => LinuxError::ELOOP,
+            e => path_err(old_path, e),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Mount the filesystem `fstype` from `source` on the directory `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: core::ffi::c_ulong,
+    _data: *const core::ffi::c_void,
+) -> c_int {
+    syscall_body!(sys_mount, {
+        let source = char_ptr_to_str(source)?;
+        let target = char_ptr_to_str(target)?;
+        debug!(
+            "sys_mount <= source: {:?}, target: {:?}, flags: {:#x}",
+            source, target, flags
+        );
+        // diff: other flags, e.g. `MS_RDONLY`, `MS_REMOUNT`, `MS_MOVE` and
+        // `MS_REC`, are not supported
+        if flags & !(ctypes::MS_BIND as core::ffi::c_ulong) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        if flags & ctypes::MS_BIND as core::ffi::c_ulong != 0 {
+            axfs::api::bind_mount(source, target)?;
+        } else {
+            // diff: `data` is ignored, `source` is only a name for in-memory
+            // filesystems
+            let fstype = char_ptr_to_str(fstype)?;
+            axfs::api::mount(source, target, fstype).map_err(|e| match e {
+                axerrno::AxError::Unsupported => LinuxError::ENODEV,
+                e => e.into(),
+            })?;
+        }
+        Ok(0)
+    })
+}
+
+/// Unmount the filesystem mounted on `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_umount2(target: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_umount2, {
+        let target = char_ptr_to_str(target)?;
+        debug!("sys_umount2 <= target: {:?}, flags: {:#x}", target, flags);
+        // diff: `MNT_FORCE` and `MNT_EXPIRE` are ignored
+        let detach = flags & ctypes::MNT_DETACH as c_int != 0;
+        axfs::api::umount(target, detach)?;
         Ok(0)
     })
 }
@@ -330,10 +786,11 @@ impl Directory {
 
     /// Open a directory by `fd`.
     pub fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
-        let f = super::fd_ops::get_file_like(fd)?;
-        f.into_any()
-            .downcast::<Self>()
-            .map_err(|_| LinuxError::EINVAL)
+        Self::from_any(super::fd_ops::get_file_like(fd)?.into_any())
+    }
+
+    fn from_any(f: Arc<dyn core::any::Any + Send + Sync>) -> LinuxResult<Arc<Self>> {
+        f.downcast::<Self>().map_err(|_| LinuxError::EINVAL)
     }
 
     /// Get the path of the directory.
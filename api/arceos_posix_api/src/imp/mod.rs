@@ -20,3 +20,5 @@ pub mod path_link;
 pub mod pipe;
 #[cfg(feature = "multitask")]
 pub mod pthread;
+#[cfg(feature = "net")]
+pub mod unix;
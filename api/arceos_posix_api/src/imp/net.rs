@@ -2,19 +2,27 @@ use alloc::{sync::Arc, vec, vec::Vec};
 use core::ffi::{c_char, c_int, c_void};
 use core::mem::size_of;
 use core::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use core::time::Duration;
 
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
-use axnet::{TcpSocket, UdpSocket};
+use axnet::{IcmpSocket, RawSocket, TcpSocket, UdpSocket};
 use axsync::Mutex;
 
 use super::fd_ops::FileLike;
+use super::unix::{UnixAddr, UnixSocket, unix_socket_type};
 use crate::ctypes;
 use crate::utils::char_ptr_to_str;
 
+/// The keep-alive interval used when `SO_KEEPALIVE` is enabled, the same as
+/// the default `tcp_keepalive_time` of Linux.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(7200);
+
 pub enum Socket {
     Udp(Mutex<UdpSocket>),
     Tcp(Mutex<TcpSocket>),
+    Icmp(Mutex<IcmpSocket>),
+    Raw(Mutex<RawSocket>),
 }
 
 impl Socket {
@@ -22,17 +30,13 @@ impl Socket {
         super::fd_ops::add_file_like(Arc::new(self))
     }
 
-    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
-        let f = super::fd_ops::get_file_like(fd)?;
-        f.into_any()
-            .downcast::<Self>()
-            .map_err(|_| LinuxError::EINVAL)
-    }
-
     fn send(&self, buf: &[u8]) -> LinuxResult<usize> {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().send(buf)?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().send(buf)?),
+            // diff: ICMP sockets cannot be connected
+            Socket::Icmp(_) => Err(LinuxError::EDESTADDRREQ),
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().send(buf)?),
         }
     }
 
@@ -40,6 +44,8 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().recv_from(buf).map(|e| e.0)?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf)?),
+            Socket::Icmp(icmpsocket) => Ok(icmpsocket.lock().recv_from(buf).map(|e| e.0)?),
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().recv(buf)?),
         }
     }
 
@@ -47,6 +53,8 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().poll()?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().poll()?),
+            Socket::Icmp(icmpsocket) => Ok(icmpsocket.lock().poll()?),
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().poll()?),
         }
     }
 
@@ -54,6 +62,12 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().local_addr()?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().local_addr()?),
+            // the ICMP identifier is reported as the port, as Linux does
+            Socket::Icmp(icmpsocket) => {
+                let ident = icmpsocket.lock().ident()?;
+                Ok(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), ident))
+            }
+            Socket::Raw(_) => Ok(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)),
         }
     }
 
@@ -61,6 +75,7 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().peer_addr()?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().peer_addr()?),
+            Socket::Icmp(_) | Socket::Raw(_) => Err(LinuxError::ENOTCONN),
         }
     }
 
@@ -68,6 +83,10 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().bind(addr)?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().bind(addr)?),
+            // the port is used as the ICMP identifier, as Linux does
+            Socket::Icmp(icmpsocket) => Ok(icmpsocket.lock().bind(addr.port())?),
+            // diff: raw sockets cannot be bound to a local address
+            Socket::Raw(_) => Ok(()),
         }
     }
 
@@ -75,6 +94,7 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().connect(addr)?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().connect(addr)?),
+            Socket::Icmp(_) | Socket::Raw(_) => Err(LinuxError::EOPNOTSUPP),
         }
     }
 
@@ -83,6 +103,9 @@ impl Socket {
             // diff: must bind before sendto
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().send_to(buf, addr)?),
             Socket::Tcp(_) => Err(LinuxError::EISCONN),
+            Socket::Icmp(icmpsocket) => Ok(icmpsocket.lock().send_to(buf, addr.ip())?),
+            // diff: the destination is taken from the IP header
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().send(buf)?),
         }
     }
 
@@ -94,20 +117,28 @@ impl Socket {
                 .recv_from(buf)
                 .map(|res| (res.0, Some(res.1)))?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf).map(|res| (res, None))?),
+            Socket::Icmp(icmpsocket) => Ok(icmpsocket
+                .lock()
+                .recv_from(buf)
+                .map(|res| (res.0, Some(SocketAddr::new(res.1, 0))))?),
+            Socket::Raw(rawsocket) => Ok(rawsocket
+                .lock()
+                .recv_from(buf)
+                .map(|res| (res.0, Some(SocketAddr::new(res.1, 0))))?),
         }
     }
 
-    fn listen(&self) -> LinuxResult {
+    fn listen(&self, backlog: usize) -> LinuxResult {
         match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen()?),
+            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen(backlog)?),
+            _ => Err(LinuxError::EOPNOTSUPP),
         }
     }
 
     fn accept(&self) -> LinuxResult<TcpSocket> {
         match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().accept()?),
+            _ => Err(LinuxError::EOPNOTSUPP),
         }
     }
 
@@ -126,10 +157,250 @@ impl Socket {
                 tcpsocket.shutdown()?;
                 Ok(())
             }
+
+            Socket::Icmp(_) | Socket::Raw(_) => Err(LinuxError::ENOTCONN),
+        }
+    }
+
+    fn set_option(
+        &self,
+        level: u32,
+        name: u32,
+        optval: *const c_void,
+        optlen: ctypes::socklen_t,
+    ) -> LinuxResult {
+        match (level, name) {
+            (ctypes::SOL_SOCKET, ctypes::SO_REUSEADDR) => {
+                let reuse = unsafe { read_sockopt::<c_int>(optval, optlen)? } != 0;
+                match self {
+                    Socket::Udp(udpsocket) => udpsocket.lock().set_reuse_addr(reuse),
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().set_reuse_addr(reuse),
+                    Socket::Icmp(_) | Socket::Raw(_) => return Err(LinuxError::ENOPROTOOPT),
+                }
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_REUSEPORT) => {
+                let reuse = unsafe { read_sockopt::<c_int>(optval, optlen)? } != 0;
+                match self {
+                    Socket::Udp(udpsocket) => udpsocket.lock().set_reuse_port(reuse),
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().set_reuse_port(reuse),
+                    Socket::Icmp(_) | Socket::Raw(_) => return Err(LinuxError::ENOPROTOOPT),
+                }
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_KEEPALIVE) => {
+                let keep_alive = unsafe { read_sockopt::<c_int>(optval, optlen)? } != 0;
+                // keep-alive is meaningless for connectionless sockets, ignore it as Linux does
+                if let Socket::Tcp(tcpsocket) = self {
+                    tcpsocket
+                        .lock()
+                        .set_keep_alive(keep_alive.then_some(KEEPALIVE_INTERVAL))?;
+                }
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_RCVBUF) | (ctypes::SOL_SOCKET, ctypes::SO_SNDBUF) => {
+                let size = unsafe { read_sockopt::<c_int>(optval, optlen)? };
+                // diff: the size is not doubled, the size of a TCP socket can't be
+                // changed after it's connected or listening, and the size of a UDP
+                // socket with datagrams to be sent can't be changed (EBUSY)
+                let size = usize::try_from(size).map_err(|_| LinuxError::EINVAL)?;
+                match (self, name) {
+                    (Socket::Udp(udpsocket), ctypes::SO_RCVBUF) => {
+                        udpsocket.lock().set_recv_buffer_size(size)?
+                    }
+                    (Socket::Udp(udpsocket), _) => udpsocket.lock().set_send_buffer_size(size)?,
+                    (Socket::Tcp(tcpsocket), ctypes::SO_RCVBUF) => {
+                        tcpsocket.lock().set_recv_buffer_size(size)
+                    }
+                    (Socket::Tcp(tcpsocket), _) => tcpsocket.lock().set_send_buffer_size(size),
+                    _ => return Err(LinuxError::ENOPROTOOPT),
+                }
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_RCVTIMEO)
+            | (ctypes::SOL_SOCKET, ctypes::SO_SNDTIMEO) => {
+                let tv = unsafe { read_sockopt::<ctypes::timeval>(optval, optlen)? };
+                if tv.tv_sec < 0 || !(0..1_000_000).contains(&tv.tv_usec) {
+                    return Err(LinuxError::EDOM);
+                }
+                // a zero timeout means blocking indefinitely
+                let timeout = Some(Duration::from(tv)).filter(|t| !t.is_zero());
+                match (self, name) {
+                    (Socket::Udp(udpsocket), ctypes::SO_RCVTIMEO) => {
+                        udpsocket.lock().set_recv_timeout(timeout)?
+                    }
+                    (Socket::Udp(udpsocket), _) => udpsocket.lock().set_send_timeout(timeout)?,
+                    (Socket::Tcp(tcpsocket), ctypes::SO_RCVTIMEO) => {
+                        tcpsocket.lock().set_recv_timeout(timeout)?
+                    }
+                    (Socket::Tcp(tcpsocket), _) => tcpsocket.lock().set_send_timeout(timeout)?,
+                    _ => return Err(LinuxError::ENOPROTOOPT),
+                }
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_LINGER) => {
+                let linger = unsafe { read_sockopt::<ctypes::linger>(optval, optlen)? };
+                let linger = (linger.l_onoff != 0)
+                    .then(|| Duration::from_secs(linger.l_linger.max(0) as u64));
+                // lingering is meaningless for connectionless sockets, ignore it as Linux does
+                if let Socket::Tcp(tcpsocket) = self {
+                    tcpsocket.lock().set_linger(linger);
+                }
+            }
+            (ctypes::IPPROTO_TCP, ctypes::TCP_NODELAY) => {
+                let nodelay = unsafe { read_sockopt::<c_int>(optval, optlen)? } != 0;
+                match self {
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().set_nodelay(nodelay),
+                    _ => return Err(LinuxError::ENOPROTOOPT),
+                }
+            }
+            _ => return Err(LinuxError::ENOPROTOOPT),
+        }
+        Ok(())
+    }
+
+    fn get_option(
+        &self,
+        level: u32,
+        name: u32,
+        optval: *mut c_void,
+        optlen: *mut ctypes::socklen_t,
+    ) -> LinuxResult {
+        unsafe {
+            match (level, name) {
+                (ctypes::SOL_SOCKET, ctypes::SO_TYPE) => {
+                    let ty = match self {
+                        Socket::Tcp(_) => ctypes::SOCK_STREAM,
+                        // diff: ICMP sockets are always reported as `SOCK_DGRAM`
+                        Socket::Udp(_) | Socket::Icmp(_) => ctypes::SOCK_DGRAM,
+                        Socket::Raw(_) => ctypes::SOCK_RAW,
+                    };
+                    write_sockopt(ty as c_int, optval, optlen)
+                }
+                // diff: errors are always reported synchronously
+                (ctypes::SOL_SOCKET, ctypes::SO_ERROR) => write_sockopt(0 as c_int, optval, optlen),
+                (ctypes::SOL_SOCKET, ctypes::SO_REUSEADDR) => {
+                    let reuse = match self {
+                        Socket::Udp(udpsocket) => udpsocket.lock().reuse_addr(),
+                        Socket::Tcp(tcpsocket) => tcpsocket.lock().reuse_addr(),
+                        Socket::Icmp(_) | Socket::Raw(_) => return Err(LinuxError::ENOPROTOOPT),
+                    };
+                    write_sockopt(reuse as c_int, optval, optlen)
+                }
+                (ctypes::SOL_SOCKET, ctypes::SO_REUSEPORT) => {
+                    let reuse = match self {
+                        Socket::Udp(udpsocket) => udpsocket.lock().reuse_port(),
+                        Socket::Tcp(tcpsocket) => tcpsocket.lock().reuse_port(),
+                        Socket::Icmp(_) | Socket::Raw(_) => return Err(LinuxError::ENOPROTOOPT),
+                    };
+                    write_sockopt(reuse as c_int, optval, optlen)
+                }
+                (ctypes::SOL_SOCKET, ctypes::SO_KEEPALIVE) => {
+                    let keep_alive = match self {
+                        Socket::Tcp(tcpsocket) => tcpsocket.lock().keep_alive().is_some(),
+                        _ => false,
+                    };
+                    write_sockopt(keep_alive as c_int, optval, optlen)
+                }
+                (ctypes::SOL_SOCKET, ctypes::SO_RCVBUF)
+                | (ctypes::SOL_SOCKET, ctypes::SO_SNDBUF) => {
+                    let size = match (self, name) {
+                        (Socket::Udp(udpsocket), ctypes::SO_RCVBUF) => {
+                            udpsocket.lock().recv_buffer_size()
+                        }
+                        (Socket::Udp(udpsocket), _) => udpsocket.lock().send_buffer_size(),
+                        (Socket::Tcp(tcpsocket), ctypes::SO_RCVBUF) => {
+                            tcpsocket.lock().recv_buffer_size()
+                        }
+                        (Socket::Tcp(tcpsocket), _) => tcpsocket.lock().send_buffer_size(),
+                        _ => return Err(LinuxError::ENOPROTOOPT),
+                    };
+                    write_sockopt(size as c_int, optval, optlen)
+                }
+                (ctypes::SOL_SOCKET, ctypes::SO_RCVTIMEO)
+                | (ctypes::SOL_SOCKET, ctypes::SO_SNDTIMEO) => {
+                    let timeout = match (self, name) {
+                        (Socket::Udp(udpsocket), ctypes::SO_RCVTIMEO) => {
+                            udpsocket.lock().recv_timeout()
+                        }
+                        (Socket::Udp(udpsocket), _) => udpsocket.lock().send_timeout(),
+                        (Socket::Tcp(tcpsocket), ctypes::SO_RCVTIMEO) => {
+                            tcpsocket.lock().recv_timeout()
+                        }
+                        (Socket::Tcp(tcpsocket), _) => tcpsocket.lock().send_timeout(),
+                        _ => return Err(LinuxError::ENOPROTOOPT),
+                    };
+                    let tv = ctypes::timeval::from(timeout.unwrap_or(Duration::ZERO));
+                    write_sockopt(tv, optval, optlen)
+                }
+                (ctypes::SOL_SOCKET, ctypes::SO_LINGER) => {
+                    let linger = match self {
+                        Socket::Tcp(tcpsocket) => tcpsocket.lock().linger(),
+                        _ => None,
+                    };
+                    let linger = ctypes::linger {
+                        l_onoff: linger.is_some() as c_int,
+                        l_linger: linger.map_or(0, |t| t.as_secs() as c_int),
+                    };
+                    write_sockopt(linger, optval, optlen)
+                }
+                (ctypes::IPPROTO_TCP, ctypes::TCP_NODELAY) => match self {
+                    Socket::Tcp(tcpsocket) => {
+                        write_sockopt(tcpsocket.lock().nodelay() as c_int, optval, optlen)
+                    }
+                    _ => Err(LinuxError::ENOPROTOOPT),
+                },
+                _ => Err(LinuxError::ENOPROTOOPT),
+            }
+        }
+    }
+}
+
+/// A socket of any address family.
+enum AnySocket {
+    Inet(Arc<Socket>),
+    Unix(Arc<UnixSocket>),
+}
+
+impl AnySocket {
+    fn from_fd(fd: c_int) -> LinuxResult<Self> {
+        let f = super::fd_ops::get_file_like(fd)?.into_any();
+        match f.downcast::<Socket>() {
+            Ok(socket) => Ok(Self::Inet(socket)),
+            Err(f) => f
+                .downcast::<UnixSocket>()
+                .map(Self::Unix)
+                .map_err(|_| LinuxError::EINVAL),
         }
     }
 }
 
+/// Reads the option value of `setsockopt`.
+unsafe fn read_sockopt<T: Copy>(
+    optval: *const c_void,
+    optlen: ctypes::socklen_t,
+) -> LinuxResult<T> {
+    if optval.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if (optlen as usize) < size_of::<T>() {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(unsafe { (optval as *const T).read_unaligned() })
+}
+
+/// Writes the option value of `getsockopt`, truncated to the given length.
+unsafe fn write_sockopt<T>(
+    val: T,
+    optval: *mut c_void,
+    optlen: *mut ctypes::socklen_t,
+) -> LinuxResult {
+    if optval.is_null() || optlen.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let len = unsafe { *optlen as usize }.min(size_of::<T>());
+    unsafe {
+        core::ptr::copy_nonoverlapping(&val as *const T as *const u8, optval as *mut u8, len);
+        *optlen = len as _;
+    }
+    Ok(())
+}
+
 impl FileLike for Socket {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
         self.recv(buf)
@@ -165,6 +436,8 @@ impl FileLike for Socket {
         match self {
             Socket::Udp(udpsocket) => udpsocket.lock().set_nonblocking(nonblock),
             Socket::Tcp(tcpsocket) => tcpsocket.lock().set_nonblocking(nonblock),
+            Socket::Icmp(icmpsocket) => icmpsocket.lock().set_nonblocking(nonblock),
+            Socket::Raw(rawsocket) => rawsocket.lock().set_nonblocking(nonblock),
         }
         Ok(())
     }
@@ -242,6 +515,19 @@ pub fn sys_socket(domain: c_int, socktype: c_int, protocol: c_int) -> c_int {
             | (ctypes::AF_INET, ctypes::SOCK_DGRAM, 0) => {
                 Socket::Udp(Mutex::new(UdpSocket::new())).add_to_fd_table()
             }
+            // a ping socket, raw ICMP sockets with the IP header are below
+            (ctypes::AF_INET, ctypes::SOCK_DGRAM, ctypes::IPPROTO_ICMP) => {
+                Socket::Icmp(Mutex::new(IcmpSocket::new())).add_to_fd_table()
+            }
+            (ctypes::AF_INET, ctypes::SOCK_RAW, 0) => Err(LinuxError::EPROTONOSUPPORT),
+            (ctypes::AF_INET, ctypes::SOCK_RAW, protocol) => {
+                let protocol = u8::try_from(protocol).map_err(|_| LinuxError::EINVAL)?;
+                Socket::Raw(Mutex::new(RawSocket::new(protocol))).add_to_fd_table()
+            }
+            (ctypes::AF_UNIX, socktype, protocol) => {
+                let ty = unix_socket_type(socktype, protocol)?;
+                super::fd_ops::add_file_like(Arc::new(UnixSocket::new(ty)))
+            }
             _ => Err(LinuxError::EINVAL),
         }
     })
@@ -260,8 +546,12 @@ pub fn sys_bind(
         socket_fd, socket_addr as usize, addrlen
     );
     syscall_body!(sys_bind, {
-        let addr = from_sockaddr(socket_addr, addrlen)?;
-        Socket::from_fd(socket_fd)?.bind(addr)?;
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.bind(from_sockaddr(socket_addr, addrlen)?)?,
+            AnySocket::Unix(socket) => {
+                socket.bind(UnixAddr::from_sockaddr(socket_addr, addrlen)?)?
+            }
+        }
         Ok(0)
     })
 }
@@ -279,8 +569,12 @@ pub fn sys_connect(
         socket_fd, socket_addr as usize, addrlen
     );
     syscall_body!(sys_connect, {
-        let addr = from_sockaddr(socket_addr, addrlen)?;
-        Socket::from_fd(socket_fd)?.connect(addr)?;
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.connect(from_sockaddr(socket_addr, addrlen)?)?,
+            AnySocket::Unix(socket) => {
+                socket.connect(&UnixAddr::from_sockaddr(socket_addr, addrlen)?)?
+            }
+        }
         Ok(0)
     })
 }
@@ -304,9 +598,14 @@ pub fn sys_sendto(
         if buf_ptr.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let addr = from_sockaddr(socket_addr, addrlen)?;
         let buf = unsafe { core::slice::from_raw_parts(buf_ptr as *const u8, len) };
-        Socket::from_fd(socket_fd)?.sendto(buf, addr)
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.sendto(buf, from_sockaddr(socket_addr, addrlen)?),
+            AnySocket::Unix(socket) => {
+                let addr = UnixAddr::from_sockaddr(socket_addr, addrlen)?;
+                socket.send_to(buf, Some(&addr))
+            }
+        }
     })
 }
 
@@ -328,7 +627,10 @@ pub fn sys_send(
             return Err(LinuxError::EFAULT);
         }
         let buf = unsafe { core::slice::from_raw_parts(buf_ptr as *const u8, len) };
-        Socket::from_fd(socket_fd)?.send(buf)
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.send(buf),
+            AnySocket::Unix(socket) => socket.send_to(buf, None),
+        }
     })
 }
 
@@ -351,16 +653,25 @@ pub unsafe fn sys_recvfrom(
         if buf_ptr.is_null() || socket_addr.is_null() || addrlen.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let socket = Socket::from_fd(socket_fd)?;
         let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, len) };
-
-        let res = socket.recvfrom(buf)?;
-        if let Some(addr) = res.1 {
-            unsafe {
-                (*socket_addr, *addrlen) = into_sockaddr(addr);
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => {
+                let res = socket.recvfrom(buf)?;
+                if let Some(addr) = res.1 {
+                    unsafe {
+                        (*socket_addr, *addrlen) = into_sockaddr(addr);
+                    }
+                }
+                Ok(res.0)
+            }
+            AnySocket::Unix(socket) => {
+                let res = socket.recv_from(buf)?;
+                if let Some(addr) = res.1 {
+                    unsafe { addr.write_to(socket_addr, addrlen)? };
+                }
+                Ok(res.0)
             }
         }
-        Ok(res.0)
     })
 }
 
@@ -382,20 +693,25 @@ pub fn sys_recv(
             return Err(LinuxError::EFAULT);
         }
         let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, len) };
-        Socket::from_fd(socket_fd)?.recv(buf)
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.recv(buf),
+            AnySocket::Unix(socket) => Ok(socket.recv_from(buf)?.0),
+        }
     })
 }
 
 /// Listen for connections on a socket
 ///
 /// Return 0 if success.
-pub fn sys_listen(
-    socket_fd: c_int,
-    backlog: c_int, // currently not used
-) -> c_int {
+pub fn sys_listen(socket_fd: c_int, backlog: c_int) -> c_int {
     debug!("sys_listen <= {} {}", socket_fd, backlog);
     syscall_body!(sys_listen, {
-        Socket::from_fd(socket_fd)?.listen()?;
+        // a negative backlog means the maximum, as Linux does
+        let backlog = usize::try_from(backlog).unwrap_or(usize::MAX);
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.listen(backlog)?,
+            AnySocket::Unix(socket) => socket.listen(backlog)?,
+        }
         Ok(0)
     })
 }
@@ -416,14 +732,24 @@ pub unsafe fn sys_accept(
         if socket_addr.is_null() || socket_len.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let socket = Socket::from_fd(socket_fd)?;
-        let new_socket = socket.accept()?;
-        let addr = new_socket.peer_addr()?;
-        let new_fd = Socket::add_to_fd_table(Socket::Tcp(Mutex::new(new_socket)))?;
-        unsafe {
-            (*socket_addr, *socket_len) = into_sockaddr(addr);
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => {
+                let new_socket = socket.accept()?;
+                let addr = new_socket.peer_addr()?;
+                let new_fd = Socket::add_to_fd_table(Socket::Tcp(Mutex::new(new_socket)))?;
+                unsafe {
+                    (*socket_addr, *socket_len) = into_sockaddr(addr);
+                }
+                Ok(new_fd)
+            }
+            AnySocket::Unix(socket) => {
+                let new_socket = socket.accept()?;
+                let addr = new_socket.peer_addr()?;
+                let new_fd = super::fd_ops::add_file_like(new_socket)?;
+                unsafe { addr.write_to(socket_addr, socket_len)? };
+                Ok(new_fd)
+            }
         }
-        Ok(new_fd)
     })
 }
 
@@ -432,25 +758,80 @@ pub unsafe fn sys_accept(
 /// Return 0 if success.
 pub fn sys_shutdown(
     socket_fd: c_int,
-    flag: c_int, // currently not used
+    flag: c_int, // only used by Unix domain sockets
 ) -> c_int {
     debug!("sys_shutdown <= {} {}", socket_fd, flag);
     syscall_body!(sys_shutdown, {
-        Socket::from_fd(socket_fd)?.shutdown()?;
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.shutdown()?,
+            AnySocket::Unix(socket) => socket.shutdown(flag)?,
+        }
+        Ok(0)
+    })
+}
+
+/// Set options on a socket.
+///
+/// Return 0 if success.
+pub unsafe fn sys_setsockopt(
+    socket_fd: c_int,
+    level: c_int,
+    optname: c_int,
+    optval: *const c_void,
+    optlen: ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_setsockopt <= {} {} {} {:#x} {}",
+        socket_fd, level, optname, optval as usize, optlen
+    );
+    syscall_body!(sys_setsockopt, {
+        let (level, optname) = (level as u32, optname as u32);
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.set_option(level, optname, optval, optlen)?,
+            AnySocket::Unix(socket) => socket.set_option(level, optname, optval, optlen)?,
+        }
+        Ok(0)
+    })
+}
+
+/// Get options on a socket.
+///
+/// Return 0 if success.
+pub unsafe fn sys_getsockopt(
+    socket_fd: c_int,
+    level: c_int,
+    optname: c_int,
+    optval: *mut c_void,
+    optlen: *mut ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_getsockopt <= {} {} {} {:#x} {:#x}",
+        socket_fd, level, optname, optval as usize, optlen as usize
+    );
+    syscall_body!(sys_getsockopt, {
+        let (level, optname) = (level as u32, optname as u32);
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.get_option(level, optname, optval, optlen)?,
+            AnySocket::Unix(socket) => {
+                let val = socket.get_option(level, optname)?;
+                unsafe { write_sockopt(val, optval, optlen)? };
+            }
+        }
         Ok(0)
     })
 }
 
 /// Query addresses for a domain name.
 ///
-/// Only IPv4. Ports are always 0. Ignore servname and hint.
+/// Only IPv4. Ports are always 0. Ignore servname. Only the socket type and
+/// protocol in hint are used, TCP is returned if they are not specified.
 /// Results' ai_flags and ai_canonname are 0 or NULL.
 ///
 /// Return address number if success.
 pub unsafe fn sys_getaddrinfo(
     nodename: *const c_char,
     servname: *const c_char,
-    _hints: *const ctypes::addrinfo,
+    hints: *const ctypes::addrinfo,
     res: *mut *mut ctypes::addrinfo,
 ) -> c_int {
     let name = char_ptr_to_str(nodename);
@@ -465,6 +846,24 @@ pub unsafe fn sys_getaddrinfo(
         }
 
         let port = port.map_or(0, |p| p.parse::<u16>().unwrap_or(0));
+        let (socktype, protocol) = if hints.is_null() {
+            (0, 0)
+        } else {
+            let hints = unsafe { &*hints };
+            (hints.ai_socktype as u32, hints.ai_protocol as u32)
+        };
+        let (socktype, protocol) = match (socktype, protocol) {
+            // diff: only one result for each address, prefer TCP
+            (0, 0) | (0, ctypes::IPPROTO_TCP) => (ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP),
+            (0, ctypes::IPPROTO_UDP) => (ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP),
+            (ctypes::SOCK_STREAM, 0) => (ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP),
+            (ctypes::SOCK_DGRAM, 0) => (ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP),
+            (ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP)
+            | (ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP)
+            | (ctypes::SOCK_DGRAM, ctypes::IPPROTO_ICMP)
+            | (ctypes::SOCK_RAW, _) => (socktype, protocol),
+            _ => return Err(LinuxError::EINVAL),
+        };
         let ip_addrs = if let Ok(domain) = name {
             if let Ok(a) = domain.parse::<IpAddr>() {
                 vec![a]
@@ -486,9 +885,8 @@ pub unsafe fn sys_getaddrinfo(
                 IpAddr::V4(ip) => ctypes::aibuf {
                     ai: ctypes::addrinfo {
                         ai_family: ctypes::AF_INET as _,
-                        // TODO: This is a hard-code part, only return TCP parameters
-                        ai_socktype: ctypes::SOCK_STREAM as _,
-                        ai_protocol: ctypes::IPPROTO_TCP as _,
+                        ai_socktype: socktype as _,
+                        ai_protocol: protocol as _,
                         ai_addrlen: size_of::<ctypes::sockaddr_in>() as _,
                         ai_addr: core::ptr::null_mut(),
                         ai_canonname: core::ptr::null_mut(),
@@ -546,11 +944,18 @@ pub unsafe fn sys_getsockname(
         if addr.is_null() || addrlen.is_null() {
             return Err(LinuxError::EFAULT);
         }
+        let socket = match AnySocket::from_fd(sock_fd)? {
+            AnySocket::Inet(socket) => socket,
+            AnySocket::Unix(socket) => {
+                unsafe { socket.local_addr().write_to(addr, addrlen)? };
+                return Ok(0);
+            }
+        };
         if unsafe { *addrlen } < size_of::<ctypes::sockaddr>() as u32 {
             return Err(LinuxError::EINVAL);
         }
         unsafe {
-            (*addr, *addrlen) = into_sockaddr(Socket::from_fd(sock_fd)?.local_addr()?);
+            (*addr, *addrlen) = into_sockaddr(socket.local_addr()?);
         }
         Ok(0)
     })
@@ -570,11 +975,18 @@ pub unsafe fn sys_getpeername(
         if addr.is_null() || addrlen.is_null() {
             return Err(LinuxError::EFAULT);
         }
+        let socket = match AnySocket::from_fd(sock_fd)? {
+            AnySocket::Inet(socket) => socket,
+            AnySocket::Unix(socket) => {
+                unsafe { socket.peer_addr()?.write_to(addr, addrlen)? };
+                return Ok(0);
+            }
+        };
         if unsafe { *addrlen } < size_of::<ctypes::sockaddr>() as u32 {
             return Err(LinuxError::EINVAL);
         }
         unsafe {
-            (*addr, *addrlen) = into_sockaddr(Socket::from_fd(sock_fd)?.peer_addr()?);
+            (*addr, *addrlen) = into_sockaddr(socket.peer_addr()?);
         }
         Ok(0)
     })
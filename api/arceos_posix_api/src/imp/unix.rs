@@ -0,0 +1,758 @@
+//! Unix domain sockets (`AF_UNIX`).
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::ffi::{c_int, c_void};
+use core::mem::{offset_of, size_of};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axsync::Mutex;
+#[cfg(feature = "multitask")]
+use axtask::WaitQueue;
+
+use super::fd_ops::{FileLike, add_file_like, close_file_like};
+use crate::ctypes;
+
+/// The capacity of the receive buffer of a stream socket.
+const UNIX_STREAM_BUF_LEN: usize = 64 * 1024;
+/// The maximum number of datagrams queued in a datagram socket.
+const UNIX_DGRAM_QUEUE_LEN: usize = 64;
+/// The maximum number of pending connections of a listening socket.
+const UNIX_MAX_BACKLOG: usize = 4096;
+
+/// Names bound by sockets, both paths and abstract names.
+static UNIX_NAMES: Mutex<BTreeMap<UnixAddr, Weak<UnixSocket>>> = Mutex::new(BTreeMap::new());
+
+/// Counts the changes of the sockets which may unblock other tasks.
+static UNIX_EVENTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Tasks blocked on sockets, woken up whenever [`UNIX_EVENTS`] changes.
+#[cfg(feature = "multitask")]
+static UNIX_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+
+/// Wakes up the tasks blocked on sockets after a change.
+fn notify() {
+    UNIX_EVENTS.fetch_add(1, Ordering::AcqRel);
+    #[cfg(feature = "multitask")]
+    UNIX_WAIT_QUEUE.notify_all(false);
+}
+
+/// Blocks until the sockets are changed after [`UNIX_EVENTS`] was `events`.
+///
+/// Only the counter is checked, without locks, as the wait queue disables
+/// preemption while checking it.
+fn wait_event(events: usize) {
+    #[cfg(feature = "multitask")]
+    UNIX_WAIT_QUEUE.wait_until(|| UNIX_EVENTS.load(Ordering::Acquire) != events);
+    #[cfg(not(feature = "multitask"))]
+    while UNIX_EVENTS.load(Ordering::Acquire) == events {
+        crate::sys_sched_yield();
+    }
+}
+
+/// The address of a Unix domain socket.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnixAddr {
+    /// Not bound to any name.
+    Unnamed,
+    /// A path in the file system.
+    Path(String),
+    /// A name in the abstract namespace (starts with a null byte).
+    Abstract(Vec<u8>),
+}
+
+impl UnixAddr {
+    /// Loads the address from a `sockaddr_un`.
+    pub fn from_sockaddr(
+        addr: *const ctypes::sockaddr,
+        addrlen: ctypes::socklen_t,
+    ) -> LinuxResult<Self> {
+        if addr.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let path_offset = offset_of!(ctypes::sockaddr_un, sun_path);
+        let addrlen = addrlen as usize;
+        if addrlen < path_offset || addrlen > size_of::<ctypes::sockaddr_un>() {
+            return Err(LinuxError::EINVAL);
+        }
+        if unsafe { (*addr).sa_family } != ctypes::AF_UNIX as u16 {
+            return Err(LinuxError::EINVAL);
+        }
+
+        let path = unsafe {
+            core::slice::from_raw_parts((addr as *const u8).add(path_offset), addrlen - path_offset)
+        };
+        let res = match path.first() {
+            None => Self::Unnamed,
+            Some(0) => Self::Abstract(path[1..].to_vec()),
+            Some(_) => {
+                let len = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+                let path = core::str::from_utf8(&path[..len]).map_err(|_| LinuxError::EINVAL)?;
+                Self::Path(path.into())
+            }
+        };
+        debug!("    load sockaddr_un:{:#x} => {:?}", addr as usize, res);
+        Ok(res)
+    }
+
+    /// Stores the address to a `sockaddr_un`, truncated to the buffer length.
+    ///
+    /// The full length of the address is written back to `addrlen`, as Linux
+    /// does.
+    pub unsafe fn write_to(
+        &self,
+        addr: *mut ctypes::sockaddr,
+        addrlen: *mut ctypes::socklen_t,
+    ) -> LinuxResult {
+        if addr.is_null() || addrlen.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let mut sun = ctypes::sockaddr_un {
+            sun_family: ctypes::AF_UNIX as _,
+            ..Default::default()
+        };
+        let (name, path_len) = match self {
+            Self::Unnamed => (&[][..], 0),
+            // with the trailing null byte
+            Self::Path(path) => (path.as_bytes(), path.len() + 1),
+            Self::Abstract(name) => (&name[..], name.len() + 1),
+        };
+        let start = matches!(self, Self::Abstract(_)) as usize;
+        for (dst, &src) in sun.sun_path[start..].iter_mut().zip(name) {
+            *dst = src as _;
+        }
+
+        let len = offset_of!(ctypes::sockaddr_un, sun_path) + path_len.min(sun.sun_path.len());
+        unsafe {
+            let copy_len = (*addrlen as usize).min(len);
+            core::ptr::copy_nonoverlapping(
+                &sun as *const _ as *const u8,
+                addr as *mut u8,
+                copy_len,
+            );
+            *addrlen = len as _;
+        }
+        Ok(())
+    }
+
+    /// Returns the key in [`UNIX_NAMES`], relative paths are resolved to
+    /// absolute paths.
+    fn resolve(&self) -> LinuxResult<Self> {
+        match self {
+            #[cfg(feature = "fs")]
+            Self::Path(path) => Ok(Self::Path(axfs::api::canonicalize(path)?)),
+            // diff: relative paths are not resolved without the file system
+            _ => Ok(self.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnixSocketType {
+    Stream,
+    Datagram,
+}
+
+/// The receive queue of a socket, written by its peers.
+#[derive(Default)]
+struct Channel {
+    /// Bytes received by a stream socket.
+    stream: VecDeque<u8>,
+    /// Datagrams received by a datagram socket, with the source addresses.
+    dgrams: VecDeque<(Vec<u8>, UnixAddr)>,
+    /// The writer will not write anymore (end of stream).
+    write_closed: bool,
+    /// The reader will not read anymore (broken pipe).
+    read_closed: bool,
+}
+
+type ChannelRef = Arc<Mutex<Channel>>;
+
+struct Listener {
+    backlog: usize,
+    pending: VecDeque<Arc<UnixSocket>>,
+}
+
+struct Inner {
+    local: UnixAddr,
+    /// The receive queue and the address of the peer.
+    peer: Option<(ChannelRef, UnixAddr)>,
+    listener: Option<Listener>,
+}
+
+/// A Unix domain socket.
+pub struct UnixSocket {
+    ty: UnixSocketType,
+    nonblocking: AtomicBool,
+    rx: ChannelRef,
+    inner: Mutex<Inner>,
+}
+
+impl UnixSocket {
+    /// Creates a new unbound socket.
+    pub fn new(ty: UnixSocketType) -> Self {
+        Self {
+            ty,
+            nonblocking: AtomicBool::new(false),
+            rx: Arc::new(Mutex::new(Channel::default())),
+            inner: Mutex::new(Inner {
+                local: UnixAddr::Unnamed,
+                peer: None,
+                listener: None,
+            }),
+        }
+    }
+
+    /// Creates a pair of connected sockets.
+    pub fn new_pair(ty: UnixSocketType) -> (Self, Self) {
+        let a = Self::new(ty);
+        let b = Self::new(ty);
+        a.inner.lock().peer = Some((b.rx.clone(), UnixAddr::Unnamed));
+        b.inner.lock().peer = Some((a.rx.clone(), UnixAddr::Unnamed));
+        (a, b)
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+
+    /// Calls `f` until it does not return `EAGAIN`, or returns `EAGAIN`
+    /// immediately if the socket is non-blocking.
+    ///
+    /// It blocks until another change of the sockets after each try.
+    fn block_on<T>(&self, mut f: impl FnMut() -> LinuxResult<T>) -> LinuxResult<T> {
+        loop {
+            let events = UNIX_EVENTS.load(Ordering::Acquire);
+            match f() {
+                Err(LinuxError::EAGAIN) if !self.is_nonblocking() => wait_event(events),
+                res => return res,
+            }
+        }
+    }
+
+    pub fn local_addr(&self) -> UnixAddr {
+        self.inner.lock().local.clone()
+    }
+
+    pub fn peer_addr(&self) -> LinuxResult<UnixAddr> {
+        let inner = self.inner.lock();
+        inner
+            .peer
+            .as_ref()
+            .map(|(_, addr)| addr.clone())
+            .ok_or(LinuxError::ENOTCONN)
+    }
+
+    /// Binds the socket to a name.
+    ///
+    /// A path name also creates a socket file in the file system, which must
+    /// not exist before.
+    pub fn bind(self: &Arc<Self>, addr: UnixAddr) -> LinuxResult {
+        if addr == UnixAddr::Unnamed {
+            return Err(LinuxError::EINVAL);
+        }
+        let addr = addr.resolve()?;
+        let mut inner = self.inner.lock();
+        if inner.local != UnixAddr::Unnamed {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut names = UNIX_NAMES.lock();
+        if names.get(&addr).is_some_and(|s| s.strong_count() > 0) {
+            return Err(LinuxError::EADDRINUSE);
+        }
+        #[cfg(feature = "fs")]
+        if let UnixAddr::Path(path) = &addr {
+            axfs::api::create_socket(path).map_err(|e| match e {
+                axerrno::AxError::AlreadyExists => LinuxError::EADDRINUSE,
+                e => LinuxError::from(e),
+            })?;
+        }
+        names.insert(addr.clone(), Arc::downgrade(self));
+        inner.local = addr;
+        Ok(())
+    }
+
+    /// Starts listening for connections, only for stream sockets.
+    pub fn listen(&self, backlog: usize) -> LinuxResult {
+        if self.ty != UnixSocketType::Stream {
+            return Err(LinuxError::EOPNOTSUPP);
+        }
+        let mut inner = self.inner.lock();
+        // diff: unbound sockets are not autobound to an abstract name
+        if inner.local == UnixAddr::Unnamed || inner.peer.is_some() {
+            return Err(LinuxError::EINVAL);
+        }
+        let backlog = backlog.clamp(1, UNIX_MAX_BACKLOG);
+        match inner.listener.as_mut() {
+            Some(listener) => listener.backlog = backlog,
+            None => {
+                inner.listener = Some(Listener {
+                    backlog,
+                    pending: VecDeque::new(),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(addr: &UnixAddr) -> LinuxResult<Arc<Self>> {
+        let addr = addr.resolve()?;
+        #[cfg(feature = "fs")]
+        if let UnixAddr::Path(path) = &addr {
+            // the name is gone if the socket file is removed
+            let md = axfs::api::metadata(path)?;
+            if md.file_type() != axfs::api::FileType::Socket {
+                return Err(LinuxError::ECONNREFUSED);
+            }
+        }
+        UNIX_NAMES
+            .lock()
+            .get(&addr)
+            .and_then(Weak::upgrade)
+            .ok_or(LinuxError::ECONNREFUSED)
+    }
+
+    /// Connects to the socket bound to `addr`.
+    ///
+    /// For stream sockets, the connection is queued in the listening socket
+    /// until it's accepted. For datagram sockets, it only sets the default
+    /// destination.
+    pub fn connect(&self, addr: &UnixAddr) -> LinuxResult {
+        let target = Self::lookup(addr)?;
+        if target.ty != self.ty {
+            return Err(LinuxError::EPROTOTYPE);
+        }
+        {
+            let inner = self.inner.lock();
+            if inner.listener.is_some() {
+                return Err(LinuxError::EINVAL);
+            }
+            if self.ty == UnixSocketType::Stream && inner.peer.is_some() {
+                return Err(LinuxError::EISCONN);
+            }
+        }
+
+        if self.ty == UnixSocketType::Datagram {
+            let peer_addr = target.local_addr();
+            self.inner.lock().peer = Some((target.rx.clone(), peer_addr));
+            return Ok(());
+        }
+
+        let local = self.local_addr();
+        let peer_addr = self.block_on(|| {
+            let mut target_inner = target.inner.lock();
+            let peer_addr = target_inner.local.clone();
+            let listener = target_inner
+                .listener
+                .as_mut()
+                .ok_or(LinuxError::ECONNREFUSED)?;
+            if listener.pending.len() >= listener.backlog {
+                return Err(LinuxError::EAGAIN);
+            }
+            // the server side of the connection, returned by `accept`
+            let server = Self::new(UnixSocketType::Stream);
+            {
+                let mut server_inner = server.inner.lock();
+                server_inner.local = peer_addr.clone();
+                server_inner.peer = Some((self.rx.clone(), local.clone()));
+            }
+            let server_rx = server.rx.clone();
+            listener.pending.push_back(Arc::new(server));
+            Ok((server_rx, peer_addr))
+        })?;
+        self.inner.lock().peer = Some(peer_addr);
+        notify();
+        Ok(())
+    }
+
+    /// Accepts a pending connection.
+    pub fn accept(&self) -> LinuxResult<Arc<Self>> {
+        let conn = self.block_on(|| {
+            let mut inner = self.inner.lock();
+            let listener = inner.listener.as_mut().ok_or(LinuxError::EINVAL)?;
+            listener.pending.pop_front().ok_or(LinuxError::EAGAIN)
+        })?;
+        notify();
+        Ok(conn)
+    }
+
+    /// Sends data to the connected peer, or to `dest` for datagram sockets.
+    pub fn send_to(&self, buf: &[u8], dest: Option<&UnixAddr>) -> LinuxResult<usize> {
+        let (peer, local) = {
+            let inner = self.inner.lock();
+            (
+                inner.peer.as_ref().map(|p| p.0.clone()),
+                inner.local.clone(),
+            )
+        };
+        let res = match self.ty {
+            UnixSocketType::Stream => {
+                if dest.is_some() {
+                    return Err(LinuxError::EISCONN);
+                }
+                let peer = peer.ok_or(LinuxError::ENOTCONN)?;
+                self.block_on(|| {
+                    let mut channel = peer.lock();
+                    if channel.read_closed || channel.write_closed {
+                        return Err(LinuxError::EPIPE);
+                    }
+                    let len = buf.len().min(UNIX_STREAM_BUF_LEN - channel.stream.len());
+                    if len == 0 && !buf.is_empty() {
+                        return Err(LinuxError::EAGAIN);
+                    }
+                    channel.stream.extend(&buf[..len]);
+                    Ok(len)
+                })
+            }
+            UnixSocketType::Datagram => {
+                let peer = match dest {
+                    Some(dest) => {
+                        let target = Self::lookup(dest)?;
+                        if target.ty != UnixSocketType::Datagram {
+                            return Err(LinuxError::EPROTOTYPE);
+                        }
+                        target.rx.clone()
+                    }
+                    None => peer.ok_or(LinuxError::ENOTCONN)?,
+                };
+                self.block_on(|| {
+                    let mut channel = peer.lock();
+                    if channel.read_closed {
+                        return Err(LinuxError::ECONNREFUSED);
+                    }
+                    if channel.dgrams.len() >= UNIX_DGRAM_QUEUE_LEN {
+                        return Err(LinuxError::EAGAIN);
+                    }
+                    channel.dgrams.push_back((buf.to_vec(), local.clone()));
+                    Ok(buf.len())
+                })
+            }
+        }?;
+        notify();
+        Ok(res)
+    }
+
+    /// Receives data, returns the number of bytes read and the source address
+    /// for datagram sockets.
+    ///
+    /// A datagram larger than `buf` is truncated.
+    pub fn recv_from(&self, buf: &mut [u8]) -> LinuxResult<(usize, Option<UnixAddr>)> {
+        let res = match self.ty {
+            UnixSocketType::Stream => {
+                if self.inner.lock().peer.is_none() {
+                    return Err(LinuxError::ENOTCONN);
+                }
+                self.block_on(|| {
+                    let mut channel = self.rx.lock();
+                    if channel.stream.is_empty() {
+                        if channel.write_closed || channel.read_closed || buf.is_empty() {
+                            return Ok((0, None)); // end of stream
+                        }
+                        return Err(LinuxError::EAGAIN);
+                    }
+                    let len = buf.len().min(channel.stream.len());
+                    for (dst, src) in buf.iter_mut().zip(channel.stream.drain(..len)) {
+                        *dst = src;
+                    }
+                    Ok((len, None))
+                })
+            }
+            UnixSocketType::Datagram => self.block_on(|| {
+                let mut channel = self.rx.lock();
+                let (data, src) = channel.dgrams.pop_front().ok_or(LinuxError::EAGAIN)?;
+                let len = buf.len().min(data.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                Ok((len, Some(src)))
+            }),
+        }?;
+        notify();
+        Ok(res)
+    }
+
+    /// Shuts down the reading (`SHUT_RD`), writing (`SHUT_WR`) or both halves
+    /// of the connection.
+    pub fn shutdown(&self, how: c_int) -> LinuxResult {
+        let (read, write) = match how as u32 {
+            ctypes::SHUT_RD => (true, false),
+            ctypes::SHUT_WR => (false, true),
+            ctypes::SHUT_RDWR => (true, true),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let inner = self.inner.lock();
+        let (peer, _) = inner.peer.as_ref().ok_or(LinuxError::ENOTCONN)?;
+        if read {
+            self.rx.lock().read_closed = true;
+        }
+        if write {
+            peer.lock().write_closed = true;
+        }
+        notify();
+        Ok(())
+    }
+
+    pub fn poll(&self) -> LinuxResult<PollState> {
+        let peer = {
+            let inner = self.inner.lock();
+            if let Some(listener) = inner.listener.as_ref() {
+                return Ok(PollState {
+                    readable: !listener.pending.is_empty(),
+                    writable: false,
+                });
+            }
+            inner.peer.as_ref().map(|(peer, _)| peer.clone())
+        };
+        // one channel is locked at a time, as the peer may be polling too
+        let readable = {
+            let rx = self.rx.lock();
+            match self.ty {
+                UnixSocketType::Stream => {
+                    peer.is_some() && (!rx.stream.is_empty() || rx.write_closed || rx.read_closed)
+                }
+                UnixSocketType::Datagram => !rx.dgrams.is_empty(),
+            }
+        };
+        let peer = peer.as_ref().map(|peer| peer.lock());
+        let writable = match self.ty {
+            UnixSocketType::Stream => peer.is_some_and(|peer| {
+                peer.read_closed || peer.write_closed || peer.stream.len() < UNIX_STREAM_BUF_LEN
+            }),
+            UnixSocketType::Datagram => {
+                peer.is_none_or(|peer| peer.read_closed || peer.dgrams.len() < UNIX_DGRAM_QUEUE_LEN)
+            }
+        };
+        Ok(PollState { readable, writable })
+    }
+
+    pub fn set_option(
+        &self,
+        level: u32,
+        name: u32,
+        _optval: *const c_void,
+        _optlen: ctypes::socklen_t,
+    ) -> LinuxResult {
+        match (level, name) {
+            // diff: the buffer sizes are fixed, ignore them
+            (ctypes::SOL_SOCKET, ctypes::SO_RCVBUF) | (ctypes::SOL_SOCKET, ctypes::SO_SNDBUF) => {
+                Ok(())
+            }
+            _ => Err(LinuxError::ENOPROTOOPT),
+        }
+    }
+
+    pub fn get_option(&self, level: u32, name: u32) -> LinuxResult<c_int> {
+        match (level, name) {
+            (ctypes::SOL_SOCKET, ctypes::SO_TYPE) => Ok(match self.ty {
+                UnixSocketType::Stream => ctypes::SOCK_STREAM as c_int,
+                UnixSocketType::Datagram => ctypes::SOCK_DGRAM as c_int,
+            }),
+            (ctypes::SOL_SOCKET, ctypes::SO_ERROR) => Ok(0),
+            (ctypes::SOL_SOCKET, ctypes::SO_RCVBUF) | (ctypes::SOL_SOCKET, ctypes::SO_SNDBUF) => {
+                Ok(UNIX_STREAM_BUF_LEN as c_int)
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_ACCEPTCONN) => {
+                Ok(self.inner.lock().listener.is_some() as c_int)
+            }
+            _ => Err(LinuxError::ENOPROTOOPT),
+        }
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+        if inner.local != UnixAddr::Unnamed {
+            let mut names = UNIX_NAMES.lock();
+            if names
+                .get(&inner.local)
+                .is_some_and(|s| s.strong_count() == 0)
+            {
+                names.remove(&inner.local);
+            }
+        }
+        self.rx.lock().read_closed = true;
+        if let Some((peer, _)) = inner.peer.take() {
+            peer.lock().write_closed = true;
+        }
+        // pending connections are dropped, and the peers see the end of stream
+        notify();
+    }
+}
+
+impl FileLike for UnixSocket {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        Ok(self.recv_from(buf)?.0)
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        self.send_to(buf, None)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o140000 | 0o777u32; // S_IFSOCK | rwxrwxrwx
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            st_uid: 1000,
+            st_gid: 1000,
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        self.poll()
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+}
+
+/// Parses the socket type of `AF_UNIX` sockets.
+pub fn unix_socket_type(socktype: u32, protocol: u32) -> LinuxResult<UnixSocketType> {
+    if protocol != 0 {
+        return Err(LinuxError::EPROTONOSUPPORT);
+    }
+    match socktype {
+        ctypes::SOCK_STREAM => Ok(UnixSocketType::Stream),
+        ctypes::SOCK_DGRAM => Ok(UnixSocketType::Datagram),
+        // diff: `SOCK_SEQPACKET` is not supported
+        _ => Err(LinuxError::EPROTONOSUPPORT),
+    }
+}
+
+/// Create a pair of connected sockets, whose file descriptors are stored to
+/// `sv`, an array of 2 integers.
+///
+/// Only `AF_UNIX` is supported. Return 0 if success.
+pub fn sys_socketpair(domain: c_int, socktype: c_int, protocol: c_int, sv: *mut c_int) -> c_int {
+    debug!(
+        "sys_socketpair <= {} {} {} {:#x}",
+        domain, socktype, protocol, sv as usize
+    );
+    syscall_body!(sys_socketpair, {
+        if sv.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if domain as u32 != ctypes::AF_UNIX {
+            return Err(LinuxError::EOPNOTSUPP);
+        }
+        let ty = unix_socket_type(socktype as u32, protocol as u32)?;
+
+        let (a, b) = UnixSocket::new_pair(ty);
+        let fd0 = add_file_like(Arc::new(a))?;
+        let fd1 = add_file_like(Arc::new(b)).inspect_err(|_| {
+            close_file_like(fd0).ok();
+        })?;
+
+        unsafe {
+            *sv = fd0;
+            *sv.add(1) = fd1;
+        }
+        Ok(0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nonblocking(socket: &UnixSocket) -> &UnixSocket {
+        socket.nonblocking.store(true, Ordering::Release);
+        socket
+    }
+
+    #[test]
+    fn test_stream_pair() {
+        let (a, b) = UnixSocket::new_pair(UnixSocketType::Stream);
+        let b = nonblocking(&b);
+        let mut buf = [0; 16];
+        assert_eq!(b.recv_from(&mut buf).err(), Some(LinuxError::EAGAIN));
+
+        assert_eq!(a.send_to(b"hello", None), Ok(5));
+        let (pa, pb) = (a.poll().unwrap(), b.poll().unwrap());
+        assert!(!pa.readable && pa.writable);
+        assert!(pb.readable && pb.writable);
+        assert_eq!(b.recv_from(&mut buf[..3]), Ok((3, None)));
+        assert_eq!(b.recv_from(&mut buf), Ok((2, None)));
+        assert_eq!(&buf[..2], b"lo");
+
+        // the end of stream after the peer shuts down writing
+        a.shutdown(ctypes::SHUT_WR as _).unwrap();
+        assert!(b.poll().unwrap().readable);
+        assert_eq!(b.recv_from(&mut buf), Ok((0, None)));
+        assert_eq!(a.send_to(b"hello", None).err(), Some(LinuxError::EPIPE));
+    }
+
+    #[test]
+    fn test_dgram_truncated() {
+        let (a, b) = UnixSocket::new_pair(UnixSocketType::Datagram);
+        let b = nonblocking(&b);
+        a.send_to(b"hello world", None).unwrap();
+        a.send_to(b"bye", None).unwrap();
+        let mut buf = [0; 5];
+        assert_eq!(b.recv_from(&mut buf), Ok((5, Some(UnixAddr::Unnamed))));
+        assert_eq!(&buf, b"hello");
+        assert_eq!(b.recv_from(&mut buf), Ok((3, Some(UnixAddr::Unnamed))));
+        assert_eq!(b.recv_from(&mut buf).err(), Some(LinuxError::EAGAIN));
+    }
+
+    #[test]
+    fn test_listen_abstract() {
+        let addr = UnixAddr::Abstract(b"test_listen_abstract".to_vec());
+        let server = Arc::new(UnixSocket::new(UnixSocketType::Stream));
+        server.bind(addr.clone()).unwrap();
+        server.listen(1).unwrap();
+        let other = Arc::new(UnixSocket::new(UnixSocketType::Stream));
+        assert_eq!(other.bind(addr.clone()).err(), Some(LinuxError::EADDRINUSE));
+
+        let client = UnixSocket::new(UnixSocketType::Stream);
+        client.connect(&addr).unwrap();
+        assert!(server.poll().unwrap().readable);
+        let conn = server.accept().unwrap();
+        assert_eq!(client.peer_addr(), Ok(addr.clone()));
+        assert_eq!(conn.local_addr(), addr);
+
+        client.send_to(b"ping", None).unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(conn.recv_from(&mut buf), Ok((4, None)));
+        assert_eq!(&buf, b"ping");
+
+        // the name is released when the socket is closed
+        drop(server);
+        other.bind(addr).unwrap();
+    }
+
+    #[cfg(feature = "multitask")]
+    #[test]
+    fn test_blocking_recv() {
+        use core::sync::atomic::AtomicBool;
+
+        static SENT: AtomicBool = AtomicBool::new(false);
+        axtask::init_scheduler();
+
+        let (a, b) = UnixSocket::new_pair(UnixSocketType::Stream);
+        axtask::spawn(move || {
+            for _ in 0..10 {
+                axtask::yield_now();
+            }
+            SENT.store(true, Ordering::Release);
+            a.send_to(b"wake", None).unwrap();
+        });
+        // blocked until the other task sends the data
+        let mut buf = [0; 4];
+        assert_eq!(b.recv_from(&mut buf), Ok((4, None)));
+        assert!(SENT.load(Ordering::Acquire));
+        assert_eq!(&buf, b"wake");
+    }
+}
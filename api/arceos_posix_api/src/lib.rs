@@ -45,8 +45,9 @@ pub use imp::fd_ops::{
 };
 #[cfg(feature = "fs")]
 pub use imp::fs::{
-    Directory, File, sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_openat, sys_rename,
-    sys_stat,
+    Directory, File, sys_access, sys_chmod, sys_chown, sys_fchmod, sys_flock, sys_fstat,
+    sys_futimens, sys_getcwd, sys_lseek, sys_lstat, sys_mkdir, sys_mount, sys_open, sys_openat,
+    sys_readlink, sys_rename, sys_stat, sys_symlink, sys_umask, sys_umount2, sys_utimensat,
 };
 #[cfg(feature = "select")]
 pub use imp::io_mpx::sys_select;
@@ -55,8 +56,8 @@ pub use imp::io_mpx::{sys_epoll_create, sys_epoll_ctl, sys_epoll_wait};
 #[cfg(feature = "net")]
 pub use imp::net::{
     sys_accept, sys_bind, sys_connect, sys_freeaddrinfo, sys_getaddrinfo, sys_getpeername,
-    sys_getsockname, sys_listen, sys_recv, sys_recvfrom, sys_send, sys_sendto, sys_shutdown,
-    sys_socket,
+    sys_getsockname, sys_getsockopt, sys_listen, sys_recv, sys_recvfrom, sys_send, sys_sendto,
+    sys_setsockopt, sys_shutdown, sys_socket,
 };
 #[cfg(feature = "pipe")]
 pub use imp::pipe::sys_pipe;
@@ -66,3 +67,5 @@ pub use imp::pthread::mutex::{
 };
 #[cfg(feature = "multitask")]
 pub use imp::pthread::{sys_pthread_create, sys_pthread_exit, sys_pthread_join, sys_pthread_self};
+#[cfg(feature = "net")]
+pub use imp::unix::sys_socketpair;
@@ -19,6 +19,16 @@ Host: ident.me\r\n\
 Accept: */*\r\n\
 \r\n";
 
+// The local server started by `scripts/net/tls-server.sh`, which is the host
+// machine in the QEMU user-net setup.
+#[cfg(feature = "tls")]
+const TLS_DEST: &str = "10.0.2.2:4433";
+#[cfg(feature = "tls")]
+const TLS_SERVER_NAME: &str = "localhost";
+#[cfg(feature = "tls")]
+const TLS_CA_CERT: &str = "/ca.pem";
+
+#[cfg_attr(feature = "tls", allow(dead_code))]
 fn client() -> io::Result<()> {
     for addr in DEST.to_socket_addrs()? {
         println!("dest: {} ({})", DEST, addr);
@@ -33,8 +43,28 @@ fn client() -> io::Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "tls")]
+fn tls_client() -> io::Result<()> {
+    use std::net::{TlsStream, tls};
+
+    let config = tls::client_config(tls::load_certs(TLS_CA_CERT)?)?;
+    let stream = TcpStream::connect(TLS_DEST)?;
+    let mut stream = TlsStream::connect(stream, TLS_SERVER_NAME, config)?;
+    println!("dest: {} (TLS 1.3)", TLS_DEST);
+
+    stream.write_all(b"GET / HTTP/1.0\r\nHost: localhost\r\n\r\n")?;
+    let mut buf = [0; 2048];
+    let n = stream.read(&mut buf)?;
+    let response = core::str::from_utf8(&buf[..n]).unwrap_or("<binary>");
+    println!("{}", response);
+    stream.shutdown()
+}
+
 #[cfg_attr(feature = "axstd", unsafe(no_mangle))]
 fn main() {
     println!("Hello, simple http client!");
+    #[cfg(not(feature = "tls"))]
     client().expect("test http client failed");
+    #[cfg(feature = "tls")]
+    tls_client().expect("test https client failed");
 }
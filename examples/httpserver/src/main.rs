@@ -13,25 +13,11 @@
 #[cfg(feature = "axstd")]
 extern crate axstd as std;
 
-use std::io::{self, prelude::*};
-use std::net::{TcpListener, TcpStream};
-use std::thread;
+use axhttp::{Request, Response, Router, Server};
 
 const LOCAL_IP: &str = "0.0.0.0";
 const LOCAL_PORT: u16 = 5555;
 
-macro_rules! header {
-    () => {
-        "\
-HTTP/1.1 200 OK\r\n\
-Content-Type: text/html\r\n\
-Content-Length: {}\r\n\
-Connection: close\r\n\
-\r\n\
-{}"
-    };
-}
-
 const CONTENT: &str = r#"<html>
 <head>
   <title>Hello, ArceOS</title>
@@ -59,38 +45,20 @@ macro_rules! info {
     };
 }
 
-fn http_server(mut stream: TcpStream) -> io::Result<()> {
-    let mut buf = [0u8; 4096];
-    let _len = stream.read(&mut buf)?;
-
-    let response = format!(header!(), CONTENT.len(), CONTENT);
-    stream.write_all(response.as_bytes())?;
-
-    Ok(())
-}
-
-fn accept_loop() -> io::Result<()> {
-    let listener = TcpListener::bind((LOCAL_IP, LOCAL_PORT))?;
-    println!("listen on: http://{}/", listener.local_addr().unwrap());
-
-    let mut i = 0;
-    loop {
-        match listener.accept() {
-            Ok((stream, addr)) => {
-                info!("new client {}: {}", i, addr);
-                thread::spawn(move || match http_server(stream) {
-                    Err(e) => info!("client connection error: {:?}", e),
-                    Ok(()) => info!("client {} closed successfully", i),
-                });
-            }
-            Err(e) => return Err(e),
-        }
-        i += 1;
-    }
+fn index(req: Request) -> Response {
+    info!("{} {} from {}", req.method(), req.path(), req.peer_addr());
+    Response::html(CONTENT)
 }
 
 #[cfg_attr(feature = "axstd", unsafe(no_mangle))]
 fn main() {
     println!("Hello, ArceOS HTTP server!");
-    accept_loop().expect("test HTTP server failed");
+
+    let router = Router::new().get("/", index);
+    #[cfg(feature = "fs")]
+    let router = router.get("/static/*", axhttp::StaticFiles::new("/www"));
+
+    let server = Server::bind((LOCAL_IP, LOCAL_PORT)).expect("failed to bind");
+    println!("listen on: http://{}/", server.local_addr().unwrap());
+    server.serve(router).expect("test HTTP server failed");
 }
@@ -26,6 +26,10 @@ const CMD_TABLE: &[(&str, CmdHandler)] = &[
     ("help", do_help),
     ("ls", do_ls),
     ("mkdir", do_mkdir),
+    #[cfg(all(feature = "axstd", feature = "capture"))]
+    ("pcap", do_pcap),
+    #[cfg(all(feature = "axstd", feature = "net"))]
+    ("ping", do_ping),
     ("pwd", do_pwd),
     ("rm", do_rm),
     ("uname", do_uname),
@@ -262,6 +266,155 @@ fn do_uname(_args: &str) {
     );
 }
 
+#[cfg(all(feature = "axstd", feature = "net"))]
+fn do_ping(args: &str) {
+    use std::net::{IpAddr, ToSocketAddrs};
+    use std::os::arceos::api::net as api;
+    use std::time::{Duration, Instant};
+
+    const ICMP_ECHO_REPLY: u8 = 0;
+    const ICMP_ECHO_REQUEST: u8 = 8;
+    const ICMP_HEADER_LEN: usize = 8;
+    const PAYLOAD_LEN: usize = 56;
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn ping_one(
+        socket: &api::AxIcmpSocketHandle,
+        dest: IpAddr,
+        seq: u16,
+    ) -> io::Result<Option<Duration>> {
+        let mut request = [0u8; ICMP_HEADER_LEN + PAYLOAD_LEN];
+        request[0] = ICMP_ECHO_REQUEST;
+        // the checksum and identifier are filled in by the network stack
+        request[6..8].copy_from_slice(&seq.to_be_bytes());
+        for (i, b) in request[ICMP_HEADER_LEN..].iter_mut().enumerate() {
+            *b = i as u8;
+        }
+
+        let start = Instant::now();
+        api::ax_icmp_send_to(socket, &request, dest)?;
+
+        let mut reply = [0u8; 1024];
+        while start.elapsed() < TIMEOUT {
+            api::ax_poll_interfaces()?;
+            match api::ax_icmp_recv_from(socket, &mut reply) {
+                Ok((len, from)) => {
+                    if from == dest
+                        && len >= ICMP_HEADER_LEN
+                        && reply[0] == ICMP_ECHO_REPLY
+                        && reply[6..8] == seq.to_be_bytes()
+                    {
+                        return Ok(Some(start.elapsed()));
+                    }
+                }
+                Err(io::Error::WouldBlock) => std::thread::yield_now(),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+
+    let mut count = 4;
+    let mut host = None;
+    let mut iter = args.split_whitespace();
+    while let Some(arg) = iter.next() {
+        match arg {
+            "-c" => match iter.next().and_then(|n| n.parse::<u16>().ok()) {
+                Some(n) if n > 0 => count = n,
+                _ => {
+                    print_err!("ping", "invalid count");
+                    return;
+                }
+            },
+            _ => host = Some(arg),
+        }
+    }
+    let Some(host) = host else {
+        print_err!("ping", "missing host operand");
+        return;
+    };
+    let dest = match (host, 0).to_socket_addrs().map(|mut addrs| addrs.next()) {
+        Ok(Some(addr)) => addr.ip(),
+        Ok(None) => {
+            print_err!("ping", host, "Name or service not known");
+            return;
+        }
+        Err(e) => {
+            print_err!("ping", host, e);
+            return;
+        }
+    };
+
+    let socket = api::ax_icmp_socket();
+    if let Err(e) = api::ax_icmp_bind(&socket, 0) {
+        print_err!("ping", e);
+        return;
+    }
+    api::ax_icmp_set_nonblocking(&socket, true).unwrap();
+
+    println!("PING {} ({}) {} bytes of data.", host, dest, PAYLOAD_LEN);
+    let mut received = 0;
+    for seq in 1..=count {
+        match ping_one(&socket, dest, seq) {
+            Ok(Some(rtt)) => {
+                received += 1;
+                println!(
+                    "{} bytes from {}: icmp_seq={} time={}.{:03} ms",
+                    ICMP_HEADER_LEN + PAYLOAD_LEN,
+                    dest,
+                    seq,
+                    rtt.as_micros() / 1000,
+                    rtt.as_micros() % 1000,
+                );
+            }
+            Ok(None) => println!("Request timeout for icmp_seq={}", seq),
+            Err(e) => {
+                print_err!("ping", dest, e);
+                return;
+            }
+        }
+        if seq < count {
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+    println!("--- {} ping statistics ---", host);
+    println!(
+        "{} packets transmitted, {} received, {}% packet loss",
+        count,
+        received,
+        (count - received) as u32 * 100 / count as u32,
+    );
+}
+
+#[cfg(all(feature = "axstd", feature = "capture"))]
+fn do_pcap(args: &str) {
+    use std::os::arceos::api::net as api;
+
+    const SNAPLEN: usize = 65535;
+    const RING_SIZE: usize = 1024 * 1024;
+
+    let (subcmd, arg) = split_whitespace(args);
+    match subcmd {
+        "start" => {
+            api::ax_net_capture_start(SNAPLEN, RING_SIZE, arg == "console");
+        }
+        "stop" => api::ax_net_capture_stop(),
+        "save" => {
+            if arg.is_empty() {
+                print_err!("pcap", "no file specified");
+                return;
+            }
+            let data = api::ax_net_capture_pcap();
+            if let Err(e) = File::create(arg).and_then(|mut file| file.write_all(&data)) {
+                print_err!("pcap", arg, e);
+            } else {
+                println!("{} bytes written to {}", data.len(), arg);
+            }
+        }
+        _ => println!("usage: pcap start [console] | stop | save FILE"),
+    }
+}
+
 fn do_help(_args: &str) {
     println!("Available commands:");
     for (name, _) in CMD_TABLE {
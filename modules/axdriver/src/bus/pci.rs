@@ -1,8 +1,8 @@
-use crate::{AllDevices, prelude::*};
+use crate::{AllDevices, DeviceBus, prelude::*};
 use axdriver_pci::{
     BarInfo, Cam, Command, DeviceFunction, HeaderType, MemoryBarType, PciRangeAllocator, PciRoot,
 };
-use axhal::mem::phys_to_virt;
+use axhal::mem::{VirtAddr, phys_to_virt};
 
 const PCI_BAR_NUM: u8 = 6;
 
@@ -82,6 +82,21 @@ fn config_pci_device(
     Ok(())
 }
 
+/// Reads the interrupt line of the function from its configuration space,
+/// `None` if it has no interrupt pin, or the line is not assigned.
+fn interrupt_line(ecam_base: VirtAddr, bdf: DeviceFunction) -> Option<usize> {
+    let offset = ((bdf.bus as usize) << 20)
+        | ((bdf.device as usize) << 15)
+        | ((bdf.function as usize) << 12)
+        | 0x3c;
+    let reg = unsafe { ((ecam_base.as_usize() + offset) as *const u16).read_volatile() };
+    let (line, pin) = (reg & 0xff, reg >> 8);
+    match (line, pin) {
+        (_, 0) | (0 | 0xff, _) => None,
+        (line, _) => Some(line as usize),
+    }
+}
+
 impl AllDevices {
     pub(crate) fn probe_bus_devices(&mut self) {
         let base_vaddr = phys_to_virt(axconfig::devices::PCI_ECAM_BASE.into());
@@ -107,7 +122,16 @@ impl AllDevices {
                                 bdf,
                                 dev.device_name(),
                             );
-                            self.add_device(dev);
+                            let bus = DeviceBus::Pci {
+                                bus: bdf.bus,
+                                device: bdf.device,
+                                function: bdf.function,
+                                vendor_id: dev_info.vendor_id,
+                                device_id: dev_info.device_id,
+                                class: [dev_info.class, dev_info.subclass, dev_info.prog_if],
+                            };
+                            let irq = interrupt_line(base_vaddr, bdf);
+                            self.add_device(dev, bus, irq);
                             continue; // skip to the next device
                         }
                     }),
@@ -0,0 +1,218 @@
+//! A software NIC that exchanges Ethernet frames through in-memory queues.
+//!
+//! It's used to test the network stack without a real or emulated NIC. The
+//! other end of the link is either another [`ChannelNetDev`] (see
+//! [`ChannelNetDev::pair`]), or a [`NetChannel`] driven by the test, which can
+//! inject frames one by one or replay a pcap file.
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec, vec::Vec};
+use core::ptr::NonNull;
+
+use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
+use axdriver_net::{EthernetAddress, NetBufPtr, NetDriverOps};
+use spin::Mutex;
+
+/// The maximum number of frames queued in each direction.
+const QUEUE_SIZE: usize = 256;
+/// The maximum length of an Ethernet frame without FCS.
+const MAX_FRAME_LEN: usize = 1514;
+
+const PCAP_HEADER_LEN: usize = 24;
+const PCAP_RECORD_HEADER_LEN: usize = 16;
+const LINKTYPE_ETHERNET: u32 = 1;
+
+type FrameQueue = Arc<Mutex<VecDeque<Vec<u8>>>>;
+
+fn new_queue() -> FrameQueue {
+    Arc::new(Mutex::new(VecDeque::with_capacity(QUEUE_SIZE)))
+}
+
+/// A NIC connected to an in-memory link.
+pub struct ChannelNetDev {
+    mac: EthernetAddress,
+    rx: FrameQueue,
+    tx: FrameQueue,
+}
+
+/// The other end of the link of a [`ChannelNetDev`].
+pub struct NetChannel {
+    to_dev: FrameQueue,
+    from_dev: FrameQueue,
+}
+
+impl ChannelNetDev {
+    /// Creates a NIC with the given MAC address, and returns the other end of
+    /// its link.
+    pub fn new(mac: [u8; 6]) -> (Self, NetChannel) {
+        let (to_dev, from_dev) = (new_queue(), new_queue());
+        let dev = Self {
+            mac: EthernetAddress(mac),
+            rx: to_dev.clone(),
+            tx: from_dev.clone(),
+        };
+        (dev, NetChannel { to_dev, from_dev })
+    }
+
+    /// Creates two NICs connected back-to-back.
+    pub fn pair(mac_a: [u8; 6], mac_b: [u8; 6]) -> (Self, Self) {
+        let (a_to_b, b_to_a) = (new_queue(), new_queue());
+        let a = Self {
+            mac: EthernetAddress(mac_a),
+            rx: b_to_a.clone(),
+            tx: a_to_b.clone(),
+        };
+        let b = Self {
+            mac: EthernetAddress(mac_b),
+            rx: a_to_b,
+            tx: b_to_a,
+        };
+        (a, b)
+    }
+}
+
+impl NetChannel {
+    /// Sends a frame to the NIC.
+    ///
+    /// Returns [`DevError::Again`] if the receive queue of the NIC is full.
+    pub fn send(&self, frame: &[u8]) -> DevResult {
+        if frame.len() > MAX_FRAME_LEN {
+            return Err(DevError::InvalidParam);
+        }
+        let mut queue = self.to_dev.lock();
+        if queue.len() >= QUEUE_SIZE {
+            return Err(DevError::Again);
+        }
+        queue.push_back(frame.to_vec());
+        Ok(())
+    }
+
+    /// Receives a frame transmitted by the NIC, if any.
+    pub fn recv(&self) -> Option<Vec<u8>> {
+        self.from_dev.lock().pop_front()
+    }
+
+    /// Returns the number of frames sent to the NIC but not received by it
+    /// yet.
+    pub fn pending(&self) -> usize {
+        self.to_dev.lock().len()
+    }
+
+    /// Sends all the frames in a pcap file to the NIC, regardless of their
+    /// timestamps and the size of the receive queue.
+    ///
+    /// Both byte orders and both timestamp resolutions are accepted, but the
+    /// link type must be Ethernet. Returns the number of frames sent.
+    pub fn replay_pcap(&self, pcap: &[u8]) -> DevResult<usize> {
+        let header = pcap.get(..PCAP_HEADER_LEN).ok_or(DevError::InvalidParam)?;
+        let read_u32: fn(&[u8]) -> u32 = match header[..4] {
+            [0xd4, 0xc3, 0xb2, 0xa1] | [0x4d, 0x3c, 0xb2, 0xa1] => {
+                |b| u32::from_le_bytes(b[..4].try_into().unwrap())
+            }
+            [0xa1, 0xb2, 0xc3, 0xd4] | [0xa1, 0xb2, 0x3c, 0x4d] => {
+                |b| u32::from_be_bytes(b[..4].try_into().unwrap())
+            }
+            _ => return Err(DevError::InvalidParam),
+        };
+        if read_u32(&header[20..]) != LINKTYPE_ETHERNET {
+            return Err(DevError::Unsupported);
+        }
+
+        let mut frames = Vec::new();
+        let mut pos = PCAP_HEADER_LEN;
+        while pos < pcap.len() {
+            let record = pcap
+                .get(pos..pos + PCAP_RECORD_HEADER_LEN)
+                .ok_or(DevError::InvalidParam)?;
+            let incl_len = read_u32(&record[8..]) as usize;
+            pos += PCAP_RECORD_HEADER_LEN;
+            let frame = pcap
+                .get(pos..pos + incl_len)
+                .ok_or(DevError::InvalidParam)?;
+            frames.push(frame.to_vec());
+            pos += incl_len;
+        }
+
+        let count = frames.len();
+        self.to_dev.lock().extend(frames);
+        Ok(count)
+    }
+}
+
+fn into_buf_ptr(frame: Vec<u8>) -> NetBufPtr {
+    let mut frame = Box::new(frame);
+    let len = frame.len();
+    let buf_ptr = NonNull::new(frame.as_mut_ptr()).unwrap();
+    let raw_ptr = NonNull::new(Box::into_raw(frame) as *mut u8).unwrap();
+    NetBufPtr::new(raw_ptr, buf_ptr, len)
+}
+
+fn from_buf_ptr(ptr: NetBufPtr) -> Vec<u8> {
+    // SAFETY: the pointer is created by `into_buf_ptr`
+    let mut frame = unsafe { Box::from_raw(ptr.raw_ptr::<Vec<u8>>()) };
+    frame.truncate(ptr.packet_len());
+    *frame
+}
+
+impl BaseDriverOps for ChannelNetDev {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Net
+    }
+
+    fn device_name(&self) -> &str {
+        "channel-net"
+    }
+}
+
+impl NetDriverOps for ChannelNetDev {
+    fn mac_address(&self) -> EthernetAddress {
+        self.mac
+    }
+
+    fn can_transmit(&self) -> bool {
+        self.tx.lock().len() < QUEUE_SIZE
+    }
+
+    fn can_receive(&self) -> bool {
+        !self.rx.lock().is_empty()
+    }
+
+    fn rx_queue_size(&self) -> usize {
+        QUEUE_SIZE
+    }
+
+    fn tx_queue_size(&self) -> usize {
+        QUEUE_SIZE
+    }
+
+    fn recycle_rx_buffer(&mut self, rx_buf: NetBufPtr) -> DevResult {
+        drop(from_buf_ptr(rx_buf));
+        Ok(())
+    }
+
+    fn recycle_tx_buffers(&mut self) -> DevResult {
+        // the buffers are consumed in `transmit`
+        Ok(())
+    }
+
+    fn transmit(&mut self, tx_buf: NetBufPtr) -> DevResult {
+        let frame = from_buf_ptr(tx_buf);
+        let mut queue = self.tx.lock();
+        if queue.len() >= QUEUE_SIZE {
+            return Err(DevError::Again);
+        }
+        queue.push_back(frame);
+        Ok(())
+    }
+
+    fn receive(&mut self) -> DevResult<NetBufPtr> {
+        let frame = self.rx.lock().pop_front().ok_or(DevError::Again)?;
+        Ok(into_buf_ptr(frame))
+    }
+
+    fn alloc_tx_buffer(&mut self, size: usize) -> DevResult<NetBufPtr> {
+        if size > MAX_FRAME_LEN {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(into_buf_ptr(vec![0; size]))
+    }
+}
@@ -174,3 +174,13 @@ cfg_if::cfg_if! {
         }
     }
 }
+
+cfg_if::cfg_if! {
+    if #[cfg(net_dev = "channel-net")] {
+        pub struct ChannelNetDriver;
+        register_net_driver!(ChannelNetDriver, crate::channel::ChannelNetDev);
+
+        // created by `ChannelNetDev::new`, there is nothing to probe
+        impl DriverProbe for ChannelNetDriver {}
+    }
+}
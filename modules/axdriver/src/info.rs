@@ -0,0 +1,65 @@
+//! Information of the probed devices, recorded by [`init_drivers`].
+//!
+//! [`init_drivers`]: crate::init_drivers
+
+use alloc::{string::String, vec::Vec};
+
+use axdriver_base::DeviceType;
+use spin::Mutex;
+
+/// The bus where a device is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceBus {
+    /// Not on a bus, the device is created by its driver, e.g. RAM disks.
+    Platform,
+    /// A memory-mapped device, e.g. VirtIO MMIO devices.
+    Mmio {
+        /// The base physical address of the registers.
+        base: usize,
+        /// The size of the registers.
+        size: usize,
+    },
+    /// A PCI device function.
+    Pci {
+        /// The bus number.
+        bus: u8,
+        /// The device number on the bus.
+        device: u8,
+        /// The function number of the device.
+        function: u8,
+        /// The vendor ID.
+        vendor_id: u16,
+        /// The device ID.
+        device_id: u16,
+        /// The class, subclass and programming interface of the function.
+        class: [u8; 3],
+    },
+}
+
+/// A probed device.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// The category of the device.
+    pub device_type: DeviceType,
+    /// The name of the driver, e.g. `virtio-blk`.
+    pub driver: String,
+    /// Where the device is found.
+    pub bus: DeviceBus,
+    /// The interrupt line, if it's known.
+    pub irq: Option<usize>,
+}
+
+static DEVICES: Mutex<Vec<DeviceInfo>> = Mutex::new(Vec::new());
+
+/// Returns the probed devices, in the order they are probed.
+///
+/// The devices of each category are in the same order as in [`AllDevices`].
+///
+/// [`AllDevices`]: crate::AllDevices
+pub fn devices() -> Vec<DeviceInfo> {
+    DEVICES.lock().clone()
+}
+
+pub(crate) fn record(info: DeviceInfo) {
+    DEVICES.lock().push(info);
+}
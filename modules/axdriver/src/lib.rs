@@ -11,6 +11,9 @@
 //! is used to represent all devices in that category. Currently, there are 3
 //! categories: [`AxNetDevice`], [`AxBlockDevice`], and [`AxDisplayDevice`].
 //!
+//! The probed devices, e.g. their buses and drivers, are also recorded and
+//! returned by [`devices`].
+//!
 //! # Concepts
 //!
 //! This crate supports two device models depending on the `dyn` feature:
@@ -33,6 +36,7 @@
 //! | Block | `ramdisk` | A RAM disk that stores data in a vector |
 //! | Block | `virtio-blk` | VirtIO block device |
 //! | Network | `virtio-net` | VirtIO network device |
+//! | Network | `channel-net` | A software NIC linked by in-memory queues, for testing |
 //! | Display | `virtio-gpu` | VirtIO graphics device |
 //!
 //! # Other Cargo Features
@@ -61,7 +65,6 @@
 #[macro_use]
 extern crate log;
 
-#[cfg(feature = "dyn")]
 extern crate alloc;
 
 #[macro_use]
@@ -70,6 +73,7 @@ mod macros;
 mod bus;
 mod drivers;
 mod dummy;
+mod info;
 mod structs;
 
 #[cfg(feature = "virtio")]
@@ -78,8 +82,12 @@ mod virtio;
 #[cfg(feature = "ixgbe")]
 mod ixgbe;
 
+#[cfg(feature = "channel-net")]
+mod channel;
+
 pub mod prelude;
 
+pub use self::info::{DeviceBus, DeviceInfo, devices};
 #[allow(unused_imports)]
 use self::prelude::*;
 pub use self::structs::{AxDeviceContainer, AxDeviceEnum};
@@ -91,6 +99,9 @@ pub use self::structs::AxDisplayDevice;
 #[cfg(feature = "net")]
 pub use self::structs::AxNetDevice;
 
+#[cfg(feature = "channel-net")]
+pub use self::channel::{ChannelNetDev, NetChannel};
+
 /// A structure that contains all device drivers, organized by their category.
 #[derive(Default)]
 pub struct AllDevices {
@@ -126,7 +137,7 @@ impl AllDevices {
                     dev.device_type(),
                     dev.device_name(),
                 );
-                self.add_device(dev);
+                self.add_device(dev, DeviceBus::Platform, None);
             }
         });
 
@@ -134,19 +145,44 @@ impl AllDevices {
     }
 
     /// Adds one device into the corresponding container, according to its device category.
+    ///
+    /// The device is recorded in [`devices`] if it's added.
     #[allow(dead_code)]
-    fn add_device(&mut self, dev: AxDeviceEnum) {
-        match dev {
+    fn add_device(&mut self, dev: AxDeviceEnum, bus: DeviceBus, irq: Option<usize>) {
+        let info = DeviceInfo {
+            device_type: dev.device_type(),
+            driver: dev.device_name().into(),
+            bus,
+            irq,
+        };
+        let added = match dev {
             #[cfg(feature = "net")]
-            AxDeviceEnum::Net(dev) => self.net.push(dev),
+            AxDeviceEnum::Net(dev) => push_device(&mut self.net, dev),
             #[cfg(feature = "block")]
-            AxDeviceEnum::Block(dev) => self.block.push(dev),
+            AxDeviceEnum::Block(dev) => push_device(&mut self.block, dev),
             #[cfg(feature = "display")]
-            AxDeviceEnum::Display(dev) => self.display.push(dev),
+            AxDeviceEnum::Display(dev) => push_device(&mut self.display, dev),
+        };
+        if added {
+            info::record(info);
+        } else {
+            warn!(
+                "ignored the {:?} device {:?}",
+                info.device_type, info.driver
+            );
         }
     }
 }
 
+/// Pushes the device, returns `false` if the container is full, i.e. it
+/// already has one device with the static device model.
+#[allow(dead_code)]
+fn push_device<D>(container: &mut AxDeviceContainer<D>, dev: D) -> bool {
+    let len = container.len();
+    container.push(dev);
+    container.len() > len
+}
+
 /// Probes and initializes all device drivers, returns the [`AllDevices`] struct.
 pub fn init_drivers() -> AllDevices {
     info!("Initialize device drivers...");
@@ -23,9 +23,10 @@ pub struct DirEntry<'a> {
 }
 
 /// A builder used to create directories in various manners.
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct DirBuilder {
     recursive: bool,
+    mode: u32,
 }
 
 impl<'a> ReadDir<'a> {
@@ -116,11 +117,20 @@ impl fmt::Debug for DirEntry<'_> {
     }
 }
 
+impl Default for DirBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DirBuilder {
     /// Creates a new set of options with default mode/security settings for all
     /// platforms and also non-recursive.
     pub fn new() -> Self {
-        Self { recursive: false }
+        Self {
+            recursive: false,
+            mode: 0o777,
+        }
     }
 
     /// Indicates that directories should be created recursively, creating all
@@ -131,13 +141,21 @@ impl DirBuilder {
         self
     }
 
+    /// Sets the mode bits that new directories will be created with, `0o777`
+    /// by default. The bits in the file mode creation mask are cleared.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
     /// Creates the specified directory with the options configured in this
     /// builder.
     pub fn create(&self, path: &str) -> Result<()> {
         if self.recursive {
             self.create_dir_all(path)
         } else {
-            crate::root::create_dir(None, path)
+            let perm = fops::FilePerm::from_bits_truncate(self.mode as u16);
+            crate::root::create_dir(None, path, perm)
         }
     }
 
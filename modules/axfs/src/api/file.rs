@@ -1,8 +1,8 @@
 use alloc::vec::Vec;
 use axio::{Result, SeekFrom, default_read_to_end, prelude::*};
-use core::fmt;
+use core::{fmt, time::Duration};
 
-use crate::fops;
+use crate::fops::{self, TimeUpdate};
 
 /// A structure representing a type of file with accessors for each file type.
 /// It is returned by [`Metadata::file_type`] method.
@@ -17,7 +17,11 @@ pub struct File {
 }
 
 /// Metadata information about a file.
-pub struct Metadata(pub(super) fops::FileAttr);
+pub struct Metadata(
+    pub(super) fops::FileAttr,
+    pub(super) fops::Owner,
+    pub(super) fops::FileTimes,
+);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Default, Clone, Debug)]
@@ -65,6 +69,13 @@ impl OpenOptions {
         self
     }
 
+    /// Sets the mode bits that a new file will be created with, `0o666` by
+    /// default. The bits in the file mode creation mask are cleared.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.0.mode(mode);
+        self
+    }
+
     /// Opens a file at `path` with the options specified by `self`.
     pub fn open(&self, path: &str) -> Result<File> {
         fops::File::open(path, &self.0).map(|inner| File { inner })
@@ -91,6 +102,12 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symbolic link, which is only
+    /// possible if it's returned by [`symlink_metadata`](super::symlink_metadata).
+    pub const fn is_symlink(&self) -> bool {
+        matches!(self.0.file_type(), FileType::SymLink)
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -111,6 +128,43 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the user ID of the owner of the file.
+    pub const fn uid(&self) -> u32 {
+        self.1.uid
+    }
+
+    /// Returns the group ID of the owner of the file.
+    pub const fn gid(&self) -> u32 {
+        self.1.gid
+    }
+
+    /// Returns the time of the last access, since the Unix epoch.
+    pub const fn accessed(&self) -> Duration {
+        self.2.accessed
+    }
+
+    /// Returns the time of the last modification of the data, since the Unix
+    /// epoch.
+    pub const fn modified(&self) -> Duration {
+        self.2.modified
+    }
+
+    /// Returns the time of the last change of the data or the metadata, since
+    /// the Unix epoch.
+    pub const fn changed(&self) -> Duration {
+        self.2.changed
+    }
+
+    /// Returns the underlying attributes.
+    pub const fn raw_metadata(&self) -> &fops::FileAttr {
+        &self.0
+    }
+
+    /// Returns the underlying times.
+    pub const fn raw_times(&self) -> &fops::FileTimes {
+        &self.2
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -119,7 +173,11 @@ impl fmt::Debug for Metadata {
             .field("file_type", &self.file_type())
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
+            .field("is_symlink", &self.is_symlink())
             .field("permissions", &self.permissions())
+            .field("uid", &self.uid())
+            .field("gid", &self.gid())
+            .field("modified", &self.modified())
             .finish_non_exhaustive()
     }
 }
@@ -161,7 +219,24 @@ impl File {
 
     /// Queries metadata about the underlying file.
     pub fn metadata(&self) -> Result<Metadata> {
-        self.inner.get_attr().map(Metadata)
+        let (attr, owner) = (self.inner.get_attr()?, self.inner.owner()?);
+        Ok(Metadata(attr, owner, self.inner.times()?))
+    }
+
+    /// Changes the permissions on the underlying file.
+    pub fn set_permissions(&self, perm: Permissions) -> Result<()> {
+        self.inner.set_perm(perm)
+    }
+
+    /// Changes the access and modification times of the underlying file, see
+    /// [`set_times`](super::set_times).
+    pub fn set_times(&self, accessed: TimeUpdate, modified: TimeUpdate) -> Result<()> {
+        self.inner.set_times(accessed, modified)
+    }
+
+    /// Changes the modification time of the underlying file.
+    pub fn set_modified(&self, time: Duration) -> Result<()> {
+        self.set_times(TimeUpdate::Omit, TimeUpdate::At(time))
     }
 }
 
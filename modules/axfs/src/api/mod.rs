@@ -5,10 +5,36 @@ mod file;
 
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
+pub use crate::cache::CacheStats;
+pub use crate::fops::{AccessMode, TimeUpdate};
 
-use alloc::{string::String, vec::Vec};
+use alloc::{format, string::String, vec::Vec};
 use axio::{self as io, prelude::*};
 
+/// A mounted filesystem, returned by [`mounts`].
+#[derive(Debug, Clone)]
+pub struct MountInfo {
+    /// The mounted device, or the source directory of bind mounts.
+    pub source: String,
+    /// The mount point.
+    pub target: String,
+    /// The filesystem type, e.g. `tmpfs`.
+    pub fstype: String,
+}
+
+/// A disk or partition, returned by [`block_devices`].
+#[derive(Debug, Clone)]
+pub struct BlockDeviceInfo {
+    /// The path used as the source to mount it, e.g. `/dev/disk0p1`.
+    pub path: String,
+    /// The size in bytes.
+    pub size: u64,
+    /// The partition name, only for GPT partitions.
+    pub label: Option<String>,
+    /// The partition UUID, as Linux's `PARTUUID`.
+    pub uuid: Option<String>,
+}
+
 /// Returns an iterator over the entries within a directory.
 pub fn read_dir(path: &str) -> io::Result<ReadDir> {
     ReadDir::new(path)
@@ -56,7 +82,104 @@ pub fn write<C: AsRef<[u8]>>(path: &str, contents: C) -> io::Result<()> {
 /// Given a path, query the file system to get information about a file,
 /// directory, etc.
 pub fn metadata(path: &str) -> io::Result<Metadata> {
-    crate::root::lookup(None, path)?.get_attr().map(Metadata)
+    crate::root::stat(path, true).map(|(attr, owner, times)| Metadata(attr, owner, times))
+}
+
+/// Queries the metadata about a file without following symlinks.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    crate::root::stat(path, false).map(|(attr, owner, times)| Metadata(attr, owner, times))
+}
+
+/// Changes the permissions found on a file or a directory.
+///
+/// It's allowed only for the owner and `root`, and fails with
+/// [`Unsupported`] on filesystems without owners, e.g. FAT. Permissions are
+/// kept in memory on `tmpfs` and `ramfs`, and stored on ext4.
+///
+/// [`Unsupported`]: io::Error::Unsupported
+pub fn set_permissions(path: &str, perm: Permissions) -> io::Result<()> {
+    crate::root::set_perm(path, perm)
+}
+
+/// Changes the owner and group of a file or a directory, `None` leaves it
+/// unchanged.
+///
+/// Only `root` can change the owner, and the owner can change the group to
+/// its own group. It fails on filesystems without owners like
+/// [`set_permissions`].
+pub fn chown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    crate::root::set_owner(path, uid, gid)
+}
+
+/// Changes the access and modification times of a file or a directory, and
+/// sets its change time to the current time.
+///
+/// Setting them to [`TimeUpdate::Now`] is allowed for the owner, `root` and
+/// the users who can write the file, while setting other times is allowed
+/// only for the owner and `root`. It fails with [`Unsupported`] on
+/// filesystems whose times can't be changed, e.g. for directories on FAT.
+///
+/// [`Unsupported`]: io::Error::Unsupported
+pub fn set_times(path: &str, accessed: TimeUpdate, modified: TimeUpdate) -> io::Result<()> {
+    crate::root::set_times(None, path, true, accessed, modified)
+}
+
+/// Changes the times like [`set_times`], but of the symbolic link itself.
+pub fn set_symlink_times(path: &str, accessed: TimeUpdate, modified: TimeUpdate) -> io::Result<()> {
+    crate::root::set_times(None, path, false, accessed, modified)
+}
+
+/// Checks whether the current user can access a file with `mode`, it fails
+/// with [`PermissionDenied`] if not.
+///
+/// [`PermissionDenied`]: io::Error::PermissionDenied
+pub fn access(path: &str, mode: AccessMode) -> io::Result<()> {
+    crate::root::access(path, mode)
+}
+
+/// Sets the file mode creation mask, whose bits are cleared from the
+/// permissions of new files, and returns the previous one. It's `0o022` by
+/// default.
+pub fn set_umask(mask: Permissions) -> Permissions {
+    crate::perm::set_umask(mask)
+}
+
+/// Returns the user and group IDs of the current user.
+pub fn current_user() -> (u32, u32) {
+    let user = crate::perm::current_user();
+    (user.uid, user.gid)
+}
+
+/// Sets the user and group IDs of the current user, which is `root` (`0`)
+/// by default.
+///
+/// There are no user accounts, the IDs are only used to check permissions,
+/// and as the owners of new files.
+pub fn set_current_user(uid: u32, gid: u32) {
+    crate::perm::set_current_user(crate::perm::Owner { uid, gid });
+}
+
+/// Creates a new symbolic link `link` pointing to `original`.
+///
+/// `original` is not checked, and relative targets are resolved from the
+/// directory containing the link. Only supported by `tmpfs`, `ramfs` and
+/// ext4.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    crate::root::symlink(original, link)
+}
+
+/// Creates a socket file at `path`, where a Unix domain socket is bound.
+///
+/// Its permissions are `0o777` without the bits in the file mode creation
+/// mask. Only supported by `tmpfs`, `ramfs` and ext4.
+pub fn create_socket(path: &str) -> io::Result<()> {
+    let perm = Permissions::from_bits_truncate(0o777);
+    crate::root::create_special(path, FileType::Socket, perm)
+}
+
+/// Reads a symbolic link, returning the path it points to.
+pub fn read_link(path: &str) -> io::Result<String> {
+    crate::root::read_link(path)
 }
 
 /// Creates a new, empty directory at the provided path.
@@ -88,7 +211,90 @@ pub fn rename(old: &str, new: &str) -> io::Result<()> {
     crate::root::rename(old, new)
 }
 
+/// Returns whether accessing the path fails because of too many levels of
+/// symbolic links.
+///
+/// The other functions report it as [`InvalidData`](io::Error::InvalidData).
+pub fn is_symlink_loop(path: &str) -> bool {
+    crate::root::is_symlink_loop(None, path)
+}
+
 /// check whether absolute path exists.
 pub fn absolute_path_exists(path: &str) -> bool {
     crate::root::lookup(None, path).is_ok()
 }
+
+/// Mounts a new filesystem of type `fstype` on the directory `target`.
+///
+/// In-memory filesystems (`tmpfs`, `ramfs`, `devfs`, `proc` and `sysfs`) can be
+/// created at runtime, and `source` is just recorded in the mount table.
+/// For disk filesystems (`vfat` and `ext4` if enabled), `source` is a block
+/// device, see [`block_devices`], and it fails with [`InvalidInput`] if the
+/// filesystem on it is of another type. Only one ext4 filesystem can be
+/// mounted at a time, it fails with [`ResourceBusy`] otherwise. `myfs` is
+/// never detected and must be given by the name.
+///
+/// [`InvalidInput`]: io::Error::InvalidInput
+/// [`ResourceBusy`]: io::Error::ResourceBusy
+pub fn mount(source: &str, target: &str, fstype: &str) -> io::Result<()> {
+    let fs = crate::mounts::new_fs(fstype, source)?;
+    crate::root::mount(source, target, fstype, fs)
+}
+
+/// Mounts the directory `source` on the directory `target`, making its
+/// contents visible at both places.
+///
+/// Mounts under `source` are not visible under `target`.
+pub fn bind_mount(source: &str, target: &str) -> io::Result<()> {
+    crate::root::bind_mount(source, target)
+}
+
+/// Unmounts the filesystem mounted on `target`.
+///
+/// It fails if the filesystem is busy, i.e. there are other mounts under it,
+/// files opened in it, or the current directory is in it. With `detach`,
+/// it's detached from the directory tree anyway, and cleaned up when it's no
+/// longer busy.
+pub fn umount(target: &str, detach: bool) -> io::Result<()> {
+    crate::root::umount(target, detach)
+}
+
+/// Returns the disks and their partitions, each disk after its partitions.
+pub fn block_devices() -> Vec<BlockDeviceInfo> {
+    crate::dev::block_devices()
+        .iter()
+        .map(|dev| BlockDeviceInfo {
+            path: format!("/dev/{}", dev.name),
+            size: dev.size(),
+            label: dev.label.clone(),
+            uuid: dev.uuid.clone(),
+        })
+        .collect()
+}
+
+/// Writes all cached pages of files and cached blocks back to the block
+/// devices.
+pub fn sync() -> io::Result<()> {
+    crate::root::sync_caches()?;
+    crate::dev::sync_all().map_err(|e| {
+        warn!("failed to sync block devices: {:?}", e);
+        io::Error::Io
+    })
+}
+
+/// Returns the statistics of the block caches of all disks.
+pub fn block_cache_stats() -> CacheStats {
+    crate::dev::cache_stats()
+}
+
+/// Returns the mounted filesystems, in the order they are mounted.
+pub fn mounts() -> Vec<MountInfo> {
+    crate::root::mounts()
+        .into_iter()
+        .map(|(source, target, fstype)| MountInfo {
+            source,
+            target,
+            fstype,
+        })
+        .collect()
+}
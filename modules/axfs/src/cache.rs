@@ -0,0 +1,220 @@
+//! A write-back block cache.
+//!
+//! Every block device has a [`BlockCache`] shared by all its [`Disk`] views,
+//! so all filesystems on the device go through it. The least recently used
+//! blocks are evicted, and written back if they are dirty. Sequential misses
+//! read ahead several blocks in one device request.
+//!
+//! [`Disk`]: crate::dev::Disk
+
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+use axdriver::prelude::*;
+use axsync::Mutex;
+
+use crate::dev::BLOCK_SIZE;
+
+/// The maximum number of blocks read in one device request on sequential
+/// misses.
+const READAHEAD_BLOCKS: u64 = 32;
+
+/// Statistics of block caches.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CacheStats {
+    /// The number of block accesses found in the cache.
+    pub hits: u64,
+    /// The number of block accesses not found in the cache.
+    pub misses: u64,
+    /// The number of read requests to the device.
+    pub reads: u64,
+    /// The number of blocks read from the device.
+    pub blocks_read: u64,
+    /// The number of write requests to the device.
+    pub writes: u64,
+    /// The number of blocks written to the device.
+    pub blocks_written: u64,
+}
+
+struct CachedBlock {
+    data: Box<[u8; BLOCK_SIZE]>,
+    dirty: bool,
+    /// The time of the last access, the key in [`CacheInner::lru`].
+    tick: u64,
+}
+
+struct CacheInner {
+    dev: AxBlockDevice,
+    capacity: usize,
+    blocks: BTreeMap<u64, CachedBlock>,
+    /// The cached blocks ordered by the time of the last access.
+    lru: BTreeMap<u64, u64>,
+    tick: u64,
+    /// The block after the last read from the device, to detect sequential
+    /// reads.
+    next_read: u64,
+    stats: CacheStats,
+}
+
+/// A block device with a write-back LRU cache.
+pub(crate) struct BlockCache {
+    num_blocks: u64,
+    inner: Mutex<CacheInner>,
+}
+
+impl CacheInner {
+    /// Marks the block as the most recently used one.
+    fn touch(&mut self, block_id: u64) -> &mut CachedBlock {
+        self.tick += 1;
+        let block = self.blocks.get_mut(&block_id).unwrap();
+        self.lru.remove(&block.tick);
+        self.lru.insert(self.tick, block_id);
+        block.tick = self.tick;
+        block
+    }
+
+    /// Adds a block to the cache, evicting the least recently used one if
+    /// it's full.
+    fn insert(&mut self, block_id: u64, data: &[u8], dirty: bool) -> DevResult {
+        while self.blocks.len() >= self.capacity {
+            let (_, victim) = self.lru.pop_first().unwrap();
+            let block = self.blocks.remove(&victim).unwrap();
+            if block.dirty {
+                self.stats.writes += 1;
+                self.stats.blocks_written += 1;
+                if let Err(e) = self.dev.write_block(victim, block.data.as_slice()) {
+                    // keep it to retry later
+                    self.lru.insert(block.tick, victim);
+                    self.blocks.insert(victim, block);
+                    return Err(e);
+                }
+            }
+        }
+        self.tick += 1;
+        self.lru.insert(self.tick, block_id);
+        self.blocks.insert(
+            block_id,
+            CachedBlock {
+                data: Box::new(data.try_into().unwrap()),
+                dirty,
+                tick: self.tick,
+            },
+        );
+        Ok(())
+    }
+
+    /// Writes the dirty blocks back, merging consecutive ones into one
+    /// request.
+    fn sync(&mut self) -> DevResult {
+        let dirty: Vec<u64> = self
+            .blocks
+            .iter()
+            .filter(|(_, block)| block.dirty)
+            .map(|(&id, _)| id)
+            .collect();
+        let mut i = 0;
+        while i < dirty.len() {
+            let mut n = 1;
+            while i + n < dirty.len() && dirty[i + n] == dirty[i] + n as u64 {
+                n += 1;
+            }
+            let mut buf = vec![0u8; n * BLOCK_SIZE];
+            for (j, chunk) in buf.chunks_exact_mut(BLOCK_SIZE).enumerate() {
+                chunk.copy_from_slice(self.blocks[&dirty[i + j]].data.as_slice());
+            }
+            self.stats.writes += 1;
+            self.stats.blocks_written += n as u64;
+            self.dev.write_block(dirty[i], &buf)?;
+            for id in &dirty[i..i + n] {
+                self.blocks.get_mut(id).unwrap().dirty = false;
+            }
+            i += n;
+        }
+        self.dev.flush()
+    }
+}
+
+impl BlockCache {
+    /// Creates a cache of at most `capacity` blocks for the device.
+    pub fn new(dev: AxBlockDevice, capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self {
+            num_blocks: dev.num_blocks(),
+            inner: Mutex::new(CacheInner {
+                dev,
+                capacity,
+                blocks: BTreeMap::new(),
+                lru: BTreeMap::new(),
+                tick: 0,
+                next_read: 0,
+                stats: CacheStats::default(),
+            }),
+        }
+    }
+
+    /// Returns the number of blocks of the device.
+    pub fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    /// Reads the block `block_id` into `buf` of one block.
+    pub fn read_block(&self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let mut inner = self.inner.lock();
+        if inner.blocks.contains_key(&block_id) {
+            inner.stats.hits += 1;
+            buf.copy_from_slice(inner.touch(block_id).data.as_slice());
+            return Ok(());
+        }
+        inner.stats.misses += 1;
+
+        // read ahead the following uncached blocks if it's sequential
+        let mut count = 1;
+        if block_id == inner.next_read {
+            let max = READAHEAD_BLOCKS.min(inner.capacity as u64);
+            while count < max
+                && block_id + count < self.num_blocks
+                && !inner.blocks.contains_key(&(block_id + count))
+            {
+                count += 1;
+            }
+        }
+        let mut data = vec![0u8; count as usize * BLOCK_SIZE];
+        inner.stats.reads += 1;
+        inner.stats.blocks_read += count;
+        inner.dev.read_block(block_id, &mut data)?;
+        inner.next_read = block_id + count;
+
+        buf.copy_from_slice(&data[..BLOCK_SIZE]);
+        // the nearer blocks are used sooner, so they are inserted later, and
+        // the requested one is the last
+        for (i, chunk) in data.chunks_exact(BLOCK_SIZE).enumerate().skip(1).rev() {
+            inner.insert(block_id + i as u64, chunk, false)?;
+        }
+        inner.insert(block_id, buf, false)
+    }
+
+    /// Writes `buf` of one block to the block `block_id`, which is written
+    /// back to the device later.
+    pub fn write_block(&self, block_id: u64, buf: &[u8]) -> DevResult {
+        let mut inner = self.inner.lock();
+        if inner.blocks.contains_key(&block_id) {
+            inner.stats.hits += 1;
+            let block = inner.touch(block_id);
+            block.data.copy_from_slice(buf);
+            block.dirty = true;
+            Ok(())
+        } else {
+            // the whole block is overwritten, no need to read it
+            inner.stats.misses += 1;
+            inner.insert(block_id, buf, true)
+        }
+    }
+
+    /// Writes all dirty blocks back to the device.
+    pub fn sync(&self) -> DevResult {
+        self.inner.lock().sync()
+    }
+
+    /// Returns the statistics of the cache.
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().stats
+    }
+}
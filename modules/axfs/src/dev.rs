@@ -1,28 +1,90 @@
+use alloc::{format, string::String, sync::Arc, vec::Vec};
 use axdriver::prelude::*;
+use lazyinit::LazyInit;
 
-const BLOCK_SIZE: usize = 512;
+use crate::cache::{BlockCache, CacheStats};
+use crate::partition;
+
+pub(crate) const BLOCK_SIZE: usize = 512;
 
 /// A disk device with a cursor.
+///
+/// It may be a view of a range of blocks on the device, e.g. a partition,
+/// with block IDs relative to the start of the range. The blocks are cached
+/// by the [`BlockCache`] of the device, see [`Disk::flush`].
 pub struct Disk {
     block_id: u64,
     offset: usize,
-    dev: AxBlockDevice,
+    dev: Arc<BlockCache>,
+    start: u64,
+    num_blocks: u64,
 }
 
 impl Disk {
     /// Create a new disk.
     pub fn new(dev: AxBlockDevice) -> Self {
         assert_eq!(BLOCK_SIZE, dev.block_size());
+        let dev = BlockCache::new(dev, axconfig::BLOCK_CACHE_SIZE);
+        let num_blocks = dev.num_blocks();
+        Self {
+            block_id: 0,
+            offset: 0,
+            dev: Arc::new(dev),
+            start: 0,
+            num_blocks,
+        }
+    }
+
+    /// Creates a view of `num_blocks` blocks starting from `start` on the
+    /// same device, with its own cursor.
+    pub fn slice(&self, start: u64, num_blocks: u64) -> Self {
+        assert!(start + num_blocks <= self.num_blocks);
         Self {
             block_id: 0,
             offset: 0,
-            dev,
+            dev: self.dev.clone(),
+            start: self.start + start,
+            num_blocks,
         }
     }
 
+    /// Returns whether the blocks of the two disks overlap, e.g. a disk and
+    /// its partitions.
+    pub fn overlaps(&self, other: &Disk) -> bool {
+        Arc::ptr_eq(&self.dev, &other.dev)
+            && self.start < other.start + other.num_blocks
+            && other.start < self.start + self.num_blocks
+    }
+
+    /// Get the number of blocks of the disk.
+    pub fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.num_blocks * BLOCK_SIZE as u64
+    }
+
+    /// Reads the block `block_id` without moving the cursor.
+    pub fn read_block(&self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        if block_id >= self.num_blocks {
+            return Err(DevError::InvalidParam);
+        }
+        self.dev.read_block(self.start + block_id, buf)
+    }
+
+    /// Writes the block `block_id` without moving the cursor.
+    pub fn write_block(&self, block_id: u64, buf: &[u8]) -> DevResult {
+        if block_id >= self.num_blocks {
+            return Err(DevError::InvalidParam);
+        }
+        self.dev.write_block(self.start + block_id, buf)
+    }
+
+    /// Writes the cached blocks of the device back.
+    pub fn flush(&self) -> DevResult {
+        self.dev.sync()
     }
 
     /// Get the position of the cursor.
@@ -41,7 +103,7 @@ impl Disk {
         let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
             // whole block
             let mut data = [0u8; BLOCK_SIZE];
-            self.dev.read_block(self.block_id, &mut data)?;
+            self.read_block(self.block_id, &mut data)?;
             buf[0..BLOCK_SIZE].copy_from_slice(&data);
             // self.dev
             //     .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
@@ -53,7 +115,7 @@ impl Disk {
             let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
 
-            self.dev.read_block(self.block_id, &mut data)?;
+            self.read_block(self.block_id, &mut data)?;
             buf[..count].copy_from_slice(&data[start..start + count]);
 
             self.offset += count;
@@ -74,7 +136,7 @@ impl Disk {
             // Because underlying driver assumes a linear mapping between virtual address and
             // physical address when converting them, which is only present in kernel address space.
             let data = buf[0..BLOCK_SIZE].to_vec();
-            self.dev.write_block(self.block_id, &data)?;
+            self.write_block(self.block_id, &data)?;
             self.block_id += 1;
             BLOCK_SIZE
         } else {
@@ -83,9 +145,9 @@ impl Disk {
             let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
 
-            self.dev.read_block(self.block_id, &mut data)?;
+            self.read_block(self.block_id, &mut data)?;
             data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
+            self.write_block(self.block_id, &data)?;
 
             self.offset += count;
             if self.offset >= BLOCK_SIZE {
@@ -102,9 +164,7 @@ impl Disk {
     pub fn read_offset(&mut self, offset: usize) -> [u8; BLOCK_SIZE] {
         let block_id = offset / BLOCK_SIZE;
         let mut block_data = [0u8; BLOCK_SIZE];
-        self.dev
-            .read_block(block_id as u64, &mut block_data)
-            .unwrap();
+        self.read_block(block_id as u64, &mut block_data).unwrap();
         block_data
     }
 
@@ -117,7 +177,153 @@ impl Disk {
         );
         assert!(offset % BLOCK_SIZE == 0);
         let block_id = offset / BLOCK_SIZE;
-        self.dev.write_block(block_id as u64, buf).unwrap();
+        self.write_block(block_id as u64, buf).unwrap();
         Ok(buf.len())
     }
 }
+
+/// A block device, or a partition on it, that filesystems can be on.
+pub(crate) struct BlockDevice {
+    /// The name, `disk<N>` for disks and `disk<N>p<M>` for partitions.
+    pub name: String,
+    /// The name of its node in devfs, like Linux's VirtIO disks, e.g. `vda`
+    /// for `disk0` and `vda1` for `disk0p1`.
+    pub node_name: String,
+    /// The partition number, `None` for disks.
+    pub partition: Option<usize>,
+    pub label: Option<String>,
+    pub uuid: Option<String>,
+    disk: Disk,
+}
+
+impl BlockDevice {
+    /// Returns a new [`Disk`] to access the device.
+    pub fn disk(&self) -> Disk {
+        self.disk.slice(0, self.disk.num_blocks())
+    }
+
+    pub fn size(&self) -> u64 {
+        self.disk.size()
+    }
+
+    /// Returns whether it overlaps `other`, i.e. they are the same device, or
+    /// a disk and one of its partitions.
+    pub fn overlaps(&self, other: &BlockDevice) -> bool {
+        self.disk.overlaps(&other.disk)
+    }
+}
+
+static BLOCK_DEVICES: LazyInit<Vec<BlockDevice>> = LazyInit::new();
+
+/// Registers the disks and their partitions.
+pub(crate) fn init_block_devices(disks: Vec<Disk>) {
+    let mut devs = Vec::new();
+    for (i, disk) in disks.into_iter().enumerate() {
+        let name = format!("disk{}", i);
+        let node_name = format!("vd{}", disk_letters(i));
+        let parts = partition::parse(&disk).unwrap_or_else(|e| {
+            warn!("failed to read the partition table of {}: {:?}", name, e);
+            Vec::new()
+        });
+        for part in parts {
+            info!(
+                "  {}p{}: {} blocks at {}, label: {:?}, uuid: {:?}",
+                name, part.number, part.num_blocks, part.start, part.label, part.uuid
+            );
+            devs.push(BlockDevice {
+                name: format!("{}p{}", name, part.number),
+                node_name: format!("{}{}", node_name, part.number),
+                partition: Some(part.number),
+                label: part.label,
+                uuid: part.uuid,
+                disk: disk.slice(part.start, part.num_blocks),
+            });
+        }
+        devs.push(BlockDevice {
+            name,
+            node_name,
+            partition: None,
+            label: None,
+            uuid: None,
+            disk,
+        });
+    }
+    BLOCK_DEVICES.init_once(devs);
+}
+
+/// Returns the letters of the disk `index` in its Linux name, i.e. `a` to
+/// `z`, then `aa`, `ab` and so on.
+fn disk_letters(mut index: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push(b'a' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    letters.iter().rev().map(|&c| c as char).collect()
+}
+
+/// Returns the disks and partitions, each disk after its partitions.
+pub(crate) fn block_devices() -> &'static [BlockDevice] {
+    BLOCK_DEVICES.get().map_or(&[], |devs| devs.as_slice())
+}
+
+/// Finds a block device by its name, or the name of its node in devfs.
+fn find_by_name(name: &str) -> Option<&'static BlockDevice> {
+    block_devices()
+        .iter()
+        .find(|dev| dev.name == name || dev.node_name == name)
+}
+
+/// Finds a block device by its path, `/dev/<name>`, e.g. `/dev/disk0p1` or
+/// `/dev/vda1`.
+pub(crate) fn find_block_device(path: &str) -> Option<&'static BlockDevice> {
+    find_by_name(path.strip_prefix("/dev/")?)
+}
+
+/// Finds the block device to mount on `/`.
+///
+/// `spec` is the name of a disk or partition (e.g. `disk0p2` or `vda2`), or
+/// `PARTLABEL=<label>` or `PARTUUID=<uuid>`. If it's empty, it's the first
+/// partition of the first disk, or the first disk if it has no partitions.
+pub(crate) fn find_root(spec: &str) -> Option<&'static BlockDevice> {
+    let devs = block_devices();
+    if spec.is_empty() {
+        devs.first()
+    } else if let Some(label) = spec.strip_prefix("PARTLABEL=") {
+        devs.iter().find(|dev| dev.label.as_deref() == Some(label))
+    } else if let Some(uuid) = spec.strip_prefix("PARTUUID=") {
+        devs.iter().find(|dev| {
+            dev.uuid
+                .as_deref()
+                .is_some_and(|u| u.eq_ignore_ascii_case(uuid))
+        })
+    } else {
+        find_by_name(spec)
+    }
+}
+
+/// Writes the cached blocks of all disks back.
+pub(crate) fn sync_all() -> DevResult {
+    for dev in block_devices().iter().filter(|dev| dev.partition.is_none()) {
+        dev.disk.flush()?;
+    }
+    Ok(())
+}
+
+/// Returns the sum of the cache statistics of all disks.
+pub(crate) fn cache_stats() -> CacheStats {
+    let mut sum = CacheStats::default();
+    for dev in block_devices().iter().filter(|dev| dev.partition.is_none()) {
+        let stats = dev.disk.dev.stats();
+        sum.hits += stats.hits;
+        sum.misses += stats.misses;
+        sum.reads += stats.reads;
+        sum.blocks_read += stats.blocks_read;
+        sum.writes += stats.writes;
+        sum.blocks_written += stats.blocks_written;
+    }
+    sum
+}
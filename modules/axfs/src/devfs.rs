@@ -0,0 +1,184 @@
+//! The devfs, whose nodes are devices registered by other modules.
+//!
+//! The disks and partitions are registered at boot as block devices named
+//! like Linux's VirtIO disks, e.g. `/dev/vda` and `/dev/vda1`, along with
+//! `/dev/null` and `/dev/zero`. Other modules, drivers and applications add
+//! character devices by [`register_char_device`], e.g. the console and the
+//! framebuffer.
+
+use alloc::sync::Arc;
+use axdriver::prelude::DevError;
+use axerrno::{AxError, AxResult, ax_err};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult};
+
+use crate::dev::{BLOCK_SIZE, Disk};
+use crate::pseudo::{PseudoEntry, Registry};
+
+/// The registered devices, the keys are paths relative to `/dev`.
+pub(crate) static DEV_ENTRIES: Registry = Registry::new();
+
+/// A character device.
+pub trait CharDevice: Send + Sync {
+    /// Reads data at `offset`, which is ignored by stream devices, e.g. the
+    /// console.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize>;
+
+    /// Writes data at `offset`, which is ignored by stream devices.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize>;
+
+    /// Returns the size, 0 for stream devices.
+    fn size(&self) -> u64 {
+        0
+    }
+}
+
+/// Registers a character device at `path` relative to `/dev`, e.g. `fb0` or
+/// `input/event0`, replacing the previous one with the same path.
+pub fn register_char_device(path: &str, dev: Arc<dyn CharDevice>) -> AxResult {
+    register_device(path, Arc::new(CharNode(dev)))
+}
+
+/// Registers a device implemented as a node directly, e.g. if it's a block
+/// device.
+pub fn register_device(path: &str, node: VfsNodeRef) -> AxResult {
+    DEV_ENTRIES.register(path, PseudoEntry::Node(node))
+}
+
+/// Removes the device at `path`, files opened on it are still usable.
+pub fn unregister_device(path: &str) -> AxResult {
+    DEV_ENTRIES.unregister(path)
+}
+
+/// Registers `null`, `zero` and the block devices.
+pub(crate) fn init() {
+    let null = Arc::new(crate::fs::devfs::NullDev);
+    let zero = Arc::new(crate::fs::devfs::ZeroDev);
+    let devs = [("null", null as VfsNodeRef), ("zero", zero)]
+        .into_iter()
+        .map(|(name, node)| (name.into(), node))
+        .chain(crate::dev::block_devices().iter().map(|dev| {
+            let node: VfsNodeRef = Arc::new(BlockNode(dev.disk()));
+            (dev.node_name.clone(), node)
+        }));
+    for (name, node) in devs {
+        if let Err(e) = register_device(&name, node) {
+            warn!("failed to register /dev/{}: {:?}", name, e);
+        }
+    }
+}
+
+struct CharNode(Arc<dyn CharDevice>);
+
+impl VfsNodeOps for CharNode {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = VfsNodePerm::from_bits_truncate(0o666);
+        Ok(VfsNodeAttr::new(
+            perm,
+            VfsNodeType::CharDevice,
+            self.0.size(),
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.0.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.0.write_at(offset, buf)
+    }
+
+    /// Truncating is ignored, so devices can be opened with `O_TRUNC`.
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+}
+
+fn as_vfs_err(err: DevError) -> AxError {
+    match err {
+        DevError::InvalidParam => AxError::InvalidInput,
+        DevError::NoMemory => AxError::NoMemory,
+        DevError::Unsupported => AxError::Unsupported,
+        DevError::Again => AxError::WouldBlock,
+        _ => AxError::Io,
+    }
+}
+
+/// A disk or partition, accessed through the block cache, so it's coherent
+/// with the filesystems on it.
+struct BlockNode(Disk);
+
+impl BlockNode {
+    /// Calls `f` with each block in `offset..offset + len`, and the range of
+    /// the bytes in it, until the end of the device.
+    fn for_each_block(
+        &self,
+        offset: u64,
+        len: usize,
+        mut f: impl FnMut(u64, core::ops::Range<usize>, usize) -> VfsResult,
+    ) -> VfsResult<usize> {
+        let end = (offset + len as u64).min(self.0.size());
+        let mut pos = offset;
+        while pos < end {
+            let block_id = pos / BLOCK_SIZE as u64;
+            let start = (pos % BLOCK_SIZE as u64) as usize;
+            let n = (BLOCK_SIZE - start).min((end - pos) as usize);
+            f(block_id, start..start + n, (pos - offset) as usize)?;
+            pos += n as u64;
+        }
+        Ok(end.saturating_sub(offset) as usize)
+    }
+}
+
+impl VfsNodeOps for BlockNode {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = VfsNodePerm::from_bits_truncate(0o660);
+        let (size, blocks) = (self.0.size(), self.0.num_blocks());
+        Ok(VfsNodeAttr::new(
+            perm,
+            VfsNodeType::BlockDevice,
+            size,
+            blocks,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut block = [0u8; BLOCK_SIZE];
+        self.for_each_block(offset, buf.len(), |block_id, range, buf_pos| {
+            self.0
+                .read_block(block_id, &mut block)
+                .map_err(as_vfs_err)?;
+            buf[buf_pos..buf_pos + range.len()].copy_from_slice(&block[range]);
+            Ok(())
+        })
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if offset >= self.0.size() && !buf.is_empty() {
+            return ax_err!(StorageFull);
+        }
+        let mut block = [0u8; BLOCK_SIZE];
+        self.for_each_block(offset, buf.len(), |block_id, range, buf_pos| {
+            if range.len() < BLOCK_SIZE {
+                self.0
+                    .read_block(block_id, &mut block)
+                    .map_err(as_vfs_err)?;
+            }
+            block[range.clone()].copy_from_slice(&buf[buf_pos..buf_pos + range.len()]);
+            self.0.write_block(block_id, &block).map_err(as_vfs_err)
+        })
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.0.flush().map_err(as_vfs_err)
+    }
+
+    /// The size is fixed, truncating is ignored.
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+}
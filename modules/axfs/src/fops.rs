@@ -1,10 +1,20 @@
 //! Low-level filesystem operations.
 
+use alloc::{string::String, sync::Arc};
 use axerrno::{AxError, AxResult, ax_err, ax_err_type};
 use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
 use cap_access::{Cap, WithCap};
 use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::lock::{LockKey, LockOwner};
+use crate::root::MountPoint;
+
+pub use crate::lock::{LockKind, RangeLock};
+pub use crate::page_cache::{PAGE_SIZE, PageCache};
+pub use crate::perm::Owner;
+pub use crate::times::{FileTimes, TimeUpdate};
 
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
@@ -19,23 +29,52 @@ pub type DirEntry = axfs_vfs::VfsDirEntry;
 pub type FileAttr = axfs_vfs::VfsNodeAttr;
 /// Alias of [`axfs_vfs::VfsNodePerm`].
 pub type FilePerm = axfs_vfs::VfsNodePerm;
+/// The access checked by [`api::access`](crate::api::access), any of `READ`,
+/// `WRITE` and `EXECUTE`.
+pub type AccessMode = Cap;
 
 /// An opened file object, with open permissions and a cursor.
 pub struct File {
     node: WithCap<VfsNodeRef>,
+    /// The page cache, for files on block devices.
+    cache: Option<Arc<PageCache>>,
+    /// The filesystem, which is kept from being unmounted.
+    mount: Arc<MountPoint>,
     is_append: bool,
     offset: u64,
+    /// Whether it has been read, the access time is updated only once.
+    accessed: AtomicBool,
+    /// The owner of its locks, which are released when it's dropped.
+    lock_owner: LockOwner,
+    /// The file in the table of locks.
+    lock_key: LockKey,
+}
+
+/// The locks of an opened [`File`], returned by [`File::locks`], to wait for
+/// them without borrowing the file, which must be kept open meanwhile.
+#[derive(Debug, Clone, Copy)]
+pub struct FileLocks {
+    key: LockKey,
+    owner: LockOwner,
+    /// The access of the file.
+    cap: Cap,
 }
 
 /// An opened directory object, with open permissions and a cursor for
 /// [`read_dir`](Directory::read_dir).
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
+    /// The filesystem, which is kept from being unmounted.
+    mount: Arc<MountPoint>,
+    /// The canonical absolute path, the base of relative paths.
+    path: String,
     entry_idx: usize,
+    /// Whether it has been read, the access time is updated only once.
+    accessed: bool,
 }
 
 /// Options and flags which can be used to configure how a file is opened.
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct OpenOptions {
     // generic
     read: bool,
@@ -48,7 +87,7 @@ pub struct OpenOptions {
     directory: bool,
     // system-specific
     _custom_flags: i32,
-    _mode: u32,
+    mode: u32,
 }
 
 impl OpenOptions {
@@ -66,7 +105,7 @@ impl OpenOptions {
             directory: false,
             // system-specific
             _custom_flags: 0,
-            _mode: 0o666,
+            mode: 0o666,
         }
     }
     /// Sets the option for read access.
@@ -101,6 +140,11 @@ impl OpenOptions {
     pub fn directory(&mut self, directory: bool) {
         self.directory = directory;
     }
+    /// Sets the permissions of the file if it's created, `0o666` by default.
+    /// The bits in the file mode creation mask are cleared.
+    pub fn mode(&mut self, mode: u32) {
+        self.mode = mode;
+    }
     /// check whether contains directory.
     pub fn has_directory(&self) -> bool {
         self.directory
@@ -151,48 +195,63 @@ impl File {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_at(dir: Option<&str>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
         }
 
-        let node_option = crate::root::lookup(dir, path);
-        let node = if opts.create || opts.create_new {
-            match node_option {
-                Ok(node) => {
+        let loc_option = crate::root::resolve(dir, path);
+        let loc = if opts.create || opts.create_new {
+            match loc_option {
+                Ok(loc) => {
                     // already exists
                     if opts.create_new {
                         return ax_err!(AlreadyExists);
                     }
-                    node
+                    loc
                 }
                 // not exists, create new
-                Err(VfsError::NotFound) => crate::root::create_file(dir, path)?,
+                Err(VfsError::NotFound) => {
+                    let perm = FilePerm::from_bits_truncate(opts.mode as u16);
+                    crate::root::create_file(dir, path, perm)?
+                }
                 Err(e) => return Err(e),
             }
         } else {
             // just open the existing
-            node_option?
+            loc_option?
         };
-
-        let attr = node.get_attr()?;
-        if attr.is_dir() {
+        if loc.attr()?.is_dir() {
             return ax_err!(IsADirectory);
         }
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
+        if !loc.access()?.contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
 
+        let cache = loc.page_cache()?;
+        let node = match &cache {
+            Some(cache) => cache.node().clone(),
+            None => loc.node,
+        };
         node.open()?;
         if opts.truncate {
-            node.truncate(0)?;
+            match &cache {
+                Some(cache) => cache.truncate(0)?,
+                None => node.truncate(0)?,
+            }
+            loc.mount.times().modified(&node);
         }
         Ok(Self {
             node: WithCap::new(node, access_cap),
+            cache,
+            mount: loc.mount,
             is_append: opts.append,
             offset: 0,
+            accessed: AtomicBool::new(false),
+            lock_owner: LockOwner::new(),
+            lock_key: loc.mount.lock_key(&node),
         })
     }
 
@@ -202,9 +261,36 @@ impl File {
         Self::_open_at(None, path, opts)
     }
 
+    fn read_node(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let node = self.access_node(Cap::READ)?;
+        let read_len = match &self.cache {
+            Some(cache) => cache.read_at(offset, buf)?,
+            None => node.read_at(offset, buf)?,
+        };
+        if !self.accessed.swap(true, Ordering::Relaxed) {
+            self.mount.times().accessed(node);
+        }
+        Ok(read_len)
+    }
+
+    fn write_node(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        let node = self.access_node(Cap::WRITE)?;
+        let write_len = match &self.cache {
+            Some(cache) => cache.write_at(offset, buf)?,
+            None => node.write_at(offset, buf)?,
+        };
+        self.mount.times().modified(node);
+        Ok(write_len)
+    }
+
     /// Truncates the file to the specified size.
     pub fn truncate(&self, size: u64) -> AxResult {
-        self.access_node(Cap::WRITE)?.truncate(size)?;
+        let node = self.access_node(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.truncate(size)?,
+            None => node.truncate(size)?,
+        }
+        self.mount.times().modified(node);
         Ok(())
     }
 
@@ -213,8 +299,7 @@ impl File {
     ///
     /// After the read, the cursor will be advanced by the number of bytes read.
     pub fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
-        let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(self.offset, buf)?;
+        let read_len = self.read_node(self.offset, buf)?;
         self.offset += read_len as u64;
         Ok(read_len)
     }
@@ -223,9 +308,7 @@ impl File {
     ///
     /// It does not update the file cursor.
     pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
-        let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(offset, buf)?;
-        Ok(read_len)
+        self.read_node(offset, buf)
     }
 
     /// Writes the file at the current position. Returns the number of bytes
@@ -239,8 +322,7 @@ impl File {
         } else {
             self.offset
         };
-        let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
+        let write_len = self.write_node(offset, buf)?;
         self.offset = offset + write_len as u64;
         Ok(write_len)
     }
@@ -250,15 +332,30 @@ impl File {
     ///
     /// It does not update the file cursor.
     pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
-        let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
-        Ok(write_len)
+        self.write_node(offset, buf)
     }
 
     /// Flushes the file, writes all buffered data to the underlying device.
     pub fn flush(&self) -> AxResult {
-        self.access_node(Cap::WRITE)?.fsync()?;
-        Ok(())
+        let node = self.access_node(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.sync(),
+            None => node.fsync(),
+        }
+    }
+
+    /// Returns the page cache of the file, to map it into memory by
+    /// [`PageCache::map_page`]. `write` is set for shared writable mappings,
+    /// which need the file to be opened for writing.
+    ///
+    /// Files on in-memory filesystems are not cached, for which a new cache
+    /// is returned, whose changes are seen by others only after synced.
+    pub fn page_cache(&self, write: bool) -> AxResult<Arc<PageCache>> {
+        let node = self.access_node(if write { Cap::WRITE } else { Cap::READ })?;
+        match &self.cache {
+            Some(cache) => Ok(cache.clone()),
+            None => Ok(Arc::new(PageCache::new(node.clone())?)),
+        }
     }
 
     /// Sets the cursor of the file to the specified offset. Returns the new
@@ -277,7 +374,104 @@ impl File {
 
     /// Gets the file attributes.
     pub fn get_attr(&self) -> AxResult<FileAttr> {
-        self.access_node(Cap::empty())?.get_attr()
+        self.mount.owners().attr(self.access_node(Cap::empty())?)
+    }
+
+    /// Gets the owner of the file.
+    pub fn owner(&self) -> AxResult<Owner> {
+        self.mount.owners().owner(self.access_node(Cap::empty())?)
+    }
+
+    /// Changes the permissions of the file, which is allowed only for its
+    /// owner and `root`.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        set_node_perm(&self.mount, self.access_node(Cap::empty())?, perm)
+    }
+
+    /// Gets the access, modification and change times of the file.
+    pub fn times(&self) -> AxResult<FileTimes> {
+        self.mount.times().get(self.access_node(Cap::empty())?)
+    }
+
+    /// Changes the access and modification times of the file, and sets the
+    /// change time to the current time.
+    ///
+    /// Setting them to [`TimeUpdate::Now`] is allowed for the owner, `root`
+    /// and the users who can write the file, while setting other times is
+    /// allowed only for the owner and `root`.
+    pub fn set_times(&self, accessed: TimeUpdate, modified: TimeUpdate) -> AxResult {
+        set_node_times(
+            &self.mount,
+            self.access_node(Cap::empty())?,
+            accessed,
+            modified,
+        )
+    }
+
+    /// Returns the locks of the file.
+    pub fn locks(&self) -> FileLocks {
+        FileLocks {
+            key: self.lock_key,
+            owner: self.lock_owner,
+            cap: self.node.cap(),
+        }
+    }
+
+    /// Applies a whole-file lock, see [`FileLocks::flock`].
+    pub fn flock(&self, kind: Option<LockKind>, wait: bool) -> AxResult {
+        self.locks().flock(kind, wait)
+    }
+
+    /// Applies a byte-range lock, see [`FileLocks::lock_range`].
+    pub fn lock_range(&self, kind: Option<LockKind>, start: u64, end: u64, wait: bool) -> AxResult {
+        self.locks().lock_range(kind, start, end, wait)
+    }
+
+    /// Tests a byte-range lock, see [`FileLocks::test_lock`].
+    pub fn test_lock(&self, kind: LockKind, start: u64, end: u64) -> AxResult<Option<RangeLock>> {
+        Ok(self.locks().test_lock(kind, start, end))
+    }
+}
+
+impl FileLocks {
+    /// Applies a whole-file lock of `kind` like `flock`, or removes it if
+    /// it's `None`, replacing the lock held by the file.
+    ///
+    /// If the file is locked by other opened files, it fails with
+    /// [`WouldBlock`](AxError::WouldBlock) unless `wait` is set, then it
+    /// waits until they are closed or unlock it.
+    pub fn flock(&self, kind: Option<LockKind>, wait: bool) -> AxResult {
+        crate::lock::set(self.key, self.owner, true, kind, 0, u64::MAX, wait)
+    }
+
+    /// Applies a lock of `kind` on the bytes from `start` to `end`
+    /// (exclusive) like `fcntl`, or removes the locks in the range if it's
+    /// `None`. The locks of the file in the range are replaced, and `end`
+    /// is [`u64::MAX`] to lock to the end of the file however it grows.
+    ///
+    /// Shared locks need the file to be opened for reading, and exclusive
+    /// locks for writing. Conflicts with other opened files are handled like
+    /// [`flock`](Self::flock), but the locks of both kinds are independent.
+    pub fn lock_range(&self, kind: Option<LockKind>, start: u64, end: u64, wait: bool) -> AxResult {
+        let cap = match kind {
+            Some(LockKind::Shared) => Cap::READ,
+            Some(LockKind::Exclusive) => Cap::WRITE,
+            None => Cap::empty(),
+        };
+        if !self.cap.contains(cap) {
+            return ax_err!(PermissionDenied);
+        }
+        if start >= end {
+            return ax_err!(InvalidInput);
+        }
+        crate::lock::set(self.key, self.owner, false, kind, start, end, wait)
+    }
+
+    /// Returns a lock of other opened files preventing the file from
+    /// locking the bytes from `start` to `end` (exclusive) with `kind` by
+    /// [`lock_range`](Self::lock_range), or `None` if it can be locked.
+    pub fn test_lock(&self, kind: LockKind, start: u64, end: u64) -> Option<RangeLock> {
+        crate::lock::test(self.key, self.owner, kind, start, end)
     }
 }
 
@@ -286,7 +480,7 @@ impl Directory {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_dir_at(dir: Option<&str>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -295,32 +489,36 @@ impl Directory {
             return ax_err!(InvalidInput);
         }
 
-        let node = crate::root::lookup(dir, path)?;
-        let attr = node.get_attr()?;
-        if !attr.is_dir() {
+        let loc = crate::root::resolve(dir, path)?;
+        if !loc.attr()?.is_dir() {
             return ax_err!(NotADirectory);
         }
         let access_cap = opts.into();
-        let cap = perm_to_cap(attr.perm());
+        let cap = loc.access()?;
         if !cap.contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
 
+        let node = loc.node;
         node.open()?;
         Ok(Self {
             // Here we use `cap` as capability instead of `access_cap` to allow the user to manipulate the directory
             // without explicitly setting [`OpenOptions::execute`], but without requiring execute access even for
             // directories that don't have this permission.
             node: WithCap::new(node, cap),
+            mount: loc.mount,
+            path: loc.path,
             entry_idx: 0,
+            accessed: false,
         })
     }
 
-    fn access_at(&self, path: &str) -> AxResult<Option<&VfsNodeRef>> {
+    fn access_at(&self, path: &str) -> AxResult<Option<&str>> {
         if path.starts_with('/') {
             Ok(None)
         } else {
-            Ok(Some(self.access_node(Cap::EXECUTE)?))
+            self.access_node(Cap::EXECUTE)?;
+            Ok(Some(&self.path))
         }
     }
 
@@ -344,12 +542,15 @@ impl Directory {
 
     /// Creates an empty file at the path relative to this directory.
     pub fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
-        crate::root::create_file(self.access_at(path)?, path)
+        let perm = FilePerm::from_bits_truncate(0o666);
+        crate::root::create_file(self.access_at(path)?, path, perm).map(|loc| loc.node)
     }
 
-    /// Creates an empty directory at the path relative to this directory.
-    pub fn create_dir(&self, path: &str) -> AxResult {
-        crate::root::create_dir(self.access_at(path)?, path)
+    /// Creates an empty directory at the path relative to this directory,
+    /// with the permissions `perm` without the bits in the file mode creation
+    /// mask.
+    pub fn create_dir(&self, path: &str, perm: FilePerm) -> AxResult {
+        crate::root::create_dir(self.access_at(path)?, path, perm)
     }
 
     /// Removes a file at the path relative to this directory.
@@ -368,9 +569,13 @@ impl Directory {
     /// After the read, the cursor will be advanced by the number of entries
     /// read.
     pub fn read_dir(&mut self, dirents: &mut [DirEntry]) -> AxResult<usize> {
-        let n = self
-            .access_node(Cap::READ)?
-            .read_dir(self.entry_idx, dirents)?;
+        let node = self.access_node(Cap::READ)?;
+        let n = node.read_dir(self.entry_idx, dirents)?;
+        self.mount.fix_dirents(&self.path, &mut dirents[..n]);
+        if !self.accessed {
+            self.mount.times().accessed(node);
+            self.accessed = true;
+        }
         self.entry_idx += n;
         Ok(n)
     }
@@ -382,11 +587,65 @@ impl Directory {
     pub fn rename(&self, old: &str, new: &str) -> AxResult {
         crate::root::rename(old, new)
     }
+
+    /// Changes the permissions of the directory, which is allowed only for
+    /// its owner and `root`.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        set_node_perm(&self.mount, self.access_node(Cap::empty())?, perm)
+    }
+
+    /// Gets the access, modification and change times of the directory.
+    pub fn times(&self) -> AxResult<FileTimes> {
+        self.mount.times().get(self.access_node(Cap::empty())?)
+    }
+
+    /// Changes the access and modification times of the directory, see
+    /// [`File::set_times`].
+    pub fn set_times(&self, accessed: TimeUpdate, modified: TimeUpdate) -> AxResult {
+        set_node_times(
+            &self.mount,
+            self.access_node(Cap::empty())?,
+            accessed,
+            modified,
+        )
+    }
+
+    /// Changes the access and modification times of the file at the path
+    /// relative to this directory, following the symbolic link at the last
+    /// component if `follow` is set. See [`File::set_times`].
+    pub fn set_times_at(
+        &self,
+        path: &str,
+        follow: bool,
+        accessed: TimeUpdate,
+        modified: TimeUpdate,
+    ) -> AxResult {
+        crate::root::set_times(self.access_at(path)?, path, follow, accessed, modified)
+    }
+}
+
+fn set_node_perm(mount: &MountPoint, node: &VfsNodeRef, perm: FilePerm) -> AxResult {
+    mount.owners().chmod(node, perm)?;
+    mount.times().changed(node);
+    Ok(())
+}
+
+fn set_node_times(
+    mount: &MountPoint,
+    node: &VfsNodeRef,
+    accessed: TimeUpdate,
+    modified: TimeUpdate,
+) -> AxResult {
+    let owner = mount.owners().owner(node)?;
+    let writable = crate::perm::allowed(&mount.owners().attr(node)?, owner).contains(Cap::WRITE);
+    mount.times().set(node, owner, writable, accessed, modified)
 }
 
 impl Drop for File {
     fn drop(&mut self) {
-        unsafe { self.node.access_unchecked().release().ok() };
+        let node = unsafe { self.node.access_unchecked() };
+        crate::lock::release(self.lock_key, self.lock_owner);
+        node.release().ok();
     }
 }
 
@@ -396,6 +655,12 @@ impl Drop for Directory {
     }
 }
 
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl fmt::Debug for OpenOptions {
     #[allow(unused_assignments)]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -436,17 +701,3 @@ impl From<&OpenOptions> for Cap {
         cap
     }
 }
-
-fn perm_to_cap(perm: FilePerm) -> Cap {
-    let mut cap = Cap::empty();
-    if perm.owner_readable() {
-        cap |= Cap::READ;
-    }
-    if perm.owner_writable() {
-        cap |= Cap::WRITE;
-    }
-    if perm.owner_executable() {
-        cap |= Cap::EXECUTE;
-    }
-    cap
-}
@@ -1,74 +1,213 @@
 use alloc::sync::Arc;
-use core::cell::UnsafeCell;
+use core::time::Duration;
 
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
+use axhal::time::wall_time;
 use axsync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Date, DateTime, Dir, DirEntry, File, LossyOemCpConverter, Time, TimeProvider};
+use fatfs::{Read, Seek, SeekFrom, Write};
 
 use crate::dev::Disk;
+use crate::times::{FileTimes, TimesFs};
 
 const BLOCK_SIZE: usize = 512;
 
+/// The range of the times of FAT since the Unix epoch in seconds, from
+/// 1980-01-01 00:00:00 to 2107-12-31 23:59:58.
+const FAT_TIME_RANGE: core::ops::RangeInclusive<u64> = 315_532_800..=4_354_819_198;
+
+/// Provides the current time to stamp the files, which is the wall time in
+/// UTC.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WallTimeProvider;
+
+impl TimeProvider for WallTimeProvider {
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        fat_date_time(wall_time())
+    }
+}
+
+/// Converts the time since the Unix epoch to the FAT date and time, clamped
+/// to the range of FAT.
+fn fat_date_time(time: Duration) -> DateTime {
+    let secs = time
+        .as_secs()
+        .clamp(*FAT_TIME_RANGE.start(), *FAT_TIME_RANGE.end());
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // the civil date from the days since 1970-01-01, by Howard Hinnant's
+    // algorithm with the years starting from March
+    let (era, doe) = ((days + 719_468) / 146_097, (days + 719_468) % 146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + yoe + (month <= 2) as u64;
+    let date = Date::new(year as u16, month as u16, day as u16);
+    let (hour, min, sec) = (secs / 3600, secs / 60 % 60, secs % 60);
+    let time = Time::new(
+        hour as u16,
+        min as u16,
+        sec as u16,
+        time.subsec_millis() as u16,
+    );
+    DateTime::new(date, time)
+}
+
+/// Converts the FAT date and time to the time since the Unix epoch.
+fn unix_time(date: Date, time: Time) -> Duration {
+    let (month, day) = (date.month as u64, date.day as u64);
+    let year = date.year as u64 - (month <= 2) as u64;
+    let (era, yoe) = (year / 400, year % 400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let secs = days * 86400 + time.hour as u64 * 3600 + time.min as u64 * 60 + time.sec as u64;
+    Duration::from_secs(secs) + Duration::from_millis(time.millis as u64)
+}
+
+/// Returns the times of a directory entry. FAT has no change times, which
+/// are the modification times, and the access times are dates only.
+fn entry_times<IO: IoTrait>(
+    entry: &DirEntry<'_, IO, WallTimeProvider, LossyOemCpConverter>,
+) -> FileTimes {
+    let modified = entry.modified();
+    let modified = unix_time(modified.date, modified.time);
+    FileTimes {
+        accessed: unix_time(entry.accessed(), Time::new(0, 0, 0, 0)),
+        modified,
+        changed: modified,
+    }
+}
+
+type FatFs<IO> = fatfs::FileSystem<IO, WallTimeProvider, LossyOemCpConverter>;
+
+/// A FAT filesystem, which is freed with the last of its nodes.
 pub struct FatFileSystem {
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
-    root_dir: UnsafeCell<Option<VfsNodeRef>>,
+    root_dir: VfsNodeRef,
 }
 
-pub struct FileWrapper<'a, IO: IoTrait>(Mutex<File<'a, IO, NullTimeProvider, LossyOemCpConverter>>);
-pub struct DirWrapper<'a, IO: IoTrait>(Dir<'a, IO, NullTimeProvider, LossyOemCpConverter>);
+/// A file, with its times read from the directory entry when it's looked up,
+/// as the entry is only written back when the file is closed.
+///
+/// The file borrows the filesystem kept alive by the last field, which is
+/// dropped after it.
+pub struct FileWrapper<'a, IO: IoTrait>(
+    Mutex<File<'a, IO, WallTimeProvider, LossyOemCpConverter>>,
+    Mutex<FileTimes>,
+    Arc<FatFs<IO>>,
+);
+/// A directory, with its times read from the directory entry, which are
+/// zero for the root directory.
+///
+/// The directory borrows the filesystem kept alive by the last field, which
+/// is dropped after it.
+pub struct DirWrapper<'a, IO: IoTrait>(
+    Dir<'a, IO, WallTimeProvider, LossyOemCpConverter>,
+    FileTimes,
+    Arc<FatFs<IO>>,
+);
 
 pub trait IoTrait: Read + Write + Seek {}
 
-unsafe impl Sync for FatFileSystem {}
-unsafe impl Send for FatFileSystem {}
 unsafe impl<'a, IO: IoTrait> Send for FileWrapper<'a, IO> {}
 unsafe impl<'a, IO: IoTrait> Sync for FileWrapper<'a, IO> {}
 unsafe impl<'a, IO: IoTrait> Send for DirWrapper<'a, IO> {}
 unsafe impl<'a, IO: IoTrait> Sync for DirWrapper<'a, IO> {}
 
 impl FatFileSystem {
+    /// Formats `disk` as a FAT filesystem.
     #[cfg(feature = "use-ramdisk")]
-    pub fn new(mut disk: Disk) -> Self {
+    pub fn format(disk: &mut Disk) -> VfsResult {
         let opts = fatfs::FormatVolumeOptions::new();
-        fatfs::format_volume(&mut disk, opts).expect("failed to format volume");
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
-        Self {
-            inner,
-            root_dir: UnsafeCell::new(None),
-        }
+        fatfs::format_volume(disk, opts).map_err(as_vfs_err)
     }
 
-    #[cfg(not(feature = "use-ramdisk"))]
-    pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
-        Self {
-            inner,
-            root_dir: UnsafeCell::new(None),
-        }
-    }
-
-    pub fn init(&'static self) {
-        // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
+    /// Opens the FAT filesystem on `disk`.
+    pub fn open(disk: Disk) -> VfsResult<Self> {
+        let opts = fatfs::FsOptions::new().time_provider(WallTimeProvider);
+        let fs = Arc::new(fatfs::FileSystem::new(disk, opts).map_err(as_vfs_err)?);
+        let root_dir = borrow_fs(&fs).root_dir();
+        Ok(Self {
+            root_dir: Self::new_dir(root_dir, FileTimes::default(), fs),
+        })
     }
 
     fn new_file<IO: IoTrait>(
-        file: File<'_, IO, NullTimeProvider, LossyOemCpConverter>,
-    ) -> Arc<FileWrapper<IO>> {
-        Arc::new(FileWrapper(Mutex::new(file)))
+        file: File<'_, IO, WallTimeProvider, LossyOemCpConverter>,
+        times: FileTimes,
+        fs: Arc<FatFs<IO>>,
+    ) -> Arc<FileWrapper<'_, IO>> {
+        Arc::new(FileWrapper(Mutex::new(file), Mutex::new(times), fs))
     }
 
     fn new_dir<IO: IoTrait>(
-        dir: Dir<'_, IO, NullTimeProvider, LossyOemCpConverter>,
-    ) -> Arc<DirWrapper<IO>> {
-        Arc::new(DirWrapper(dir))
+        dir: Dir<'_, IO, WallTimeProvider, LossyOemCpConverter>,
+        times: FileTimes,
+        fs: Arc<FatFs<IO>>,
+    ) -> Arc<DirWrapper<'_, IO>> {
+        Arc::new(DirWrapper(dir, times, fs))
+    }
+}
+
+/// Borrows the filesystem for the files and directories in it, which hold
+/// the `Arc` to keep it alive.
+fn borrow_fs<IO: IoTrait + 'static>(fs: &Arc<FatFs<IO>>) -> &'static FatFs<IO> {
+    // SAFETY: the filesystem is never moved out of the `Arc`, and the
+    // wrappers drop the borrowing files and directories before the `Arc`.
+    unsafe { &*Arc::as_ptr(fs) }
+}
+
+/// Only the times of files can be changed. They are written back with the
+/// directory entries when the files are closed.
+impl TimesFs for FatFileSystem {
+    fn times(&self, node: &VfsNodeRef) -> VfsResult<FileTimes> {
+        let node = node.as_any();
+        if let Some(file) = node.downcast_ref::<FileWrapper<'static, Disk>>() {
+            Ok(*file.1.lock())
+        } else if let Some(dir) = node.downcast_ref::<DirWrapper<'static, Disk>>() {
+            Ok(dir.1)
+        } else {
+            Err(VfsError::InvalidInput)
+        }
+    }
+
+    fn set_times(
+        &self,
+        node: &VfsNodeRef,
+        accessed: Option<Duration>,
+        modified: Option<Duration>,
+        _changed: Option<Duration>,
+    ) -> VfsResult {
+        let file = node
+            .as_any()
+            .downcast_ref::<FileWrapper<'static, Disk>>()
+            .ok_or(VfsError::Unsupported)?;
+        let mut inner = file.0.lock();
+        let mut times = file.1.lock();
+        if let Some(time) = accessed {
+            let date = fat_date_time(time).date;
+            inner.set_accessed(date);
+            times.accessed = unix_time(date, Time::new(0, 0, 0, 0));
+        }
+        if let Some(time) = modified {
+            let date_time = fat_date_time(time);
+            inner.set_modified(date_time);
+            // stored in 2 seconds
+            let secs = unix_time(date_time.date, date_time.time).as_secs() & !1;
+            times.modified = Duration::from_secs(secs);
+            times.changed = times.modified;
+        }
+        Ok(())
     }
 }
 
-impl<IO: IoTrait> VfsNodeOps for FileWrapper<'static, IO> {
+impl<IO: IoTrait + 'static> VfsNodeOps for FileWrapper<'static, IO> {
     axfs_vfs::impl_vfs_non_dir_default! {}
 
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
@@ -96,9 +235,32 @@ impl<IO: IoTrait> VfsNodeOps for FileWrapper<'static, IO> {
         file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
         file.truncate().map_err(as_vfs_err)
     }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
 }
 
-impl<IO: IoTrait> VfsNodeOps for DirWrapper<'static, IO> {
+impl<IO: IoTrait> DirWrapper<'static, IO> {
+    /// Finds the entry at `path` by the long or the short name, in a single
+    /// pass over its directory.
+    // TODO: use `fatfs::Dir::find_entry`, but it's not public.
+    fn find_entry(
+        &self,
+        path: &str,
+    ) -> Option<DirEntry<'static, IO, WallTimeProvider, LossyOemCpConverter>> {
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((dir, name)) => (self.0.open_dir(dir).ok()?, name),
+            None => (self.0.clone(), path),
+        };
+        dir.iter().filter_map(Result::ok).find(|entry| {
+            entry.file_name().eq_ignore_ascii_case(name)
+                || entry.short_file_name().eq_ignore_ascii_case(name)
+        })
+    }
+}
+
+impl<IO: IoTrait + 'static> VfsNodeOps for DirWrapper<'static, IO> {
     axfs_vfs::impl_vfs_dir_default! {}
 
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
@@ -112,9 +274,13 @@ impl<IO: IoTrait> VfsNodeOps for DirWrapper<'static, IO> {
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
-        self.0
-            .open_dir("..")
-            .map_or(None, |dir| Some(FatFileSystem::new_dir(dir)))
+        self.0.open_dir("..").map_or(None, |dir| {
+            Some(FatFileSystem::new_dir(
+                dir,
+                FileTimes::default(),
+                self.2.clone(),
+            ))
+        })
     }
 
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
@@ -127,13 +293,14 @@ impl<IO: IoTrait> VfsNodeOps for DirWrapper<'static, IO> {
             return self.lookup(rest);
         }
 
-        // TODO: use `fatfs::Dir::find_entry`, but it's not public.
-        if let Ok(file) = self.0.open_file(path) {
-            Ok(FatFileSystem::new_file(file))
-        } else if let Ok(dir) = self.0.open_dir(path) {
-            Ok(FatFileSystem::new_dir(dir))
+        let Some(entry) = self.find_entry(path) else {
+            return Err(VfsError::NotFound);
+        };
+        let (times, fs) = (entry_times(&entry), self.2.clone());
+        if entry.is_dir() {
+            Ok(FatFileSystem::new_dir(entry.to_dir(), times, fs))
         } else {
-            Err(VfsError::NotFound)
+            Ok(FatFileSystem::new_file(entry.to_file(), times, fs))
         }
     }
 
@@ -202,12 +369,15 @@ impl<IO: IoTrait> VfsNodeOps for DirWrapper<'static, IO> {
             .rename(src_path, &self.0, dst_path)
             .map_err(as_vfs_err)
     }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
 }
 
 impl VfsOps for FatFileSystem {
     fn root_dir(&self) -> VfsNodeRef {
-        let root_dir = unsafe { (*self.root_dir.get()).as_ref().unwrap() };
-        root_dir.clone()
+        self.root_dir.clone()
     }
 }
 
@@ -251,7 +421,7 @@ impl Write for Disk {
         Ok(write_len)
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        Disk::flush(self).map_err(|_| ())
     }
 }
 
@@ -294,39 +464,35 @@ impl Clone for FileWrapper<'static, Disk> {
     fn clone(&self) -> Self {
         let file = self.0.lock();
         let cloned_file = file.clone();
-        Self(Mutex::new(cloned_file))
+        Self(
+            Mutex::new(cloned_file),
+            Mutex::new(*self.1.lock()),
+            self.2.clone(),
+        )
     }
 }
 
 pub struct FatFileSystemFromFile {
-    inner: fatfs::FileSystem<FileWrapper<'static, Disk>, NullTimeProvider, LossyOemCpConverter>,
-    root_dir: UnsafeCell<Option<VfsNodeRef>>,
+    root_dir: VfsNodeRef,
 }
 
-unsafe impl Sync for FatFileSystemFromFile {}
-unsafe impl Send for FatFileSystemFromFile {}
-
 #[allow(unused)]
 impl FatFileSystemFromFile {
     pub fn new(file: FileWrapper<'static, Disk>) -> Self {
-        let inner = fatfs::FileSystem::new(file, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
+        let opts = fatfs::FsOptions::new().time_provider(WallTimeProvider);
+        let fs = Arc::new(
+            fatfs::FileSystem::new(file, opts).expect("failed to initialize FAT filesystem"),
+        );
+        let root_dir = borrow_fs(&fs).root_dir();
         Self {
-            inner,
-            root_dir: UnsafeCell::new(None),
+            root_dir: FatFileSystem::new_dir(root_dir, FileTimes::default(), fs),
         }
     }
-
-    pub fn init(&'static self) {
-        // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(FatFileSystem::new_dir(self.inner.root_dir())) }
-    }
 }
 
 impl VfsOps for FatFileSystemFromFile {
     fn root_dir(&self) -> VfsNodeRef {
-        let root_dir = unsafe { (*self.root_dir.get()).as_ref().unwrap() };
-        root_dir.clone()
+        self.root_dir.clone()
     }
 }
 
@@ -1,43 +1,76 @@
 use crate::alloc::string::String;
-use alloc::sync::Arc;
+use alloc::{ffi::CString, format, sync::Arc, vec};
 use axerrno::AxError;
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
+use core::ffi::{c_char, c_int};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
 use lwext4_rust::bindings::{
-    O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, SEEK_CUR, SEEK_END, SEEK_SET,
+    O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, SEEK_CUR, SEEK_END, SEEK_SET, ext4_atime_get,
+    ext4_atime_set, ext4_ctime_get, ext4_ctime_set, ext4_fsymlink, ext4_mode_set, ext4_mtime_get,
+    ext4_mtime_set, ext4_owner_get, ext4_owner_set, ext4_raw_inode_fill, ext4_readlink,
 };
 use lwext4_rust::{Ext4BlockWrapper, Ext4File, InodeTypes, KernelDevOp};
 
 use crate::dev::Disk;
+use crate::perm::{Owner, OwnerFs};
+use crate::symlink::SymlinkFs;
+use crate::times::{FileTimes, TimesFs};
 pub const BLOCK_SIZE: usize = 512;
 
+/// Whether an ext4 filesystem is mounted, as lwext4 mounts it at a global
+/// mount point, so only one can be opened at a time.
+static MOUNTED: AtomicBool = AtomicBool::new(false);
+
+/// Marks the ext4 filesystem as mounted until it's dropped.
+struct MountGuard;
+
+impl MountGuard {
+    fn acquire() -> VfsResult<Self> {
+        if MOUNTED.swap(true, Ordering::Acquire) {
+            return Err(VfsError::ResourceBusy);
+        }
+        Ok(Self)
+    }
+}
+
+impl Drop for MountGuard {
+    fn drop(&mut self) {
+        MOUNTED.store(false, Ordering::Release);
+    }
+}
+
 #[allow(dead_code)]
 pub struct Ext4FileSystem {
     inner: Ext4BlockWrapper<Disk>,
     root: VfsNodeRef,
+    /// Dropped after `inner`, which unmounts it from lwext4.
+    _guard: MountGuard,
 }
 
 unsafe impl Sync for Ext4FileSystem {}
 unsafe impl Send for Ext4FileSystem {}
 
 impl Ext4FileSystem {
-    #[cfg(feature = "use-ramdisk")]
-    pub fn new(mut disk: Disk) -> Self {
-        unimplemented!()
-    }
-
-    #[cfg(not(feature = "use-ramdisk"))]
-    pub fn new(disk: Disk) -> Self {
+    /// Opens the ext4 filesystem on `disk`, which fails with
+    /// [`ResourceBusy`](VfsError::ResourceBusy) if another one is opened.
+    pub fn open(disk: Disk) -> VfsResult<Self> {
+        let guard = MountGuard::acquire()?;
         info!(
             "Got Disk size:{}, position:{}",
             disk.size(),
             disk.position()
         );
-        let inner =
-            Ext4BlockWrapper::<Disk>::new(disk).expect("failed to initialize EXT4 filesystem");
+        let inner = Ext4BlockWrapper::<Disk>::new(disk)
+            .map_err(|e| AxError::try_from(e).unwrap_or(AxError::Io))?;
         let root = Arc::new(FileWrapper::new("/", InodeTypes::EXT4_DE_DIR));
-        Self { inner, root }
+        Ok(Self {
+            inner,
+            root,
+            _guard: guard,
+        })
     }
 }
 
@@ -52,6 +85,121 @@ impl VfsOps for Ext4FileSystem {
     }
 }
 
+impl SymlinkFs for Ext4FileSystem {
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        let path = CString::new(format!("/{}", path)).map_err(|_| VfsError::InvalidInput)?;
+        let target = CString::new(target).map_err(|_| VfsError::InvalidInput)?;
+        let r = unsafe { ext4_fsymlink(target.as_ptr(), path.as_ptr()) };
+        if r != 0 {
+            return Err(r.try_into().unwrap());
+        }
+        Ok(())
+    }
+
+    fn readlink(&self, path: &str) -> VfsResult<String> {
+        let path = CString::new(format!("/{}", path)).map_err(|_| VfsError::InvalidInput)?;
+        let mut buf = vec![0u8; 4096]; // PATH_MAX
+        let mut len = 0;
+        let r = unsafe { ext4_readlink(path.as_ptr(), buf.as_mut_ptr() as _, buf.len(), &mut len) };
+        if r != 0 {
+            return Err(r.try_into().unwrap());
+        }
+        buf.truncate(len);
+        String::from_utf8(buf).map_err(|_| VfsError::InvalidData)
+    }
+}
+
+impl OwnerFs for Ext4FileSystem {
+    fn owner(&self, node: &VfsNodeRef) -> VfsResult<Owner> {
+        let path = FileWrapper::path_of(node)?;
+        let (mut uid, mut gid) = (0, 0);
+        let r = unsafe { ext4_owner_get(path.as_ptr(), &mut uid, &mut gid) };
+        if r != 0 {
+            return Err(r.try_into().unwrap());
+        }
+        Ok(Owner { uid, gid })
+    }
+
+    fn set_owner(&self, node: &VfsNodeRef, owner: Owner) -> VfsResult {
+        let path = FileWrapper::path_of(node)?;
+        let r = unsafe { ext4_owner_set(path.as_ptr(), owner.uid, owner.gid) };
+        if r != 0 {
+            return Err(r.try_into().unwrap());
+        }
+        Ok(())
+    }
+
+    /// The file type bits are kept by lwext4.
+    fn set_perm(&self, node: &VfsNodeRef, perm: VfsNodePerm) -> VfsResult {
+        let path = FileWrapper::path_of(node)?;
+        let r = unsafe { ext4_mode_set(path.as_ptr(), perm.bits() as u32) };
+        if r != 0 {
+            return Err(r.try_into().unwrap());
+        }
+        Ok(())
+    }
+
+    fn inode(&self, node: &VfsNodeRef) -> VfsResult<u64> {
+        let path = FileWrapper::path_of(node)?;
+        let mut ino = 0;
+        let mut inode = unsafe { core::mem::zeroed() };
+        let r = unsafe { ext4_raw_inode_fill(path.as_ptr(), &mut ino, &mut inode) };
+        if r != 0 {
+            return Err(r.try_into().unwrap());
+        }
+        Ok(ino as u64)
+    }
+}
+
+/// A function of lwext4 getting a time of the file at a path.
+type TimeGetter = unsafe extern "C" fn(*const c_char, *mut u32) -> c_int;
+/// A function of lwext4 setting a time of the file at a path.
+type TimeSetter = unsafe extern "C" fn(*const c_char, u32) -> c_int;
+
+/// The times are stored in seconds, and those after 2106 are not supported.
+impl TimesFs for Ext4FileSystem {
+    fn times(&self, node: &VfsNodeRef) -> VfsResult<FileTimes> {
+        let path = FileWrapper::path_of(node)?;
+        let get = |f: TimeGetter| -> VfsResult<Duration> {
+            let mut time = 0;
+            let r = unsafe { f(path.as_ptr(), &mut time) };
+            if r != 0 {
+                return Err(r.try_into().unwrap());
+            }
+            Ok(Duration::from_secs(time as u64))
+        };
+        Ok(FileTimes {
+            accessed: get(ext4_atime_get)?,
+            modified: get(ext4_mtime_get)?,
+            changed: get(ext4_ctime_get)?,
+        })
+    }
+
+    fn set_times(
+        &self,
+        node: &VfsNodeRef,
+        accessed: Option<Duration>,
+        modified: Option<Duration>,
+        changed: Option<Duration>,
+    ) -> VfsResult {
+        let path = FileWrapper::path_of(node)?;
+        let set = |f: TimeSetter, time: Option<Duration>| -> VfsResult {
+            let Some(time) = time else {
+                return Ok(());
+            };
+            let secs = time.as_secs().min(u32::MAX as u64) as u32;
+            let r = unsafe { f(path.as_ptr(), secs) };
+            if r != 0 {
+                return Err(r.try_into().unwrap());
+            }
+            Ok(())
+        };
+        set(ext4_atime_set, accessed)?;
+        set(ext4_mtime_set, modified)?;
+        set(ext4_ctime_set, changed)
+    }
+}
+
 pub struct FileWrapper(Mutex<Ext4File>);
 
 unsafe impl Send for FileWrapper {}
@@ -65,6 +213,15 @@ impl FileWrapper {
         Self(Mutex::new(Ext4File::new(path, types)))
     }
 
+    /// Returns the absolute path of the ext4 node `node`.
+    fn path_of(node: &VfsNodeRef) -> VfsResult<CString> {
+        let file = node
+            .as_any()
+            .downcast_ref::<Self>()
+            .ok_or(VfsError::InvalidInput)?;
+        Ok(file.0.lock().get_path())
+    }
+
     fn path_deal_with(&self, path: &str) -> String {
         if path.starts_with('/') {
             warn!("path_deal_with: {}", path);
@@ -266,6 +423,9 @@ impl VfsNodeOps for FileWrapper {
         } else if file.check_inode_exist(fpath, InodeTypes::EXT4_DE_REG_FILE) {
             trace!("lookup new FILE FileWrapper");
             Ok(Arc::new(Self::new(fpath, InodeTypes::EXT4_DE_REG_FILE)))
+        } else if file.check_inode_exist(fpath, InodeTypes::EXT4_DE_SYMLINK) {
+            trace!("lookup new SYMLINK FileWrapper");
+            Ok(Arc::new(Self::new(fpath, InodeTypes::EXT4_DE_SYMLINK)))
         } else {
             Err(VfsError::NotFound)
         }
@@ -372,7 +532,8 @@ impl KernelDevOp for Disk {
         trace!("WRITE rt len={}", write_len);
         Ok(write_len)
     }
-    fn flush(_dev: &mut Self::DevType) -> Result<usize, i32> {
+    fn flush(dev: &mut Self::DevType) -> Result<usize, i32> {
+        Disk::flush(dev).map_err(|_| -1)?;
         Ok(0)
     }
     fn seek(dev: &mut Disk, off: i64, whence: i32) -> Result<i64, i32> {
@@ -0,0 +1,146 @@
+//! Types of disk filesystems.
+//!
+//! Each enabled filesystem driver is registered in [`FS_TYPES`] with a
+//! function to probe its superblock, so the type of the filesystem on a disk
+//! is detected at mount time unless it's specified.
+
+use alloc::sync::Arc;
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::VfsOps;
+
+use crate::dev::{BLOCK_SIZE, Disk};
+use crate::fs;
+use crate::perm::Owners;
+use crate::symlink::Symlinks;
+use crate::times::Times;
+
+/// An opened disk filesystem.
+pub(crate) struct DiskFs {
+    pub fs: Arc<dyn VfsOps>,
+    pub links: Symlinks,
+    pub owners: Owners,
+    pub times: Times,
+}
+
+/// A type of disk filesystems.
+pub(crate) struct FsType {
+    /// The name used by `mount`, as on Linux.
+    pub name: &'static str,
+    /// Checks whether the filesystem on the disk is of this type, `None` if
+    /// it can't be detected.
+    probe: Option<fn(&Disk) -> bool>,
+    /// Opens the filesystem on the disk.
+    open: fn(Disk) -> AxResult<DiskFs>,
+}
+
+/// The types of disk filesystems, in the order of probing.
+static FS_TYPES: &[FsType] = &[
+    #[cfg(feature = "lwext4_rs")]
+    FsType {
+        name: "ext4",
+        probe: Some(probe_ext4),
+        open: open_ext4,
+    },
+    #[cfg(feature = "fatfs")]
+    FsType {
+        name: "vfat",
+        probe: Some(probe_fat),
+        open: open_fat,
+    },
+    // custom filesystems can't be detected, and are mounted by the name only
+    #[cfg(feature = "myfs")]
+    FsType {
+        name: "myfs",
+        probe: None,
+        open: |disk| {
+            Ok(DiskFs {
+                fs: fs::myfs::new_myfs(disk),
+                links: Symlinks::Unsupported,
+                owners: Owners::Fixed,
+                times: Times::Fixed,
+            })
+        },
+    },
+];
+
+impl FsType {
+    /// Opens the filesystem on `disk`, which fails with
+    /// [`InvalidInput`](axerrno::AxError::InvalidInput) if it's detected not
+    /// of this type.
+    pub fn open(&self, disk: Disk) -> AxResult<DiskFs> {
+        if self.probe.is_some_and(|probe| !probe(&disk)) {
+            return ax_err!(InvalidInput, "wrong filesystem type");
+        }
+        (self.open)(disk)
+    }
+}
+
+/// Finds the filesystem type by its name.
+pub(crate) fn find(name: &str) -> Option<&'static FsType> {
+    FS_TYPES.iter().find(|ty| ty.name == name)
+}
+
+/// Detects the type of the filesystem on `disk`.
+pub(crate) fn probe(disk: &Disk) -> Option<&'static FsType> {
+    FS_TYPES
+        .iter()
+        .find(|ty| ty.probe.is_some_and(|probe| probe(disk)))
+}
+
+/// Checks the FAT boot sector, whose BIOS parameter block has sane values.
+#[cfg(feature = "fatfs")]
+fn probe_fat(disk: &Disk) -> bool {
+    let mut boot = [0; BLOCK_SIZE];
+    if disk.read_block(0, &mut boot).is_err() {
+        return false;
+    }
+    let bytes_per_sector = u16::from_le_bytes([boot[11], boot[12]]);
+    let sectors_per_cluster = boot[13];
+    let reserved_sectors = u16::from_le_bytes([boot[14], boot[15]]);
+    let num_fats = boot[16];
+    matches!(boot[0], 0xeb | 0xe9)
+        && boot[510..] == [0x55, 0xaa]
+        && matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096)
+        && sectors_per_cluster.is_power_of_two()
+        && reserved_sectors > 0
+        && num_fats > 0
+}
+
+#[cfg(feature = "fatfs")]
+fn open_fat(disk: Disk) -> AxResult<DiskFs> {
+    let fs = Arc::new(fs::fatfs::FatFileSystem::open(disk)?);
+    Ok(DiskFs {
+        fs: fs.clone(),
+        links: Symlinks::Unsupported,
+        owners: Owners::Fixed,
+        times: Times::Native(fs),
+    })
+}
+
+/// Checks the magic number of the ext2/3/4 superblock at offset 1024.
+#[cfg(feature = "lwext4_rs")]
+fn probe_ext4(disk: &Disk) -> bool {
+    const SUPERBLOCK_OFFSET: usize = 1024;
+    const MAGIC_OFFSET: usize = 0x38;
+    let mut block = [0; BLOCK_SIZE];
+    let pos = SUPERBLOCK_OFFSET + MAGIC_OFFSET;
+    if disk
+        .read_block((pos / BLOCK_SIZE) as u64, &mut block)
+        .is_err()
+    {
+        return false;
+    }
+    let offset = pos % BLOCK_SIZE;
+    u16::from_le_bytes([block[offset], block[offset + 1]]) == 0xef53
+}
+
+#[cfg(feature = "lwext4_rs")]
+fn open_ext4(disk: Disk) -> AxResult<DiskFs> {
+    let fs = Arc::new(fs::lwext4_rust::Ext4FileSystem::open(disk)?);
+    Ok(DiskFs {
+        fs: fs.clone(),
+        links: Symlinks::Native(fs.clone()),
+        owners: Owners::Native(fs.clone()),
+        times: Times::Native(fs),
+    })
+}
@@ -4,19 +4,76 @@
 //!
 //! # Cargo Features
 //!
-//! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. This feature
-//!   is **enabled** by default.
-//! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
+//! - `fatfs`: Support [FAT] filesystems (`vfat`). This feature is **enabled**
+//!   by default.
+//! - `lwext4_rs`: Support ext4 filesystems (`ext4`) by [lwext4]. This feature
+//!   is **disabled** by default.
+//! - `devfs`: Mount a devfs on `/dev` with the block devices, other modules can
+//!   add devices to it by [`devfs::register_char_device`]. This feature is
 //!   **enabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
 //!   **enabled** by default.
-//! - `myfs`: Allow users to define their custom filesystems to override the
-//!   default. In this case, [`MyFileSystemIf`] is required to be implemented
-//!   to create and initialize other filesystems. This feature is **disabled** by
-//!   by default, but it will override other filesystem selection features if
-//!   both are enabled.
+//! - `procfs`: Mount a procfs on `/proc`, other modules can add generated
+//!   files and directories to it by [`proc::register_file`],
+//!   [`proc::register_tunable`] and [`proc::register_dir`]. This feature is
+//!   **enabled** by default.
+//! - `sysfs`: Mount a sysfs on `/sys`, generated in the same way by
+//!   [`sys::register_file`] and [`sys::register_dir`]. This feature is
+//!   **enabled** by default.
+//! - `myfs`: Allow users to define their custom filesystems (`myfs`) to
+//!   override the default. In this case, [`MyFileSystemIf`] is required to be
+//!   implemented to create and initialize other filesystems. This feature is
+//!   **disabled** by default, but it will be chosen for the root filesystem
+//!   over other filesystems if enabled.
+//! - `multitask`: Block the tasks waiting for file locks, which fail
+//!   immediately otherwise. This feature is **disabled** by default.
+//!
+//! # Block devices
+//!
+//! All block devices are used, and the MBR or GPT partition tables on them
+//! are parsed. They are named `disk<N>`, and their partitions `disk<N>p<M>`.
+//! The root filesystem is on the first partition of the first disk (or the
+//! whole disk if it has no partitions) by default, and its type is detected
+//! by probing the superblock. Both can be changed on the kernel command line,
+//! see [`init_filesystems`]. Other partitions can be mounted by
+//! [`api::mount`] with the source `/dev/<name>`. With the `devfs` feature,
+//! they are also device nodes named like Linux's VirtIO disks, e.g.
+//! `/dev/vda` for `disk0` and `/dev/vda1` for `disk0p1`, and these names can be
+//! used as well.
+//!
+//! Blocks are cached by a write-back LRU cache of each device, whose size is
+//! set by `block-cache-size` in the platform config. Dirty blocks are written
+//! back when evicted, when files are flushed, or by [`api::sync`].
+//!
+//! File contents on FAT and ext4 filesystems are cached in pages, up to
+//! `page-cache-size` pages of all files. The pages can be mapped into memory
+//! through [`fops::File::page_cache`].
+//!
+//! # Owners and permissions
+//!
+//! Files are owned by users and groups, and their permissions are checked
+//! against the current user set by [`api::set_current_user`], which is `root`
+//! by default. They can be changed by [`api::set_permissions`] and
+//! [`api::chown`] on `tmpfs`, `ramfs` and ext4, while the files on other
+//! filesystems are owned by `root` with fixed permissions.
+//!
+//! # Times
+//!
+//! The access, modification and change times of files are updated as on
+//! Linux with the `relatime` option, from [`axhal::time::wall_time`], and can
+//! be set by [`api::set_times`]. They are stored natively on FAT (whose
+//! access times are dates only, with no change times) and ext4 (in seconds),
+//! in memory on `tmpfs` and `ramfs`, while the files on pseudo filesystems
+//! always have the current time.
+//!
+//! # Locks
+//!
+//! Opened files can be locked by [`fops::File::flock`] for the whole files,
+//! and by [`fops::File::lock_range`] for byte ranges. The locks are advisory,
+//! owned by the opened files and released when they are closed.
 //!
 //! [FAT]: https://en.wikipedia.org/wiki/File_Allocation_Table
+//! [lwext4]: https://github.com/gkostka/lwext4
 //! [`MyFileSystemIf`]: fops::MyFileSystemIf
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
@@ -26,22 +83,75 @@
 extern crate log;
 extern crate alloc;
 
+mod cache;
 mod dev;
 mod fs;
+mod fstype;
+mod lock;
+mod meta;
 mod mounts;
+mod page_cache;
+mod partition;
+mod perm;
+#[cfg(any(feature = "devfs", feature = "procfs", feature = "sysfs"))]
+mod pseudo;
 mod root;
+mod special;
+mod symlink;
+mod times;
 
 pub mod api;
+#[cfg(feature = "devfs")]
+pub mod devfs;
 pub mod fops;
+#[cfg(feature = "procfs")]
+pub mod proc;
+#[cfg(feature = "sysfs")]
+pub mod sys;
 pub use root::{CURRENT_DIR, CURRENT_DIR_PATH};
 
+use alloc::{format, vec::Vec};
 use axdriver::{AxDeviceContainer, prelude::*};
 
 /// Initializes filesystems by block devices.
+///
+/// The root filesystem is chosen by the parameters of the kernel command line
+/// (see [`axhal::cmdline`]):
+///
+/// - `root=`: the block device, which is the name of a disk or partition
+///   (e.g. `disk0p2`, `/dev/disk0p2` or `vda2`), `PARTLABEL=<label>` or
+///   `PARTUUID=<uuid>`.
+/// - `rootfstype=`: the filesystem type, e.g. `vfat` or `ext4`, detected if
+///   it's empty.
+///
+/// If not given, they default to the environment variables `AX_ROOT` and
+/// `AX_ROOTFSTYPE` at build time.
 pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
     info!("Initialize filesystems...");
 
-    let dev = blk_devs.take_one().expect("No block device found!");
-    info!("  use block device 0: {:?}", dev.device_name());
-    self::root::init_rootfs(self::dev::Disk::new(dev));
+    let mut disks = Vec::new();
+    while let Some(dev) = blk_devs.take_one() {
+        info!(
+            "  use block device {}: {:?}",
+            disks.len(),
+            dev.device_name()
+        );
+        disks.push(self::dev::Disk::new(dev));
+    }
+    assert!(!disks.is_empty(), "No block device found!");
+    self::dev::init_block_devices(disks);
+    #[cfg(feature = "devfs")]
+    self::devfs::init();
+
+    let spec = axhal::cmdline::get("root")
+        .or(option_env!("AX_ROOT"))
+        .unwrap_or("");
+    let spec = spec.strip_prefix("/dev/").unwrap_or(spec);
+    let fstype = axhal::cmdline::get("rootfstype")
+        .or(option_env!("AX_ROOTFSTYPE"))
+        .filter(|ty| !ty.is_empty());
+    let root =
+        self::dev::find_root(spec).unwrap_or_else(|| panic!("root device {:?} not found", spec));
+    info!("  mount {} on /", root.name);
+    self::root::init_rootfs(root.disk(), &format!("/dev/{}", root.name), fstype);
 }
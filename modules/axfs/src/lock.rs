@@ -0,0 +1,257 @@
+//! Advisory file locks.
+//!
+//! Locks are kept in a table keyed by the files, see [`LockKey`]: the nodes
+//! of in-memory filesystems live as long as the files, and those on disk
+//! filesystems are shared through the page caches, but the hard links of a
+//! file are opened through different nodes, so the files on filesystems with
+//! hard links are identified by their inode numbers.
+//!
+//! Each lock is owned by an opened [`File`](crate::fops::File), like Linux's
+//! open file description locks, and released when it's closed. Whole-file
+//! locks of `flock` and byte-range locks of `fcntl` don't conflict with each
+//! other, as on Linux. Tasks waiting for locks are blocked on a wait queue
+//! with the `multitask` feature, otherwise waiting fails immediately as no
+//! other tasks can release the locks.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use axerrno::{AxResult, ax_err};
+use axsync::spin::SpinNoIrq;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "multitask")]
+use axtask::WaitQueue;
+
+/// The type of a file lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// A shared (read) lock, which can be held by multiple files.
+    Shared,
+    /// An exclusive (write) lock, which can be held by only one file.
+    Exclusive,
+}
+
+/// A byte-range lock held by another file, returned by
+/// [`File::test_lock`](crate::fops::File::test_lock).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeLock {
+    /// The type of the lock.
+    pub kind: LockKind,
+    /// The first byte locked.
+    pub start: u64,
+    /// The end of the locked range (exclusive), [`u64::MAX`] if it extends
+    /// to the end of the file however it grows.
+    pub end: u64,
+}
+
+/// Identifies the owner of locks, unique for each opened file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LockOwner(usize);
+
+/// Identifies a file in the table of locks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum LockKey {
+    /// The address of the only node of the file.
+    Node(usize),
+    /// The inode number of the file, and the address of the owners of its
+    /// filesystem, which are shared by the bind mounts.
+    Inode(usize, u64),
+}
+
+#[derive(Clone, Copy)]
+struct Lock {
+    owner: LockOwner,
+    kind: LockKind,
+    start: u64,
+    end: u64,
+}
+
+/// The locks of a node.
+#[derive(Default)]
+struct NodeLocks {
+    /// The whole-file locks of `flock`.
+    whole: Vec<Lock>,
+    /// The byte-range locks of `fcntl`.
+    ranges: Vec<Lock>,
+}
+
+/// The locks by the nodes. It's taken by the tasks checking the locks in the
+/// wait queue with IRQs disabled, so preemption must be disabled as well.
+static LOCKS: SpinNoIrq<BTreeMap<LockKey, NodeLocks>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Tasks waiting for locks, woken up whenever locks are released.
+#[cfg(feature = "multitask")]
+static WAIT_QUEUE: WaitQueue = WaitQueue::new();
+
+impl LockOwner {
+    /// Returns a new owner, distinct from all others.
+    pub fn new() -> Self {
+        static NEXT_OWNER: AtomicUsize = AtomicUsize::new(0);
+        Self(NEXT_OWNER.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Lock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    /// Whether it conflicts with a lock of `kind` on `start..end` by `owner`.
+    fn conflicts(&self, owner: LockOwner, kind: LockKind, start: u64, end: u64) -> bool {
+        self.owner != owner
+            && self.overlaps(start, end)
+            && (self.kind == LockKind::Exclusive || kind == LockKind::Exclusive)
+    }
+}
+
+impl NodeLocks {
+    fn list(&mut self, whole: bool) -> &mut Vec<Lock> {
+        if whole {
+            &mut self.whole
+        } else {
+            &mut self.ranges
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.whole.is_empty() && self.ranges.is_empty()
+    }
+}
+
+/// Replaces the locks of `owner` on `start..end` with a lock of `kind`, or
+/// removes them if it's `None`, splitting the locks partly in the range and
+/// merging the adjacent ones of the same type.
+fn set_range(
+    locks: &mut Vec<Lock>,
+    owner: LockOwner,
+    kind: Option<LockKind>,
+    start: u64,
+    end: u64,
+) {
+    let mut new = Vec::with_capacity(locks.len() + 2);
+    for lock in locks.drain(..) {
+        if lock.owner != owner || !lock.overlaps(start, end) {
+            new.push(lock);
+            continue;
+        }
+        if lock.start < start {
+            new.push(Lock { end: start, ..lock });
+        }
+        if lock.end > end {
+            new.push(Lock { start: end, ..lock });
+        }
+    }
+    if let Some(kind) = kind {
+        let (mut start, mut end) = (start, end);
+        new.retain(|lock| {
+            let adjacent = lock.owner == owner && lock.kind == kind;
+            if adjacent && lock.start <= end && start <= lock.end {
+                start = start.min(lock.start);
+                end = end.max(lock.end);
+                false
+            } else {
+                true
+            }
+        });
+        new.push(Lock {
+            owner,
+            kind,
+            start,
+            end,
+        });
+    }
+    *locks = new;
+}
+
+/// Sets the lock, or returns `false` if it conflicts with other locks.
+fn try_set(
+    key: LockKey,
+    owner: LockOwner,
+    whole: bool,
+    kind: Option<LockKind>,
+    start: u64,
+    end: u64,
+) -> bool {
+    let mut table = LOCKS.lock();
+    if kind.is_none() && !table.contains_key(&key) {
+        return true;
+    }
+    let node_locks = table.entry(key).or_default();
+    let locks = node_locks.list(whole);
+    if kind.is_some_and(|kind| locks.iter().any(|l| l.conflicts(owner, kind, start, end))) {
+        return false;
+    }
+    set_range(locks, owner, kind, start, end);
+    if node_locks.is_empty() {
+        table.remove(&key);
+    }
+    true
+}
+
+/// Sets or removes (if `kind` is `None`) a lock of `owner` on `start..end`
+/// of the file `key`, a whole-file lock if `whole` is set. If it conflicts with the
+/// locks of other owners, it fails with [`WouldBlock`] unless `wait` is set,
+/// then it waits until they are released.
+///
+/// [`WouldBlock`]: axerrno::AxError::WouldBlock
+pub(crate) fn set(
+    key: LockKey,
+    owner: LockOwner,
+    whole: bool,
+    kind: Option<LockKind>,
+    start: u64,
+    end: u64,
+    wait: bool,
+) -> AxResult {
+    if !try_set(key, owner, whole, kind, start, end) {
+        if !wait {
+            return ax_err!(WouldBlock, "the file is locked");
+        }
+        #[cfg(feature = "multitask")]
+        WAIT_QUEUE.wait_until(|| try_set(key, owner, whole, kind, start, end));
+        #[cfg(not(feature = "multitask"))]
+        return ax_err!(WouldBlock, "no other tasks to release the lock");
+    }
+    // exclusive locks can't release anything for others
+    #[cfg(feature = "multitask")]
+    if kind != Some(LockKind::Exclusive) {
+        WAIT_QUEUE.notify_all(false);
+    }
+    Ok(())
+}
+
+/// Returns a byte-range lock of other owners on the file `key` conflicting
+/// with a lock of `kind` on `start..end` by `owner`.
+pub(crate) fn test(
+    key: LockKey,
+    owner: LockOwner,
+    kind: LockKind,
+    start: u64,
+    end: u64,
+) -> Option<RangeLock> {
+    let table = LOCKS.lock();
+    let locks = &table.get(&key)?.ranges;
+    locks
+        .iter()
+        .find(|lock| lock.conflicts(owner, kind, start, end))
+        .map(|lock| RangeLock {
+            kind: lock.kind,
+            start: lock.start,
+            end: lock.end,
+        })
+}
+
+/// Releases all the locks of `owner` on the file `key` after it's closed.
+pub(crate) fn release(key: LockKey, owner: LockOwner) {
+    let mut table = LOCKS.lock();
+    let Some(node_locks) = table.get_mut(&key) else {
+        return;
+    };
+    node_locks.whole.retain(|lock| lock.owner != owner);
+    node_locks.ranges.retain(|lock| lock.owner != owner);
+    if node_locks.is_empty() {
+        table.remove(&key);
+    }
+    drop(table);
+    #[cfg(feature = "multitask")]
+    WAIT_QUEUE.notify_all(false);
+}
@@ -0,0 +1,102 @@
+//! Metadata of the nodes of in-memory filesystems.
+//!
+//! The nodes of [`axfs_vfs`] have no owners or times, and their permissions
+//! can't be changed, so in-memory filesystems keep them in a [`NodeTable`]
+//! shared by the [`Owners`](crate::perm::Owners) and the
+//! [`Times`](crate::times::Times) of the mount.
+
+use alloc::{collections::BTreeMap, sync::Arc, sync::Weak};
+use axfs_vfs::{VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axhal::time::wall_time;
+use core::time::Duration;
+use spin::RwLock;
+
+use crate::perm::Owner;
+use crate::times::FileTimes;
+
+/// Returns the key of `node` in the tables of nodes, its address, which
+/// stays the same while the node is alive.
+pub(crate) fn node_key(node: &VfsNodeRef) -> usize {
+    Arc::as_ptr(node) as *const () as usize
+}
+
+/// The owner, permissions and times of a node.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NodeMeta {
+    pub owner: Owner,
+    /// The changed permissions, `None` if they were never changed.
+    pub perm: Option<VfsNodePerm>,
+    pub times: FileTimes,
+}
+
+struct Entry {
+    /// The node, to tell whether the entry is stale.
+    node: Weak<dyn VfsNodeOps>,
+    meta: NodeMeta,
+}
+
+/// The metadata of the nodes of a mounted in-memory filesystem, the keys are
+/// the addresses of the nodes, which are kept alive by the filesystem until
+/// they are removed.
+pub(crate) struct NodeTable {
+    entries: RwLock<BTreeMap<usize, Entry>>,
+    /// The times of the nodes not in the table, which have existed since the
+    /// filesystem was mounted.
+    mounted: Duration,
+}
+
+impl NodeTable {
+    /// Returns the table for the filesystems of the given type, if they keep
+    /// the metadata in memory.
+    pub fn for_fstype(fstype: &str) -> Option<Arc<Self>> {
+        match fstype {
+            "tmpfs" | "ramfs" => Some(Arc::new(Self {
+                entries: RwLock::new(BTreeMap::new()),
+                mounted: wall_time(),
+            })),
+            _ => None,
+        }
+    }
+
+    fn default_meta(&self) -> NodeMeta {
+        NodeMeta {
+            owner: Owner::ROOT,
+            perm: None,
+            times: FileTimes {
+                accessed: self.mounted,
+                modified: self.mounted,
+                changed: self.mounted,
+            },
+        }
+    }
+
+    /// Returns the metadata of `node`.
+    pub fn get(&self, node: &VfsNodeRef) -> NodeMeta {
+        match self.entries.read().get(&node_key(node)) {
+            Some(entry) if entry.node.strong_count() > 0 => entry.meta,
+            _ => self.default_meta(),
+        }
+    }
+
+    /// Updates the metadata of `node` with `f`, atomically.
+    pub fn update<R>(&self, node: &VfsNodeRef, f: impl FnOnce(&mut NodeMeta) -> R) -> R {
+        let mut entries = self.entries.write();
+        let key = node_key(node);
+        let mut meta = match entries.get(&key) {
+            Some(entry) if entry.node.strong_count() > 0 => entry.meta,
+            _ => self.default_meta(),
+        };
+        let ret = f(&mut meta);
+        let entry = Entry {
+            node: Arc::downgrade(node),
+            meta,
+        };
+        entries.insert(key, entry);
+        ret
+    }
+
+    /// Removes `node` from the table after it's removed.
+    pub fn removed(&self, node: &VfsNodeRef) {
+        self.entries.write().remove(&node_key(node));
+    }
+}
@@ -1,19 +1,60 @@
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::VfsOps;
 
 use crate::fs;
+use crate::fstype::{self, DiskFs};
+use crate::meta::NodeTable;
+use crate::perm::Owners;
+#[cfg(any(feature = "devfs", feature = "procfs", feature = "sysfs"))]
+use crate::pseudo::PseudoFileSystem;
+use crate::symlink::Symlinks;
+use crate::times::Times;
+
+/// Creates a filesystem of the given type to be mounted at runtime.
+///
+/// `source` is the block device for disk filesystems, see [`fstype`], and
+/// ignored for the in-memory ones.
+pub(crate) fn new_fs(fstype: &str, source: &str) -> AxResult<DiskFs> {
+    let fs: Arc<dyn VfsOps> = match fstype {
+        #[cfg(feature = "ramfs")]
+        "tmpfs" | "ramfs" => ramfs(),
+        #[cfg(feature = "devfs")]
+        "devfs" => devfs(),
+        #[cfg(feature = "procfs")]
+        "proc" => procfs(),
+        #[cfg(feature = "sysfs")]
+        "sysfs" => sysfs(),
+        _ => return disk_fs(fstype, source),
+    };
+    let table = NodeTable::for_fstype(fstype);
+    Ok(DiskFs {
+        fs,
+        links: Symlinks::for_fstype(fstype),
+        owners: Owners::for_table(table.clone()),
+        times: Times::for_table(table),
+    })
+}
+
+/// Opens the filesystem on the block device `source`.
+fn disk_fs(fstype: &str, source: &str) -> AxResult<DiskFs> {
+    let Some(ty) = fstype::find(fstype) else {
+        return ax_err!(Unsupported, "unknown filesystem type");
+    };
+    let Some(dev) = crate::dev::find_block_device(source) else {
+        return ax_err!(NotFound, "block device not found");
+    };
+    // a disk and its partitions can't be mounted at the same time
+    let mounted = |s: &str| crate::dev::find_block_device(s).is_some_and(|d| d.overlaps(dev));
+    if crate::root::mounts().iter().any(|(s, ..)| mounted(s)) {
+        return ax_err!(ResourceBusy, "block device already mounted");
+    }
+    ty.open(dev.disk())
+}
 
 #[cfg(feature = "devfs")]
-pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
-    let null = fs::devfs::NullDev;
-    let zero = fs::devfs::ZeroDev;
-    let bar = fs::devfs::ZeroDev;
-    let devfs = fs::devfs::DeviceFileSystem::new();
-    let foo_dir = devfs.mkdir("foo");
-    devfs.add("null", Arc::new(null));
-    devfs.add("zero", Arc::new(zero));
-    foo_dir.add("bar", Arc::new(bar));
-    Arc::new(devfs)
+pub(crate) fn devfs() -> Arc<PseudoFileSystem> {
+    Arc::new(PseudoFileSystem::new(&crate::devfs::DEV_ENTRIES))
 }
 
 #[cfg(feature = "ramfs")]
@@ -22,59 +63,11 @@ pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
 }
 
 #[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let procfs = fs::ramfs::RamFileSystem::new();
-    let proc_root = procfs.root_dir();
-
-    // Create /proc/sys/net/core/somaxconn
-    proc_root.create("sys", VfsNodeType::Dir)?;
-    proc_root.create("sys/net", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core/somaxconn", VfsNodeType::File)?;
-    let file_somaxconn = proc_root.clone().lookup("./sys/net/core/somaxconn")?;
-    file_somaxconn.write_at(0, b"4096\n")?;
-
-    // Create /proc/sys/vm/overcommit_memory
-    proc_root.create("sys/vm", VfsNodeType::Dir)?;
-    proc_root.create("sys/vm/overcommit_memory", VfsNodeType::File)?;
-    let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
-    file_over.write_at(0, b"0\n")?;
-
-    // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
-
-    Ok(Arc::new(procfs))
+pub(crate) fn procfs() -> Arc<PseudoFileSystem> {
+    Arc::new(PseudoFileSystem::new(&crate::proc::PROC_ENTRIES))
 }
 
 #[cfg(feature = "sysfs")]
-pub(crate) fn sysfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let sysfs = fs::ramfs::RamFileSystem::new();
-    let sys_root = sysfs.root_dir();
-
-    // Create /sys/kernel/mm/transparent_hugepage/enabled
-    sys_root.create("kernel", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage/enabled", VfsNodeType::File)?;
-    let file_hp = sys_root
-        .clone()
-        .lookup("./kernel/mm/transparent_hugepage/enabled")?;
-    file_hp.write_at(0, b"always [madvise] never\n")?;
-
-    // Create /sys/devices/system/clocksource/clocksource0/current_clocksource
-    sys_root.create("devices", VfsNodeType::Dir)?;
-    sys_root.create("devices/system", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource/clocksource0", VfsNodeType::Dir)?;
-    sys_root.create(
-        "devices/system/clocksource/clocksource0/current_clocksource",
-        VfsNodeType::File,
-    )?;
-    let file_cc = sys_root
-        .clone()
-        .lookup("devices/system/clocksource/clocksource0/current_clocksource")?;
-    file_cc.write_at(0, b"tsc\n")?;
-
-    Ok(Arc::new(sysfs))
+pub(crate) fn sysfs() -> Arc<PseudoFileSystem> {
+    Arc::new(PseudoFileSystem::new(&crate::sys::SYS_ENTRIES))
 }
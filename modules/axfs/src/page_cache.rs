@@ -0,0 +1,483 @@
+//! Page cache of file contents.
+//!
+//! Files on block devices are cached in pages, shared by all opened files and
+//! memory mappings of the same file. Reads are served from the cache, and
+//! writes go through it to the filesystem. Pages modified by shared mappings
+//! are dirty, and written back by [`PageCache::sync`] or when evicted.
+//!
+//! The least recently used pages of all files are evicted when there are more
+//! than `page-cache-size` pages in the platform config, except those mapped
+//! into memory.
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::VfsNodeRef;
+use axsync::Mutex;
+use core::ptr::NonNull;
+
+/// The size of a cached page.
+pub const PAGE_SIZE: usize = 0x1000;
+
+/// A page of memory, aligned to be mapped.
+#[repr(align(4096))]
+struct PageBuf([u8; PAGE_SIZE]);
+
+struct Page {
+    data: Box<PageBuf>,
+    dirty: bool,
+    /// The number of mappings of the page. Mapped pages are not evicted.
+    pins: usize,
+    /// The time of the last access, the key in [`Lru::pages`].
+    tick: u64,
+}
+
+struct CacheInner {
+    /// The file size, kept here since all writes go through the cache.
+    size: u64,
+    pages: BTreeMap<u64, Page>,
+}
+
+/// The cached pages of a file.
+pub struct PageCache {
+    node: VfsNodeRef,
+    inner: Mutex<CacheInner>,
+}
+
+/// The cached pages of all files ordered by the time of the last access,
+/// which keep their [`PageCache`]s alive.
+struct Lru {
+    pages: BTreeMap<u64, (Arc<PageCache>, u64)>,
+    tick: u64,
+}
+
+static LRU: Mutex<Lru> = Mutex::new(Lru {
+    pages: BTreeMap::new(),
+    tick: 0,
+});
+
+/// The key of the page cache of a file in a filesystem.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum CacheKey {
+    /// The inode number, shared by all the hard links of the file, on
+    /// filesystems providing it, e.g. ext4.
+    Inode(u64),
+    /// The path relative to the root of the filesystem, e.g. on FAT, which
+    /// has no hard links.
+    Path(String),
+}
+
+/// The page caches of the files in a filesystem.
+type FileCaches = Mutex<BTreeMap<CacheKey, Weak<PageCache>>>;
+
+/// The page caches seen from a mount point.
+#[derive(Clone)]
+pub(crate) struct MountCaches {
+    /// `None` if the filesystem is not cached.
+    caches: Option<Arc<FileCaches>>,
+    /// The path of the mounted directory in the filesystem, which is not
+    /// empty for bind mounts.
+    root: String,
+}
+
+impl Lru {
+    /// Marks the page `index` of `cache` as the most recently used one.
+    fn touch(&mut self, cache: &Arc<PageCache>, index: u64, page: &mut Page) {
+        self.pages.remove(&page.tick);
+        self.tick += 1;
+        page.tick = self.tick;
+        self.pages.insert(self.tick, (cache.clone(), index));
+    }
+}
+
+/// Evicts the least recently used pages until the number of cached pages is
+/// within the capacity.
+fn shrink() {
+    let mut skipped = Vec::new();
+    loop {
+        let (tick, (cache, index)) = {
+            let mut lru = LRU.lock();
+            if lru.pages.len() <= axconfig::PAGE_CACHE_SIZE {
+                break;
+            }
+            lru.pages.pop_first().unwrap()
+        };
+        let mut inner = cache.inner.lock();
+        let size = inner.size;
+        let Some(page) = inner.pages.get_mut(&index) else {
+            continue;
+        };
+        if page.tick != tick {
+            continue; // accessed again after popped
+        }
+        if page.pins > 0 {
+            skipped.push((tick, (cache.clone(), index)));
+            continue;
+        }
+        if page.dirty
+            && let Err(e) = cache.write_back(size, index, page)
+        {
+            warn!("failed to write back page {} of a file: {:?}", index, e);
+            skipped.push((tick, (cache.clone(), index)));
+            continue;
+        }
+        inner.pages.remove(&index);
+    }
+    if !skipped.is_empty() {
+        LRU.lock().pages.extend(skipped);
+    }
+}
+
+impl PageCache {
+    pub(crate) fn new(node: VfsNodeRef) -> AxResult<Self> {
+        let size = node.get_attr()?.size();
+        Ok(Self {
+            node,
+            inner: Mutex::new(CacheInner {
+                size,
+                pages: BTreeMap::new(),
+            }),
+        })
+    }
+
+    /// Returns the node of the file, through which all operations on the file
+    /// are done.
+    pub(crate) fn node(&self) -> &VfsNodeRef {
+        &self.node
+    }
+
+    /// Returns the page `index`, reading it from the file if it's not cached.
+    /// The part after the end of the file is zero.
+    fn load<'a>(self: &Arc<Self>, inner: &'a mut CacheInner, index: u64) -> AxResult<&'a mut Page> {
+        if !inner.pages.contains_key(&index) {
+            let mut data = Box::new(PageBuf([0; PAGE_SIZE]));
+            let pos = index * PAGE_SIZE as u64;
+            if pos < inner.size {
+                let len = (inner.size - pos).min(PAGE_SIZE as u64) as usize;
+                let mut read = 0;
+                while read < len {
+                    match self
+                        .node
+                        .read_at(pos + read as u64, &mut data.0[read..len])?
+                    {
+                        0 => break,
+                        n => read += n,
+                    }
+                }
+            }
+            let page = Page {
+                data,
+                dirty: false,
+                pins: 0,
+                tick: 0,
+            };
+            inner.pages.insert(index, page);
+        }
+        let page = inner.pages.get_mut(&index).unwrap();
+        LRU.lock().touch(self, index, page);
+        Ok(page)
+    }
+
+    /// Writes the page `index` back to the file, except the part after the end.
+    fn write_back(&self, size: u64, index: u64, page: &mut Page) -> AxResult {
+        let pos = index * PAGE_SIZE as u64;
+        if pos < size {
+            let len = (size - pos).min(PAGE_SIZE as u64) as usize;
+            let mut written = 0;
+            while written < len {
+                match self
+                    .node
+                    .write_at(pos + written as u64, &page.data.0[written..len])?
+                {
+                    0 => return ax_err!(WriteZero),
+                    n => written += n,
+                }
+            }
+        }
+        // mapped pages may be modified again at any time
+        page.dirty = page.pins > 0;
+        Ok(())
+    }
+
+    /// Drops the pages not mapped, discarding the changes.
+    fn invalidate(&self) {
+        let mut inner = self.inner.lock();
+        let mut lru = LRU.lock();
+        inner.pages.retain(|_, page| {
+            if page.pins == 0 {
+                lru.pages.remove(&page.tick);
+            }
+            page.pins > 0
+        });
+    }
+
+    /// Reads the file at `offset` through the cache. Returns the number of
+    /// bytes read.
+    pub(crate) fn read_at(self: &Arc<Self>, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let mut inner = self.inner.lock();
+        let end = inner.size.min(offset.saturating_add(buf.len() as u64));
+        let mut pos = offset;
+        while pos < end {
+            let page = self.load(&mut inner, pos / PAGE_SIZE as u64)?;
+            let start = (pos % PAGE_SIZE as u64) as usize;
+            let len = (PAGE_SIZE - start).min((end - pos) as usize);
+            let done = (pos - offset) as usize;
+            buf[done..done + len].copy_from_slice(&page.data.0[start..start + len]);
+            pos += len as u64;
+        }
+        drop(inner);
+        shrink();
+        Ok((pos - offset) as usize)
+    }
+
+    /// Writes the file at `offset`, and updates the cached pages. Returns the
+    /// number of bytes written.
+    pub(crate) fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        let mut inner = self.inner.lock();
+        let n = self.node.write_at(offset, buf)?;
+        let end = offset + n as u64;
+        if n > 0 {
+            let pages = offset / PAGE_SIZE as u64..=(end - 1) / PAGE_SIZE as u64;
+            for (&index, page) in inner.pages.range_mut(pages) {
+                let page_pos = index * PAGE_SIZE as u64;
+                let start = page_pos.max(offset);
+                let stop = (page_pos + PAGE_SIZE as u64).min(end);
+                page.data.0[(start - page_pos) as usize..(stop - page_pos) as usize]
+                    .copy_from_slice(&buf[(start - offset) as usize..(stop - offset) as usize]);
+            }
+        }
+        inner.size = inner.size.max(end);
+        Ok(n)
+    }
+
+    /// Truncates the file to `size`, and drops the cached pages after it.
+    pub(crate) fn truncate(&self, size: u64) -> AxResult {
+        let mut inner = self.inner.lock();
+        self.node.truncate(size)?;
+        let mut lru = LRU.lock();
+        let after: Vec<u64> = inner
+            .pages
+            .range(size.div_ceil(PAGE_SIZE as u64)..)
+            .map(|(&index, _)| index)
+            .collect();
+        for index in after {
+            let page = inner.pages.get_mut(&index).unwrap();
+            if page.pins > 0 {
+                // still mapped, which sees zeros
+                page.data.0.fill(0);
+                page.dirty = false;
+            } else {
+                lru.pages.remove(&page.tick);
+                inner.pages.remove(&index);
+            }
+        }
+        let tail = (size % PAGE_SIZE as u64) as usize;
+        if let Some(page) = inner.pages.get_mut(&(size / PAGE_SIZE as u64)) {
+            page.data.0[tail..].fill(0);
+        }
+        drop(lru);
+        // some filesystems can't extend files by truncating
+        inner.size = self.node.get_attr()?.size();
+        Ok(())
+    }
+
+    /// Returns the page `index` to be mapped into memory, which stays in
+    /// place until [`unmap_page`](Self::unmap_page). The page is dirty from
+    /// then on if the mapping is `writable`.
+    pub fn map_page(self: &Arc<Self>, index: u64, writable: bool) -> AxResult<NonNull<u8>> {
+        let mut inner = self.inner.lock();
+        let page = self.load(&mut inner, index)?;
+        page.pins += 1;
+        page.dirty |= writable;
+        let ptr = NonNull::from(&mut page.data.0).cast();
+        drop(inner);
+        shrink();
+        Ok(ptr)
+    }
+
+    /// Maps the page `index` like [`map_page`](Self::map_page) if it's cached,
+    /// without any I/O, e.g. when it's already mapped.
+    pub fn pin_page(&self, index: u64, writable: bool) -> Option<NonNull<u8>> {
+        let mut inner = self.inner.lock();
+        let page = inner.pages.get_mut(&index)?;
+        page.pins += 1;
+        page.dirty |= writable;
+        Some(NonNull::from(&mut page.data.0).cast())
+    }
+
+    /// Releases the page `index` mapped by [`map_page`](Self::map_page) or
+    /// [`pin_page`](Self::pin_page).
+    pub fn unmap_page(&self, index: u64) {
+        if let Some(page) = self.inner.lock().pages.get_mut(&index) {
+            page.pins -= 1;
+        }
+    }
+
+    /// Writes the dirty pages from `start` to `end` (exclusive) back to the
+    /// file, and flushes the file.
+    pub fn sync_pages(&self, start: u64, end: u64) -> AxResult {
+        let mut inner = self.inner.lock();
+        let size = inner.size;
+        for (&index, page) in inner.pages.range_mut(start..end) {
+            if page.dirty {
+                self.write_back(size, index, page)?;
+            }
+        }
+        self.node.fsync()
+    }
+
+    /// Writes all dirty pages back to the file, and flushes the file.
+    pub fn sync(&self) -> AxResult {
+        self.sync_pages(0, u64::MAX)
+    }
+}
+
+impl MountCaches {
+    /// Returns the caches of a new filesystem of the given type. Only
+    /// filesystems on block devices are cached.
+    pub fn for_fstype(fstype: &str) -> Self {
+        let caches = match fstype {
+            "vfat" | "ext4" => Some(Arc::new(Mutex::new(BTreeMap::new()))),
+            _ => None,
+        };
+        Self {
+            caches,
+            root: String::new(),
+        }
+    }
+
+    /// Returns the caches seen from a bind mount of the directory at `path`.
+    pub fn subdir(&self, path: &str) -> Self {
+        Self {
+            caches: self.caches.clone(),
+            root: self.fs_path(path),
+        }
+    }
+
+    /// Maps a path relative to the mount point to the path in the filesystem.
+    fn fs_path(&self, path: &str) -> String {
+        match (self.root.is_empty(), path.is_empty()) {
+            (true, _) => path.into(),
+            (false, true) => self.root.clone(),
+            (false, false) => format!("{}/{}", self.root, path),
+        }
+    }
+
+    /// Returns the page caches of the files in the filesystem.
+    fn all(&self) -> Vec<Arc<PageCache>> {
+        match &self.caches {
+            Some(caches) => caches.lock().values().filter_map(Weak::upgrade).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the key of the cache of the file at `path`, whose inode number
+    /// is `inode` if the filesystem provides it.
+    fn key(&self, path: &str, inode: Option<u64>) -> CacheKey {
+        match inode {
+            Some(ino) => CacheKey::Inode(ino),
+            None => CacheKey::Path(self.fs_path(path)),
+        }
+    }
+
+    /// Returns the page cache of the file `node` at `path`, or `None` if the
+    /// filesystem is not cached.
+    ///
+    /// The hard links of a file share its cache if `inode` is given.
+    pub fn get(
+        &self,
+        path: &str,
+        node: &VfsNodeRef,
+        inode: Option<u64>,
+    ) -> AxResult<Option<Arc<PageCache>>> {
+        let Some(caches) = &self.caches else {
+            return Ok(None);
+        };
+        let key = self.key(path, inode);
+        let mut caches = caches.lock();
+        if let Some(cache) = caches.get(&key).and_then(Weak::upgrade) {
+            return Ok(Some(cache));
+        }
+        let cache = Arc::new(PageCache::new(node.clone())?);
+        caches.retain(|_, cache| cache.strong_count() > 0);
+        caches.insert(key, Arc::downgrade(&cache));
+        Ok(Some(cache))
+    }
+
+    /// Drops the cache of the file at `path` after it's removed, `inode` is
+    /// its inode number before the removal.
+    ///
+    /// The cache of a file with hard links is only forgotten, so that it's not
+    /// found again if the inode number is reused, since the file may still be
+    /// opened through its other links.
+    pub fn removed(&self, path: &str, inode: Option<u64>) {
+        let Some(caches) = &self.caches else {
+            return;
+        };
+        let key = self.key(path, inode);
+        let cache = caches.lock().remove(&key);
+        if let (CacheKey::Path(_), Some(cache)) = (key, cache.and_then(|cache| cache.upgrade())) {
+            cache.invalidate();
+        }
+    }
+
+    /// Updates the caches after the node at `src` is renamed to `dst`,
+    /// including the files under it if it's a directory. The caches keyed by
+    /// inode numbers are unchanged.
+    pub fn renamed(&self, src: &str, dst: &str) {
+        let Some(caches) = &self.caches else {
+            return;
+        };
+        let (src, dst) = (self.fs_path(src), self.fs_path(dst));
+        let mut caches = caches.lock();
+        let moved: Vec<String> = caches
+            .keys()
+            .filter_map(|key| match key {
+                CacheKey::Path(path) => Some(path),
+                CacheKey::Inode(_) => None,
+            })
+            .filter(|path| {
+                path.strip_prefix(src.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .cloned()
+            .collect();
+        for path in moved {
+            let cache = caches.remove(&CacheKey::Path(path.clone())).unwrap();
+            let path = format!("{}{}", dst, &path[src.len()..]);
+            caches.insert(CacheKey::Path(path), cache);
+        }
+    }
+
+    /// Writes the dirty pages of all files back.
+    pub fn sync(&self) -> AxResult {
+        for cache in self.all() {
+            cache.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Writes back and drops the cached pages before the filesystem is
+    /// unmounted, if no other mount points see it.
+    pub fn release(&self) {
+        if self
+            .caches
+            .as_ref()
+            .is_none_or(|c| Arc::strong_count(c) > 1)
+        {
+            return;
+        }
+        for cache in self.all() {
+            if let Err(e) = cache.sync() {
+                warn!("failed to write back a file before unmounting: {:?}", e);
+            }
+            cache.invalidate();
+        }
+    }
+}
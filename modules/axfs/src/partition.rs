@@ -0,0 +1,197 @@
+//! MBR and GPT partition tables.
+
+use alloc::{format, string::String, vec::Vec};
+use axdriver::prelude::*;
+
+use crate::dev::{BLOCK_SIZE, Disk};
+
+/// The maximum number of logical partitions in an MBR extended partition,
+/// to stop at broken (looping) chains.
+const MAX_LOGICAL_PARTITIONS: usize = 64;
+/// The maximum number of GPT partition entries to read.
+const MAX_GPT_ENTRIES: u32 = 256;
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+
+/// A partition on a disk.
+#[derive(Debug, Clone)]
+pub struct Partition {
+    /// The partition number, starting from 1. Logical partitions in an MBR
+    /// extended partition start from 5.
+    pub number: usize,
+    /// The first block.
+    pub start: u64,
+    /// The number of blocks.
+    pub num_blocks: u64,
+    /// The partition name, only for GPT.
+    pub label: Option<String>,
+    /// The unique partition GUID for GPT, or the disk signature and the
+    /// partition number (e.g. `1234abcd-01`) for MBR, as Linux's `PARTUUID`.
+    pub uuid: Option<String>,
+}
+
+/// An entry of an MBR (or EBR) partition table.
+struct MbrEntry {
+    ty: u8,
+    start: u64,
+    num_blocks: u64,
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+fn mbr_entries(block: &[u8]) -> Option<[MbrEntry; 4]> {
+    if block[510..512] != MBR_SIGNATURE {
+        return None;
+    }
+    // FAT boot sectors have the same signature
+    if &block[54..57] == b"FAT" || &block[82..87] == b"FAT32" {
+        return None;
+    }
+    // the boot indicators must be valid
+    if (0..4).any(|i| !matches!(block[446 + i * 16], 0x00 | 0x80)) {
+        return None;
+    }
+    Some(core::array::from_fn(|i| {
+        let entry = &block[446 + i * 16..446 + (i + 1) * 16];
+        MbrEntry {
+            ty: entry[4],
+            start: read_u32(entry, 8) as u64,
+            num_blocks: read_u32(entry, 12) as u64,
+        }
+    }))
+}
+
+/// Formats a GUID stored in the mixed-endian on-disk layout.
+fn format_guid(guid: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        read_u32(guid, 0),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        guid[8],
+        guid[9],
+        guid[10],
+        guid[11],
+        guid[12],
+        guid[13],
+        guid[14],
+        guid[15],
+    )
+}
+
+/// Returns the partitions of `disk`, or an empty list if it has no partition
+/// table.
+pub fn parse(disk: &Disk) -> DevResult<Vec<Partition>> {
+    if disk.num_blocks() == 0 {
+        return Ok(Vec::new());
+    }
+    let mut block = [0u8; BLOCK_SIZE];
+    disk.read_block(0, &mut block)?;
+    let Some(entries) = mbr_entries(&block) else {
+        return Ok(Vec::new());
+    };
+    if entries.iter().any(|e| e.ty == MBR_TYPE_GPT_PROTECTIVE) {
+        return parse_gpt(disk);
+    }
+
+    let disk_id = read_u32(&block, 440);
+    let mut parts = Vec::new();
+    let mut add = |number: usize, start: u64, num_blocks: u64| {
+        // skip the partitions out of the disk
+        if num_blocks > 0 && start + num_blocks <= disk.num_blocks() {
+            parts.push(Partition {
+                number,
+                start,
+                num_blocks,
+                label: None,
+                uuid: Some(format!("{:08x}-{:02x}", disk_id, number)),
+            });
+        }
+    };
+    let mut extended = None;
+    for (i, entry) in entries.iter().enumerate() {
+        if MBR_TYPES_EXTENDED.contains(&entry.ty) {
+            extended.get_or_insert(entry.start);
+        } else if entry.ty != 0 {
+            add(i + 1, entry.start, entry.num_blocks);
+        }
+    }
+
+    // logical partitions, in a chain of EBRs whose first entry is relative to
+    // the EBR and second entry is relative to the extended partition
+    if let Some(ext_start) = extended {
+        let mut ebr = ext_start;
+        for number in 5..5 + MAX_LOGICAL_PARTITIONS {
+            if ebr >= disk.num_blocks() {
+                break;
+            }
+            disk.read_block(ebr, &mut block)?;
+            let Some([logical, next, ..]) = mbr_entries(&block) else {
+                break;
+            };
+            if logical.ty != 0 {
+                add(number, ebr + logical.start, logical.num_blocks);
+            }
+            if next.ty == 0 || next.start == 0 {
+                break;
+            }
+            ebr = ext_start + next.start;
+        }
+    }
+    Ok(parts)
+}
+
+fn parse_gpt(disk: &Disk) -> DevResult<Vec<Partition>> {
+    let mut header = [0u8; BLOCK_SIZE];
+    disk.read_block(1, &mut header)?;
+    if &header[0..8] != GPT_SIGNATURE {
+        warn!("invalid GPT header");
+        return Ok(Vec::new());
+    }
+    let entries_lba = read_u64(&header, 72);
+    let num_entries = read_u32(&header, 80).min(MAX_GPT_ENTRIES) as usize;
+    let entry_size = read_u32(&header, 84) as usize;
+    if !(128..=BLOCK_SIZE).contains(&entry_size) || BLOCK_SIZE % entry_size != 0 {
+        warn!("unsupported GPT entry size: {}", entry_size);
+        return Ok(Vec::new());
+    }
+
+    let per_block = BLOCK_SIZE / entry_size;
+    let mut block = [0u8; BLOCK_SIZE];
+    let mut parts = Vec::new();
+    for i in 0..num_entries {
+        if i % per_block == 0 {
+            disk.read_block(entries_lba + (i / per_block) as u64, &mut block)?;
+        }
+        let entry = &block[(i % per_block) * entry_size..][..entry_size];
+        if entry[0..16].iter().all(|&b| b == 0) {
+            continue; // unused entry
+        }
+        let (first, last) = (read_u64(entry, 32), read_u64(entry, 40));
+        if first > last || last >= disk.num_blocks() {
+            continue;
+        }
+        let name: Vec<u16> = entry[56..128]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        parts.push(Partition {
+            number: i + 1,
+            start: first,
+            num_blocks: last - first + 1,
+            label: Some(String::from_utf16_lossy(&name)),
+            uuid: Some(format_guid(&entry[16..32])),
+        });
+    }
+    Ok(parts)
+}
@@ -0,0 +1,228 @@
+//! Owners and permissions of files.
+//!
+//! The nodes of [`axfs_vfs`] only have permission bits, which can't be
+//! changed, so owners and changed permissions are provided per mounted
+//! filesystem by [`Owners`]: in-memory filesystems keep them in a table of
+//! nodes, and filesystems storing them natively implement [`OwnerFs`].
+//!
+//! Permissions are checked against the current user, as on Linux: the owner
+//! bits apply to the owner, the group bits to the members of the group, and
+//! the others bits to the rest. `root` can read and write everything, and
+//! execute files executable by anyone.
+
+use alloc::sync::Arc;
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::{VfsNodeAttr, VfsNodePerm, VfsNodeRef, VfsResult};
+use axns::def_resource;
+use cap_access::Cap;
+use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+
+use crate::meta::NodeTable;
+
+def_resource! {
+    static CURRENT_UID: AtomicU32 = AtomicU32::new(0);
+    static CURRENT_GID: AtomicU32 = AtomicU32::new(0);
+    static CURRENT_UMASK: AtomicU16 = AtomicU16::new(0o022);
+}
+
+/// The user and group owning a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Owner {
+    /// The user ID.
+    pub uid: u32,
+    /// The group ID.
+    pub gid: u32,
+}
+
+impl Owner {
+    /// The owner of files on filesystems without owners.
+    pub const ROOT: Self = Self { uid: 0, gid: 0 };
+}
+
+/// Returns the user and group of the current process.
+pub(crate) fn current_user() -> Owner {
+    Owner {
+        uid: CURRENT_UID.load(Ordering::Acquire),
+        gid: CURRENT_GID.load(Ordering::Acquire),
+    }
+}
+
+/// Sets the user and group of the current process.
+pub(crate) fn set_current_user(user: Owner) {
+    CURRENT_UID.store(user.uid, Ordering::Release);
+    CURRENT_GID.store(user.gid, Ordering::Release);
+}
+
+/// Sets the file mode creation mask, returns the previous one.
+pub(crate) fn set_umask(mask: VfsNodePerm) -> VfsNodePerm {
+    VfsNodePerm::from_bits_truncate(CURRENT_UMASK.swap(mask.bits(), Ordering::AcqRel))
+}
+
+/// Returns the access allowed to the current user on a file with the
+/// attributes `attr` and the owner `owner`.
+pub(crate) fn allowed(attr: &VfsNodeAttr, owner: Owner) -> Cap {
+    let bits = attr.perm().bits();
+    let user = current_user();
+    if user.uid == 0 {
+        let mut cap = Cap::READ | Cap::WRITE;
+        if attr.is_dir() || bits & 0o111 != 0 {
+            cap |= Cap::EXECUTE;
+        }
+        return cap;
+    }
+    let bits = if user.uid == owner.uid {
+        bits >> 6
+    } else if user.gid == owner.gid {
+        bits >> 3
+    } else {
+        bits
+    };
+    let mut cap = Cap::empty();
+    if bits & 0o4 != 0 {
+        cap |= Cap::READ;
+    }
+    if bits & 0o2 != 0 {
+        cap |= Cap::WRITE;
+    }
+    if bits & 0o1 != 0 {
+        cap |= Cap::EXECUTE;
+    }
+    cap
+}
+
+/// A filesystem storing owners and permissions natively.
+#[cfg_attr(not(feature = "lwext4_rs"), allow(dead_code))]
+pub(crate) trait OwnerFs: Send + Sync {
+    /// Returns the owner of `node`.
+    fn owner(&self, node: &VfsNodeRef) -> VfsResult<Owner>;
+    /// Changes the owner of `node`.
+    fn set_owner(&self, node: &VfsNodeRef, owner: Owner) -> VfsResult;
+    /// Changes the permissions of `node`.
+    fn set_perm(&self, node: &VfsNodeRef, perm: VfsNodePerm) -> VfsResult;
+    /// Returns the inode number of `node`, which stores the owner and is the
+    /// same for all the hard links of the file.
+    fn inode(&self, node: &VfsNodeRef) -> VfsResult<u64>;
+}
+
+/// How a filesystem stores owners and permissions.
+pub(crate) enum Owners {
+    /// Not stored, files are owned by `root` and the permissions can't be
+    /// changed, e.g. FAT and the pseudo filesystems.
+    Fixed,
+    /// Kept in memory, in the table shared with the times of the mount.
+    Memory(Arc<NodeTable>),
+    /// Stored by the filesystem.
+    #[cfg_attr(not(feature = "lwext4_rs"), allow(dead_code))]
+    Native(Arc<dyn OwnerFs>),
+}
+
+impl Owners {
+    /// Returns how the filesystems of the given type store owners and
+    /// permissions, except the native ones, given the table of the mount.
+    pub fn for_table(table: Option<Arc<NodeTable>>) -> Self {
+        table.map_or(Self::Fixed, Self::Memory)
+    }
+
+    /// Updates the owner and permissions of `node` in the table.
+    fn update(&self, node: &VfsNodeRef, f: impl FnOnce(&mut Owner, &mut VfsNodePerm)) -> AxResult {
+        let Self::Memory(table) = self else {
+            return ax_err!(Unsupported, "the filesystem has no owners");
+        };
+        let node_perm = node.get_attr()?.perm();
+        table.update(node, |meta| {
+            let mut perm = meta.perm.unwrap_or(node_perm);
+            f(&mut meta.owner, &mut perm);
+            meta.perm = Some(perm);
+        });
+        Ok(())
+    }
+
+    /// Returns the attributes of `node`, with the changed permissions.
+    pub fn attr(&self, node: &VfsNodeRef) -> AxResult<VfsNodeAttr> {
+        let attr = node.get_attr()?;
+        let perm = match self {
+            Self::Memory(table) => table.get(node).perm,
+            _ => None,
+        };
+        Ok(match perm {
+            Some(perm) => VfsNodeAttr::new(perm, attr.file_type(), attr.size(), attr.blocks()),
+            None => attr,
+        })
+    }
+
+    /// Returns the owner of `node`.
+    pub fn owner(&self, node: &VfsNodeRef) -> AxResult<Owner> {
+        match self {
+            Self::Fixed => Ok(Owner::ROOT),
+            Self::Memory(table) => Ok(table.get(node).owner),
+            Self::Native(fs) => fs.owner(node),
+        }
+    }
+
+    /// Returns the inode number of `node` on filesystems storing the owners
+    /// natively, which may have hard links.
+    pub fn inode(&self, node: &VfsNodeRef) -> Option<u64> {
+        match self {
+            Self::Native(fs) => fs.inode(node).ok(),
+            _ => None,
+        }
+    }
+
+    /// Sets the owner of the new node to the current user, and its
+    /// permissions to `perm` without the bits in the file mode creation mask.
+    pub fn created(&self, node: &VfsNodeRef, perm: VfsNodePerm) -> AxResult {
+        let owner = current_user();
+        let umask = VfsNodePerm::from_bits_truncate(CURRENT_UMASK.load(Ordering::Acquire));
+        let perm = perm - umask;
+        match self {
+            Self::Fixed => Ok(()),
+            Self::Memory(_) => self.update(node, |o, p| (*o, *p) = (owner, perm)),
+            Self::Native(fs) => {
+                fs.set_owner(node, owner)?;
+                fs.set_perm(node, perm)
+            }
+        }
+    }
+
+    /// Changes the permissions of `node`, which is allowed only for its
+    /// owner and `root`.
+    pub fn chmod(&self, node: &VfsNodeRef, perm: VfsNodePerm) -> AxResult {
+        let user = current_user();
+        if user.uid != 0 && user.uid != self.owner(node)?.uid {
+            return ax_err!(PermissionDenied);
+        }
+        match self {
+            Self::Native(fs) => fs.set_perm(node, perm),
+            _ => self.update(node, |_, p| *p = perm),
+        }
+    }
+
+    /// Changes the owner of `node`, leaving the user or group unchanged if
+    /// it's `None`.
+    ///
+    /// Only `root` can change the user. The owner can change the group to
+    /// its own group.
+    pub fn chown(&self, node: &VfsNodeRef, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+        let user = current_user();
+        let old = self.owner(node)?;
+        let new = Owner {
+            uid: uid.unwrap_or(old.uid),
+            gid: gid.unwrap_or(old.gid),
+        };
+        let own_group = new.gid == old.gid || new.gid == user.gid;
+        if user.uid != 0 && (user.uid != old.uid || new.uid != old.uid || !own_group) {
+            return ax_err!(PermissionDenied);
+        }
+        match self {
+            Self::Native(fs) => fs.set_owner(node, new),
+            _ => self.update(node, |o, _| *o = new),
+        }
+    }
+
+    /// Updates the table after `node` is removed.
+    pub fn removed(&self, node: &VfsNodeRef) {
+        if let Self::Memory(table) = self {
+            table.removed(node);
+        }
+    }
+}
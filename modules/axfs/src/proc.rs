@@ -0,0 +1,41 @@
+//! The procfs, whose files are generated on read.
+//!
+//! Other modules register their files by [`register_file`], e.g. axnet
+//! registers `/proc/net/dev`, and writable files feeding back into them by
+//! [`register_tunable`], e.g. `/proc/sys/net/core/somaxconn`. Directories
+//! whose entries change at runtime, e.g. one for each task, are registered by
+//! [`register_dir`].
+
+use alloc::sync::Arc;
+use axerrno::AxResult;
+
+use crate::pseudo::Registry;
+pub use crate::pseudo::{
+    PseudoDirFn as ProcDirFn, PseudoEntry as ProcEntry, PseudoReadFn as ProcReadFn,
+    PseudoWriteFn as ProcWriteFn,
+};
+
+/// The registered entries, the keys are paths relative to `/proc`.
+pub(crate) static PROC_ENTRIES: Registry = Registry::new();
+
+/// Registers a procfs file, `path` is relative to `/proc`, e.g. `net/dev`.
+///
+/// The parent directories exist as long as there are files in them. A
+/// registered file replaces the previous one with the same path.
+pub fn register_file(path: &str, read: ProcReadFn) -> AxResult {
+    PROC_ENTRIES.register(path, ProcEntry::File(Arc::new(read), None))
+}
+
+/// Registers a writable procfs file, whose written content is passed to
+/// `write`, e.g. `sys/net/core/somaxconn`.
+pub fn register_tunable(path: &str, read: ProcReadFn, write: ProcWriteFn) -> AxResult {
+    let entry = ProcEntry::File(Arc::new(read), Some(Arc::new(write)));
+    PROC_ENTRIES.register(path, entry)
+}
+
+/// Registers a procfs directory whose entries are generated by `entries`, in
+/// addition to the registered files in it. An empty `path` adds entries to
+/// `/proc` itself.
+pub fn register_dir(path: &str, entries: ProcDirFn) -> AxResult {
+    PROC_ENTRIES.register(path, ProcEntry::Dir(Arc::new(entries)))
+}
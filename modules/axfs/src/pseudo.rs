@@ -0,0 +1,384 @@
+//! Filesystems whose files are generated on read, i.e. procfs and sysfs, or
+//! provided by other modules, i.e. devfs.
+//!
+//! Nothing is stored, every lookup and listing is answered from the entries
+//! registered in a [`Registry`].
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::{
+    VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps, VfsResult,
+};
+use spin::RwLock;
+
+/// A function generating the content of a file.
+pub type PseudoReadFn = fn() -> String;
+/// A function handling the content written to a file.
+pub type PseudoWriteFn = fn(&str) -> AxResult;
+/// A function generating the entries of a directory.
+pub type PseudoDirFn = fn() -> Vec<(String, PseudoEntry)>;
+
+type ReadFn = Arc<dyn Fn() -> String + Send + Sync>;
+type WriteFn = Arc<dyn Fn(&str) -> AxResult + Send + Sync>;
+type DirFn = Arc<dyn Fn() -> Vec<(String, PseudoEntry)> + Send + Sync>;
+
+/// An entry of a generated filesystem.
+#[derive(Clone)]
+pub enum PseudoEntry {
+    /// A file generated on each read, and passing the written content to the
+    /// write function if it's writable.
+    File(ReadFn, Option<WriteFn>),
+    /// A directory whose entries are generated on each lookup or listing.
+    Dir(DirFn),
+    /// A node implemented elsewhere, e.g. a device.
+    Node(VfsNodeRef),
+}
+
+impl PseudoEntry {
+    /// Creates a read-only file generated by `read`.
+    pub fn file(read: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        Self::File(Arc::new(read), None)
+    }
+
+    /// Creates a directory whose entries are generated by `entries`.
+    pub fn dir(entries: impl Fn() -> Vec<(String, PseudoEntry)> + Send + Sync + 'static) -> Self {
+        Self::Dir(Arc::new(entries))
+    }
+
+    fn node(&self) -> VfsNodeRef {
+        match self {
+            Self::File(read, write) => Arc::new(PseudoFile {
+                read: read.clone(),
+                write: write.clone(),
+            }),
+            Self::Dir(entries) => Arc::new(GeneratedDir(entries.clone())),
+            Self::Node(node) => node.clone(),
+        }
+    }
+}
+
+/// The registered entries of a filesystem, the keys are paths relative to its
+/// root.
+pub(crate) struct Registry(RwLock<BTreeMap<String, PseudoEntry>>);
+
+impl Registry {
+    pub const fn new() -> Self {
+        Self(RwLock::new(BTreeMap::new()))
+    }
+
+    /// Registers `entry` at `path`, replacing the previous one.
+    ///
+    /// Directories registered at an empty `path` add entries to the root.
+    pub fn register(&self, path: &str, entry: PseudoEntry) -> AxResult {
+        let path = path.trim_matches('/');
+        if path.is_empty() && !matches!(entry, PseudoEntry::Dir(_)) {
+            return ax_err!(InvalidInput, "empty path of a file");
+        }
+        self.0.write().insert(path.into(), entry);
+        Ok(())
+    }
+
+    /// Removes the entry at `path`.
+    pub fn unregister(&self, path: &str) -> AxResult {
+        match self.0.write().remove(path.trim_matches('/')) {
+            Some(_) => Ok(()),
+            None => ax_err!(NotFound),
+        }
+    }
+}
+
+fn dir_attr() -> VfsNodeAttr {
+    let perm = VfsNodePerm::from_bits_truncate(0o555);
+    VfsNodeAttr::new(perm, VfsNodeType::Dir, 0, 0)
+}
+
+/// Lists the entries from `start_idx`, after `.` and `..`.
+fn fill_dirents(
+    entries: impl Iterator<Item = (String, VfsNodeType)>,
+    start_idx: usize,
+    dirents: &mut [VfsDirEntry],
+) -> usize {
+    let dots = [
+        (".".into(), VfsNodeType::Dir),
+        ("..".into(), VfsNodeType::Dir),
+    ];
+    let mut n = 0;
+    for ((name, ty), ent) in dots
+        .into_iter()
+        .chain(entries)
+        .skip(start_idx)
+        .zip(dirents.iter_mut())
+    {
+        *ent = VfsDirEntry::new(&name, ty);
+        n += 1;
+    }
+    n
+}
+
+/// Looks up the first component of `path` by `lookup_one`, and the rest in
+/// the node found.
+fn lookup_path(
+    this: VfsNodeRef,
+    path: &str,
+    lookup_one: impl FnOnce(&str) -> VfsResult<VfsNodeRef>,
+) -> VfsResult<VfsNodeRef> {
+    let path = path.trim_start_matches('/');
+    let (name, rest) = path.split_once('/').unwrap_or((path, ""));
+    let node = match name {
+        "" | "." => this,
+        ".." => this.parent().ok_or(axerrno::AxError::NotFound)?,
+        _ => lookup_one(name)?,
+    };
+    if rest.trim_matches('/').is_empty() {
+        Ok(node)
+    } else {
+        node.lookup(rest)
+    }
+}
+
+fn entry_type(entry: &PseudoEntry) -> VfsNodeType {
+    match entry {
+        PseudoEntry::File(..) => VfsNodeType::File,
+        PseudoEntry::Dir(_) => VfsNodeType::Dir,
+        PseudoEntry::Node(node) => node
+            .get_attr()
+            .map_or(VfsNodeType::File, |attr| attr.file_type()),
+    }
+}
+
+/// Nothing can be created in the directories, so it fails with
+/// `AlreadyExists` if `path` exists in `dir`, and `PermissionDenied` if not.
+fn create_in(dir: VfsNodeRef, path: &str) -> VfsResult {
+    match dir.lookup(path) {
+        Ok(_) => ax_err!(AlreadyExists),
+        Err(_) => ax_err!(PermissionDenied),
+    }
+}
+
+/// A filesystem generated from the entries in `registry`.
+pub(crate) struct PseudoFileSystem {
+    registry: &'static Registry,
+    parent: RwLock<Option<VfsNodeRef>>,
+}
+
+impl PseudoFileSystem {
+    pub fn new(registry: &'static Registry) -> Self {
+        Self {
+            registry,
+            parent: RwLock::new(None),
+        }
+    }
+}
+
+impl VfsOps for PseudoFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        *self.parent.write() = mount_point.parent();
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        Arc::new(PseudoDir {
+            registry: self.registry,
+            path: String::new(),
+            parent: self.parent.read().clone(),
+        })
+    }
+}
+
+/// A directory of registered entries, and the generated ones if there is a
+/// directory registered at `path`.
+struct PseudoDir {
+    registry: &'static Registry,
+    /// The path relative to the root, empty for the root.
+    path: String,
+    parent: Option<VfsNodeRef>,
+}
+
+impl PseudoDir {
+    fn child(&self, path: String) -> Self {
+        Self {
+            registry: self.registry,
+            path,
+            parent: self.parent.clone(),
+        }
+    }
+
+    fn child_path(&self, name: &str) -> String {
+        if self.path.is_empty() {
+            name.into()
+        } else {
+            alloc::format!("{}/{}", self.path, name)
+        }
+    }
+
+    /// Returns the entries, the registered ones before the generated ones.
+    ///
+    /// Directories are `None`, which are created by registering entries in
+    /// them, or by registering them.
+    fn entries(&self) -> Vec<(String, Option<PseudoEntry>)> {
+        let registered = self.registry.0.read();
+        let prefix = self.child_path("");
+        let mut entries = BTreeMap::new();
+        for (path, entry) in registered.range(prefix.clone()..) {
+            let Some(rel) = path.strip_prefix(&prefix) else {
+                break;
+            };
+            match (rel.split_once('/'), entry) {
+                (Some((name, _)), _) => {
+                    entries.insert(name.into(), None);
+                }
+                (None, _) if rel.is_empty() => {}
+                (None, PseudoEntry::Dir(_)) => {
+                    entries.insert(rel.into(), None);
+                }
+                (None, _) => {
+                    entries.entry(rel.into()).or_insert(Some(entry.clone()));
+                }
+            }
+        }
+        let generated = match registered.get(&self.path) {
+            Some(PseudoEntry::Dir(generated)) => generated.clone(),
+            _ => return entries.into_iter().collect(),
+        };
+        drop(registered);
+        let mut entries: Vec<_> = entries.into_iter().collect();
+        for (name, entry) in generated() {
+            if !entries.iter().any(|(n, _)| *n == name) {
+                entries.push((name, Some(entry)));
+            }
+        }
+        entries
+    }
+}
+
+impl VfsNodeOps for PseudoDir {
+    axfs_vfs::impl_vfs_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(dir_attr())
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.path.is_empty() {
+            return self.parent.clone();
+        }
+        let path = self.path.rsplit_once('/').map_or("", |(p, _)| p);
+        Some(Arc::new(self.child(path.into())))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let this = self.clone();
+        lookup_path(this, path, |name| {
+            let entry = self
+                .entries()
+                .into_iter()
+                .find(|(n, _)| n == name)
+                .ok_or(axerrno::AxError::NotFound)?;
+            Ok(match entry.1 {
+                Some(entry) => entry.node(),
+                None => Arc::new(self.child(self.child_path(name))),
+            })
+        })
+    }
+
+    fn create(&self, path: &str, _ty: VfsNodeType) -> VfsResult {
+        create_in(Arc::new(self.child(self.path.clone())), path)
+    }
+
+    fn remove(&self, _path: &str) -> VfsResult {
+        ax_err!(PermissionDenied)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.entries().into_iter().map(|(name, entry)| {
+            let ty = entry.as_ref().map_or(VfsNodeType::Dir, entry_type);
+            (name, ty)
+        });
+        Ok(fill_dirents(entries, start_idx, dirents))
+    }
+}
+
+/// A directory whose entries are all generated.
+struct GeneratedDir(DirFn);
+
+impl VfsNodeOps for GeneratedDir {
+    axfs_vfs::impl_vfs_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(dir_attr())
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let entries = self.0.clone();
+        lookup_path(self, path, |name| {
+            entries()
+                .into_iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, entry)| entry.node())
+                .ok_or(axerrno::AxError::NotFound)
+        })
+    }
+
+    fn create(&self, path: &str, _ty: VfsNodeType) -> VfsResult {
+        create_in(Arc::new(GeneratedDir(self.0.clone())), path)
+    }
+
+    fn remove(&self, _path: &str) -> VfsResult {
+        ax_err!(PermissionDenied)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = (self.0)()
+            .into_iter()
+            .map(|(name, entry)| (name, entry_type(&entry)));
+        Ok(fill_dirents(entries, start_idx, dirents))
+    }
+}
+
+struct PseudoFile {
+    read: ReadFn,
+    write: Option<WriteFn>,
+}
+
+impl VfsNodeOps for PseudoFile {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    /// The size is 0 as on Linux, the content is generated only when read.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mode = if self.write.is_some() { 0o644 } else { 0o444 };
+        let perm = VfsNodePerm::from_bits_truncate(mode);
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, 0, 0))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.read)();
+        let start = content.len().min(offset as usize);
+        let len = buf.len().min(content.len() - start);
+        buf[..len].copy_from_slice(&content.as_bytes()[start..start + len]);
+        Ok(len)
+    }
+
+    /// The whole content must be written at once.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let Some(write) = &self.write else {
+            return ax_err!(PermissionDenied);
+        };
+        if offset != 0 {
+            return ax_err!(InvalidInput, "partial write to a generated file");
+        }
+        let Ok(content) = core::str::from_utf8(buf) else {
+            return ax_err!(InvalidInput, "invalid UTF-8");
+        };
+        write(content)?;
+        Ok(buf.len())
+    }
+
+    /// Truncating is allowed, but ignored, for writable files, so they can be
+    /// opened with `O_TRUNC`.
+    fn truncate(&self, _size: u64) -> VfsResult {
+        if self.write.is_none() {
+            return ax_err!(PermissionDenied);
+        }
+        Ok(())
+    }
+}
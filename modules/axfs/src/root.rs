@@ -1,19 +1,26 @@
-//! Root directory of the filesystem
-//!
-//! TODO: it doesn't work very well if the mount points have containment relationships.
+//! Root directory of the filesystem, and the mount table.
 
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc, vec, vec::Vec};
 use axerrno::{AxError, AxResult, ax_err};
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+use axfs_vfs::{
+    VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps, VfsResult,
+};
 use axns::{ResArc, def_resource};
 use axsync::Mutex;
+use cap_access::Cap;
 use lazyinit::LazyInit;
 use spin::RwLock;
 
 use crate::{
-    api::FileType,
-    fs::{self},
+    dev::Disk,
+    fstype::{self, DiskFs},
+    lock::LockKey,
+    meta::{NodeTable, node_key},
     mounts,
+    page_cache::{MountCaches, PageCache},
+    perm::{Owner, Owners},
+    symlink::{MAX_SYMLINK_FOLLOWS, MountLinks, Symlinks},
+    times::{FileTimes, TimeUpdate, Times},
 };
 
 def_resource! {
@@ -35,90 +42,531 @@ impl CURRENT_DIR {
     }
 }
 
-struct MountPoint {
-    path: &'static str,
+/// A filesystem mounted at a path.
+pub(crate) struct MountPoint {
+    path: String,
+    source: String,
+    fstype: String,
     fs: Arc<dyn VfsOps>,
+    links: MountLinks,
+    caches: MountCaches,
+    owners: Arc<Owners>,
+    times: Arc<Times>,
+}
+
+/// The mount table, a trie of path components leading to the mount points.
+#[derive(Default)]
+struct MountTrie {
+    mount: Option<Arc<MountPoint>>,
+    children: BTreeMap<String, MountTrie>,
 }
 
+/// The root directory, resolving paths through the mounted filesystems.
+///
+/// Paths are resolved component by component. Entering a mount point
+/// switches to the root directory of the filesystem mounted on it, and `..`
+/// goes back to the parent directory of the mount point, so mounts can be
+/// nested. Symbolic links are followed by resolving their targets in place.
+/// The root of the trie is the main filesystem on `/`.
+///
+/// Opened files and directories, and bind mounts of the directories, hold
+/// references to the [`MountPoint`] they are in, which makes the mount busy.
 struct RootDirectory {
-    main_fs: Arc<dyn VfsOps>,
-    mounts: RwLock<Vec<MountPoint>>,
+    mounts: RwLock<MountTrie>,
+}
+
+/// A bind mount, i.e. a directory mounted at another path, which keeps the
+/// mount point of the directory from being unmounted.
+struct BindFs(VfsNodeRef, Arc<MountPoint>);
+
+/// A resolved path.
+pub(crate) struct Location {
+    pub node: VfsNodeRef,
+    pub mount: Arc<MountPoint>,
+    /// The canonical absolute path.
+    pub path: String,
+    /// The target if it's a symbolic link not followed.
+    pub symlink: Option<String>,
 }
 
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
 
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        path: String,
+        source: String,
+        fstype: String,
+        fs: Arc<dyn VfsOps>,
+        links: MountLinks,
+        caches: MountCaches,
+        owners: Arc<Owners>,
+        times: Arc<Times>,
+    ) -> Self {
+        Self {
+            path,
+            source,
+            fstype,
+            fs,
+            links,
+            caches,
+            owners,
+            times,
+        }
+    }
+
+    /// Returns the owners and permissions of the files in it.
+    pub fn owners(&self) -> &Owners {
+        &self.owners
+    }
+
+    /// Fixes the types of the entries read from the directory at the
+    /// absolute path `path`, see [`MountLinks::fix_types`].
+    pub fn fix_dirents(&self, path: &str, dirents: &mut [VfsDirEntry]) {
+        let dir = strip_mount_path(path, &self.path).unwrap_or_default();
+        self.links.fix_types(dir, dirents);
+    }
+
+    /// Returns the times of the files in it.
+    pub fn times(&self) -> &Times {
+        &self.times
+    }
+
+    /// Returns the key of the file of `node` in the table of locks.
+    pub fn lock_key(&self, node: &VfsNodeRef) -> LockKey {
+        match self.owners.inode(node) {
+            Some(ino) => LockKey::Inode(Arc::as_ptr(&self.owners) as usize, ino),
+            None => LockKey::Node(node_key(node)),
+        }
     }
 }
 
 impl Drop for MountPoint {
     fn drop(&mut self) {
+        self.caches.release();
         self.fs.umount().ok();
     }
 }
 
+impl VfsOps for BindFs {
+    fn root_dir(&self) -> VfsNodeRef {
+        self.0.clone()
+    }
+}
+
+impl Location {
+    /// Returns `true` if it's the root directory of the mounted filesystem.
+    fn is_mount_root(&self) -> bool {
+        self.path == self.mount.path
+    }
+
+    /// Returns the path relative to the root of the mounted filesystem.
+    fn path_in_mount(&self) -> &str {
+        strip_mount_path(&self.path, &self.mount.path).unwrap_or_default()
+    }
+
+    /// Returns the attributes, those of the link itself for symbolic links.
+    pub fn attr(&self) -> AxResult<VfsNodeAttr> {
+        match &self.symlink {
+            Some(target) => Ok(VfsNodeAttr::new(
+                VfsNodePerm::from_bits_truncate(0o777),
+                VfsNodeType::SymLink,
+                target.len() as u64,
+                0,
+            )),
+            None => {
+                let attr = self.mount.owners.attr(&self.node)?;
+                Ok(crate::special::attr(&self.node, attr))
+            }
+        }
+    }
+
+    /// Returns the owner.
+    pub fn owner(&self) -> AxResult<Owner> {
+        self.mount.owners.owner(&self.node)
+    }
+
+    /// Returns the access, modification and change times.
+    pub fn times(&self) -> AxResult<FileTimes> {
+        self.mount.times.get(&self.node)
+    }
+
+    /// Returns the access allowed to the current user.
+    pub fn access(&self) -> AxResult<Cap> {
+        Ok(crate::perm::allowed(&self.attr()?, self.owner()?))
+    }
+
+    /// Checks whether the current user can create and remove entries in the
+    /// directory.
+    fn check_modify(&self) -> AxResult {
+        if self.access()?.contains(Cap::WRITE | Cap::EXECUTE) {
+            Ok(())
+        } else {
+            ax_err!(PermissionDenied)
+        }
+    }
+
+    /// Returns the page cache of the file, or `None` if the filesystem is
+    /// not cached.
+    pub fn page_cache(&self) -> AxResult<Option<Arc<PageCache>>> {
+        let inode = self.mount.owners.inode(&self.node);
+        self.mount
+            .caches
+            .get(self.path_in_mount(), &self.node, inode)
+    }
+}
+
+/// Checks whether the current user can search the directory `node`.
+fn check_search(mount: &MountPoint, node: &VfsNodeRef) -> AxResult {
+    let attr = mount.owners.attr(node)?;
+    if !attr.is_dir() {
+        ax_err!(NotADirectory)
+    } else if !crate::perm::allowed(&attr, mount.owners.owner(node)?).contains(Cap::EXECUTE) {
+        ax_err!(PermissionDenied)
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the non-empty components of a path.
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|comp| !comp.is_empty())
+}
+
+/// Returns the rest of the canonical absolute `path` relative to the mount
+/// path, or `None` if the path is not under the mount point.
+fn strip_mount_path<'a>(path: &'a str, mount_path: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(mount_path.trim_end_matches('/'))?;
+    if rest.is_empty() || rest.starts_with('/') {
+        Some(rest.trim_start_matches('/'))
+    } else {
+        None // e.g. `/mntfoo` is not under `/mnt`
+    }
+}
+
+/// Splits a path into the parent directory and the last component.
+fn split_parent(path: &str) -> (&str, &str) {
+    let trimmed = path.trim_end_matches('/');
+    match trimmed.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None if trimmed.is_empty() && path.starts_with('/') => ("/", "."),
+        None => ("", trimmed),
+    }
+}
+
+impl MountTrie {
+    fn get(&self, path: &str) -> Option<&Self> {
+        components(path).try_fold(self, |node, comp| node.children.get(comp))
+    }
+
+    fn insert(&mut self, path: &str, mp: Arc<MountPoint>) -> AxResult {
+        let node = components(path).fold(self, |node, comp| {
+            node.children.entry(comp.into()).or_default()
+        });
+        if node.mount.is_some() {
+            return ax_err!(ResourceBusy, "mount point already exists");
+        }
+        node.mount = Some(mp);
+        Ok(())
+    }
+
+    /// Removes the mount at `comps` and the mounts under it, and the nodes
+    /// left empty.
+    fn remove(&mut self, comps: &[&str]) {
+        match comps.split_first() {
+            None => {
+                self.mount = None;
+                self.children.clear();
+            }
+            Some((first, rest)) => {
+                if let Some(child) = self.children.get_mut(*first) {
+                    child.remove(rest);
+                    if child.mount.is_none() && child.children.is_empty() {
+                        self.children.remove(*first);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if there are mounts under this node, itself excluded.
+    fn has_submounts(&self) -> bool {
+        self.children
+            .values()
+            .any(|child| child.mount.is_some() || child.has_submounts())
+    }
+
+    /// Collects the mounts at and under this node, parents first.
+    fn collect(&self, mounts: &mut Vec<Arc<MountPoint>>) {
+        mounts.extend(self.mount.clone());
+        for child in self.children.values() {
+            child.collect(mounts);
+        }
+    }
+}
+
 impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+    pub fn new(main_fs: DiskFs, source: &str, fstype: &str) -> Self {
+        let DiskFs {
+            fs,
+            links,
+            owners,
+            times,
+        } = main_fs;
+        let links = MountLinks::new(links);
+        let caches = MountCaches::for_fstype(fstype);
+        let root = MountPoint::new(
+            "/".into(),
+            source.into(),
+            fstype.into(),
+            fs,
+            links,
+            caches,
+            Arc::new(owners),
+            Arc::new(times),
+        );
         Self {
-            main_fs,
-            mounts: RwLock::new(Vec::new()),
+            mounts: RwLock::new(MountTrie {
+                mount: Some(Arc::new(root)),
+                children: BTreeMap::new(),
+            }),
         }
     }
 
-    pub fn mount(&self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
-        if path == "/" {
-            return ax_err!(InvalidInput, "cannot mount root filesystem");
+    /// Resolves an absolute path component by component.
+    ///
+    /// Symbolic links in the middle are always followed, while the last one
+    /// is followed only if `follow` is set or the path ends with `/`.
+    fn walk(&self, path: &str, follow: bool) -> AxResult<Location> {
+        self.walk_counted(path, follow, &mut 0)
+    }
+
+    /// Resolves an absolute path like [`Self::walk`], and counts the symbolic
+    /// links followed in `follows`.
+    fn walk_counted(&self, path: &str, follow: bool, follows: &mut usize) -> AxResult<Location> {
+        debug!("walk at root: {}", path);
+        struct Frame {
+            path: String,
+            node: VfsNodeRef,
+            mount: Arc<MountPoint>,
+        }
+
+        // the mount table is not locked during the lookups in filesystems
+        let root = self.mounts.read().mount.clone().unwrap();
+        let mut stack = vec![Frame {
+            path: String::new(),
+            node: root.fs.root_dir(),
+            mount: root,
+        }];
+        // the components to resolve, in reverse order
+        let mut pending: Vec<String> = components(path).rev().map(String::from).collect();
+        let follow = follow || path.ends_with('/');
+        let mut symlink = None;
+        while let Some(comp) = pending.pop() {
+            let top = stack.last().unwrap();
+            match comp.as_str() {
+                "." | ".." => {
+                    check_search(&top.mount, &top.node)?;
+                    // the parent of `/` is itself
+                    if comp == ".." && stack.len() > 1 {
+                        stack.pop();
+                    }
+                }
+                name => {
+                    check_search(&top.mount, &top.node)?;
+                    let path = format!("{}/{}", top.path, name);
+                    let sub = self.mounts.read().get(&path).and_then(|t| t.mount.clone());
+                    let (node, mount) = match sub {
+                        // enter the filesystem mounted on it
+                        Some(mp) => (mp.fs.root_dir(), mp),
+                        None => (top.node.clone().lookup(name)?, top.mount.clone()),
+                    };
+                    let path_in_mount = strip_mount_path(&path, &mount.path).unwrap_or_default();
+                    if let Some(target) = mount.links.read(path_in_mount, &node)? {
+                        if !pending.is_empty() || follow {
+                            *follows += 1;
+                            if *follows > MAX_SYMLINK_FOLLOWS {
+                                return ax_err!(InvalidData, "too many levels of symbolic links");
+                            }
+                            if target.starts_with('/') {
+                                stack.truncate(1);
+                            }
+                            pending.extend(components(&target).rev().map(String::from));
+                            continue;
+                        }
+                        symlink = Some(target);
+                    }
+                    stack.push(Frame { path, node, mount });
+                }
+            }
         }
+
+        let top = stack.pop().unwrap();
+        Ok(Location {
+            node: top.node,
+            mount: top.mount,
+            path: if top.path.is_empty() {
+                "/".into()
+            } else {
+                top.path
+            },
+            symlink,
+        })
+    }
+
+    /// Resolves the parent directory of an absolute path, returns it and the
+    /// last component.
+    fn walk_parent<'a>(&self, path: &'a str) -> AxResult<(Location, &'a str)> {
+        let (parent, name) = split_parent(path);
+        Ok((self.walk(parent, true)?, name))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn mount(
+        &self,
+        path: &str,
+        source: &str,
+        fstype: &str,
+        fs: Arc<dyn VfsOps>,
+        links: MountLinks,
+        caches: MountCaches,
+        owners: Arc<Owners>,
+        times: Arc<Times>,
+    ) -> AxResult {
         if !path.starts_with('/') {
             return ax_err!(InvalidInput, "mount path must start with '/'");
         }
-        if self.mounts.read().iter().any(|mp| mp.path == path) {
-            return ax_err!(InvalidInput, "mount point already exists");
+        // the mount point may be in another mounted filesystem
+        let loc = self.walk(path, true)?;
+        if loc.path == "/" {
+            return ax_err!(InvalidInput, "cannot mount root filesystem");
+        }
+        if !loc.node.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory, "mount point is not a directory");
         }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.write().push(MountPoint::new(path, fs));
-        Ok(())
-    }
 
-    pub fn _umount(&self, path: &str) {
-        self.mounts.write().retain(|mp| mp.path != path);
+        let exists = self
+            .mounts
+            .read()
+            .get(&loc.path)
+            .is_some_and(|t| t.mount.is_some());
+        if exists {
+            return ax_err!(ResourceBusy, "mount point already exists");
+        }
+        fs.mount(&loc.path, loc.node)?;
+        let mp = MountPoint::new(
+            loc.path.clone(),
+            source.into(),
+            fstype.into(),
+            fs,
+            links,
+            caches,
+            owners,
+            times,
+        );
+        self.mounts.write().insert(&loc.path, Arc::new(mp))
     }
 
-    pub fn contains(&self, path: &str) -> bool {
-        self.mounts.read().iter().any(|mp| mp.path == path)
+    /// Mounts a filesystem at boot, creating the mount point if it doesn't
+    /// exist.
+    fn mount_at_boot(&self, path: &str, fstype: &str, fs: Arc<dyn VfsOps>) -> AxResult {
+        match self.create(path, VfsNodeType::Dir) {
+            Ok(()) | Err(AxError::AlreadyExists) => {}
+            Err(e) => return Err(e),
+        }
+        let links = MountLinks::new(Symlinks::for_fstype(fstype));
+        let caches = MountCaches::for_fstype(fstype);
+        let table = NodeTable::for_fstype(fstype);
+        let owners = Arc::new(Owners::for_table(table.clone()));
+        let times = Arc::new(Times::for_table(table));
+        self.mount(path, fstype, fstype, fs, links, caches, owners, times)
     }
 
-    fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
-    where
-        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
-    {
-        debug!("lookup at root: {}", path);
-        let path = path.trim_matches('/');
-        if let Some(rest) = path.strip_prefix("./") {
-            return self.lookup_mounted_fs(rest, f);
+    /// Unmounts the filesystem at `path`.
+    ///
+    /// It fails with [`AxError::ResourceBusy`] if there are other mounts under
+    /// it, files opened or directories bound elsewhere in it, or the current
+    /// directory is in it. With `detach`, the filesystem and the mounts under
+    /// it are detached from the directory tree anyway, and actually unmounted
+    /// when the last opened file is closed and the last bind mount is gone.
+    pub fn umount(&self, path: &str, detach: bool) -> AxResult {
+        if !path.starts_with('/') {
+            return ax_err!(InvalidInput, "mount path must start with '/'");
+        }
+        let path = axfs_vfs::path::canonicalize(path);
+        if path == "/" {
+            return ax_err!(ResourceBusy, "cannot unmount root filesystem");
         }
 
-        let mut idx = 0;
-        let mut max_len = 0;
-
-        // Find the filesystem that has the longest mounted path match
-        // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.read().iter().enumerate() {
-            // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
-                max_len = mp.path.len() - 1;
-                idx = i;
+        let mut mounts = self.mounts.write();
+        let Some(node) = mounts.get(&path) else {
+            return ax_err!(InvalidInput, "not a mount point");
+        };
+        let Some(mp) = &node.mount else {
+            return ax_err!(InvalidInput, "not a mount point");
+        };
+        if !detach {
+            if node.has_submounts() {
+                return ax_err!(ResourceBusy, "other filesystems are mounted under it");
+            }
+            if Arc::strong_count(mp) > 1 {
+                return ax_err!(ResourceBusy, "files are opened or bound in it");
+            }
+            if strip_mount_path(CURRENT_DIR_PATH.lock().as_str(), &path).is_some() {
+                return ax_err!(ResourceBusy, "the current directory is in it");
             }
         }
+        mounts.remove(&components(&path).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    /// Returns the mounted filesystems, parents first.
+    fn mounts(&self) -> Vec<Arc<MountPoint>> {
+        let mut mounts = Vec::new();
+        self.mounts.read().collect(&mut mounts);
+        mounts
+    }
+
+    fn remove_at(&self, path: &str) -> AxResult {
+        let loc = self.walk(path, false)?;
+        if loc.is_mount_root() {
+            return ax_err!(PermissionDenied); // cannot remove mount points
+        }
+        let (parent, name) = self.walk_parent(path)?;
+        parent.check_modify()?;
+        let inode = loc.mount.owners.inode(&loc.node);
+        parent.node.remove(name)?;
+        parent.mount.times.modified(&parent.node);
+        loc.mount.links.removed(loc.path_in_mount());
+        loc.mount.caches.removed(loc.path_in_mount(), inode);
+        loc.mount.owners.removed(&loc.node);
+        loc.mount.times.removed(&loc.node);
+        crate::special::removed(&loc.node);
+        Ok(())
+    }
 
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
+    fn rename_at(&self, src_path: &str, dst_path: &str) -> AxResult {
+        let src = self.walk(src_path, false)?;
+        let (src_parent, _) = self.walk_parent(src_path)?;
+        let (dst_parent, dst_name) = self.walk_parent(dst_path)?;
+        src_parent.check_modify()?;
+        dst_parent.check_modify()?;
+        if src.is_mount_root() {
+            ax_err!(PermissionDenied) // cannot rename mount points
+        } else if !Arc::ptr_eq(&src.mount, &dst_parent.mount) {
+            ax_err!(InvalidInput, "cannot rename across filesystems")
         } else {
-            f(self.mounts.read()[idx].fs.clone(), &path[max_len..]) // matched at `idx`
+            let dst = format!("{}/{}", dst_parent.path_in_mount(), dst_name);
+            let dst = dst.trim_start_matches('/');
+            src.mount.fs.root_dir().rename(src.path_in_mount(), dst)?;
+            src.mount.links.renamed(src.path_in_mount(), dst);
+            src.mount.caches.renamed(src.path_in_mount(), dst);
+            let times = &src.mount.times;
+            times.changed(&src.node);
+            times.modified(&src_parent.node);
+            times.modified(&dst_parent.node);
+            Ok(())
         }
     }
 }
@@ -127,84 +575,73 @@ impl VfsNodeOps for RootDirectory {
     axfs_vfs::impl_vfs_dir_default! {}
 
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        self.main_fs.root_dir().get_attr()
+        self.walk("/", true)?.node.get_attr()
     }
 
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
-        self.lookup_mounted_fs(path, |fs, rest_path| fs.root_dir().lookup(rest_path))
+        Ok(self.walk(&format!("/{}", path), true)?.node)
     }
 
     fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                Ok(()) // already exists
-            } else {
-                fs.root_dir().create(rest_path, ty)
-            }
-        })
+        let (parent, name) = self.walk_parent(&format!("/{}", path))?;
+        parent.node.create(name, ty)
     }
 
     fn remove(&self, path: &str) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot remove mount points
-            } else {
-                fs.root_dir().remove(rest_path)
-            }
-        })
+        self.remove_at(&format!("/{}", path))
     }
 
     fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
-        self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot rename mount points
-            } else {
-                fs.root_dir().rename(rest_path, dst_path)
-            }
-        })
+        self.rename_at(&format!("/{}", src_path), &format!("/{}", dst_path))
     }
 }
 
-pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "myfs")] { // override the default filesystem
-            let main_fs = fs::myfs::new_myfs(disk);
-        } else if #[cfg(feature = "lwext4_rs")] {
-            static EXT4_FS: LazyInit<Arc<fs::lwext4_rust::Ext4FileSystem>> = LazyInit::new();
-            EXT4_FS.init_once(Arc::new(fs::lwext4_rust::Ext4FileSystem::new(disk)));
-            let main_fs = EXT4_FS.clone();
-        } else if #[cfg(feature = "fatfs")] {
-            static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
-            FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
-            FAT_FS.init();
-            let main_fs = FAT_FS.clone();
+/// Mounts the root filesystem on `disk`, of type `fstype`, or the detected
+/// type if it's `None`, and the in-memory filesystems under it.
+pub(crate) fn init_rootfs(#[allow(unused_mut)] mut disk: Disk, source: &str, fstype: Option<&str>) {
+    // the ramdisk is blank
+    #[cfg(all(feature = "use-ramdisk", feature = "fatfs", not(feature = "myfs")))]
+    crate::fs::fatfs::FatFileSystem::format(&mut disk).expect("failed to format the ramdisk");
+
+    // custom filesystems can't be detected, and replace the root filesystem
+    #[cfg(feature = "myfs")]
+    let fstype = fstype.or(Some("myfs"));
+    let ty = match fstype {
+        Some(name) => {
+            fstype::find(name).unwrap_or_else(|| panic!("unknown root filesystem type {:?}", name))
         }
-    }
+        None => fstype::probe(&disk).expect("unknown root filesystem type"),
+    };
+    info!("  root filesystem type: {}", ty.name);
+    let main_fs = ty
+        .open(disk)
+        .unwrap_or_else(|e| panic!("failed to open the root filesystem: {:?}", e));
 
-    let root_dir = RootDirectory::new(main_fs);
+    let root_dir = RootDirectory::new(main_fs, source, ty.name);
 
     #[cfg(feature = "devfs")]
     root_dir
-        .mount("/dev", mounts::devfs())
+        .mount_at_boot("/dev", "devfs", mounts::devfs())
         .expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
     root_dir
-        .mount("/tmp", mounts::ramfs())
+        .mount_at_boot("/tmp", "tmpfs", mounts::ramfs())
         .expect("failed to mount ramfs at /tmp");
 
-    // Mount another ramfs as procfs
     #[cfg(feature = "procfs")]
     root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
+        .mount_at_boot("/proc", "proc", mounts::procfs())
         .expect("fail to mount procfs at /proc");
 
-    // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
     root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
+        .mount_at_boot("/sys", "sysfs", mounts::sysfs())
         .expect("fail to mount sysfs at /sys");
 
+    #[cfg(feature = "procfs")]
+    crate::proc::register_file("mounts", mounts_file).expect("fail to create /proc/mounts");
+
     ROOT_DIR.init_once(Arc::new(root_dir));
     info!("rootfs initialized");
     CURRENT_DIR.init_new(Mutex::new(ROOT_DIR.clone()));
@@ -212,11 +649,25 @@ pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
     CURRENT_DIR_PATH.init_new(Mutex::new("/".into()));
 }
 
-fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
+/// Generates `/proc/mounts`.
+#[cfg(feature = "procfs")]
+fn mounts_file() -> String {
+    let mut out = String::new();
+    for (source, target, fstype) in mounts() {
+        out += &format!("{} {} {} rw 0 0\n", source, target, fstype);
+    }
+    out
+}
+
+/// Joins `path` to the base directory `dir`, or to the current directory if
+/// `dir` is `None`. Absolute paths are returned as is.
+fn full_path(dir: Option<&str>, path: &str) -> String {
     if path.starts_with('/') {
-        ROOT_DIR.clone()
+        path.into()
+    } else if let Some(dir) = dir {
+        format!("{}/{}", dir, path)
     } else {
-        dir.cloned().unwrap_or_else(|| CURRENT_DIR.lock().clone())
+        CURRENT_DIR_PATH.lock().clone() + path
     }
 }
 
@@ -229,50 +680,130 @@ pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
     }
 }
 
-pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+/// Resolves a path relative to the base directory `dir`, or to the current
+/// directory if `dir` is `None`, following symbolic links.
+pub(crate) fn resolve(dir: Option<&str>, path: &str) -> AxResult<Location> {
+    resolve_at(dir, path, true)
+}
+
+/// Resolves a path like [`resolve`], but does not follow the symbolic link
+/// at the last component.
+pub(crate) fn resolve_nofollow(dir: Option<&str>, path: &str) -> AxResult<Location> {
+    resolve_at(dir, path, false)
+}
+
+fn resolve_at(dir: Option<&str>, path: &str, follow: bool) -> AxResult<Location> {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
-    let node = parent_node_of(dir, path).lookup(path)?;
-    if path.ends_with('/') && !node.get_attr()?.is_dir() {
+    let loc = ROOT_DIR.walk(&full_path(dir, path), follow)?;
+    if path.ends_with('/') && !loc.node.get_attr()?.is_dir() {
         ax_err!(NotADirectory)
     } else {
-        Ok(node)
+        Ok(loc)
     }
 }
 
-pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+/// Returns whether resolving the path fails because of too many levels of
+/// symbolic links, which is reported as [`AxError::InvalidData`].
+pub(crate) fn is_symlink_loop(dir: Option<&str>, path: &str) -> bool {
+    let mut follows = 0;
+    ROOT_DIR
+        .walk_counted(&full_path(dir, path), true, &mut follows)
+        .is_err()
+        && follows > MAX_SYMLINK_FOLLOWS
+}
+
+pub(crate) fn lookup(dir: Option<&str>, path: &str) -> AxResult<VfsNodeRef> {
+    resolve(dir, path).map(|loc| loc.node)
+}
+
+/// Creates a file with the permissions `perm`, without the bits in the file
+/// mode creation mask.
+pub(crate) fn create_file(dir: Option<&str>, path: &str, perm: VfsNodePerm) -> AxResult<Location> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
-    let parent = parent_node_of(dir, path);
-    parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    // create the target of a dangling symbolic link
+    let mut path = full_path(dir, path);
+    let mut follows = 0;
+    while let Ok(Location {
+        symlink: Some(target),
+        ..
+    }) = ROOT_DIR.walk(&path, false)
+    {
+        follows += 1;
+        if follows > MAX_SYMLINK_FOLLOWS {
+            return ax_err!(InvalidData, "too many levels of symbolic links");
+        }
+        path = if target.starts_with('/') {
+            target
+        } else {
+            format!("{}/{}", split_parent(&path).0, target)
+        };
+    }
+    let (parent, name) = ROOT_DIR.walk_parent(&path)?;
+    parent.check_modify()?;
+    parent.node.create(name, VfsNodeType::File)?;
+    parent.mount.times.modified(&parent.node);
+    let loc = ROOT_DIR.walk(&path, true)?;
+    loc.mount.times.created(&loc.node);
+    loc.mount.owners.created(&loc.node, perm)?;
+    Ok(loc)
+}
+
+/// Creates a directory with the permissions `perm`, without the bits in the
+/// file mode creation mask.
+pub(crate) fn create_dir(dir: Option<&str>, path: &str, perm: VfsNodePerm) -> AxResult {
+    match resolve_nofollow(dir, path) {
+        Ok(_) => ax_err!(AlreadyExists),
+        Err(AxError::NotFound) => {
+            let path = full_path(dir, path);
+            let (parent, name) = ROOT_DIR.walk_parent(&path)?;
+            parent.check_modify()?;
+            parent.node.create(name, VfsNodeType::Dir)?;
+            parent.mount.times.modified(&parent.node);
+            let loc = ROOT_DIR.walk(&path, true)?;
+            loc.mount.times.created(&loc.node);
+            loc.mount.owners.created(&loc.node, perm)
+        }
+        Err(e) => Err(e),
+    }
 }
 
-pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    match lookup(dir, path) {
+/// Creates a special file of type `ty`, e.g. a socket, with the permissions
+/// `perm`, without the bits in the file mode creation mask.
+pub(crate) fn create_special(path: &str, ty: VfsNodeType, perm: VfsNodePerm) -> AxResult {
+    match resolve_nofollow(None, path) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => {
+            let path = full_path(None, path);
+            let (parent, name) = ROOT_DIR.walk_parent(&path)?;
+            parent.check_modify()?;
+            let stored = crate::special::stored_type(&parent.mount.fstype, ty);
+            parent.node.create(name, stored)?;
+            parent.mount.times.modified(&parent.node);
+            let loc = ROOT_DIR.walk(&path, false)?;
+            crate::special::created(&loc.mount.fstype, &loc.node, ty);
+            loc.mount.times.created(&loc.node);
+            loc.mount.owners.created(&loc.node, perm)
+        }
         Err(e) => Err(e),
     }
 }
 
-pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path)?;
-    let attr = node.get_attr()?;
+pub(crate) fn remove_file(dir: Option<&str>, path: &str) -> AxResult {
+    let attr = resolve_nofollow(dir, path)?.attr()?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        ROOT_DIR.remove_at(&full_path(dir, path))
     }
 }
 
-pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+pub(crate) fn remove_dir(dir: Option<&str>, path: &str) -> AxResult {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
@@ -286,18 +817,15 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     {
         return ax_err!(InvalidInput);
     }
-    if ROOT_DIR.contains(&absolute_path(path)?) {
-        return ax_err!(PermissionDenied);
-    }
 
-    let node = lookup(dir, path)?;
-    let attr = node.get_attr()?;
-    if !attr.is_dir() {
-        ax_err!(NotADirectory)
-    } else if !attr.perm().owner_writable() {
+    let loc = resolve_nofollow(dir, path)?;
+    let attr = loc.attr()?;
+    if loc.is_mount_root() {
         ax_err!(PermissionDenied)
+    } else if !attr.is_dir() {
+        ax_err!(NotADirectory)
     } else {
-        parent_node_of(dir, path).remove(path)
+        ROOT_DIR.remove_at(&full_path(dir, path))
     }
 }
 
@@ -306,33 +834,178 @@ pub(crate) fn current_dir() -> AxResult<String> {
 }
 
 pub(crate) fn set_current_dir(path: &str) -> AxResult {
-    let mut abs_path = absolute_path(path)?;
-    if !abs_path.ends_with('/') {
-        abs_path += "/";
-    }
-    if abs_path == "/" {
-        *CURRENT_DIR.lock() = ROOT_DIR.clone();
-        *CURRENT_DIR_PATH.lock() = "/".into();
-        return Ok(());
-    }
-
-    let node = lookup(None, &abs_path)?;
-    let attr = node.get_attr()?;
-    if !attr.is_dir() {
+    let loc = resolve(None, path)?;
+    if !loc.attr()?.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_executable() {
+    } else if !loc.access()?.contains(Cap::EXECUTE) {
         ax_err!(PermissionDenied)
     } else {
-        *CURRENT_DIR.lock() = node;
-        *CURRENT_DIR_PATH.lock() = abs_path;
+        let mut path = loc.path;
+        if !path.ends_with('/') {
+            path += "/";
+        }
+        *CURRENT_DIR.lock() = if path == "/" {
+            ROOT_DIR.clone()
+        } else {
+            loc.node
+        };
+        *CURRENT_DIR_PATH.lock() = path;
         Ok(())
     }
 }
 
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
-    if parent_node_of(None, new).lookup(new).is_ok() {
+    if resolve_nofollow(None, new).is_ok() {
         warn!("dst file already exist, now remove it");
         remove_file(None, new)?;
     }
-    parent_node_of(None, old).rename(old, new)
+    ROOT_DIR.rename_at(&full_path(None, old), &full_path(None, new))
+}
+
+/// Mounts `fs` on `target`.
+pub(crate) fn mount(source: &str, target: &str, fstype: &str, fs: DiskFs) -> AxResult {
+    let DiskFs {
+        fs,
+        links,
+        owners,
+        times,
+    } = fs;
+    let links = MountLinks::new(links);
+    let caches = MountCaches::for_fstype(fstype);
+    ROOT_DIR.mount(
+        &full_path(None, target),
+        source,
+        fstype,
+        fs,
+        links,
+        caches,
+        Arc::new(owners),
+        Arc::new(times),
+    )
+}
+
+/// Mounts the directory `source` on `target`.
+pub(crate) fn bind_mount(source: &str, target: &str) -> AxResult {
+    let loc = resolve(None, source)?;
+    if !loc.node.get_attr()?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    ROOT_DIR.mount(
+        &full_path(None, target),
+        &loc.path,
+        &loc.mount.fstype,
+        Arc::new(BindFs(loc.node.clone(), loc.mount.clone())),
+        loc.mount.links.subdir(loc.path_in_mount()),
+        loc.mount.caches.subdir(loc.path_in_mount()),
+        loc.mount.owners.clone(),
+        loc.mount.times.clone(),
+    )
+}
+
+/// Unmounts the filesystem on `target`, see [`RootDirectory::umount`].
+pub(crate) fn umount(target: &str, detach: bool) -> AxResult {
+    ROOT_DIR.umount(&absolute_path(target)?, detach)
+}
+
+/// Creates a symbolic link at `link` pointing to `target`.
+pub(crate) fn symlink(target: &str, link: &str) -> AxResult {
+    if target.is_empty() {
+        return ax_err!(NotFound);
+    }
+    match resolve_nofollow(None, link) {
+        Ok(_) => ax_err!(AlreadyExists),
+        Err(AxError::NotFound) => {
+            let path = full_path(None, link);
+            let (parent, name) = ROOT_DIR.walk_parent(&path)?;
+            if name == "." || name == ".." {
+                return ax_err!(AlreadyExists);
+            }
+            parent.check_modify()?;
+            let path = format!("{}/{}", parent.path_in_mount(), name);
+            let path = path.trim_start_matches('/');
+            parent
+                .mount
+                .links
+                .create(&parent.node, name, path, target)?;
+            parent.mount.times.modified(&parent.node);
+            let loc = ROOT_DIR.walk(&full_path(None, link), false)?;
+            loc.mount.times.created(&loc.node);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the target of the symbolic link at `path`.
+pub(crate) fn read_link(path: &str) -> AxResult<String> {
+    match resolve_nofollow(None, path)?.symlink {
+        Some(target) => Ok(target),
+        None => ax_err!(InvalidInput, "not a symbolic link"),
+    }
+}
+
+/// Returns the attributes, the owner and the times of `path`, following the
+/// symbolic link at the last component if `follow` is set.
+pub(crate) fn stat(path: &str, follow: bool) -> AxResult<(VfsNodeAttr, Owner, FileTimes)> {
+    let loc = resolve_at(None, path, follow)?;
+    Ok((loc.attr()?, loc.owner()?, loc.times()?))
+}
+
+/// Checks whether the current user has the access `mode` to `path`.
+pub(crate) fn access(path: &str, mode: Cap) -> AxResult {
+    if resolve(None, path)?.access()?.contains(mode) {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// Changes the permissions of `path`.
+pub(crate) fn set_perm(path: &str, perm: VfsNodePerm) -> AxResult {
+    let loc = resolve(None, path)?;
+    loc.mount.owners.chmod(&loc.node, perm)?;
+    loc.mount.times.changed(&loc.node);
+    Ok(())
+}
+
+/// Changes the owner of `path`, see [`Owners::chown`].
+pub(crate) fn set_owner(path: &str, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+    let loc = resolve(None, path)?;
+    loc.mount.owners.chown(&loc.node, uid, gid)?;
+    loc.mount.times.changed(&loc.node);
+    Ok(())
+}
+
+/// Changes the access and modification times of `path` relative to the base
+/// directory `dir`, see [`Times::set`].
+pub(crate) fn set_times(
+    dir: Option<&str>,
+    path: &str,
+    follow: bool,
+    accessed: TimeUpdate,
+    modified: TimeUpdate,
+) -> AxResult {
+    let loc = resolve_at(dir, path, follow)?;
+    let writable = loc.access()?.contains(Cap::WRITE);
+    loc.mount
+        .times
+        .set(&loc.node, loc.owner()?, writable, accessed, modified)
+}
+
+/// Writes the dirty pages in the page caches of all mounted filesystems back.
+pub(crate) fn sync_caches() -> AxResult {
+    for mp in ROOT_DIR.mounts() {
+        mp.caches.sync()?;
+    }
+    Ok(())
+}
+
+/// Returns the source, target and type of the mounted filesystems, parents
+/// first.
+pub(crate) fn mounts() -> Vec<(String, String, String)> {
+    ROOT_DIR
+        .mounts()
+        .iter()
+        .map(|mp| (mp.source.clone(), mp.path.clone(), mp.fstype.clone()))
+        .collect()
 }
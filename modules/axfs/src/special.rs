@@ -0,0 +1,61 @@
+//! Special files, e.g. sockets, on in-memory filesystems.
+//!
+//! The in-memory filesystems only create regular files and directories, so a
+//! special file is created as a regular file on them, and its type is kept in
+//! a table keyed by the address of the node, see [`node_key`]. Other
+//! filesystems create the special files themselves, or fail.
+
+use alloc::{collections::BTreeMap, sync::Arc, sync::Weak};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use spin::RwLock;
+
+use crate::meta::node_key;
+
+struct Entry {
+    /// The node, to tell whether the entry is stale.
+    node: Weak<dyn VfsNodeOps>,
+    ty: VfsNodeType,
+}
+
+static TYPES: RwLock<BTreeMap<usize, Entry>> = RwLock::new(BTreeMap::new());
+
+/// Whether the filesystems of the given type keep the types of special files
+/// in the table.
+fn keeps_types(fstype: &str) -> bool {
+    matches!(fstype, "tmpfs" | "ramfs")
+}
+
+/// Returns the type a filesystem of `fstype` creates for a node of type `ty`.
+pub(crate) fn stored_type(fstype: &str, ty: VfsNodeType) -> VfsNodeType {
+    match ty {
+        VfsNodeType::File | VfsNodeType::Dir => ty,
+        _ if keeps_types(fstype) => VfsNodeType::File,
+        _ => ty,
+    }
+}
+
+/// Records the type of the new node created with [`stored_type`].
+pub(crate) fn created(fstype: &str, node: &VfsNodeRef, ty: VfsNodeType) {
+    if stored_type(fstype, ty) != ty {
+        let entry = Entry {
+            node: Arc::downgrade(node),
+            ty,
+        };
+        TYPES.write().insert(node_key(node), entry);
+    }
+}
+
+/// Returns `attr` of `node` with the type of the special file.
+pub(crate) fn attr(node: &VfsNodeRef, attr: VfsNodeAttr) -> VfsNodeAttr {
+    match TYPES.read().get(&node_key(node)) {
+        Some(entry) if entry.node.strong_count() > 0 => {
+            VfsNodeAttr::new(attr.perm(), entry.ty, attr.size(), attr.blocks())
+        }
+        _ => attr,
+    }
+}
+
+/// Removes `node` from the table after it's removed.
+pub(crate) fn removed(node: &VfsNodeRef) {
+    TYPES.write().remove(&node_key(node));
+}
@@ -0,0 +1,161 @@
+//! Symbolic links.
+//!
+//! The nodes of [`axfs_vfs`] have no operations for symbolic links, so they
+//! are provided per mounted filesystem by [`Symlinks`]: in-memory filesystems
+//! keep the links in a table, with empty placeholder files in the directories,
+//! and filesystems supporting them natively implement [`SymlinkFs`].
+
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc, vec::Vec};
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::{VfsDirEntry, VfsNodeRef, VfsNodeType, VfsResult};
+use spin::RwLock;
+
+/// The maximum number of symbolic links followed in a path resolution.
+pub(crate) const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+/// A filesystem supporting symbolic links natively.
+///
+/// Paths are relative to the root of the filesystem.
+#[cfg_attr(not(feature = "lwext4_rs"), allow(dead_code))]
+pub(crate) trait SymlinkFs: Send + Sync {
+    /// Creates a symbolic link at `path` pointing to `target`.
+    fn symlink(&self, path: &str, target: &str) -> VfsResult;
+    /// Returns the target of the symbolic link at `path`.
+    fn readlink(&self, path: &str) -> VfsResult<String>;
+}
+
+/// How a filesystem stores symbolic links.
+pub(crate) enum Symlinks {
+    /// Not supported, e.g. FAT.
+    Unsupported,
+    /// Kept in memory, the keys are paths relative to the root of the
+    /// filesystem.
+    Memory(RwLock<BTreeMap<String, String>>),
+    /// Supported by the filesystem.
+    #[cfg_attr(not(feature = "lwext4_rs"), allow(dead_code))]
+    Native(Arc<dyn SymlinkFs>),
+}
+
+/// The symbolic links seen from a mount point.
+#[derive(Clone)]
+pub(crate) struct MountLinks {
+    links: Arc<Symlinks>,
+    /// The path of the mounted directory in the filesystem, which is not
+    /// empty for bind mounts.
+    root: String,
+}
+
+impl Symlinks {
+    /// Returns how the filesystems of the given type store symbolic links,
+    /// except the native ones.
+    pub fn for_fstype(fstype: &str) -> Self {
+        match fstype {
+            "tmpfs" | "ramfs" => Self::Memory(RwLock::new(BTreeMap::new())),
+            _ => Self::Unsupported,
+        }
+    }
+}
+
+impl MountLinks {
+    pub fn new(links: Symlinks) -> Self {
+        Self {
+            links: Arc::new(links),
+            root: String::new(),
+        }
+    }
+
+    /// Returns the links seen from a bind mount of the directory at `path`.
+    pub fn subdir(&self, path: &str) -> Self {
+        Self {
+            links: self.links.clone(),
+            root: self.fs_path(path),
+        }
+    }
+
+    /// Maps a path relative to the mount point to the path in the filesystem.
+    fn fs_path(&self, path: &str) -> String {
+        match (self.root.is_empty(), path.is_empty()) {
+            (true, _) => path.into(),
+            (false, true) => self.root.clone(),
+            (false, false) => format!("{}/{}", self.root, path),
+        }
+    }
+
+    /// Returns the target if `node` at `path` is a symbolic link.
+    pub fn read(&self, path: &str, node: &VfsNodeRef) -> AxResult<Option<String>> {
+        match &*self.links {
+            Symlinks::Unsupported => Ok(None),
+            Symlinks::Memory(links) => Ok(links.read().get(&self.fs_path(path)).cloned()),
+            Symlinks::Native(fs) => {
+                if node.get_attr()?.file_type() == VfsNodeType::SymLink {
+                    fs.readlink(&self.fs_path(path)).map(Some)
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    /// Fixes the types of the entries read from the directory at `dir`, the
+    /// placeholder files of the links kept in memory are reported as links.
+    pub fn fix_types(&self, dir: &str, dirents: &mut [VfsDirEntry]) {
+        if let Symlinks::Memory(links) = &*self.links {
+            let links = links.read();
+            for ent in dirents.iter_mut() {
+                let Ok(name) = core::str::from_utf8(ent.name_as_bytes()) else {
+                    continue;
+                };
+                let path = match dir {
+                    "" => self.fs_path(name),
+                    dir => self.fs_path(&format!("{}/{}", dir, name)),
+                };
+                if ent.entry_type() == VfsNodeType::File && links.contains_key(&path) {
+                    let name = String::from(name);
+                    *ent = VfsDirEntry::new(&name, VfsNodeType::SymLink);
+                }
+            }
+        }
+    }
+
+    /// Creates a symbolic link named `name` in the directory `dir`, whose
+    /// path is `path`.
+    pub fn create(&self, dir: &VfsNodeRef, name: &str, path: &str, target: &str) -> AxResult {
+        match &*self.links {
+            Symlinks::Unsupported => ax_err!(Unsupported, "symbolic links are not supported"),
+            Symlinks::Memory(links) => {
+                dir.create(name, VfsNodeType::File)?;
+                links.write().insert(self.fs_path(path), target.into());
+                Ok(())
+            }
+            Symlinks::Native(fs) => fs.symlink(&self.fs_path(path), target),
+        }
+    }
+
+    /// Updates the links after the node at `path` is removed.
+    pub fn removed(&self, path: &str) {
+        if let Symlinks::Memory(links) = &*self.links {
+            links.write().remove(&self.fs_path(path));
+        }
+    }
+
+    /// Updates the links after the node at `src` is renamed to `dst`,
+    /// including the links under it if it's a directory.
+    pub fn renamed(&self, src: &str, dst: &str) {
+        if let Symlinks::Memory(links) = &*self.links {
+            let (src, dst) = (self.fs_path(src), self.fs_path(dst));
+            let mut links = links.write();
+            let moved: Vec<String> = links
+                .keys()
+                .filter(|path| {
+                    path.strip_prefix(src.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                })
+                .cloned()
+                .collect();
+            for path in moved {
+                let target = links.remove(&path).unwrap();
+                links.insert(format!("{}{}", dst, &path[src.len()..]), target);
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! The sysfs, whose files are generated on read.
+//!
+//! Other modules describe what they know by [`register_file`], e.g. the
+//! clock source in `/sys/devices/system/clocksource`, and [`register_dir`]
+//! for directories whose entries change at runtime, e.g. one for each probed
+//! device in `/sys/bus/pci/devices`.
+
+use alloc::sync::Arc;
+use axerrno::AxResult;
+
+use crate::pseudo::Registry;
+pub use crate::pseudo::{
+    PseudoDirFn as SysDirFn, PseudoEntry as SysEntry, PseudoReadFn as SysReadFn,
+};
+
+/// The registered entries, the keys are paths relative to `/sys`.
+pub(crate) static SYS_ENTRIES: Registry = Registry::new();
+
+/// Registers a sysfs file, `path` is relative to `/sys`, e.g.
+/// `kernel/mm/transparent_hugepage/enabled`.
+///
+/// The parent directories exist as long as there are files in them. A
+/// registered file replaces the previous one with the same path.
+pub fn register_file(path: &str, read: SysReadFn) -> AxResult {
+    SYS_ENTRIES.register(path, SysEntry::File(Arc::new(read), None))
+}
+
+/// Registers a sysfs directory whose entries are generated by `entries`, in
+/// addition to the registered files in it.
+pub fn register_dir(path: &str, entries: SysDirFn) -> AxResult {
+    SYS_ENTRIES.register(path, SysEntry::Dir(Arc::new(entries)))
+}
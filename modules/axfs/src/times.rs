@@ -0,0 +1,216 @@
+//! Access, modification and change times of files.
+//!
+//! The nodes of [`axfs_vfs`] have no times, so they are provided per mounted
+//! filesystem by [`Times`], like the owners in [`perm`](crate::perm):
+//! in-memory filesystems keep them in a table of nodes, and filesystems
+//! storing them natively implement [`TimesFs`].
+//!
+//! The times are updated as on Linux: reading a file or listing a directory
+//! updates the access time, changing the data or the entries updates the
+//! modification and change times, and changing the owner or the permissions
+//! updates the change time. Like the default `relatime` mount option, the
+//! access time is updated only if it's earlier than the other times or a day
+//! old, and at most once for each opened file.
+
+use alloc::sync::Arc;
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::{VfsNodeRef, VfsResult};
+use axhal::time::wall_time;
+use core::time::Duration;
+
+use crate::meta::NodeTable;
+use crate::perm::{Owner, current_user};
+
+/// The access time older than it is updated on reads.
+const RELATIME_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The access, modification and change times of a file, since the Unix epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileTimes {
+    /// The time of the last access.
+    pub accessed: Duration,
+    /// The time of the last modification of the data.
+    pub modified: Duration,
+    /// The time of the last change of the data or the metadata.
+    pub changed: Duration,
+}
+
+/// A new access or modification time of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUpdate {
+    /// Leaves the time unchanged.
+    Omit,
+    /// Sets the time to the current time.
+    Now,
+    /// Sets the time to the given time since the Unix epoch.
+    At(Duration),
+}
+
+impl TimeUpdate {
+    fn resolve(self, now: Duration) -> Option<Duration> {
+        match self {
+            Self::Omit => None,
+            Self::Now => Some(now),
+            Self::At(time) => Some(time),
+        }
+    }
+}
+
+/// A filesystem storing the times natively.
+#[cfg_attr(not(any(feature = "fatfs", feature = "lwext4_rs")), allow(dead_code))]
+pub(crate) trait TimesFs: Send + Sync {
+    /// Returns the times of `node`.
+    fn times(&self, node: &VfsNodeRef) -> VfsResult<FileTimes>;
+    /// Changes the times of `node`, leaving those which are `None` unchanged.
+    fn set_times(
+        &self,
+        node: &VfsNodeRef,
+        accessed: Option<Duration>,
+        modified: Option<Duration>,
+        changed: Option<Duration>,
+    ) -> VfsResult;
+}
+
+/// How a filesystem stores the times.
+pub(crate) enum Times {
+    /// Not stored, all the times are the current time, e.g. for the pseudo
+    /// filesystems whose files are generated when read.
+    Fixed,
+    /// Kept in memory, in the table shared with the owners of the mount.
+    /// Nodes not in the table have existed since the filesystem was mounted.
+    Memory(Arc<NodeTable>),
+    /// Stored by the filesystem.
+    #[cfg_attr(not(any(feature = "fatfs", feature = "lwext4_rs")), allow(dead_code))]
+    Native(Arc<dyn TimesFs>),
+}
+
+impl Times {
+    /// Returns how the filesystems of the given type store the times, except
+    /// the native ones, given the table of the mount.
+    pub fn for_table(table: Option<Arc<NodeTable>>) -> Self {
+        table.map_or(Self::Fixed, Self::Memory)
+    }
+
+    /// Returns the times of `node`.
+    pub fn get(&self, node: &VfsNodeRef) -> AxResult<FileTimes> {
+        match self {
+            Self::Fixed => {
+                let now = wall_time();
+                Ok(FileTimes {
+                    accessed: now,
+                    modified: now,
+                    changed: now,
+                })
+            }
+            Self::Memory(table) => Ok(table.get(node).times),
+            Self::Native(fs) => fs.times(node),
+        }
+    }
+
+    fn update(
+        &self,
+        node: &VfsNodeRef,
+        accessed: Option<Duration>,
+        modified: Option<Duration>,
+        changed: Option<Duration>,
+    ) -> AxResult {
+        match self {
+            Self::Fixed => ax_err!(Unsupported, "the filesystem has no times"),
+            Self::Memory(table) => {
+                table.update(node, |meta| {
+                    let times = &mut meta.times;
+                    times.accessed = accessed.unwrap_or(times.accessed);
+                    times.modified = modified.unwrap_or(times.modified);
+                    times.changed = changed.unwrap_or(times.changed);
+                });
+                Ok(())
+            }
+            Self::Native(fs) => fs.set_times(node, accessed, modified, changed),
+        }
+    }
+
+    /// Updates the times automatically, the errors are ignored as they don't
+    /// fail the operations, e.g. directories on FAT can't be changed.
+    fn touch(&self, node: &VfsNodeRef, accessed: bool, modified: bool) {
+        if matches!(self, Self::Fixed) {
+            return;
+        }
+        let now = wall_time();
+        let (accessed, modified) = (accessed.then_some(now), modified.then_some(now));
+        if let Err(e) = self.update(node, accessed, modified, Some(now)) {
+            trace!("failed to update the times: {:?}", e);
+        }
+    }
+
+    /// Sets all the times of the new node to the current time.
+    pub fn created(&self, node: &VfsNodeRef) {
+        self.touch(node, true, true)
+    }
+
+    /// Updates the access time of `node` after it's read, if it's earlier
+    /// than the other times or a day old.
+    pub fn accessed(&self, node: &VfsNodeRef) {
+        if matches!(self, Self::Fixed) {
+            return;
+        }
+        let Ok(times) = self.get(node) else {
+            return;
+        };
+        let now = wall_time();
+        let stale = times.accessed <= times.modified
+            || times.accessed <= times.changed
+            || now.saturating_sub(times.accessed) >= RELATIME_INTERVAL;
+        if !stale {
+            return;
+        }
+        if let Err(e) = self.update(node, Some(now), None, None) {
+            trace!("failed to update the access time: {:?}", e);
+        }
+    }
+
+    /// Updates the modification and change times of `node` after its data
+    /// or entries are changed.
+    pub fn modified(&self, node: &VfsNodeRef) {
+        self.touch(node, false, true)
+    }
+
+    /// Updates the change time of `node` after its metadata is changed.
+    pub fn changed(&self, node: &VfsNodeRef) {
+        self.touch(node, false, false)
+    }
+
+    /// Changes the access and modification times of `node` owned by `owner`,
+    /// and sets the change time to the current time.
+    ///
+    /// Setting the times to the current time is allowed for the owner,
+    /// `root` and the users who can write the file, while setting other
+    /// times is allowed only for the owner and `root`.
+    pub fn set(
+        &self,
+        node: &VfsNodeRef,
+        owner: Owner,
+        writable: bool,
+        accessed: TimeUpdate,
+        modified: TimeUpdate,
+    ) -> AxResult {
+        if accessed == TimeUpdate::Omit && modified == TimeUpdate::Omit {
+            return Ok(());
+        }
+        let user = current_user();
+        let explicit =
+            matches!(accessed, TimeUpdate::At(_)) || matches!(modified, TimeUpdate::At(_));
+        if user.uid != 0 && user.uid != owner.uid && (explicit || !writable) {
+            return ax_err!(PermissionDenied);
+        }
+        let now = wall_time();
+        let (accessed, modified) = (accessed.resolve(now), modified.resolve(now));
+        self.update(node, accessed, modified, Some(now))
+    }
+
+    /// Updates the table after `node` is removed.
+    pub fn removed(&self, node: &VfsNodeRef) {
+        if let Self::Memory(table) = self {
+            table.removed(node);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+#![cfg(not(feature = "myfs"))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api::{self as fs, File};
+use axio::{Read, Result};
+
+use test_common::BLOCK_SIZE;
+
+fn test_sequential_read() -> Result<()> {
+    // larger than the cache, so it's not cached when read back
+    const FILE_SIZE: usize = 1024 * 1024;
+    let data: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    fs::write("/big.bin", &data)?;
+    fs::sync()?;
+
+    let before = fs::block_cache_stats();
+    let mut file = File::open("/big.bin")?;
+    let mut buf = [0u8; 4096];
+    let mut contents = Vec::new();
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        contents.extend_from_slice(&buf[..n]);
+    }
+    assert!(contents == data);
+    let after = fs::block_cache_stats();
+
+    let accesses = (after.hits + after.misses) - (before.hits + before.misses);
+    let reads = after.reads - before.reads;
+    let blocks_read = after.blocks_read - before.blocks_read;
+    println!(
+        "sequential read of {} blocks: {} block accesses, {} device reads of {} blocks",
+        FILE_SIZE / BLOCK_SIZE,
+        accesses,
+        reads,
+        blocks_read
+    );
+    // without the cache, every block access is a device read
+    assert!(blocks_read >= (FILE_SIZE / BLOCK_SIZE) as u64 / 2);
+    assert!(reads * 8 < accesses);
+    Ok(())
+}
+
+fn test_write_back() -> Result<()> {
+    let before = fs::block_cache_stats();
+    fs::write("/small.txt", "Rust is cool!\n")?;
+    assert_eq!(fs::read_to_string("/small.txt")?, "Rust is cool!\n");
+    let written = fs::block_cache_stats().blocks_written - before.blocks_written;
+    fs::sync()?;
+    let synced = fs::block_cache_stats().blocks_written - before.blocks_written;
+    println!("blocks written: {} before sync, {} after", written, synced);
+    assert!(synced > written);
+    Ok(())
+}
+
+#[test]
+fn test_block_cache() {
+    println!("Testing block cache with ramdisk ...");
+
+    let disk = test_common::make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_sequential_read().expect("test_sequential_read() failed");
+    test_write_back().expect("test_write_back() failed");
+}
@@ -1,18 +1,54 @@
+//! Helpers shared by the tests, and the common tests of the root filesystem.
+
+#![allow(dead_code, unused_macros)]
+
+#[cfg(all(feature = "devfs", feature = "ramfs"))]
+use std::sync::Arc;
+
+use axdriver_block::ramdisk::RamDisk;
 use axfs::api as fs;
+#[cfg(all(feature = "devfs", feature = "ramfs"))]
+use axfs::devfs::{self, CharDevice};
 use axio as io;
 
 use fs::{File, FileType, OpenOptions};
-use io::{Error, Result, prelude::*};
+use io::{Result, prelude::*};
+
+pub const BLOCK_SIZE: usize = 512;
+pub const IMG_PATH: &str = "resources/fat16.img";
 
 macro_rules! assert_err {
     ($expr: expr) => {
         assert!(($expr).is_err())
     };
     ($expr: expr, $err: ident) => {
-        assert_eq!(($expr).err(), Some(Error::$err))
+        assert_eq!(($expr).err(), Some(axio::Error::$err))
     };
 }
 
+/// Reads the FAT16 disk image.
+pub fn read_image() -> std::io::Result<Vec<u8>> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(data)
+}
+
+/// Loads the FAT16 disk image into a ramdisk.
+pub fn make_disk() -> std::io::Result<RamDisk> {
+    Ok(RamDisk::from(&read_image()?))
+}
+
+/// Writes an entry of the partition table in the MBR (or EBR) at `block`.
+pub fn write_mbr_entry(img: &mut [u8], block: usize, index: usize, ty: u8, start: u32, len: u32) {
+    let entry = &mut img[block * BLOCK_SIZE + 446 + index * 16..][..16];
+    entry[4] = ty;
+    entry[8..12].copy_from_slice(&start.to_le_bytes());
+    entry[12..16].copy_from_slice(&len.to_le_bytes());
+    img[block * BLOCK_SIZE + 510..][..2].copy_from_slice(&[0x55, 0xaa]);
+}
+
 fn test_read_write_file() -> Result<()> {
     let fname = "///very/long//.././long//./path/./test.txt";
     println!("read and write file {:?}:", fname);
@@ -164,10 +200,28 @@ fn test_remove_file_dir() -> Result<()> {
     Ok(())
 }
 
+/// A device discarding the written data, and reading nothing.
+#[cfg(all(feature = "devfs", feature = "ramfs"))]
+struct EmptyDev;
+
+#[cfg(all(feature = "devfs", feature = "ramfs"))]
+impl CharDevice for EmptyDev {
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+}
+
+#[cfg(all(feature = "devfs", feature = "ramfs"))]
 fn test_devfs_ramfs() -> Result<()> {
     const N: usize = 32;
     let mut buf = [1; N];
 
+    devfs::register_char_device("foo/bar", Arc::new(EmptyDev))?;
+
     // list '/' and check if /dev and /tmp exist
     let dirents = fs::read_dir("././//.//")?
         .map(|e| e.unwrap().file_name())
@@ -229,7 +283,7 @@ fn test_devfs_ramfs() -> Result<()> {
     // parent of '/dev'
     assert_eq!(fs::create_dir("///dev//..//233//"), Ok(()));
     assert_eq!(fs::write(".///dev//..//233//.///test.txt", "test"), Ok(()));
-    assert_err!(fs::remove_file("./dev//../..//233//.///test.txt"), NotFound);
+    assert!(fs::metadata("./dev//../..//233//.///test.txt")?.is_file()); // the parent of '/' is itself
     assert_eq!(fs::remove_file("./dev//..//233//../233/./test.txt"), Ok(()));
     assert_eq!(fs::remove_dir("dev//foo/../foo/../.././/233"), Ok(()));
     assert_err!(fs::remove_dir("very/../dev//"), PermissionDenied);
@@ -240,7 +294,7 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_eq!(fs::read_dir("tmp").unwrap().count(), 1);
     assert_eq!(fs::write(".///tmp///dir//.///test.txt", "test"), Ok(()));
     assert_eq!(fs::read("tmp//././/dir//.///test.txt"), Ok("test".into()));
-    // assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty); // TODO
+    assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty);
     assert_err!(fs::remove_dir("/tmp/dir/../dir"), DirectoryNotEmpty);
     assert_eq!(fs::remove_file("./tmp//dir//test.txt"), Ok(()));
     assert_eq!(fs::remove_dir("tmp/dir/.././dir///"), Ok(()));
@@ -256,5 +310,6 @@ pub fn test_all() {
     test_file_permission().expect("test_file_permission() failed");
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
+    #[cfg(all(feature = "devfs", feature = "ramfs"))]
     test_devfs_ramfs().expect("test_devfs_ramfs() failed");
 }
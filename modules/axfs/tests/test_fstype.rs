@@ -0,0 +1,94 @@
+#![cfg(all(feature = "fatfs", not(feature = "myfs"), not(feature = "lwext4_rs")))]
+
+#[macro_use]
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::Result;
+
+use test_common::{BLOCK_SIZE, write_mbr_entry};
+
+/// Makes a disk with the FAT image on the first and third partitions, and a
+/// blank second partition.
+fn make_partitioned_disk(fat: &[u8]) -> RamDisk {
+    let fat_blocks = fat.len() / BLOCK_SIZE;
+    let third = 8 + fat_blocks + 64;
+    let mut img = vec![0u8; (third + fat_blocks) * BLOCK_SIZE];
+    img[8 * BLOCK_SIZE..][..fat.len()].copy_from_slice(fat);
+    img[third * BLOCK_SIZE..][..fat.len()].copy_from_slice(fat);
+    write_mbr_entry(&mut img, 0, 0, 0x83, 8, fat_blocks as u32);
+    write_mbr_entry(&mut img, 0, 1, 0x83, (8 + fat_blocks) as u32, 64);
+    write_mbr_entry(&mut img, 0, 2, 0x83, third as u32, fat_blocks as u32);
+    RamDisk::from(&img)
+}
+
+fn test_mount_disks() -> Result<()> {
+    fs::create_dir("/mnt")?;
+    assert_err!(fs::mount("/dev/disk0p2", "/mnt", "xfs"), Unsupported);
+    assert_err!(fs::mount("/dev/disk0p4", "/mnt", "vfat"), NotFound);
+    // not formatted
+    assert_err!(fs::mount("/dev/disk0p2", "/mnt", "vfat"), InvalidInput);
+    // the whole disk overlaps the mounted partition
+    assert_err!(fs::mount("/dev/disk0", "/mnt", "vfat"), ResourceBusy);
+    assert_err!(fs::mount("/dev/disk0p1", "/mnt", "vfat"), ResourceBusy);
+    assert_err!(fs::mount("/dev/vda1", "/mnt", "vfat"), ResourceBusy);
+    assert!(fs::mounts().iter().all(|m| m.target != "/mnt"));
+    Ok(())
+}
+
+/// Mounts, unmounts and mounts again the FAT filesystem on the third
+/// partition.
+fn test_remount() -> Result<()> {
+    fs::mount("/dev/disk0p3", "/mnt", "vfat")?;
+    let info = fs::mounts().into_iter().find(|m| m.target == "/mnt");
+    assert_eq!(info.map(|m| m.fstype), Some("vfat".into()));
+    assert_err!(fs::mount("/dev/vda3", "/mnt", "vfat"), ResourceBusy);
+    fs::write("/mnt/remount.txt", "persisted")?;
+    fs::umount("/mnt", false)?;
+    assert_err!(fs::metadata("/mnt/remount.txt"), NotFound);
+
+    fs::mount("/dev/vda3", "/mnt", "vfat")?;
+    assert_eq!(fs::read_to_string("/mnt/remount.txt")?, "persisted");
+    // the root filesystem is on the other partition
+    assert_err!(fs::metadata("/remount.txt"), NotFound);
+    fs::remove_file("/mnt/remount.txt")?;
+    fs::umount("/mnt", false)?;
+    Ok(())
+}
+
+/// Reads the partitions through their nodes in devfs.
+fn test_block_nodes(fat: &[u8]) -> Result<()> {
+    let md = fs::metadata("/dev/vda1")?;
+    assert_eq!(md.file_type(), fs::FileType::BlockDevice);
+    assert_eq!(md.len(), fat.len() as u64);
+    let data = fs::read("/dev/vda1")?;
+    assert_eq!(data.len(), fat.len());
+    assert_eq!(data[..BLOCK_SIZE], fat[..BLOCK_SIZE]);
+    assert_eq!(fs::metadata("/dev/vda2")?.len(), 64 * BLOCK_SIZE as u64);
+    assert_eq!(fs::metadata("/dev/vda3")?.len(), fat.len() as u64);
+    assert_err!(fs::metadata("/dev/vda4"), NotFound);
+    Ok(())
+}
+
+#[test]
+fn test_fstype() {
+    println!("Testing filesystem types ...");
+
+    let fat = test_common::read_image().expect("failed to load disk image");
+    let disk = make_partitioned_disk(&fat);
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    // the type of the root filesystem is detected
+    let root = &fs::mounts()[0];
+    assert_eq!(
+        (root.source.as_str(), root.fstype.as_str()),
+        ("/dev/disk0p1", "vfat")
+    );
+
+    test_mount_disks().expect("test_mount_disks() failed");
+    test_remount().expect("test_remount() failed");
+    test_block_nodes(&fat).expect("test_block_nodes() failed");
+}
@@ -0,0 +1,106 @@
+#![cfg(all(feature = "ramfs", feature = "fatfs", not(feature = "myfs")))]
+
+#[macro_use]
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axfs::fops::{File, LockKind, OpenOptions, RangeLock};
+use axio::Result;
+
+fn open(path: &str, write: bool) -> Result<File> {
+    let mut opts = OpenOptions::new();
+    opts.read(true);
+    opts.write(write);
+    File::open(path, &opts)
+}
+
+fn test_flock(path: &str) -> Result<()> {
+    fs::write(path, "hello")?;
+    let (a, b) = (open(path, true)?, open(path, false)?);
+
+    a.flock(Some(LockKind::Exclusive), false)?;
+    assert_err!(b.flock(Some(LockKind::Shared), false), WouldBlock);
+    // converted to a shared lock
+    a.flock(Some(LockKind::Shared), false)?;
+    b.flock(Some(LockKind::Shared), false)?;
+    assert_err!(a.flock(Some(LockKind::Exclusive), false), WouldBlock);
+
+    // released when closed
+    drop(b);
+    a.flock(Some(LockKind::Exclusive), false)?;
+    a.flock(None, false)?;
+    drop(a);
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+fn test_ranges(path: &str) -> Result<()> {
+    fs::write(path, "hello")?;
+    let (a, b) = (open(path, true)?, open(path, true)?);
+
+    a.lock_range(Some(LockKind::Exclusive), 0, 10, false)?;
+    let lock = RangeLock {
+        kind: LockKind::Exclusive,
+        start: 0,
+        end: 10,
+    };
+    assert_eq!(b.test_lock(LockKind::Shared, 5, 15)?, Some(lock));
+    assert_eq!(a.test_lock(LockKind::Exclusive, 5, 15)?, None);
+    assert_err!(
+        b.lock_range(Some(LockKind::Shared), 5, 15, false),
+        WouldBlock
+    );
+    b.lock_range(Some(LockKind::Shared), 10, u64::MAX, false)?;
+    // whole-file locks are independent
+    b.flock(Some(LockKind::Exclusive), false)?;
+
+    // unlocking a part splits the lock
+    a.lock_range(None, 0, 5, false)?;
+    b.lock_range(Some(LockKind::Exclusive), 0, 5, false)?;
+    assert_eq!(
+        b.test_lock(LockKind::Shared, 0, 100)?,
+        Some(RangeLock { start: 5, ..lock })
+    );
+    assert_eq!(
+        a.test_lock(LockKind::Exclusive, 10, 11)?,
+        Some(RangeLock {
+            kind: LockKind::Shared,
+            start: 10,
+            end: u64::MAX,
+        })
+    );
+    drop(a);
+    assert_eq!(
+        open(path, true)?.test_lock(LockKind::Exclusive, 0, 8)?,
+        Some(RangeLock {
+            kind: LockKind::Exclusive,
+            start: 0,
+            end: 5,
+        })
+    );
+
+    // the lock types need the access
+    let c = open(path, false)?;
+    assert_err!(
+        c.lock_range(Some(LockKind::Exclusive), 100, 200, false),
+        PermissionDenied
+    );
+    drop((b, c));
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+#[test]
+fn test_lock() {
+    println!("Testing file locks ...");
+
+    let disk = test_common::make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_flock("/tmp/flock.txt").expect("test_flock() on tmpfs failed");
+    test_flock("/flock.txt").expect("test_flock() on FAT failed");
+    test_ranges("/tmp/ranges.txt").expect("test_ranges() on tmpfs failed");
+    test_ranges("/ranges.txt").expect("test_ranges() on FAT failed");
+}
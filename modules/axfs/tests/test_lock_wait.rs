@@ -0,0 +1,75 @@
+#![cfg(all(
+    feature = "ramfs",
+    feature = "fatfs",
+    feature = "multitask",
+    not(feature = "myfs")
+))]
+
+mod test_common;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axfs::fops::{File, LockKind, OpenOptions};
+use axio::Result;
+
+fn open(path: &str) -> Result<File> {
+    let mut opts = OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    File::open(path, &opts)
+}
+
+fn test_wait(path: &str) -> Result<()> {
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+    LOCKED.store(false, Ordering::Release);
+
+    fs::write(path, "hello")?;
+    let (a, b) = (open(path)?, open(path)?);
+    a.lock_range(Some(LockKind::Exclusive), 0, 10, false)?;
+
+    // the waiter doesn't borrow `b`, which is kept open here
+    let locks = b.locks();
+    let waiter = axtask::spawn(move || {
+        locks
+            .lock_range(Some(LockKind::Exclusive), 5, 15, true)
+            .expect("failed to wait for the lock");
+        LOCKED.store(true, Ordering::Release);
+    });
+    for _ in 0..10 {
+        axtask::yield_now();
+    }
+    assert!(!LOCKED.load(Ordering::Acquire));
+
+    // unlocking a part doesn't wake it up for good
+    a.lock_range(None, 0, 5, false)?;
+    for _ in 0..10 {
+        axtask::yield_now();
+    }
+    assert!(!LOCKED.load(Ordering::Acquire));
+
+    a.lock_range(None, 5, 10, false)?;
+    waiter.join();
+    assert!(LOCKED.load(Ordering::Acquire));
+    assert_eq!(
+        a.test_lock(LockKind::Shared, 0, 100)?.map(|l| l.start),
+        Some(5)
+    );
+
+    drop((a, b));
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+#[test]
+fn test_lock_wait() {
+    println!("Testing waiting for file locks ...");
+
+    let disk = test_common::make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_wait("/tmp/wait.txt").expect("test_wait() on tmpfs failed");
+    test_wait("/wait.txt").expect("test_wait() on FAT failed");
+}
@@ -0,0 +1,165 @@
+#![cfg(all(feature = "myfs", feature = "ramfs"))]
+
+#[macro_use]
+mod test_common;
+
+use std::sync::Arc;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api::{self as fs, File};
+use axfs::fops::{Disk, MyFileSystemIf};
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::VfsOps;
+use axio::{Result, Write};
+
+struct MyFileSystemIfImpl;
+
+#[crate_interface::impl_interface]
+impl MyFileSystemIf for MyFileSystemIfImpl {
+    fn new_myfs(_disk: Disk) -> Arc<dyn VfsOps> {
+        Arc::new(RamFileSystem::new())
+    }
+}
+
+fn is_mounted(target: &str) -> bool {
+    fs::mounts().iter().any(|m| m.target == target)
+}
+
+fn test_overlapping_mounts() -> Result<()> {
+    fs::create_dir("/mnt")?;
+    fs::create_dir("/mntfoo")?;
+    fs::write("/mntfoo/file.txt", "root")?;
+    fs::mount("tmpfs", "/mnt", "tmpfs")?;
+
+    // `/mntfoo` is not under `/mnt`
+    assert_eq!(fs::read_to_string("/mntfoo/file.txt")?, "root");
+    assert_err!(fs::metadata("/mnt/file.txt"), NotFound);
+
+    // a mount under another mount hides the directory it's mounted on
+    fs::create_dir("/mnt/a")?;
+    fs::write("/mnt/a/hidden.txt", "outer")?;
+    fs::mount("tmpfs", "/mnt/a", "tmpfs")?;
+    assert_err!(fs::metadata("/mnt/a/hidden.txt"), NotFound);
+    fs::write("/mnt/a/inner.txt", "inner")?;
+    assert_eq!(fs::read_to_string("/mnt/a/inner.txt")?, "inner");
+    assert_eq!(fs::read_dir("/mnt/a")?.count(), 1);
+
+    // `..` goes back across the mount boundaries
+    assert_eq!(fs::read_to_string("/mnt/a/../a/inner.txt")?, "inner");
+    assert_eq!(fs::read_to_string("/mnt/a/../../mntfoo/file.txt")?, "root");
+    assert_eq!(
+        fs::read_to_string("/mnt/a/../../../../mntfoo/./file.txt")?,
+        "root"
+    );
+    assert!(fs::read_dir("/mnt/a/..")?.any(|e| e.unwrap().file_name() == "a"));
+    assert_err!(fs::metadata("/mnt/a/inner.txt/.."), NotADirectory);
+
+    // relative to the current directory
+    fs::set_current_dir("/mnt/a/")?;
+    assert_eq!(fs::current_dir()?, "/mnt/a/");
+    assert_eq!(fs::read_to_string("inner.txt")?, "inner");
+    assert_eq!(fs::read_to_string("../../mntfoo/file.txt")?, "root");
+    assert_err!(fs::umount("/mnt/a", false), ResourceBusy);
+    fs::set_current_dir("..")?;
+    assert_eq!(fs::current_dir()?, "/mnt/");
+    fs::set_current_dir("/")?;
+
+    // mount points can't be mounted on twice, removed or renamed
+    assert_err!(fs::mount("tmpfs", "/mnt/a/", "tmpfs"), ResourceBusy);
+    assert_err!(fs::remove_dir("/mnt/a"), PermissionDenied);
+    assert_err!(fs::remove_dir("/mntfoo/../mnt"), PermissionDenied);
+    assert_err!(fs::rename("/mnt/a", "/mnt/b"), PermissionDenied);
+    assert_err!(
+        fs::rename("/mnt/a/inner.txt", "/mnt/moved.txt"),
+        InvalidInput
+    );
+
+    // unmount in the reverse order
+    assert_eq!(
+        fs::mounts()
+            .iter()
+            .filter(|m| m.target.starts_with("/mnt"))
+            .count(),
+        2
+    );
+    assert_err!(fs::umount("/mnt", false), ResourceBusy);
+    fs::umount("/mnt/a", false)?;
+    assert_eq!(fs::read_to_string("/mnt/a/hidden.txt")?, "outer");
+    fs::umount("/mnt", false)?;
+    assert_err!(fs::metadata("/mnt/a"), NotFound);
+    assert_err!(fs::umount("/mnt", false), InvalidInput);
+    assert_err!(fs::umount("/", false), ResourceBusy);
+    assert!(!is_mounted("/mnt"));
+
+    println!("test_overlapping_mounts() OK!");
+    Ok(())
+}
+
+fn test_busy_and_detach() -> Result<()> {
+    fs::create_dir("/busy")?;
+    fs::mount("tmpfs", "/busy", "tmpfs")?;
+    fs::create_dir("/busy/sub")?;
+    fs::mount("tmpfs", "/busy/sub", "tmpfs")?;
+
+    let mut file = File::create("/busy/file.txt")?;
+    fs::umount("/busy/sub", false)?;
+    assert_err!(fs::umount("/busy", false), ResourceBusy);
+
+    // detach it anyway, the opened file is still usable
+    fs::mount("tmpfs", "/busy/sub", "tmpfs")?;
+    fs::umount("/busy", true)?;
+    assert!(!is_mounted("/busy"));
+    assert!(!is_mounted("/busy/sub"));
+    assert_err!(fs::metadata("/busy/file.txt"), NotFound);
+    file.write_all(b"still here")?;
+    drop(file);
+
+    println!("test_busy_and_detach() OK!");
+    Ok(())
+}
+
+fn test_bind_mount() -> Result<()> {
+    fs::create_dir("/src")?;
+    fs::write("/src/x.txt", "x")?;
+    fs::create_dir("/dst")?;
+    assert_err!(fs::mount("tmpfs", "/src/x.txt", "tmpfs"), NotADirectory);
+    assert_err!(fs::mount("none", "/dst", "unknownfs"), Unsupported);
+
+    fs::bind_mount("/src", "/dst")?;
+    assert_eq!(fs::read_to_string("/dst/x.txt")?, "x");
+    fs::write("/dst/y.txt", "y")?;
+    assert_eq!(fs::read_to_string("/src/y.txt")?, "y");
+    let info = fs::mounts().into_iter().find(|m| m.target == "/dst");
+    assert_eq!(info.map(|m| m.source), Some("/src".into()));
+    assert_eq!(fs::read_to_string("/dst/../src/x.txt")?, "x");
+
+    fs::umount("/dst", false)?;
+    assert_err!(fs::metadata("/dst/x.txt"), NotFound);
+    assert_eq!(fs::read_to_string("/src/y.txt")?, "y");
+
+    // a bind mount of a subdirectory keeps the filesystem mounted
+    fs::mount("tmpfs", "/src", "tmpfs")?;
+    fs::create_dir("/src/sub")?;
+    fs::write("/src/sub/z.txt", "z")?;
+    fs::bind_mount("/src/sub", "/dst")?;
+    assert_err!(fs::umount("/src", false), ResourceBusy);
+    fs::umount("/src", true)?;
+    assert_eq!(fs::read_to_string("/dst/z.txt")?, "z");
+    fs::umount("/dst", false)?;
+
+    println!("test_bind_mount() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_mount() {
+    println!("Testing mount ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::default())); // dummy disk, actually not used.
+
+    test_overlapping_mounts().expect("test_overlapping_mounts() failed");
+    test_busy_and_detach().expect("test_busy_and_detach() failed");
+    test_bind_mount().expect("test_bind_mount() failed");
+}
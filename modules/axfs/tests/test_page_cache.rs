@@ -0,0 +1,107 @@
+#![cfg(not(feature = "myfs"))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axfs::fops::{File, OpenOptions, PAGE_SIZE};
+use axio::Result;
+
+fn open(path: &str, write: bool) -> Result<File> {
+    let mut opts = OpenOptions::new();
+    opts.read(true);
+    opts.write(write);
+    File::open(path, &opts)
+}
+
+/// Returns the number of block accesses so far.
+fn block_accesses() -> u64 {
+    let stats = fs::block_cache_stats();
+    stats.hits + stats.misses
+}
+
+fn test_read() -> Result<()> {
+    let data: Vec<u8> = (0..3 * PAGE_SIZE + 100).map(|i| (i % 251) as u8).collect();
+    fs::write("/cached.bin", &data)?;
+
+    let file = open("/cached.bin", false)?;
+    let mut buf = vec![0; data.len()];
+    assert_eq!(file.read_at(0, &mut buf)?, data.len());
+    assert!(buf == data);
+
+    // read again from the cache, even by another opened file
+    let accesses = block_accesses();
+    let other = open("/cached.bin", false)?;
+    let accesses_open = block_accesses();
+    buf.fill(0);
+    assert_eq!(file.read_at(0, &mut buf)?, data.len());
+    assert_eq!(other.read_at(PAGE_SIZE as u64 - 10, &mut buf[..20])?, 20);
+    assert!(buf[..20] == data[PAGE_SIZE - 10..PAGE_SIZE + 10]);
+    assert_eq!(other.read_at(data.len() as u64 - 50, &mut buf)?, 50);
+    println!(
+        "block accesses: {} to open, {} to read cached pages",
+        accesses_open - accesses,
+        block_accesses() - accesses_open
+    );
+    assert_eq!(block_accesses(), accesses_open);
+
+    // writes and truncation go through the cache
+    let writer = open("/cached.bin", true)?;
+    assert_eq!(writer.write_at(PAGE_SIZE as u64 - 2, b"abcd")?, 4);
+    assert_eq!(file.read_at(PAGE_SIZE as u64 - 2, &mut buf[..4])?, 4);
+    assert_eq!(&buf[..4], b"abcd");
+    writer.truncate(PAGE_SIZE as u64)?;
+    assert_eq!(file.read_at(0, &mut buf)?, PAGE_SIZE);
+    assert_eq!(&buf[PAGE_SIZE - 2..PAGE_SIZE], b"ab");
+    drop((file, other, writer));
+
+    // the cache of a removed file is dropped
+    fs::remove_file("/cached.bin")?;
+    fs::write("/cached.bin", "new")?;
+    assert_eq!(fs::read_to_string("/cached.bin")?, "new");
+    Ok(())
+}
+
+fn test_mapped_pages() -> Result<()> {
+    fs::write("/mapped.txt", "hello, world")?;
+    let file = open("/mapped.txt", true)?;
+
+    // a shared writable mapping of the first page
+    let cache = file.page_cache(true)?;
+    let page = cache.map_page(0, true)?;
+    let mapped = unsafe { core::slice::from_raw_parts_mut(page.as_ptr(), PAGE_SIZE) };
+    assert_eq!(&mapped[..12], b"hello, world");
+    assert!(mapped[12..].iter().all(|&b| b == 0));
+
+    // changes are seen by reads at once, and written back by `sync`
+    mapped[..5].copy_from_slice(b"HELLO");
+    assert_eq!(fs::read_to_string("/mapped.txt")?, "HELLO, world");
+    cache.sync()?;
+    file.write_at(7, b"WORLD")?;
+    assert_eq!(&mapped[..12], b"HELLO, WORLD");
+    cache.unmap_page(0);
+    drop((cache, file));
+    fs::sync()?;
+    assert_eq!(fs::read_to_string("/mapped.txt")?, "HELLO, WORLD");
+
+    // read-only files can't be mapped for writing
+    let file = open("/mapped.txt", false)?;
+    assert_eq!(
+        file.page_cache(true).err(),
+        Some(axio::Error::PermissionDenied)
+    );
+    assert!(file.page_cache(false).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_page_cache() {
+    println!("Testing page cache with ramdisk ...");
+
+    let disk = test_common::make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_read().expect("test_read() failed");
+    test_mapped_pages().expect("test_mapped_pages() failed");
+}
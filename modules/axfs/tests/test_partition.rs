@@ -0,0 +1,64 @@
+#![cfg(feature = "myfs")]
+
+mod test_common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axfs::fops::{Disk, MyFileSystemIf};
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::VfsOps;
+
+use test_common::{BLOCK_SIZE, write_mbr_entry};
+
+static ROOT_SIZE: AtomicU64 = AtomicU64::new(0);
+
+struct MyFileSystemIfImpl;
+
+#[crate_interface::impl_interface]
+impl MyFileSystemIf for MyFileSystemIfImpl {
+    fn new_myfs(disk: Disk) -> Arc<dyn VfsOps> {
+        ROOT_SIZE.store(disk.size(), Ordering::SeqCst);
+        Arc::new(RamFileSystem::new())
+    }
+}
+
+/// Makes a disk with two primary partitions (one of them is extended) and
+/// two logical partitions.
+fn make_partitioned_disk() -> RamDisk {
+    let mut img = vec![0u8; 64 * BLOCK_SIZE];
+    img[440..444].copy_from_slice(&0x1234abcdu32.to_le_bytes());
+    write_mbr_entry(&mut img, 0, 0, 0x83, 4, 16);
+    write_mbr_entry(&mut img, 0, 1, 0x05, 24, 40);
+    // the first entry is relative to the EBR, the second to the extended one
+    write_mbr_entry(&mut img, 24, 0, 0x83, 2, 10);
+    write_mbr_entry(&mut img, 24, 1, 0x05, 20, 20);
+    write_mbr_entry(&mut img, 44, 0, 0x0c, 2, 8);
+    RamDisk::from(&img)
+}
+
+#[test]
+fn test_partition() {
+    println!("Testing partition ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(make_partitioned_disk()));
+
+    // the root is on the first partition
+    assert_eq!(ROOT_SIZE.load(Ordering::SeqCst), 16 * BLOCK_SIZE as u64);
+    assert_eq!(fs::mounts()[0].source, "/dev/disk0p1");
+
+    let devs = fs::block_devices();
+    let paths: Vec<_> = devs.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        ["/dev/disk0p1", "/dev/disk0p5", "/dev/disk0p6", "/dev/disk0"]
+    );
+    let sizes: Vec<_> = devs.iter().map(|d| d.size / BLOCK_SIZE as u64).collect();
+    assert_eq!(sizes, [16, 10, 8, 64]);
+    assert_eq!(devs[1].uuid.as_deref(), Some("1234abcd-05"));
+    assert_eq!(devs[3].uuid, None);
+}
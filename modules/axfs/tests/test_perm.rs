@@ -0,0 +1,133 @@
+#![cfg(all(feature = "ramfs", feature = "fatfs", not(feature = "myfs")))]
+
+#[macro_use]
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api::{self as fs, AccessMode, DirBuilder, File, OpenOptions, Permissions};
+use axio::{Read, Result};
+
+fn perm_bits(path: &str) -> Result<u16> {
+    Ok(fs::metadata(path)?.permissions().bits())
+}
+
+fn test_modes() -> Result<()> {
+    fs::write("/tmp/default.txt", "hello")?;
+    let md = fs::metadata("/tmp/default.txt")?;
+    assert_eq!((md.uid(), md.gid()), (0, 0));
+    assert_eq!(md.permissions().bits(), 0o644);
+
+    fs::set_permissions("/tmp/default.txt", Permissions::from_bits_truncate(0o600))?;
+    assert_eq!(perm_bits("/tmp/default.txt")?, 0o600);
+    let file = File::open("/tmp/default.txt")?;
+    file.set_permissions(Permissions::from_bits_truncate(0o640))?;
+    assert_eq!(file.metadata()?.permissions().bits(), 0o640);
+
+    // the mode of new files is masked by the umask
+    let old = fs::set_umask(Permissions::from_bits_truncate(0o077));
+    assert_eq!(old.bits(), 0o022);
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .mode(0o666)
+        .open("/tmp/masked.txt")?;
+    fs::create_dir("/tmp/masked")?;
+    DirBuilder::new().mode(0o750).create("/tmp/masked2")?;
+    assert_eq!(perm_bits("/tmp/masked.txt")?, 0o600);
+    assert_eq!(perm_bits("/tmp/masked")?, 0o700);
+    assert_eq!(perm_bits("/tmp/masked2")?, 0o700);
+    fs::set_umask(old);
+    DirBuilder::new().mode(0o711).create("/tmp/mode")?;
+    assert_eq!(perm_bits("/tmp/mode")?, 0o711);
+
+    // FAT has no owners
+    fs::write("/perm.txt", "hello")?;
+    assert_eq!(fs::metadata("/perm.txt")?.uid(), 0);
+    assert_err!(
+        fs::set_permissions("/perm.txt", Permissions::from_bits_truncate(0o600)),
+        Unsupported
+    );
+    assert_err!(fs::chown("/perm.txt", Some(1000), None), Unsupported);
+    fs::remove_file("/perm.txt")?;
+    Ok(())
+}
+
+fn test_checks() -> Result<()> {
+    fs::create_dir("/tmp/root")?;
+    fs::write("/tmp/root/owned.txt", "root")?;
+    fs::create_dir("/tmp/user")?;
+    fs::chown("/tmp/user", Some(1000), Some(1000))?;
+    fs::write("/tmp/secret.txt", "secret")?;
+    fs::set_permissions("/tmp/secret.txt", Permissions::from_bits_truncate(0o600))?;
+    fs::write("/tmp/shared.txt", "shared")?;
+
+    fs::set_current_user(1000, 1000);
+    assert_eq!(fs::current_user(), (1000, 1000));
+
+    // the others bits apply to files of other users
+    assert_err!(File::open("/tmp/secret.txt"), PermissionDenied);
+    assert_err!(
+        fs::access("/tmp/secret.txt", AccessMode::READ),
+        PermissionDenied
+    );
+    assert_eq!(fs::access("/tmp/secret.txt", AccessMode::empty()), Ok(()));
+    assert_eq!(fs::read_to_string("/tmp/shared.txt")?, "shared");
+    assert_err!(fs::write("/tmp/shared.txt", "mine"), PermissionDenied);
+    assert_err!(
+        fs::access("/tmp/shared.txt", AccessMode::READ | AccessMode::WRITE),
+        PermissionDenied
+    );
+
+    // creating and removing entries needs the directory to be writable
+    assert_err!(fs::write("/tmp/root/file.txt", "test"), PermissionDenied);
+    assert_err!(fs::create_dir("/tmp/root/dir"), PermissionDenied);
+    assert_err!(fs::remove_file("/tmp/root/owned.txt"), PermissionDenied);
+    fs::write("/tmp/user/file.txt", "test")?;
+    let md = fs::metadata("/tmp/user/file.txt")?;
+    assert_eq!((md.uid(), md.gid()), (1000, 1000));
+
+    // only the owner can change the permissions, and the group to its own
+    assert_err!(
+        fs::set_permissions("/tmp/secret.txt", Permissions::from_bits_truncate(0o644)),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::chown("/tmp/user/file.txt", Some(0), None),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::chown("/tmp/user/file.txt", None, Some(0)),
+        PermissionDenied
+    );
+    fs::chown("/tmp/user/file.txt", None, Some(1000))?;
+
+    // searching a directory needs the execute permission
+    fs::set_permissions("/tmp/user", Permissions::from_bits_truncate(0o600))?;
+    assert_err!(fs::metadata("/tmp/user/file.txt"), PermissionDenied);
+    assert_err!(fs::set_current_dir("/tmp/user"), PermissionDenied);
+    fs::set_permissions("/tmp/user", Permissions::from_bits_truncate(0o700))?;
+    fs::remove_file("/tmp/user/file.txt")?;
+
+    // `root` can read and write everything
+    fs::set_current_user(0, 0);
+    let mut content = String::new();
+    File::open("/tmp/secret.txt")?.read_to_string(&mut content)?;
+    assert_eq!(content, "secret");
+    assert_err!(
+        fs::access("/tmp/secret.txt", AccessMode::EXECUTE),
+        PermissionDenied
+    );
+    Ok(())
+}
+
+#[test]
+fn test_perm() {
+    println!("Testing permissions ...");
+
+    let disk = test_common::make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_modes().expect("test_modes() failed");
+    test_checks().expect("test_checks() failed");
+}
@@ -0,0 +1,92 @@
+#![cfg(all(feature = "procfs", not(feature = "myfs")))]
+
+#[macro_use]
+mod test_common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axfs::proc::{self, ProcEntry};
+use axio::Result;
+
+static VALUE: AtomicUsize = AtomicUsize::new(1);
+
+fn read_value() -> String {
+    format!("{}\n", VALUE.load(Ordering::Relaxed))
+}
+
+fn write_value(value: &str) -> axerrno::AxResult {
+    let value = value
+        .trim()
+        .parse()
+        .map_err(|_| axerrno::AxError::InvalidInput)?;
+    VALUE.store(value, Ordering::Relaxed);
+    Ok(())
+}
+
+fn list_dir(path: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(path)? {
+        names.push(entry?.file_name());
+    }
+    Ok(names)
+}
+
+fn test_files() -> Result<()> {
+    proc::register_file("test/hello", || "hello\n".into())?;
+    proc::register_tunable("test/sys/value", read_value, write_value)?;
+
+    assert_eq!(fs::read_to_string("/proc/test/hello")?, "hello\n");
+    // the content is not generated for the size
+    assert_eq!(fs::metadata("/proc/test/hello")?.len(), 0);
+    assert_err!(fs::write("/proc/test/hello", "bye\n"), PermissionDenied);
+
+    assert_eq!(fs::read_to_string("/proc/test/sys/value")?, "1\n");
+    fs::write("/proc/test/sys/value", "42\n")?;
+    assert_eq!(fs::read_to_string("/proc/test/sys/value")?, "42\n");
+    assert_err!(fs::write("/proc/test/sys/value", "x"), InvalidInput);
+    assert_eq!(VALUE.load(Ordering::Relaxed), 42);
+
+    assert_eq!(list_dir("/proc/test")?, ["hello", "sys"]);
+    assert_err!(fs::read_to_string("/proc/test/none"), NotFound);
+    Ok(())
+}
+
+fn test_generated_dirs() -> Result<()> {
+    proc::register_dir("", || {
+        (1..=VALUE.load(Ordering::Relaxed) % 4)
+            .map(|i| {
+                let stat = ProcEntry::file(move || format!("{} stat\n", i));
+                let dir = ProcEntry::dir(move || vec![("stat".into(), stat.clone())]);
+                (i.to_string(), dir)
+            })
+            .collect()
+    })?;
+
+    let names = list_dir("/proc")?;
+    assert!(names.iter().any(|n| n == "mounts"));
+    assert!(names.ends_with(&["1".into(), "2".into()]));
+    assert_eq!(list_dir("/proc/2")?, ["stat"]);
+    assert_eq!(fs::read_to_string("/proc/2/stat")?, "2 stat\n");
+
+    // the entries are generated on each lookup
+    VALUE.store(1, Ordering::Relaxed);
+    assert_err!(fs::read_to_string("/proc/2/stat"), NotFound);
+    Ok(())
+}
+
+#[test]
+fn test_procfs() {
+    println!("Testing procfs ...");
+
+    let disk = test_common::make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    let mounts = fs::read_to_string("/proc/mounts").expect("failed to read /proc/mounts");
+    assert!(mounts.contains("proc /proc proc rw 0 0\n"));
+
+    test_files().expect("test_files() failed");
+    test_generated_dirs().expect("test_generated_dirs() failed");
+}
@@ -0,0 +1,49 @@
+#![cfg(all(feature = "ramfs", feature = "fatfs", not(feature = "myfs")))]
+
+#[macro_use]
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api::{self as fs, FileType, Permissions};
+use axio::Result;
+
+fn test_socket() -> Result<()> {
+    // sockets are kept as regular files with the type in memory
+    fs::create_socket("/tmp/sock")?;
+    let md = fs::symlink_metadata("/tmp/sock")?;
+    assert_eq!(md.file_type(), FileType::Socket);
+    assert_eq!(md.permissions().bits(), 0o755);
+    assert_err!(fs::create_socket("/tmp/sock"), AlreadyExists);
+    fs::set_permissions("/tmp/sock", Permissions::from_bits_truncate(0o700))?;
+    assert_eq!(fs::metadata("/tmp/sock")?.file_type(), FileType::Socket);
+
+    // the type is kept across renames and bind mounts
+    fs::create_dir("/tmp/dir")?;
+    fs::rename("/tmp/sock", "/tmp/dir/sock")?;
+    assert_eq!(fs::metadata("/tmp/dir/sock")?.file_type(), FileType::Socket);
+    fs::create_dir("/mnt")?;
+    fs::bind_mount("/tmp/dir", "/mnt")?;
+    assert_eq!(fs::metadata("/mnt/sock")?.file_type(), FileType::Socket);
+    fs::umount("/mnt", false)?;
+
+    fs::remove_file("/tmp/dir/sock")?;
+    assert_err!(fs::metadata("/tmp/dir/sock"), NotFound);
+    // a regular file created in its place is not a socket
+    fs::write("/tmp/dir/sock", "")?;
+    assert_eq!(fs::metadata("/tmp/dir/sock")?.file_type(), FileType::File);
+
+    // FAT has no special files
+    assert_err!(fs::create_socket("/sock"), Unsupported);
+    Ok(())
+}
+
+#[test]
+fn test_special() {
+    println!("Testing special files ...");
+
+    let disk = test_common::make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_socket().expect("test_socket() failed");
+}
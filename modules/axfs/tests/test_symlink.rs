@@ -0,0 +1,139 @@
+#![cfg(all(feature = "myfs", feature = "ramfs"))]
+
+#[macro_use]
+mod test_common;
+
+use std::sync::Arc;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axfs::fops::{Disk, MyFileSystemIf};
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::VfsOps;
+use axio::Result;
+
+struct MyFileSystemIfImpl;
+
+#[crate_interface::impl_interface]
+impl MyFileSystemIf for MyFileSystemIfImpl {
+    fn new_myfs(_disk: Disk) -> Arc<dyn VfsOps> {
+        Arc::new(RamFileSystem::new())
+    }
+}
+
+fn test_follow() -> Result<()> {
+    fs::create_dir("/tmp/dir")?;
+    fs::write("/tmp/dir/file.txt", "hello")?;
+
+    // relative targets are resolved from the directory containing the link
+    fs::symlink("dir/file.txt", "/tmp/rel")?;
+    fs::symlink("/tmp/dir", "/tmp/abs")?;
+    fs::symlink("../tmp/dir/./file.txt", "/tmp/dotdot")?;
+    assert_eq!(fs::read_to_string("/tmp/rel")?, "hello");
+    assert_eq!(fs::read_to_string("/tmp/abs/file.txt")?, "hello");
+    assert_eq!(fs::read_to_string("/tmp/dotdot")?, "hello");
+    assert!(fs::metadata("/tmp/abs/")?.is_dir());
+    assert_eq!(fs::read_dir("/tmp/abs")?.count(), 1);
+    let ty = |name: &str| -> Result<_> {
+        let mut entries = fs::read_dir("/tmp")?;
+        Ok(entries
+            .find_map(|e| e.ok().filter(|e| e.file_name() == name))
+            .map(|e| e.file_type()))
+    };
+    assert!(ty("rel")?.is_some_and(|t| t.is_symlink()));
+    assert!(ty("abs")?.is_some_and(|t| t.is_symlink()));
+    assert!(ty("dir")?.is_some_and(|t| t.is_dir()));
+
+    // `..` after a link goes to the parent of its target
+    fs::create_dir("/tmp/dir/sub")?;
+    fs::symlink("dir/sub", "/tmp/sub")?;
+    assert_eq!(fs::read_to_string("/tmp/sub/../file.txt")?, "hello");
+
+    // a link to a link, and the current directory through a link
+    fs::symlink("abs", "/tmp/abs2")?;
+    assert_eq!(fs::read_to_string("/tmp/abs2/file.txt")?, "hello");
+    fs::set_current_dir("/tmp/abs2")?;
+    assert_eq!(fs::current_dir()?, "/tmp/dir/");
+    assert_eq!(fs::read_to_string("file.txt")?, "hello");
+    fs::set_current_dir("/")?;
+
+    // writes go to the target, and dangling links create it
+    fs::write("/tmp/rel", "world")?;
+    assert_eq!(fs::read_to_string("/tmp/dir/file.txt")?, "world");
+    fs::symlink("dir/new.txt", "/tmp/dangling")?;
+    assert_err!(fs::metadata("/tmp/dangling"), NotFound);
+    fs::write("/tmp/dangling", "new")?;
+    assert_eq!(fs::read_to_string("/tmp/dir/new.txt")?, "new");
+    Ok(())
+}
+
+fn test_lstat_and_readlink() -> Result<()> {
+    let meta = fs::symlink_metadata("/tmp/rel")?;
+    assert!(meta.is_symlink());
+    assert_eq!(meta.len(), "dir/file.txt".len() as u64);
+    assert!(fs::metadata("/tmp/rel")?.is_file());
+    assert!(!fs::symlink_metadata("/tmp/dir")?.is_symlink());
+    // only the last component is not followed
+    assert!(fs::symlink_metadata("/tmp/abs/file.txt")?.is_file());
+    assert!(fs::symlink_metadata("/tmp/abs/")?.is_dir());
+
+    assert_eq!(fs::read_link("/tmp/rel")?, "dir/file.txt");
+    assert_eq!(fs::read_link("/tmp/abs2")?, "abs");
+    assert_err!(fs::read_link("/tmp/dir"), InvalidInput);
+    assert_err!(fs::read_link("/tmp/nonexistent"), NotFound);
+
+    assert_err!(fs::symlink("dir", "/tmp/rel"), AlreadyExists);
+    assert_err!(fs::symlink("dir", "/tmp/dangling"), AlreadyExists);
+    assert_err!(fs::symlink("", "/tmp/empty"), NotFound);
+    // the main filesystem does not support symbolic links
+    assert_err!(fs::symlink("/tmp", "/link"), Unsupported);
+    Ok(())
+}
+
+fn test_loops() -> Result<()> {
+    fs::symlink("loop", "/tmp/loop")?;
+    fs::symlink("pong", "/tmp/ping")?;
+    fs::symlink("ping", "/tmp/pong")?;
+    assert_err!(fs::metadata("/tmp/loop"), InvalidData);
+    assert_err!(fs::read_to_string("/tmp/ping"), InvalidData);
+    assert_err!(fs::metadata("/tmp/pong/file.txt"), InvalidData);
+    assert!(fs::symlink_metadata("/tmp/loop")?.is_symlink());
+    assert_eq!(fs::read_link("/tmp/ping")?, "pong");
+    assert!(fs::is_symlink_loop("/tmp/pong/file.txt"));
+    assert!(!fs::is_symlink_loop("/tmp/rel"));
+    assert!(!fs::is_symlink_loop("/tmp/nonexistent"));
+
+    // creating the target of a loop
+    assert_err!(fs::write("/tmp/loop", "x"), InvalidData);
+    assert!(fs::is_symlink_loop("/tmp/loop"));
+    Ok(())
+}
+
+fn test_remove() -> Result<()> {
+    // removing a link does not touch the target
+    fs::remove_file("/tmp/rel")?;
+    assert_err!(fs::symlink_metadata("/tmp/rel"), NotFound);
+    assert_eq!(fs::read_to_string("/tmp/dir/file.txt")?, "world");
+    fs::remove_file("/tmp/loop")?;
+    assert_err!(fs::symlink_metadata("/tmp/loop"), NotFound);
+    // links to directories are removed as files
+    assert_err!(fs::remove_dir("/tmp/abs"), NotADirectory);
+    fs::remove_file("/tmp/abs2")?;
+    assert!(fs::metadata("/tmp/dir")?.is_dir());
+    assert_eq!(fs::read_to_string("/tmp/abs/file.txt")?, "world");
+    Ok(())
+}
+
+#[test]
+fn test_symlink() {
+    println!("Testing symlink ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::default())); // dummy disk, actually not used.
+
+    test_follow().expect("test_follow() failed");
+    test_lstat_and_readlink().expect("test_lstat_and_readlink() failed");
+    test_loops().expect("test_loops() failed");
+    test_remove().expect("test_remove() failed");
+}
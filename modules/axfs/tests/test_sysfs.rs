@@ -0,0 +1,59 @@
+#![cfg(all(feature = "sysfs", not(feature = "myfs")))]
+
+#[macro_use]
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axfs::sys::{self, SysEntry};
+use axio::Result;
+
+fn list_dir(path: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(path)? {
+        names.push(entry?.file_name());
+    }
+    Ok(names)
+}
+
+fn test_sys_entries() -> Result<()> {
+    sys::register_file("kernel/platform/arch", || "x86_64\n".into())?;
+    sys::register_dir("bus/pci/devices", || {
+        let dev =
+            SysEntry::dir(|| vec![("driver".into(), SysEntry::file(|| "virtio-blk\n".into()))]);
+        vec![("0000:00:01.0".into(), dev)]
+    })?;
+
+    assert_eq!(list_dir("/sys")?, ["bus", "kernel"]);
+    assert_eq!(fs::read_to_string("/sys/kernel/platform/arch")?, "x86_64\n");
+    assert_err!(
+        fs::write("/sys/kernel/platform/arch", "riscv64\n"),
+        PermissionDenied
+    );
+    assert_eq!(list_dir("/sys/bus/pci/devices")?, ["0000:00:01.0"]);
+    assert_eq!(
+        fs::read_to_string("/sys/bus/pci/devices/0000:00:01.0/driver")?,
+        "virtio-blk\n"
+    );
+    Ok(())
+}
+
+fn test_mount_sysfs() -> Result<()> {
+    fs::create_dir("/mnt")?;
+    fs::mount("sysfs", "/mnt", "sysfs")?;
+    assert_eq!(fs::read_to_string("/mnt/kernel/platform/arch")?, "x86_64\n");
+    fs::umount("/mnt", false)?;
+    Ok(())
+}
+
+#[test]
+fn test_sysfs() {
+    println!("Testing sysfs ...");
+
+    let disk = test_common::make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_sys_entries().expect("test_sys_entries() failed");
+    test_mount_sysfs().expect("test_mount_sysfs() failed");
+}
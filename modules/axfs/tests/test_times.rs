@@ -0,0 +1,106 @@
+#![cfg(all(feature = "ramfs", feature = "fatfs", not(feature = "myfs")))]
+
+#[macro_use]
+mod test_common;
+
+use std::time::Duration;
+
+use axdriver::AxDeviceContainer;
+use axfs::api::{self as fs, File, Permissions, TimeUpdate};
+use axio::{Result, Write};
+
+fn secs(secs: u64) -> TimeUpdate {
+    TimeUpdate::At(Duration::from_secs(secs))
+}
+
+fn test_memory() -> Result<()> {
+    // the wall time is always zero on the host
+    fs::write("/tmp/times.txt", "hello")?;
+    fs::set_times("/tmp/times.txt", secs(3000), secs(2000))?;
+    let md = fs::metadata("/tmp/times.txt")?;
+    assert_eq!(md.accessed(), Duration::from_secs(3000));
+    assert_eq!(md.modified(), Duration::from_secs(2000));
+    assert_eq!(md.changed(), Duration::ZERO);
+
+    // reading doesn't update a recent access time later than the others
+    fs::read_to_string("/tmp/times.txt")?;
+    let md = fs::metadata("/tmp/times.txt")?;
+    assert_eq!(md.accessed(), Duration::from_secs(3000));
+
+    // writing updates the modification time
+    let mut file = File::options().write(true).open("/tmp/times.txt")?;
+    file.write_all(b"world")?;
+    assert_eq!(file.metadata()?.modified(), Duration::ZERO);
+    fs::set_times("/tmp/times.txt", TimeUpdate::Omit, secs(5000))?;
+    let md = file.metadata()?;
+    assert_eq!(md.accessed(), Duration::from_secs(3000));
+    assert_eq!(md.modified(), Duration::from_secs(5000));
+
+    // other users can only set the current time, if they can write it
+    fs::set_current_user(1000, 1000);
+    assert_err!(
+        fs::set_times("/tmp/times.txt", TimeUpdate::Now, TimeUpdate::Now),
+        PermissionDenied
+    );
+    fs::set_current_user(0, 0);
+    fs::set_permissions("/tmp/times.txt", Permissions::from_bits_truncate(0o666))?;
+    fs::set_current_user(1000, 1000);
+    fs::set_times("/tmp/times.txt", TimeUpdate::Now, TimeUpdate::Now)?;
+    assert_err!(
+        fs::set_times("/tmp/times.txt", secs(0), TimeUpdate::Omit),
+        PermissionDenied
+    );
+    fs::set_current_user(0, 0);
+    let md = fs::metadata("/tmp/times.txt")?;
+    assert_eq!(md.modified(), Duration::ZERO);
+    // changing the times keeps the changed permissions
+    assert_eq!(md.permissions().bits(), 0o666);
+    Ok(())
+}
+
+fn test_fat() -> Result<()> {
+    // FAT stores the modification times in 2 seconds
+    let file = File::create("/times.txt")?;
+    file.set_modified(Duration::from_secs(1_000_000_001))?;
+    let md = file.metadata()?;
+    assert_eq!(md.modified(), Duration::from_secs(1_000_000_000));
+    assert_eq!(md.changed(), md.modified());
+    drop(file);
+
+    // and dates before 1980 are clamped
+    let file = File::open("/times.txt")?;
+    file.set_modified(Duration::ZERO)?;
+    assert_eq!(
+        file.metadata()?.modified(),
+        Duration::from_secs(315_532_800)
+    );
+    drop(file);
+
+    // the times of directories can't be changed
+    assert_err!(
+        fs::set_times("/", TimeUpdate::Now, TimeUpdate::Now),
+        Unsupported
+    );
+    fs::remove_file("/times.txt")?;
+
+    // the times are found by the short names as well
+    let file = File::create("/long times name.txt")?;
+    file.set_modified(Duration::from_secs(1_000_000_000))?;
+    drop(file);
+    let md = fs::metadata("/LONGTI~1.TXT")?;
+    assert_eq!(md.modified(), Duration::from_secs(1_000_000_000));
+    fs::remove_file("/long times name.txt")?;
+    Ok(())
+}
+
+#[test]
+fn test_times() {
+    println!("Testing file times ...");
+
+    let disk = test_common::make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_memory().expect("test_memory() failed");
+    test_fat().expect("test_fat() failed");
+}
@@ -0,0 +1,180 @@
+//! Kernel command line passed by the bootloader.
+//!
+//! It's read from `/chosen/bootargs` of the device tree on riscv64 and
+//! aarch64, from the multiboot information on x86_64, and from the string
+//! passed in `a1` by the direct kernel boot of QEMU on loongarch64. It's
+//! copied early at boot, before the memory holding it may be reused.
+//!
+//! The command line consists of parameters separated by spaces, either `key`
+//! or `key=value`.
+
+use lazyinit::LazyInit;
+
+use crate::mem::phys_to_virt;
+
+/// The maximum length of the command line, the rest is ignored.
+const MAX_CMDLINE_LEN: usize = 1024;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+/// Multiboot information flag: `cmdline` is valid.
+const MULTIBOOT_INFO_CMDLINE: u32 = 1 << 2;
+
+struct Cmdline {
+    buf: [u8; MAX_CMDLINE_LEN],
+    len: usize,
+}
+
+static CMDLINE: LazyInit<Cmdline> = LazyInit::new();
+
+/// Returns the kernel command line, empty if none is passed.
+pub fn cmdline() -> &'static str {
+    match CMDLINE.get() {
+        // checked in `set`
+        Some(c) => unsafe { core::str::from_utf8_unchecked(&c.buf[..c.len]) },
+        None => "",
+    }
+}
+
+/// Returns the value of the parameter `key=value`. If the parameter is given
+/// more than once, the last one takes effect.
+pub fn get(key: &str) -> Option<&'static str> {
+    cmdline()
+        .split_ascii_whitespace()
+        .filter_map(|param| param.split_once('='))
+        .filter(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+        .next_back()
+}
+
+fn set(bytes: &[u8]) {
+    let bytes = &bytes[..bytes.len().min(MAX_CMDLINE_LEN)];
+    let len = match core::str::from_utf8(bytes) {
+        Ok(s) => s.len(),
+        Err(e) => e.valid_up_to(),
+    };
+    let mut buf = [0; MAX_CMDLINE_LEN];
+    buf[..len].copy_from_slice(&bytes[..len]);
+    CMDLINE.init_once(Cmdline { buf, len });
+}
+
+/// Returns the bytes of the NUL-terminated string at `paddr`, up to
+/// [`MAX_CMDLINE_LEN`].
+unsafe fn c_str<'a>(paddr: usize) -> &'a [u8] {
+    let ptr = phys_to_virt(pa!(paddr)).as_ptr();
+    let mut len = 0;
+    while len < MAX_CMDLINE_LEN && unsafe { ptr.add(len).read() } != 0 {
+        len += 1;
+    }
+    unsafe { core::slice::from_raw_parts(ptr, len) }
+}
+
+fn be32(buf: &[u8], off: usize) -> Option<u32> {
+    let bytes = buf.get(off..off + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Returns the bytes of a NUL-terminated string at `off` of `buf`.
+fn fdt_str(buf: &[u8], off: usize) -> Option<&[u8]> {
+    let rest = buf.get(off..)?;
+    let len = rest.iter().position(|&b| b == 0)?;
+    Some(&rest[..len])
+}
+
+/// Finds the `bootargs` property of the `/chosen` node in a flattened device
+/// tree.
+fn fdt_bootargs(fdt: &[u8]) -> Option<&[u8]> {
+    let structs = fdt.get(be32(fdt, 8)? as usize..)?;
+    let strings = fdt.get(be32(fdt, 12)? as usize..)?;
+    let align = |off: usize| (off + 3) & !3;
+
+    let mut off = 0;
+    let mut depth = 0;
+    let mut in_chosen = false;
+    loop {
+        let token = be32(structs, off)?;
+        off += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = fdt_str(structs, off)?;
+                off = align(off + name.len() + 1);
+                depth += 1;
+                in_chosen = depth == 2 && name == b"chosen";
+            }
+            FDT_END_NODE => {
+                if in_chosen || depth <= 1 {
+                    return None;
+                }
+                depth -= 1;
+            }
+            FDT_PROP => {
+                let len = be32(structs, off)? as usize;
+                let name = fdt_str(strings, be32(structs, off + 4)? as usize)?;
+                let value = structs.get(off + 8..off + 8 + len)?;
+                off = align(off + 8 + len);
+                if in_chosen && depth == 2 && name == b"bootargs" {
+                    return fdt_str(value, 0).or(Some(value));
+                }
+            }
+            FDT_NOP => {}
+            _ => return None,
+        }
+    }
+}
+
+/// Reads the command line from the device tree at `dtb`.
+///
+/// # Safety
+///
+/// `dtb` must be 0 or the physical address of a device tree in the memory
+/// mapped at boot.
+#[allow(dead_code)]
+pub(crate) unsafe fn init_from_dtb(dtb: usize) {
+    if dtb == 0 {
+        return;
+    }
+    let ptr = phys_to_virt(pa!(dtb)).as_ptr();
+    let header = unsafe { core::slice::from_raw_parts(ptr, 8) };
+    // it's too early to log an invalid one
+    if be32(header, 0) != Some(FDT_MAGIC) {
+        return;
+    }
+    let size = be32(header, 4).unwrap() as usize;
+    let fdt = unsafe { core::slice::from_raw_parts(ptr, size) };
+    if let Some(bootargs) = fdt_bootargs(fdt) {
+        set(bootargs);
+    }
+}
+
+/// Reads the command line from the multiboot information at `mbi`.
+///
+/// # Safety
+///
+/// `mbi` must be the physical address of the multiboot information passed by
+/// the bootloader.
+#[allow(dead_code)]
+pub(crate) unsafe fn init_from_multiboot(mbi: usize) {
+    let info = phys_to_virt(pa!(mbi)).as_ptr() as *const u32;
+    let flags = unsafe { info.read_unaligned() };
+    if flags & MULTIBOOT_INFO_CMDLINE != 0 {
+        let cmdline = unsafe { info.add(4).read_unaligned() };
+        set(unsafe { c_str(cmdline as usize) });
+    }
+}
+
+/// Reads the command line from the NUL-terminated string at `paddr`.
+///
+/// # Safety
+///
+/// `paddr` must be 0 or the physical address of a string in the memory mapped
+/// at boot.
+#[allow(dead_code)]
+pub(crate) unsafe fn init_from_str(paddr: usize) {
+    if paddr != 0 {
+        set(unsafe { c_str(paddr) });
+    }
+}
@@ -1,5 +1,7 @@
 //! Interrupt management.
 
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use handler_table::HandlerTable;
 
 use crate::platform::irq::{MAX_IRQ_COUNT, dispatch_irq};
@@ -12,10 +14,31 @@ pub type IrqHandler = handler_table::Handler;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// The number of times each IRQ occurred, on all CPUs.
+static IRQ_COUNTS: [AtomicU64; MAX_IRQ_COUNT] = [const { AtomicU64::new(0) }; MAX_IRQ_COUNT];
+
+/// Returns the IRQs that have occurred and the number of times each one
+/// occurred, in the order of the IRQ numbers.
+pub fn irq_counts() -> impl Iterator<Item = (usize, u64)> {
+    IRQ_COUNTS
+        .iter()
+        .map(|count| count.load(Ordering::Relaxed))
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+}
+
+/// Counts an occurrence of the IRQ.
+pub(crate) fn count_irq(irq_num: usize) {
+    if let Some(count) = IRQ_COUNTS.get(irq_num) {
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
 /// Platform-independent IRQ dispatching.
 #[allow(dead_code)]
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
     trace!("IRQ {}", irq_num);
+    count_irq(irq_num);
     if !IRQ_HANDLER_TABLE.handle(irq_num) {
         warn!("Unhandled IRQ {}", irq_num);
     }
@@ -21,6 +21,9 @@
 //! - `paging`: Enable page table manipulation.
 //! - `irq`: Enable interrupt handling support.
 //!
+//! The [`random`] module provides a kernel CSPRNG for all platforms, and the
+//! [`cmdline`] module the kernel command line passed by the bootloader.
+//!
 //! [ArceOS]: https://github.com/arceos-org/arceos
 //! [cargo test]: https://doc.rust-lang.org/cargo/guide/tests.html
 
@@ -42,8 +45,10 @@ mod platform;
 pub mod trap;
 
 pub mod arch;
+pub mod cmdline;
 pub mod cpu;
 pub mod mem;
+pub mod random;
 pub mod time;
 
 #[cfg(feature = "tls")]
@@ -27,6 +27,7 @@ unsafe extern "C" {
 
 pub(crate) unsafe extern "C" fn rust_entry(cpu_id: usize, dtb: usize) {
     crate::mem::clear_bss();
+    unsafe { crate::cmdline::init_from_dtb(dtb) };
     crate::cpu::init_primary(cpu_id);
     dw_apb_uart::init_early();
     super::aarch64_common::generic_timer::init_early();
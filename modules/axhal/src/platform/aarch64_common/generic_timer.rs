@@ -6,6 +6,9 @@ use tock_registers::interfaces::{Readable, Writeable};
 
 static mut CNTPCT_TO_NANOS_RATIO: Ratio = Ratio::zero();
 static mut NANOS_TO_CNTPCT_RATIO: Ratio = Ratio::zero();
+/// The name of the clock source, as Linux names it.
+pub const CLOCK_SOURCE: &str = "arch_sys_counter";
+
 /// RTC wall time offset in nanoseconds at monotonic time base.
 static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
 
@@ -33,6 +33,7 @@ unsafe extern "C" {
 
 pub(crate) unsafe extern "C" fn rust_entry(cpu_id: usize, dtb: usize) {
     crate::mem::clear_bss();
+    unsafe { crate::cmdline::init_from_dtb(dtb) };
     let cpu_id = cpu_hard_id_to_logic_id(cpu_id);
     crate::arch::write_page_table_root0(0.into()); // disable low address access
     crate::cpu::init_primary(cpu_id);
@@ -35,6 +35,9 @@ pub mod mem {
 }
 
 pub mod time {
+    /// The name of the clock source.
+    pub const CLOCK_SOURCE: &str = "dummy";
+
     /// Returns the current clock time in hardware ticks.
     pub fn current_ticks() -> u64 {
         0
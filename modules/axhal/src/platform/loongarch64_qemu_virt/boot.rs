@@ -51,6 +51,9 @@ unsafe fn init_mmu() {
 #[unsafe(link_section = ".text.boot")]
 unsafe extern "C" fn _start() -> ! {
     core::arch::naked_asm!("
+        move        $s0, $a0            # save the boot flag
+        move        $s1, $a1            # save the command line pointer
+
         ori         $t0, $zero, 0x1     # CSR_DMW1_PLV0
         lu52i.d     $t0, $t0, -2048     # UC, PLV0, 0x8000 xxxx xxxx xxxx
         csrwr       $t0, 0x180          # LOONGARCH_CSR_DMWIN0
@@ -68,6 +71,8 @@ unsafe extern "C" fn _start() -> ! {
         bl          {init_mmu}          # setup boot page table and enabel MMU
 
         csrrd       $a0, 0x20           # cpuid
+        move        $a1, $s0            # boot flag
+        move        $a2, $s1            # command line
         la.global   $t0, {entry}
         jirl        $zero, $t0, 0",
         boot_stack_size = const TASK_STACK_SIZE,
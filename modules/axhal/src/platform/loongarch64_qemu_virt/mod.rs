@@ -24,9 +24,15 @@ unsafe extern "C" {
 
 /// Rust temporary entry point
 ///
-/// This function will be called after assembly boot stage.
-unsafe extern "C" fn rust_entry(cpu_id: usize) {
+/// This function will be called after assembly boot stage. `boot_flag` and
+/// `cmdline` are `a0` and `a1` set by the direct kernel boot of QEMU, where
+/// `cmdline` is the physical address of the command line if `boot_flag` is
+/// not 0.
+unsafe extern "C" fn rust_entry(cpu_id: usize, boot_flag: usize, cmdline: usize) {
     crate::mem::clear_bss();
+    if boot_flag != 0 {
+        unsafe { crate::cmdline::init_from_str(cmdline) };
+    }
     super::console::init_early();
     crate::cpu::init_primary(cpu_id);
     super::time::init_primary();
@@ -3,6 +3,9 @@ use loongArch64::time::Time;
 
 static NANOS_PER_TICK: LazyInit<u64> = LazyInit::new();
 
+/// The name of the clock source, as Linux names it.
+pub const CLOCK_SOURCE: &str = "Constant";
+
 /// RTC wall time offset in nanoseconds at monotonic time base.
 static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
 
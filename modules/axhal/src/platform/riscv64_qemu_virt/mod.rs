@@ -19,6 +19,7 @@ unsafe extern "C" {
 
 unsafe extern "C" fn rust_entry(cpu_id: usize, dtb: usize) {
     crate::mem::clear_bss();
+    unsafe { crate::cmdline::init_from_dtb(dtb) };
     crate::cpu::init_primary(cpu_id);
     #[cfg(feature = "uspace")]
     riscv::register::sstatus::set_sum();
@@ -1,6 +1,8 @@
 use riscv::register::time;
 
 const NANOS_PER_TICK: u64 = crate::time::NANOS_PER_SEC / axconfig::devices::TIMER_FREQUENCY as u64;
+/// The name of the clock source, as Linux names it.
+pub const CLOCK_SOURCE: &str = "riscv_clocksource";
 /// RTC wall time offset in nanoseconds at monotonic time base.
 static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
 
@@ -0,0 +1,187 @@
+//! Kernel random number generator.
+//!
+//! It is a ChaCha20 based CSPRNG, seeded from the hardware random number
+//! generator if available, mixed with the jitter of the system timer. The
+//! hardware sources are `RDRAND` on x86_64, `RNDR` on aarch64 with FEAT_RNG,
+//! and the `seed` CSR on riscv64 built with the Zkr extension. A warning is
+//! logged if there is none, as the jitter is nearly deterministic on
+//! emulators.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kspin::SpinNoIrq;
+use rand_chacha::ChaCha20Rng;
+use rand_core::{RngCore, SeedableRng};
+
+use crate::time::{current_ticks, wall_time_nanos};
+
+/// Number of random bytes generated before the CSPRNG is reseeded.
+const RESEED_INTERVAL: usize = 1 << 20;
+
+struct KernelRng {
+    rng: Option<ChaCha20Rng>,
+    generated: usize,
+}
+
+static RNG: SpinNoIrq<KernelRng> = SpinNoIrq::new(KernelRng {
+    rng: None,
+    generated: 0,
+});
+
+#[inline]
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn hw_random() -> Option<u64> {
+    #[target_feature(enable = "rdrand")]
+    unsafe fn rdrand64() -> Option<u64> {
+        let mut val = 0;
+        // retry a few times as recommended by Intel
+        for _ in 0..10 {
+            if unsafe { core::arch::x86_64::_rdrand64_step(&mut val) } == 1 {
+                return Some(val);
+            }
+        }
+        None
+    }
+
+    let has_rdrand = raw_cpuid::CpuId::new()
+        .get_feature_info()
+        .is_some_and(|info| info.has_rdrand());
+    if has_rdrand {
+        unsafe { rdrand64() }
+    } else {
+        None
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn hw_random() -> Option<u64> {
+    use core::arch::asm;
+
+    let isar0: u64;
+    unsafe { asm!("mrs {}, ID_AA64ISAR0_EL1", out(reg) isar0) };
+    if (isar0 >> 60) & 0xf == 0 {
+        return None; // no FEAT_RNG
+    }
+    for _ in 0..10 {
+        let (val, ok): (u64, u64);
+        // RNDR, which clears the Z flag on success
+        unsafe {
+            asm!(
+                "mrs {val}, s3_3_c2_c4_0",
+                "cset {ok}, ne",
+                val = out(reg) val,
+                ok = out(reg) ok,
+                options(nomem, nostack),
+            )
+        };
+        if ok != 0 {
+            return Some(val);
+        }
+    }
+    None
+}
+
+#[cfg(all(target_arch = "riscv64", target_feature = "zkr"))]
+fn hw_random() -> Option<u64> {
+    const SEED_CSR_ES16: usize = 0b10;
+
+    // each read of the `seed` CSR gives 16 bits of entropy when it's ready
+    let mut val = 0u64;
+    let mut bits = 0;
+    for _ in 0..256 {
+        let seed: usize;
+        unsafe { core::arch::asm!("csrrw {}, seed, x0", out(reg) seed) };
+        match seed >> 30 {
+            SEED_CSR_ES16 => {
+                val = (val << 16) | (seed & 0xffff) as u64;
+                bits += 16;
+                if bits == 64 {
+                    return Some(val);
+                }
+            }
+            0b11 => return None, // DEAD
+            _ => core::hint::spin_loop(),
+        }
+    }
+    None
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    all(target_arch = "riscv64", target_feature = "zkr")
+)))]
+fn hw_random() -> Option<u64> {
+    None
+}
+
+/// Collects entropy from the jitter of the system timer.
+fn timer_jitter() -> u64 {
+    let mut acc = wall_time_nanos();
+    let mut last = current_ticks();
+    for i in 0..64u64 {
+        // the number of iterations between two timer reads varies slightly
+        let mut spins = 0u64;
+        let mut now = current_ticks();
+        while now == last && spins < 1024 {
+            spins += 1;
+            core::hint::spin_loop();
+            now = current_ticks();
+        }
+        acc = splitmix64(acc ^ now.wrapping_sub(last) ^ spins.rotate_left(i as u32));
+        last = now;
+    }
+    acc
+}
+
+fn collect_seed() -> [u8; 32] {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    let mut seed = [0; 32];
+    for chunk in seed.chunks_exact_mut(8) {
+        let mut val = timer_jitter();
+        match hw_random() {
+            Some(hw) => val ^= hw,
+            None if !WARNED.swap(true, Ordering::Relaxed) => {
+                warn!(
+                    "no hardware random number generator, seeding the CSPRNG from timer jitter only"
+                );
+            }
+            None => {}
+        }
+        chunk.copy_from_slice(&val.to_ne_bytes());
+    }
+    seed
+}
+
+/// Fills `buf` with cryptographically secure random bytes.
+pub fn fill_bytes(buf: &mut [u8]) {
+    let mut guard = RNG.lock();
+    if guard.rng.is_none() || guard.generated >= RESEED_INTERVAL {
+        let mut seed = collect_seed();
+        if let Some(rng) = guard.rng.as_mut() {
+            // keep the entropy of the old state
+            let mut old = [0; 32];
+            rng.fill_bytes(&mut old);
+            seed.iter_mut().zip(old).for_each(|(s, o)| *s ^= o);
+        }
+        guard.rng = Some(ChaCha20Rng::from_seed(seed));
+        guard.generated = 0;
+    }
+    guard.generated += buf.len();
+    guard.rng.as_mut().unwrap().fill_bytes(buf);
+}
+
+/// Returns a random `u64`.
+pub fn random_u64() -> u64 {
+    let mut buf = [0; 8];
+    fill_bytes(&mut buf);
+    u64::from_ne_bytes(buf)
+}
@@ -1,3 +1,4 @@
+use alloc::{sync::Arc, vec::Vec};
 use core::fmt;
 
 use axerrno::{AxError, AxResult, ax_err};
@@ -6,8 +7,9 @@ use axhal::paging::{MappingFlags, PageSize, PageTable, PagingError};
 use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, VirtAddrRange, is_aligned};
 use memory_set::{MemoryArea, MemorySet};
 
-use crate::backend::Backend;
+use crate::backend::{Backend, MmapFile};
 use crate::mapping_err_to_ax_err;
+use crate::overcommit::{commit, uncommit};
 use crate::page_iter_wrapper::{PAGE_SIZE_4K, PageIterWrapper};
 
 #[cfg(feature = "cow")]
@@ -206,7 +208,8 @@ impl AddrSpace {
     /// The `flags` parameter indicates the mapping permissions and attributes.
     ///
     /// Returns an error if the address range is out of the address space or not
-    /// aligned.
+    /// aligned, or [`AxError::NoMemory`] if it's not populated and refused by
+    /// the [`OvercommitPolicy`](crate::OvercommitPolicy).
     pub fn map_alloc(
         &mut self,
         start: VirtAddr,
@@ -216,14 +219,120 @@ impl AddrSpace {
         align: PageSize,
     ) -> AxResult {
         self.validate_region(start, size, align)?;
+        if !populate {
+            commit(size)?;
+        }
 
         let area = MemoryArea::new(start, size, flags, Backend::new_alloc(populate, align));
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)
+            .inspect_err(|_| {
+                if !populate {
+                    uncommit(size);
+                }
+            })
+    }
+
+    /// Returns the size of the mappings allocated on demand in the range,
+    /// which are committed by [`commit`].
+    fn lazy_size(&self, start: VirtAddr, end: VirtAddr) -> usize {
+        self.areas
+            .iter()
+            .filter(|area| is_committed(area.backend()))
+            .map(|area| {
+                let area_end = end.min(area.end()).as_usize();
+                area_end.saturating_sub(start.max(area.start()).as_usize())
+            })
+            .sum()
+    }
+
+    /// Add a new file mapping.
+    ///
+    /// See [`Backend`] for more details about the mapping backends.
+    ///
+    /// The pages of `file` starting from the page `offset` are mapped. The
+    /// `flags` parameter indicates the mapping permissions and attributes.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    #[allow(clippy::too_many_arguments)]
+    pub fn map_file(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        file: Arc<dyn MmapFile>,
+        offset: u64,
+        shared: bool,
+        populate: bool,
+    ) -> AxResult {
+        self.validate_region(start, size, PageSize::Size4K)?;
+
+        let backend = Backend::new_file(start, file, offset, shared, populate);
+        let area = MemoryArea::new(start, size, flags, backend);
         self.areas
             .map(area, &mut self.pt, false)
             .map_err(mapping_err_to_ax_err)?;
         Ok(())
     }
 
+    /// Returns the files of the shared file mappings within the specified
+    /// virtual address range, with the indexes of the first and the last
+    /// (exclusive) pages mapped.
+    ///
+    /// The pages are written back to the files by [`MmapFile::sync`], which
+    /// should be called after the address space is unlocked, as it does I/O.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn shared_files(
+        &self,
+        start: VirtAddr,
+        size: usize,
+    ) -> AxResult<Vec<(Arc<dyn MmapFile>, u64, u64)>> {
+        self.validate_region(start, size, PageSize::Size4K)?;
+
+        let end = start + size;
+        let mut files = Vec::new();
+        for area in self
+            .areas
+            .iter()
+            .skip_while(move |a| a.end() <= start)
+            .take_while(move |a| a.start() < end)
+        {
+            let backend = area.backend();
+            if let Backend::File {
+                file, shared: true, ..
+            } = backend
+            {
+                let first = backend.file_page_index(start.max(area.start()));
+                let last = backend.file_page_index(end.min(area.end()));
+                files.push((file.clone(), first, last));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Whether the specified virtual address range is fully mapped by file
+    /// mappings.
+    pub fn is_file_mapped(&self, start: VirtAddr, size: usize) -> bool {
+        let end = start + size;
+        let mut next = start;
+        for area in self
+            .areas
+            .iter()
+            .skip_while(move |a| a.end() <= start)
+            .take_while(move |a| a.start() < end)
+        {
+            if area.start() > next || !matches!(area.backend(), Backend::File { .. }) {
+                return false;
+            }
+            next = area.end();
+        }
+        next >= end
+    }
+
     /// Ensures that the specified virtual memory region is fully mapped.
     ///
     /// This function walks through the given virtual address range and attempts to ensure
@@ -310,6 +419,21 @@ impl AddrSpace {
                         Err(_) => return Err(AxError::BadAddress),
                     };
                 }
+            } else if let Backend::File { .. } = backend {
+                for addr in PageIterWrapper::new(
+                    start.align_down_4k(),
+                    end.align_up_4k().min(area.end()),
+                    PageSize::Size4K,
+                )
+                .unwrap()
+                {
+                    // If the page is not mapped, try map it.
+                    if self.pt.query(addr).is_err()
+                        && !backend.handle_page_fault(addr, area.flags(), &mut self.pt)
+                    {
+                        return Err(AxError::NoMemory);
+                    }
+                }
             }
             start = area.end();
             assert!(start.is_aligned(PageSize::Size4K));
@@ -343,6 +467,7 @@ impl AddrSpace {
                     pa_va_offset: _,
                     align,
                 } => align,
+                Backend::File { .. } => PageSize::Size4K,
             };
 
             let unmap_start = start.max(area.start());
@@ -352,15 +477,17 @@ impl AddrSpace {
             }
         }
 
+        let lazy_size = self.lazy_size(start, end);
         self.areas
             .unmap(start, size, &mut self.pt)
             .map_err(mapping_err_to_ax_err)?;
+        uncommit(lazy_size);
         Ok(())
     }
 
     /// To remove user area mappings from address space.
     pub fn unmap_user_areas(&mut self) -> AxResult {
-        self.areas.clear(&mut self.pt).unwrap();
+        self.clear();
         Ok(())
     }
 
@@ -463,7 +590,9 @@ impl AddrSpace {
 
     /// Removes all mappings in the address space.
     pub fn clear(&mut self) {
+        let lazy_size = self.lazy_size(self.base(), self.end());
         self.areas.clear(&mut self.pt).unwrap();
+        uncommit(lazy_size);
     }
 
     /// Checks whether an access to the specified memory region is valid.
@@ -571,20 +700,40 @@ impl AddrSpace {
                     // from mapping page table entries for the virtual addresses.
                     Backend::new_alloc(false, *align)
                 }
+                // The pages are mapped on demand in the new address space.
+                Backend::File {
+                    file,
+                    start,
+                    offset,
+                    shared,
+                    populate: _,
+                } => Backend::new_file(*start, file.clone(), *offset, *shared, false),
                 other => other.clone(),
             };
 
             // Remap the memory area in the new address space.
+            let lazy = is_committed(&backend);
+            if lazy {
+                commit(area.size())?;
+            }
             let new_area = MemoryArea::new(area.start(), area.size(), area.flags(), backend);
             new_aspace
                 .areas
                 .map(new_area, &mut new_aspace.pt, false)
-                .map_err(mapping_err_to_ax_err)?;
+                .map_err(mapping_err_to_ax_err)
+                .inspect_err(|_| {
+                    if lazy {
+                        uncommit(area.size());
+                    }
+                })?;
 
             let align = match area.backend() {
                 Backend::Alloc { align, .. } => *align,
-                // Linear-backed regions are usually allocated by the kernel and are shared
-                Backend::Linear { .. } => continue,
+                // Private file mappings are copied like allocated ones
+                Backend::File { shared: false, .. } => PageSize::Size4K,
+                // Linear-backed regions are usually allocated by the kernel and are shared,
+                // and shared file mappings map the same pages of the files
+                Backend::Linear { .. } | Backend::File { .. } => continue,
             };
 
             #[cfg(feature = "cow")]
@@ -710,6 +859,12 @@ impl AddrSpace {
     }
 }
 
+/// Returns whether the mapping is allocated on demand, whose size is
+/// committed by [`commit`].
+fn is_committed(backend: &Backend) -> bool {
+    matches!(*backend, Backend::Alloc { populate, .. } if !populate)
+}
+
 impl fmt::Debug for AddrSpace {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("AddrSpace")
@@ -0,0 +1,160 @@
+use alloc::sync::Arc;
+use axerrno::AxResult;
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageSize, PageTable};
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr};
+
+use crate::page_iter_wrapper::{PAGE_SIZE_4K, PageIterWrapper};
+
+use super::{Backend, alloc_frame, dealloc_frame};
+
+/// A file that can be mapped by [`Backend::File`], usually through its page
+/// cache.
+///
+/// Pages are indexed by their offsets in the file divided by the page size
+/// (4K).
+pub trait MmapFile: Send + Sync {
+    /// Returns the physical frame of the page `index`, which stays in place
+    /// until released by [`put_page`](MmapFile::put_page). `write` is set if
+    /// the page is mapped writable and shared, so it's modified from then on.
+    fn get_page(&self, index: u64, write: bool) -> Option<PhysAddr>;
+    /// Releases the page `index` returned by [`get_page`](MmapFile::get_page).
+    fn put_page(&self, index: u64);
+    /// Writes the modified pages from `start` to `end` (exclusive) back to
+    /// the file.
+    fn sync(&self, start: u64, end: u64) -> AxResult;
+}
+
+impl Backend {
+    /// Creates a new file mapping backend.
+    pub fn new_file(
+        start: VirtAddr,
+        file: Arc<dyn MmapFile>,
+        offset: u64,
+        shared: bool,
+        populate: bool,
+    ) -> Self {
+        Self::File {
+            file,
+            start,
+            offset,
+            shared,
+            populate,
+        }
+    }
+
+    /// Returns the index of the file page mapped at `vaddr`.
+    pub(crate) fn file_page_index(&self, vaddr: VirtAddr) -> u64 {
+        match self {
+            Self::File { start, offset, .. } => {
+                offset + ((vaddr.as_usize() - start.as_usize()) / PAGE_SIZE_4K) as u64
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn map_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        let Self::File { populate, .. } = *self else {
+            unreachable!()
+        };
+        debug!(
+            "map_file: [{:#x}, {:#x}) {:?} (populate={})",
+            start,
+            start + size,
+            flags,
+            populate
+        );
+        if populate {
+            if let Some(iter) = PageIterWrapper::new(start, start + size, PageSize::Size4K) {
+                for addr in iter {
+                    if !self.handle_page_fault_file(addr, flags, pt) {
+                        return false;
+                    }
+                }
+            }
+        } else {
+            // create mapping entries on demand later in `handle_page_fault_file`.
+        }
+        true
+    }
+
+    pub(crate) fn unmap_file(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
+        let Self::File { file, shared, .. } = self else {
+            unreachable!()
+        };
+        debug!("unmap_file: [{:#x}, {:#x})", start, start + size);
+        if let Some(iter) = PageIterWrapper::new(start, start + size, PageSize::Size4K) {
+            for addr in iter {
+                if let Ok((frame, _page_size, tlb)) = pt.unmap(addr) {
+                    tlb.flush();
+                    if *shared {
+                        // The frame is in the page cache of the file.
+                        file.put_page(self.file_page_index(addr));
+                    } else {
+                        dealloc_frame(frame, PageSize::Size4K);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    pub(crate) fn handle_page_fault_file(
+        &self,
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        let Self::File { file, shared, .. } = self else {
+            unreachable!()
+        };
+        let vaddr = vaddr.align_down_4k();
+        let index = self.file_page_index(vaddr);
+        let frame = if *shared {
+            match file.get_page(index, orig_flags.contains(MappingFlags::WRITE)) {
+                Some(frame) => frame,
+                None => return false,
+            }
+        } else {
+            // Private mappings map a copy of the page.
+            let Some(page) = file.get_page(index, false) else {
+                return false;
+            };
+            let frame = alloc_frame(false, PageSize::Size4K);
+            if let Some(frame) = frame {
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        phys_to_virt(page).as_ptr(),
+                        phys_to_virt(frame).as_mut_ptr(),
+                        PAGE_SIZE_4K,
+                    )
+                };
+            }
+            file.put_page(index);
+            match frame {
+                Some(frame) => frame,
+                None => return false,
+            }
+        };
+        match pt.map(vaddr, frame, PageSize::Size4K, orig_flags) {
+            Ok(tlb) => {
+                tlb.flush();
+                true
+            }
+            Err(_) => {
+                if *shared {
+                    file.put_page(index);
+                } else {
+                    dealloc_frame(frame, PageSize::Size4K);
+                }
+                false
+            }
+        }
+    }
+}
@@ -1,22 +1,27 @@
 //! Memory mapping backends.
 
+use ::alloc::sync::Arc;
 use axhal::paging::{MappingFlags, PageSize, PageTable};
 use memory_addr::VirtAddr;
 use memory_set::MappingBackend;
 mod alloc;
+mod file;
 mod linear;
 
 #[allow(unused_imports)]
 pub(crate) use alloc::{alloc_frame, dealloc_frame};
+pub use file::MmapFile;
 
 /// A unified enum type for different memory mapping backends.
 ///
-/// Currently, two backends are implemented:
+/// Currently, three backends are implemented:
 ///
 /// - **Linear**: used for linear mappings. The target physical frames are
 ///   contiguous and their addresses should be known when creating the mapping.
 /// - **Allocation**: used in general, or for lazy mappings. The target physical
 ///   frames are obtained from the global allocator.
+/// - **File**: used for memory-mapped files. The target physical frames are
+///   the pages of a [`MmapFile`], or copies of them.
 #[derive(Clone)]
 pub enum Backend {
     /// Linear mapping backend.
@@ -42,6 +47,28 @@ pub enum Backend {
         /// Alignment parameters for the starting address and memory range.
         align: PageSize,
     },
+    /// File mapping backend.
+    ///
+    /// Shared mappings map the pages of the file directly, so the changes are
+    /// seen by others and written back to the file by [`AddrSpace::msync`].
+    /// Private mappings map copies of the pages made on the first access. Like
+    /// the allocation backend, the pages are mapped on demand unless
+    /// `populate` is `true`. Only 4K pages are supported.
+    ///
+    /// [`AddrSpace::msync`]: crate::AddrSpace::msync
+    File {
+        /// The file to map.
+        file: Arc<dyn MmapFile>,
+        /// The starting address of the whole mapping, which does not change
+        /// when the mapping is split.
+        start: VirtAddr,
+        /// The index of the file page mapped at `start`.
+        offset: u64,
+        /// Whether the changes are shared with the file.
+        shared: bool,
+        /// Whether to populate the pages when creating the mapping.
+        populate: bool,
+    },
 }
 
 impl MappingBackend for Backend {
@@ -57,6 +84,7 @@ impl MappingBackend for Backend {
             Self::Alloc { populate, align } => {
                 Self::map_alloc(start, size, flags, pt, populate, align)
             }
+            Self::File { .. } => self.map_file(start, size, flags, pt),
         }
     }
 
@@ -67,6 +95,7 @@ impl MappingBackend for Backend {
                 align: _,
             } => Self::unmap_linear(start, size, pt, pa_va_offset),
             Self::Alloc { populate, align } => Self::unmap_alloc(start, size, pt, populate, align),
+            Self::File { .. } => self.unmap_file(start, size, pt),
         }
     }
 
@@ -96,6 +125,7 @@ impl Backend {
             Self::Alloc { populate, align } => {
                 Self::handle_page_fault_alloc(vaddr, orig_flags, page_table, populate, align)
             }
+            Self::File { .. } => self.handle_page_fault_file(vaddr, orig_flags, page_table),
         }
     }
 }
@@ -10,10 +10,14 @@ mod aspace;
 mod backend;
 #[cfg(feature = "cow")]
 mod frameinfo;
+mod overcommit;
 
 pub mod page_iter_wrapper;
 pub use self::aspace::AddrSpace;
-pub use self::backend::Backend;
+pub use self::backend::{Backend, MmapFile};
+pub use self::overcommit::{
+    OvercommitPolicy, committed_pages, overcommit_policy, set_overcommit_policy,
+};
 
 use axerrno::{AxError, AxResult};
 use axhal::mem::phys_to_virt;
@@ -0,0 +1,88 @@
+//! The overcommit policy of lazily allocated memory.
+
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use axerrno::{AxResult, ax_err};
+
+use crate::page_iter_wrapper::PAGE_SIZE_4K;
+
+static OVERCOMMIT_POLICY: AtomicU8 = AtomicU8::new(OvercommitPolicy::Heuristic as u8);
+
+/// The pages of the mappings allocated on demand in all address spaces.
+static COMMITTED_PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// How much memory can be mapped without being allocated, as
+/// `vm.overcommit_memory` on Linux.
+///
+/// It applies to the mappings by [`AddrSpace::map_alloc`] that are not
+/// populated, whose pages are allocated on page faults. Their sizes in all
+/// address spaces are committed, see [`committed_pages`].
+///
+/// [`AddrSpace::map_alloc`]: crate::AddrSpace::map_alloc
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OvercommitPolicy {
+    /// A mapping can't be larger than all the memory.
+    Heuristic = 0,
+    /// Mappings are never refused.
+    Always = 1,
+    /// The committed memory can't be larger than all the memory, i.e. the
+    /// commit limit of Linux without swap and with `vm.overcommit_ratio` of
+    /// 100.
+    Never = 2,
+}
+
+impl TryFrom<u8> for OvercommitPolicy {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, ()> {
+        match value {
+            0 => Ok(Self::Heuristic),
+            1 => Ok(Self::Always),
+            2 => Ok(Self::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Returns the overcommit policy.
+pub fn overcommit_policy() -> OvercommitPolicy {
+    OVERCOMMIT_POLICY
+        .load(Ordering::Relaxed)
+        .try_into()
+        .unwrap()
+}
+
+/// Sets the overcommit policy.
+pub fn set_overcommit_policy(policy: OvercommitPolicy) {
+    OVERCOMMIT_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// Returns the number of pages committed by the mappings allocated on
+/// demand, as `Committed_AS` on Linux.
+pub fn committed_pages() -> usize {
+    COMMITTED_PAGES.load(Ordering::Relaxed)
+}
+
+/// Commits a mapping of `size` bytes allocated on demand if it's allowed, it
+/// must be released by [`uncommit`] when it's unmapped.
+pub(crate) fn commit(size: usize) -> AxResult {
+    let allocator = axalloc::global_allocator();
+    let total = allocator.used_pages() + allocator.available_pages();
+    let pages = size.div_ceil(PAGE_SIZE_4K);
+    let policy = overcommit_policy();
+    if policy == OvercommitPolicy::Heuristic && pages > total {
+        return ax_err!(NoMemory, "overcommit refused");
+    }
+    COMMITTED_PAGES
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |committed| {
+            let new = committed.checked_add(pages)?;
+            (policy != OvercommitPolicy::Never || new <= total).then_some(new)
+        })
+        .map_or_else(|_| ax_err!(NoMemory, "overcommit refused"), |_| Ok(()))
+}
+
+/// Releases a mapping of `size` bytes committed by [`commit`].
+pub(crate) fn uncommit(size: usize) {
+    COMMITTED_PAGES.fetch_sub(size.div_ceil(PAGE_SIZE_4K), Ordering::Relaxed);
+}
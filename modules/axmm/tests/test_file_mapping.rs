@@ -0,0 +1,64 @@
+use std::alloc::{Layout, alloc};
+use std::sync::Arc;
+
+use axerrno::AxResult;
+use axhal::paging::{MappingFlags, PageSize};
+use axmm::{AddrSpace, MmapFile};
+use memory_addr::{PhysAddr, va};
+
+const PAGE_SIZE: usize = 0x1000;
+const HEAP_SIZE: usize = 0x10_0000;
+
+/// A file whose pages are never accessed, as the mappings are not populated.
+struct DummyFile;
+
+impl MmapFile for DummyFile {
+    fn get_page(&self, _index: u64, _write: bool) -> Option<PhysAddr> {
+        None
+    }
+
+    fn put_page(&self, _index: u64) {}
+
+    fn sync(&self, _start: u64, _end: u64) -> AxResult {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_file_mapping() {
+    // the page table is allocated by the global allocator
+    let heap = unsafe { alloc(Layout::from_size_align(HEAP_SIZE, PAGE_SIZE).unwrap()) };
+    axalloc::global_init(heap as usize, HEAP_SIZE);
+
+    let base = va!(0x1000_0000);
+    let page = |n: usize| base + n * PAGE_SIZE;
+    let mut aspace = AddrSpace::new_empty(base, 0x100_0000).unwrap();
+    let file: Arc<dyn MmapFile> = Arc::new(DummyFile);
+    let flags = MappingFlags::READ | MappingFlags::WRITE;
+    aspace
+        .map_file(base, 4 * PAGE_SIZE, flags, file.clone(), 2, true, false)
+        .unwrap();
+    aspace
+        .map_file(page(4), 2 * PAGE_SIZE, flags, file.clone(), 0, false, false)
+        .unwrap();
+    aspace
+        .map_alloc(page(8), 2 * PAGE_SIZE, flags, false, PageSize::Size4K)
+        .unwrap();
+
+    // adjacent file mappings, but not holes or other mappings
+    assert!(aspace.is_file_mapped(base, 6 * PAGE_SIZE));
+    assert!(aspace.is_file_mapped(page(1), 2 * PAGE_SIZE));
+    assert!(!aspace.is_file_mapped(base, 7 * PAGE_SIZE));
+    assert!(!aspace.is_file_mapped(page(8), PAGE_SIZE));
+    assert!(!aspace.is_file_mapped(page(16), PAGE_SIZE));
+
+    // only the pages of the shared mapping are synced
+    let files = aspace.shared_files(page(1), 6 * PAGE_SIZE).unwrap();
+    assert_eq!(files.len(), 1);
+    assert!(Arc::ptr_eq(&files[0].0, &file));
+    assert_eq!((files[0].1, files[0].2), (3, 6));
+
+    aspace.unmap(base, 6 * PAGE_SIZE).unwrap();
+    assert!(!aspace.is_file_mapped(base, PAGE_SIZE));
+    assert!(aspace.shared_files(base, PAGE_SIZE).unwrap().is_empty());
+}
@@ -1,6 +1,6 @@
 //! [ArceOS](https://github.com/arceos-org/arceos) network module.
 //!
-//! It provides unified networking primitives for TCP/UDP communication
+//! It provides unified networking primitives for TCP/UDP/ICMP communication
 //! using various underlying network stacks. Currently, only [smoltcp] is
 //! supported.
 //!
@@ -8,12 +8,23 @@
 //!
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
+//! - [`IcmpSocket`]: An ICMP socket that provides POSIX-like APIs.
+//! - [`RawSocket`]: A raw IP socket that provides POSIX-like APIs.
 //! - [`dns_query`]: Function for DNS query.
+//! - [`DnsQuery`]: A non-blocking DNS query, resolving A or AAAA records.
+//! - [`stats`]: Interface counters and the socket table.
+//! - [`start_capture`]: Starts capturing frames on the NIC (pcap), with the
+//!   `capture` feature.
 //!
 //! # Cargo Features
 //!
 //! - `smoltcp`: Use [smoltcp] as the underlying network stack. This is enabled
 //!   by default.
+//! - `capture`: Enable packet capture on the network device path.
+//! - `fs`: Read the DNS configuration from `/etc/hosts` and
+//!   `/etc/resolv.conf`.
+//! - `procfs`: Render the [`stats`] as `/proc/net/dev`, `/proc/net/tcp` and
+//!   `/proc/net/udp`.
 //!
 //! [smoltcp]: https://github.com/smoltcp-rs/smoltcp
 
@@ -30,10 +41,21 @@ cfg_if::cfg_if! {
     }
 }
 
-pub use self::net_impl::TcpSocket;
 pub use self::net_impl::UdpSocket;
+pub use self::net_impl::poll_interfaces;
+pub use self::net_impl::stats;
+pub use self::net_impl::{
+    DnsQuery, DnsRecordType, dns_lookup, dns_query, dns_servers, flush_dns_cache,
+    reload_dns_config, set_dns_servers,
+};
+pub use self::net_impl::{IcmpSocket, RawSocket};
+pub use self::net_impl::{TcpListenStats, TcpSocket, set_somaxconn, somaxconn};
 pub use self::net_impl::{bench_receive, bench_transmit};
-pub use self::net_impl::{dns_query, poll_interfaces};
+
+#[cfg(feature = "capture")]
+pub use self::net_impl::{
+    CONSOLE_PREFIX, CaptureConfig, CaptureMode, capture_to_pcap, start_capture, stop_capture,
+};
 
 use axdriver::{AxDeviceContainer, prelude::*};
 
@@ -0,0 +1,207 @@
+//! Packet capture on the network device path.
+//!
+//! Captured Ethernet frames are either kept in a ring buffer, which can be
+//! exported as a pcap file later, or streamed over the console as hex encoded
+//! pcap data. In the latter case, the lines are prefixed with
+//! [`CONSOLE_PREFIX`], and the pcap file can be recovered on the host by:
+//!
+//! ```sh
+//! grep '^PCAP:' serial.log | cut -c6- | xxd -r -p > trace.pcap
+//! ```
+
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use axhal::time::wall_time;
+use axsync::Mutex;
+
+/// The prefix of the lines of the pcap data streamed over the console.
+pub const CONSOLE_PREFIX: &str = "PCAP:";
+
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4; // microsecond resolution
+const PCAP_VERSION_MAJOR: u16 = 2;
+const PCAP_VERSION_MINOR: u16 = 4;
+const LINKTYPE_ETHERNET: u32 = 1;
+const PCAP_HEADER_LEN: usize = 24;
+const PCAP_RECORD_HEADER_LEN: usize = 16;
+
+/// Where the captured frames go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// Keep the frames in a ring buffer, the oldest frames are dropped when
+    /// it's full.
+    Ring,
+    /// Print the frames to the console immediately.
+    Console,
+}
+
+/// Configuration of a packet capture.
+#[derive(Debug, Clone, Copy)]
+pub struct CaptureConfig {
+    /// Where the captured frames go.
+    pub mode: CaptureMode,
+    /// The maximum number of bytes captured from each frame.
+    pub snaplen: usize,
+    /// The size of the ring buffer in bytes, only used by
+    /// [`CaptureMode::Ring`].
+    pub ring_size: usize,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            mode: CaptureMode::Ring,
+            snaplen: 65535,
+            ring_size: 1024 * 1024,
+        }
+    }
+}
+
+struct Frame {
+    timestamp: Duration,
+    orig_len: usize,
+    data: Vec<u8>,
+}
+
+impl Frame {
+    fn write_pcap_record(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.timestamp.as_secs() as u32).to_le_bytes());
+        out.extend_from_slice(&self.timestamp.subsec_micros().to_le_bytes());
+        out.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.orig_len as u32).to_le_bytes());
+        out.extend_from_slice(&self.data);
+    }
+}
+
+struct Capture {
+    config: CaptureConfig,
+    frames: VecDeque<Frame>,
+    ring_used: usize,
+    dropped: u64,
+}
+
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+fn write_pcap_header(out: &mut Vec<u8>, snaplen: usize) {
+    out.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+    out.extend_from_slice(&PCAP_VERSION_MAJOR.to_le_bytes());
+    out.extend_from_slice(&PCAP_VERSION_MINOR.to_le_bytes());
+    out.extend_from_slice(&0i32.to_le_bytes()); // thiszone
+    out.extend_from_slice(&0u32.to_le_bytes()); // sigfigs
+    out.extend_from_slice(&(snaplen as u32).to_le_bytes());
+    out.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+}
+
+fn print_hex(data: &[u8]) {
+    const BYTES_PER_LINE: usize = 32;
+    let mut line = String::with_capacity(CONSOLE_PREFIX.len() + BYTES_PER_LINE * 2 + 1);
+    for chunk in data.chunks(BYTES_PER_LINE) {
+        line.clear();
+        line.push_str(CONSOLE_PREFIX);
+        for b in chunk {
+            write!(line, "{:02x}", b).unwrap();
+        }
+        line.push('\n');
+        axhal::console::write_bytes(line.as_bytes());
+    }
+}
+
+/// Starts capturing frames, the previously captured frames are discarded.
+pub fn start_capture(config: CaptureConfig) {
+    let snaplen = config.snaplen.clamp(1, 65535);
+    let config = CaptureConfig { snaplen, ..config };
+    if config.mode == CaptureMode::Console {
+        let mut header = Vec::with_capacity(PCAP_HEADER_LEN);
+        write_pcap_header(&mut header, snaplen);
+        print_hex(&header);
+    }
+    *CAPTURE.lock() = Some(Capture {
+        config,
+        frames: VecDeque::new(),
+        ring_used: 0,
+        dropped: 0,
+    });
+    CAPTURING.store(true, Ordering::Release);
+    info!("packet capture started: {:?}", config);
+}
+
+/// Stops capturing frames.
+///
+/// The frames in the ring buffer are kept until the next
+/// [`start_capture`].
+pub fn stop_capture() {
+    CAPTURING.store(false, Ordering::Release);
+    if let Some(capture) = CAPTURE.lock().as_ref() {
+        info!(
+            "packet capture stopped: {} frames buffered, {} dropped",
+            capture.frames.len(),
+            capture.dropped
+        );
+    }
+}
+
+/// Returns the frames in the ring buffer as the content of a pcap file.
+pub fn capture_to_pcap() -> Vec<u8> {
+    let capture = CAPTURE.lock();
+    let Some(capture) = capture.as_ref() else {
+        let mut out = Vec::with_capacity(PCAP_HEADER_LEN);
+        write_pcap_header(&mut out, CaptureConfig::default().snaplen);
+        return out;
+    };
+    let mut out = Vec::with_capacity(
+        PCAP_HEADER_LEN + capture.ring_used + capture.frames.len() * PCAP_RECORD_HEADER_LEN,
+    );
+    write_pcap_header(&mut out, capture.config.snaplen);
+    for frame in &capture.frames {
+        frame.write_pcap_record(&mut out);
+    }
+    out
+}
+
+/// Records a frame received from or transmitted to the device.
+#[inline]
+pub(crate) fn capture_frame(frame: &[u8]) {
+    if CAPTURING.load(Ordering::Acquire) {
+        record(frame);
+    }
+}
+
+#[cold]
+fn record(data: &[u8]) {
+    let mut guard = CAPTURE.lock();
+    let Some(capture) = guard.as_mut() else {
+        return;
+    };
+    let len = data.len().min(capture.config.snaplen);
+    let frame = Frame {
+        timestamp: wall_time(),
+        orig_len: data.len(),
+        data: data[..len].to_vec(),
+    };
+
+    match capture.config.mode {
+        CaptureMode::Console => {
+            // printing is slow, don't hold the lock on the NIC path
+            drop(guard);
+            let mut out = Vec::with_capacity(PCAP_RECORD_HEADER_LEN + len);
+            frame.write_pcap_record(&mut out);
+            print_hex(&out);
+        }
+        CaptureMode::Ring => {
+            if len > capture.config.ring_size {
+                capture.dropped += 1;
+                return;
+            }
+            while capture.ring_used + len > capture.config.ring_size {
+                let old = capture.frames.pop_front().unwrap();
+                capture.ring_used -= old.data.len();
+                capture.dropped += 1;
+            }
+            capture.ring_used += len;
+            capture.frames.push_back(frame);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Parsers of the resolver configuration files, `/etc/hosts` and
+//! `/etc/resolv.conf`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+use core::time::Duration;
+
+/// The maximum number of name servers, same as `MAXNS` of glibc.
+const MAX_SERVERS: usize = 3;
+/// The upper bound of `options timeout:n`.
+const MAX_TIMEOUT_SECS: u64 = 30;
+/// The upper bound of `options attempts:n`.
+const MAX_ATTEMPTS: usize = 5;
+
+/// Settings read from `/etc/resolv.conf`.
+#[derive(Debug, Default)]
+pub struct ResolvConf {
+    pub servers: Vec<IpAddr>,
+    pub timeout: Option<Duration>,
+    pub attempts: Option<usize>,
+}
+
+/// Parses the content of `/etc/hosts`.
+///
+/// Returns the address and the names (in lower case) of each entry.
+pub fn parse_hosts(text: &str) -> Vec<(IpAddr, Vec<String>)> {
+    let mut entries = Vec::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let Some(addr) = fields.next() else {
+            continue;
+        };
+        let Ok(addr) = addr.parse::<IpAddr>() else {
+            warn!("/etc/hosts: invalid address {:?}", addr);
+            continue;
+        };
+        let names: Vec<String> = fields
+            .map(|name| name.trim_end_matches('.').to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+        if !names.is_empty() {
+            entries.push((addr, names));
+        }
+    }
+    entries
+}
+
+/// Parses the content of `/etc/resolv.conf`.
+///
+/// Only the `nameserver` lines and the `timeout` and `attempts` options are
+/// supported, other settings (e.g. `search`) are ignored.
+pub fn parse_resolv_conf(text: &str) -> ResolvConf {
+    let mut conf = ResolvConf::default();
+    for line in text.lines() {
+        let line = line.split(['#', ';']).next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("nameserver") => {
+                let Some(addr) = fields.next() else {
+                    continue;
+                };
+                match addr.parse::<IpAddr>() {
+                    Ok(addr) if conf.servers.len() < MAX_SERVERS => conf.servers.push(addr),
+                    Ok(_) => warn!("/etc/resolv.conf: too many name servers, {} ignored", addr),
+                    Err(_) => warn!("/etc/resolv.conf: invalid name server {:?}", addr),
+                }
+            }
+            Some("options") => {
+                for opt in fields {
+                    if let Some(n) = opt.strip_prefix("timeout:") {
+                        if let Ok(n) = n.parse::<u64>() {
+                            conf.timeout = Some(Duration::from_secs(n.clamp(1, MAX_TIMEOUT_SECS)));
+                        }
+                    } else if let Some(n) = opt.strip_prefix("attempts:") {
+                        if let Ok(n) = n.parse::<usize>() {
+                            conf.attempts = Some(n.clamp(1, MAX_ATTEMPTS));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    conf
+}
+
+#[cfg(test)]
+mod tests {
+    use core::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn hosts() {
+        let text = "# comment\n\
+            127.0.0.1\tlocalhost Localhost.localdomain # trailing\n\
+            \n\
+            ::1 localhost ip6-localhost\n\
+            10.0.0.1 Gateway.\n\
+            10.0.0.2\n\
+            not-an-ip foo\n";
+        let entries = parse_hosts(text);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].0, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(entries[0].1, ["localhost", "localhost.localdomain"]);
+        assert_eq!(entries[1].0, IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(entries[1].1, ["localhost", "ip6-localhost"]);
+        assert_eq!(entries[2].0, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(entries[2].1, ["gateway"]);
+    }
+
+    #[test]
+    fn resolv_conf() {
+        let text = "search example.com\n\
+            nameserver 10.0.0.1 # primary\n\
+            ; nameserver 10.0.0.9\n\
+            nameserver bad\n\
+            nameserver fd00::1\n\
+            nameserver 10.0.0.2\n\
+            nameserver 10.0.0.3\n\
+            options ndots:2 timeout:100 attempts:0\n";
+        let conf = parse_resolv_conf(text);
+        assert_eq!(
+            conf.servers,
+            [
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+                "fd00::1".parse().unwrap(),
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            ]
+        );
+        // clamped to the bounds
+        assert_eq!(conf.timeout, Some(Duration::from_secs(MAX_TIMEOUT_SECS)));
+        assert_eq!(conf.attempts, Some(1));
+
+        let conf = parse_resolv_conf("options timeout:x attempts:3\n");
+        assert!(conf.servers.is_empty());
+        assert_eq!(conf.timeout, None);
+        assert_eq!(conf.attempts, Some(3));
+    }
+}
@@ -0,0 +1,441 @@
+//! Encoding and decoding of DNS messages ([RFC 1035]).
+//!
+//! [RFC 1035]: https://www.rfc-editor.org/rfc/rfc1035
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::net::{Ipv4Addr, Ipv6Addr};
+
+use axerrno::{AxError, AxResult, ax_err, ax_err_type};
+
+/// The maximum length of a DNS message over UDP (without EDNS).
+pub const MAX_MESSAGE_LEN: usize = 512;
+/// The maximum length of a domain name in its text form.
+pub const MAX_NAME_LEN: usize = 253;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_CNAME: u16 = 5;
+pub const TYPE_SOA: u16 = 6;
+pub const TYPE_AAAA: u16 = 28;
+
+pub const RCODE_NOERROR: u8 = 0;
+pub const RCODE_NXDOMAIN: u8 = 3;
+
+const HEADER_LEN: usize = 12;
+const MAX_LABEL_LEN: usize = 63;
+const MAX_POINTER_JUMPS: usize = 16;
+const CLASS_IN: u16 = 1;
+
+const FLAG_QR: u16 = 0x8000;
+const FLAG_TC: u16 = 0x0200;
+const FLAG_RD: u16 = 0x0100;
+const RCODE_MASK: u16 = 0x000f;
+
+/// The data of a resource record.
+#[derive(Debug)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(String),
+    /// Only the `MINIMUM` field is kept, it's used as the TTL of negative
+    /// answers ([RFC 2308]).
+    ///
+    /// [RFC 2308]: https://www.rfc-editor.org/rfc/rfc2308
+    Soa {
+        minimum: u32,
+    },
+    Other,
+}
+
+/// A resource record. The owner name is in lower case without the trailing
+/// dot.
+#[derive(Debug)]
+pub struct Record {
+    pub name: String,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+/// A decoded DNS response.
+#[derive(Debug)]
+pub struct Response {
+    pub id: u16,
+    pub rcode: u8,
+    pub truncated: bool,
+    pub question: Option<(String, u16)>,
+    pub answers: Vec<Record>,
+    pub authorities: Vec<Record>,
+}
+
+impl Response {
+    /// Returns the TTL of a negative answer, i.e. the smaller of the TTL and
+    /// the `MINIMUM` field of the SOA record in the authority section.
+    pub fn negative_ttl(&self) -> Option<u32> {
+        self.authorities.iter().find_map(|r| match r.data {
+            RecordData::Soa { minimum } => Some(r.ttl.min(minimum)),
+            _ => None,
+        })
+    }
+}
+
+/// Encodes a recursive query for `name` with the given record type.
+pub fn encode_query(id: u16, name: &str, qtype: u16) -> AxResult<Vec<u8>> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return ax_err!(InvalidInput, "dns query: invalid name length");
+    }
+    let mut buf = Vec::with_capacity(HEADER_LEN + name.len() + 6);
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf.extend_from_slice(&FLAG_RD.to_be_bytes());
+    buf.extend_from_slice(&1u16.to_be_bytes()); // QDCOUNT
+    buf.extend_from_slice(&[0; 6]); // ANCOUNT, NSCOUNT, ARCOUNT
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > MAX_LABEL_LEN {
+            return ax_err!(InvalidInput, "dns query: invalid label");
+        }
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+    buf.extend_from_slice(&qtype.to_be_bytes());
+    buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(buf)
+}
+
+/// Decodes a DNS response.
+///
+/// Records of classes other than `IN`, and the additional section are
+/// ignored.
+pub fn decode_response(buf: &[u8]) -> AxResult<Response> {
+    let mut r = Reader { buf, pos: 0 };
+    let id = r.u16()?;
+    let flags = r.u16()?;
+    if flags & FLAG_QR == 0 {
+        return ax_err!(InvalidData, "dns: not a response");
+    }
+    let qdcount = r.u16()?;
+    let ancount = r.u16()?;
+    let nscount = r.u16()?;
+    let _arcount = r.u16()?;
+
+    let mut question = None;
+    for _ in 0..qdcount {
+        let name = r.name()?;
+        let qtype = r.u16()?;
+        let _qclass = r.u16()?;
+        question.get_or_insert((name, qtype));
+    }
+    let answers = (0..ancount)
+        .map(|_| r.record())
+        .collect::<AxResult<Vec<_>>>()?;
+    // a truncated response may end in the middle of the authority section
+    let mut authorities = Vec::new();
+    for _ in 0..nscount {
+        match r.record() {
+            Ok(rec) => authorities.push(rec),
+            Err(_) if flags & FLAG_TC != 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(Response {
+        id,
+        rcode: (flags & RCODE_MASK) as u8,
+        truncated: flags & FLAG_TC != 0,
+        question,
+        answers,
+        authorities,
+    })
+}
+
+fn malformed() -> AxError {
+    ax_err_type!(InvalidData, "dns: malformed message")
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> AxResult<&[u8]> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or_else(malformed)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> AxResult<u16> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> AxResult<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    /// Reads a possibly compressed domain name.
+    fn name(&mut self) -> AxResult<String> {
+        let mut name = String::new();
+        let mut pos = self.pos;
+        let mut jumps = 0;
+        loop {
+            let len = *self.buf.get(pos).ok_or_else(malformed)? as usize;
+            match len & 0xc0 {
+                0x00 if len == 0 => {
+                    pos += 1;
+                    break;
+                }
+                0x00 => {
+                    let label = self.buf.get(pos + 1..pos + 1 + len).ok_or_else(malformed)?;
+                    if !name.is_empty() {
+                        name.push('.');
+                    }
+                    name.extend(label.iter().map(|b| b.to_ascii_lowercase() as char));
+                    if name.len() > MAX_NAME_LEN {
+                        return Err(malformed());
+                    }
+                    pos += 1 + len;
+                }
+                0xc0 => {
+                    let low = *self.buf.get(pos + 1).ok_or_else(malformed)? as usize;
+                    if jumps == 0 {
+                        self.pos = pos + 2;
+                    }
+                    jumps += 1;
+                    if jumps > MAX_POINTER_JUMPS {
+                        return Err(malformed());
+                    }
+                    pos = ((len & 0x3f) << 8) | low;
+                }
+                _ => return Err(malformed()),
+            }
+        }
+        if jumps == 0 {
+            self.pos = pos;
+        }
+        Ok(name)
+    }
+
+    fn record(&mut self) -> AxResult<Record> {
+        let name = self.name()?;
+        let rtype = self.u16()?;
+        let class = self.u16()?;
+        // RFC 2181: a TTL with the most significant bit set is treated as zero
+        let ttl = match self.u32()? {
+            ttl if ttl > i32::MAX as u32 => 0,
+            ttl => ttl,
+        };
+        let rdlen = self.u16()? as usize;
+        let rdata_end = self.pos + rdlen;
+        if rdata_end > self.buf.len() {
+            return Err(malformed());
+        }
+
+        let data = match (class, rtype) {
+            (CLASS_IN, TYPE_A) if rdlen == 4 => {
+                let octets: [u8; 4] = self.bytes(4)?.try_into().unwrap();
+                RecordData::A(Ipv4Addr::from(octets))
+            }
+            (CLASS_IN, TYPE_AAAA) if rdlen == 16 => {
+                let octets: [u8; 16] = self.bytes(16)?.try_into().unwrap();
+                RecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            (CLASS_IN, TYPE_CNAME) => RecordData::Cname(self.name()?),
+            (CLASS_IN, TYPE_SOA) => {
+                let _mname = self.name()?;
+                let _rname = self.name()?;
+                let _serial_refresh_retry_expire = self.bytes(16)?;
+                RecordData::Soa {
+                    minimum: self.u32()?,
+                }
+            }
+            _ => RecordData::Other,
+        };
+        if self.pos > rdata_end {
+            return Err(malformed());
+        }
+        self.pos = rdata_end;
+        Ok(Record { name, ttl, data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a response with the given flags and section counts, and the
+    /// question `www.example.com` (the name at offset 12, `example.com` at 16).
+    fn response(flags: u16, counts: [u16; 3], qtype: u16) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&0x1234u16.to_be_bytes());
+        buf.extend_from_slice(&(FLAG_QR | FLAG_RD | flags).to_be_bytes());
+        buf.extend_from_slice(&1u16.to_be_bytes());
+        for count in counts {
+            buf.extend_from_slice(&count.to_be_bytes());
+        }
+        buf.extend_from_slice(b"\x03WWW\x07Example\x03com\x00");
+        buf.extend_from_slice(&qtype.to_be_bytes());
+        buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+        buf
+    }
+
+    /// Appends a record whose owner name is already encoded in `name`, and
+    /// returns the offset of its data.
+    fn push_record(buf: &mut Vec<u8>, name: &[u8], rtype: u16, ttl: u32, rdata: &[u8]) -> usize {
+        buf.extend_from_slice(name);
+        buf.extend_from_slice(&rtype.to_be_bytes());
+        buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+        buf.extend_from_slice(&ttl.to_be_bytes());
+        buf.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        buf.extend_from_slice(rdata);
+        buf.len() - rdata.len()
+    }
+
+    fn is_invalid_data(buf: &[u8]) -> bool {
+        matches!(decode_response(buf), Err(AxError::InvalidData))
+    }
+
+    #[test]
+    fn encode() {
+        let buf = encode_query(0xabcd, "www.example.com", TYPE_AAAA).unwrap();
+        assert_eq!(
+            buf,
+            b"\xab\xcd\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\
+              \x03www\x07example\x03com\x00\x00\x1c\x00\x01"
+        );
+
+        let long_label = "a".repeat(MAX_LABEL_LEN + 1);
+        let long_name = "a.".repeat(MAX_NAME_LEN / 2) + "ab";
+        for name in ["", "a..b", ".a", "a.", &long_label, &long_name] {
+            assert!(
+                matches!(encode_query(1, name, TYPE_A), Err(AxError::InvalidInput)),
+                "{:?}",
+                name
+            );
+        }
+        let label = "a".repeat(MAX_LABEL_LEN);
+        assert!(encode_query(1, &label, TYPE_A).is_ok());
+    }
+
+    #[test]
+    fn decode_addresses() {
+        let mut buf = response(0, [2, 0, 0], TYPE_A);
+        push_record(&mut buf, b"\xc0\x0c", TYPE_A, 300, &[10, 0, 0, 1]);
+        let v6 = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+        push_record(&mut buf, b"\xc0\x0c", TYPE_AAAA, 0x8000_0000, &v6.octets());
+
+        let resp = decode_response(&buf).unwrap();
+        assert_eq!(resp.id, 0x1234);
+        assert_eq!(resp.rcode, RCODE_NOERROR);
+        assert!(!resp.truncated);
+        assert_eq!(resp.question, Some(("www.example.com".into(), TYPE_A)));
+        assert_eq!(resp.answers.len(), 2);
+        assert_eq!(resp.answers[0].name, "www.example.com");
+        assert_eq!(resp.answers[0].ttl, 300);
+        assert!(
+            matches!(resp.answers[0].data, RecordData::A(addr) if addr == Ipv4Addr::new(10, 0, 0, 1))
+        );
+        // a TTL with the most significant bit set is taken as zero
+        assert_eq!(resp.answers[1].ttl, 0);
+        assert!(matches!(resp.answers[1].data, RecordData::Aaaa(addr) if addr == v6));
+    }
+
+    #[test]
+    fn decode_cname_chain() {
+        // www.example.com -> web.example.com -> cdn.example.net, all but the
+        // last name compressed
+        let mut buf = response(0, [3, 0, 0], TYPE_A);
+        let web = push_record(&mut buf, b"\xc0\x0c", TYPE_CNAME, 600, b"\x03web\xc0\x10");
+        let web_ptr = [0xc0, web as u8];
+        let cdn = push_record(
+            &mut buf,
+            &web_ptr,
+            TYPE_CNAME,
+            120,
+            b"\x03cdn\x07example\x03net\x00",
+        );
+        push_record(&mut buf, &[0xc0, cdn as u8], TYPE_A, 60, &[10, 0, 0, 2]);
+
+        let resp = decode_response(&buf).unwrap();
+        let names: Vec<&str> = resp.answers.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["www.example.com", "web.example.com", "cdn.example.net"]
+        );
+        assert!(matches!(&resp.answers[0].data, RecordData::Cname(t) if t == "web.example.com"));
+        assert!(matches!(&resp.answers[1].data, RecordData::Cname(t) if t == "cdn.example.net"));
+        assert!(matches!(resp.answers[2].data, RecordData::A(_)));
+    }
+
+    #[test]
+    fn decode_negative() {
+        let mut buf = response(RCODE_NXDOMAIN as u16, [0, 1, 0], TYPE_A);
+        let mut soa = b"\x02ns\xc0\x10\x05admin\xc0\x10".to_vec();
+        soa.extend_from_slice(&[0; 16]);
+        soa.extend_from_slice(&30u32.to_be_bytes());
+        push_record(&mut buf, b"\xc0\x10", TYPE_SOA, 3600, &soa);
+
+        let resp = decode_response(&buf).unwrap();
+        assert_eq!(resp.rcode, RCODE_NXDOMAIN);
+        assert!(resp.answers.is_empty());
+        assert_eq!(resp.authorities[0].name, "example.com");
+        assert_eq!(resp.negative_ttl(), Some(30));
+
+        // a truncated response may be cut in the authority section
+        let mut buf = response(FLAG_TC, [1, 1, 0], TYPE_A);
+        push_record(&mut buf, b"\xc0\x0c", TYPE_A, 300, &[10, 0, 0, 1]);
+        buf.extend_from_slice(b"\xc0\x10\x00");
+        let resp = decode_response(&buf).unwrap();
+        assert!(resp.truncated);
+        assert_eq!(resp.answers.len(), 1);
+        assert!(resp.authorities.is_empty());
+    }
+
+    #[test]
+    fn decode_malformed() {
+        let mut buf = response(0, [2, 0, 0], TYPE_A);
+        push_record(&mut buf, b"\xc0\x0c", TYPE_CNAME, 600, b"\x03web\xc0\x10");
+        push_record(&mut buf, b"\x03web\xc0\x10", TYPE_A, 60, &[10, 0, 0, 2]);
+        assert!(decode_response(&buf).is_ok());
+        // every prefix of a response is rejected
+        for len in 0..buf.len() {
+            assert!(is_invalid_data(&buf[..len]), "truncated at {}", len);
+        }
+
+        // a query
+        let query = encode_query(1, "example.com", TYPE_A).unwrap();
+        assert!(is_invalid_data(&query));
+
+        let with_answer = |name: &[u8], rtype: u16, rdata: &[u8]| {
+            let mut buf = response(0, [1, 0, 0], TYPE_A);
+            push_record(&mut buf, name, rtype, 60, rdata);
+            buf
+        };
+        // a pointer loop, a pointer past the end, and a reserved label type
+        assert!(is_invalid_data(&with_answer(
+            b"\x01a\xc0\x21",
+            TYPE_A,
+            &[0; 4]
+        )));
+        assert!(is_invalid_data(&with_answer(b"\xc0\xff", TYPE_A, &[0; 4])));
+        assert!(is_invalid_data(&with_answer(b"\x40", TYPE_A, &[0; 4])));
+        // a name longer than allowed
+        let mut long_name = Vec::new();
+        for _ in 0..4 {
+            long_name.push(MAX_LABEL_LEN as u8);
+            long_name.extend_from_slice(&[b'a'; MAX_LABEL_LEN]);
+        }
+        long_name.push(0);
+        assert!(is_invalid_data(&with_answer(&long_name, TYPE_A, &[0; 4])));
+        // a CNAME running past its data, and data running past the message
+        let mut buf = with_answer(b"\xc0\x0c", TYPE_CNAME, b"\x03web\x00");
+        let rdlen = buf.len() - 7;
+        buf[rdlen..rdlen + 2].copy_from_slice(&2u16.to_be_bytes());
+        assert!(is_invalid_data(&buf));
+        let mut buf = with_answer(b"\xc0\x0c", TYPE_A, &[0; 4]);
+        let rdlen = buf.len() - 6;
+        buf[rdlen..rdlen + 2].copy_from_slice(&5u16.to_be_bytes());
+        assert!(is_invalid_data(&buf));
+    }
+}
@@ -0,0 +1,567 @@
+//! DNS resolver.
+//!
+//! A name is looked up in the hosts table first, then in the cache of previous
+//! answers, and finally by sending recursive queries over UDP to the
+//! configured name servers in turn, until one of them answers or all the
+//! attempts time out. Both positive and negative answers are cached according
+//! to their TTLs, and CNAME chains are followed. Queries over TCP are not
+//! supported, so a query fails if the answer is truncated.
+//!
+//! With the `fs` feature, the hosts table and the name servers are read from
+//! `/etc/hosts` and `/etc/resolv.conf` on first use, and can be reloaded by
+//! [`reload_dns_config`].
+
+#[cfg(feature = "fs")]
+mod config;
+mod message;
+
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use core::time::Duration;
+
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::time::monotonic_time;
+use axsync::{Mutex, MutexGuard};
+
+use self::message::{MAX_MESSAGE_LEN, MAX_NAME_LEN, RCODE_NOERROR, RCODE_NXDOMAIN, RecordData};
+use super::{SOCKET_SET, UdpSocket};
+
+const DNS_PORT: u16 = 53;
+const DEFAULT_SERVERS: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+    IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+];
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_ATTEMPTS: usize = 2;
+
+const MAX_CNAME_DEPTH: usize = 8;
+const CACHE_CAPACITY: usize = 256;
+const MAX_TTL: u32 = 24 * 3600;
+/// The TTL of negative answers without a SOA record.
+const DEFAULT_NEGATIVE_TTL: u32 = 60;
+const MAX_NEGATIVE_TTL: u32 = 300;
+
+#[cfg(feature = "fs")]
+const HOSTS_PATH: &str = "/etc/hosts";
+#[cfg(feature = "fs")]
+const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+/// The type of the records to query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DnsRecordType {
+    /// IPv4 addresses.
+    A,
+    /// IPv6 addresses.
+    Aaaa,
+}
+
+impl DnsRecordType {
+    const fn code(self) -> u16 {
+        match self {
+            Self::A => message::TYPE_A,
+            Self::Aaaa => message::TYPE_AAAA,
+        }
+    }
+
+    const fn matches(self, addr: &IpAddr) -> bool {
+        matches!(
+            (self, addr),
+            (Self::A, IpAddr::V4(_)) | (Self::Aaaa, IpAddr::V6(_))
+        )
+    }
+}
+
+struct ResolverConfig {
+    loaded: bool,
+    servers: Vec<IpAddr>,
+    timeout: Duration,
+    attempts: usize,
+    hosts: BTreeMap<String, Vec<IpAddr>>,
+}
+
+impl ResolverConfig {
+    const fn new() -> Self {
+        Self {
+            loaded: false,
+            servers: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            attempts: DEFAULT_ATTEMPTS,
+            hosts: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the configuration from the files, or the defaults.
+    fn load() -> Self {
+        let mut config = Self::new();
+        config.servers = DEFAULT_SERVERS.to_vec();
+        #[cfg(feature = "fs")]
+        config.load_files();
+
+        if !config.hosts.contains_key("localhost") {
+            config.hosts.insert(
+                "localhost".to_owned(),
+                alloc::vec![Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()],
+            );
+        }
+        config.loaded = true;
+        config
+    }
+
+    #[cfg(feature = "fs")]
+    fn load_files(&mut self) {
+        if let Ok(text) = axfs::api::read_to_string(HOSTS_PATH) {
+            for (addr, names) in config::parse_hosts(&text) {
+                for name in names {
+                    self.hosts.entry(name).or_default().push(addr);
+                }
+            }
+        }
+        if let Ok(text) = axfs::api::read_to_string(RESOLV_CONF_PATH) {
+            let conf = config::parse_resolv_conf(&text);
+            // only IPv4 is supported by the network stack
+            let servers: Vec<IpAddr> = conf.servers.into_iter().filter(IpAddr::is_ipv4).collect();
+            if !servers.is_empty() {
+                self.servers = servers;
+            }
+            self.timeout = conf.timeout.unwrap_or(DEFAULT_TIMEOUT);
+            self.attempts = conf.attempts.unwrap_or(DEFAULT_ATTEMPTS);
+        }
+        debug!(
+            "DNS config loaded: servers {:?}, {} hosts",
+            self.servers,
+            self.hosts.len()
+        );
+    }
+}
+
+struct CacheEntry {
+    /// Empty for negative answers.
+    addrs: Vec<IpAddr>,
+    expires: Duration,
+}
+
+static CONFIG: Mutex<ResolverConfig> = Mutex::new(ResolverConfig::new());
+static CACHE: Mutex<BTreeMap<(String, DnsRecordType), CacheEntry>> = Mutex::new(BTreeMap::new());
+
+/// Locks the configuration, which is loaded on first use. The files are read
+/// without the lock held.
+fn lock_config() -> MutexGuard<'static, ResolverConfig> {
+    let config = CONFIG.lock();
+    if config.loaded {
+        return config;
+    }
+    drop(config);
+    let loaded = ResolverConfig::load();
+    let mut config = CONFIG.lock();
+    if !config.loaded {
+        *config = loaded;
+    }
+    config
+}
+
+fn with_config<R>(f: impl FnOnce(&ResolverConfig) -> R) -> R {
+    f(&lock_config())
+}
+
+fn cache_lookup(name: &str, qtype: DnsRecordType) -> Option<Vec<IpAddr>> {
+    let mut cache = CACHE.lock();
+    let key = (name.to_owned(), qtype);
+    let entry = cache.get(&key)?;
+    if entry.expires > monotonic_time() {
+        Some(entry.addrs.clone())
+    } else {
+        cache.remove(&key);
+        None
+    }
+}
+
+fn cache_insert(name: String, qtype: DnsRecordType, addrs: Vec<IpAddr>, ttl: u32) {
+    if ttl == 0 {
+        return;
+    }
+    let now = monotonic_time();
+    let mut cache = CACHE.lock();
+    if cache.len() >= CACHE_CAPACITY {
+        cache.retain(|_, entry| entry.expires > now);
+    }
+    if cache.len() >= CACHE_CAPACITY {
+        // evict the entry that expires first
+        let oldest = cache
+            .iter()
+            .min_by_key(|(_, entry)| entry.expires)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            cache.remove(&key);
+        }
+    }
+    let expires = now + Duration::from_secs(ttl as u64);
+    cache.insert((name, qtype), CacheEntry { addrs, expires });
+}
+
+/// Looks up a name in the hosts table and the cache.
+fn lookup_local(name: &str, qtype: DnsRecordType) -> Option<Vec<IpAddr>> {
+    let hosts = with_config(|config| {
+        config.hosts.get(name).map(|addrs| {
+            addrs
+                .iter()
+                .filter(|addr| qtype.matches(addr))
+                .copied()
+                .collect::<Vec<_>>()
+        })
+    });
+    hosts.or_else(|| cache_lookup(name, qtype))
+}
+
+fn into_result(addrs: Vec<IpAddr>) -> AxResult<Vec<IpAddr>> {
+    if addrs.is_empty() {
+        ax_err!(NotFound, "dns query: no such host")
+    } else {
+        Ok(addrs)
+    }
+}
+
+/// The answer of a name, `addrs` is empty if the name does not exist or has
+/// no records of the queried type.
+struct Resolved {
+    addrs: Vec<IpAddr>,
+    ttl: u32,
+}
+
+/// A query which has been sent to the name servers.
+struct PendingQuery {
+    socket: UdpSocket,
+    /// The name currently queried, it differs from the original name when
+    /// following a CNAME chain.
+    name: String,
+    cname_depth: usize,
+    /// The minimum TTL of the CNAME records followed so far.
+    ttl: u32,
+    id: u16,
+    request: Vec<u8>,
+    servers: Vec<SocketAddr>,
+    timeout: Duration,
+    tries: usize,
+    max_tries: usize,
+    deadline: Duration,
+}
+
+impl PendingQuery {
+    fn start(name: String, qtype: DnsRecordType) -> AxResult<Self> {
+        let (servers, timeout, attempts) = with_config(|config| {
+            let servers: Vec<SocketAddr> = config
+                .servers
+                .iter()
+                .map(|&ip| SocketAddr::new(ip, DNS_PORT))
+                .collect();
+            (servers, config.timeout, config.attempts)
+        });
+        if servers.is_empty() {
+            return ax_err!(NotFound, "dns query: no name server");
+        }
+
+        let socket = UdpSocket::new();
+        socket.set_nonblocking(true);
+        socket.bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))?;
+        let mut query = Self {
+            socket,
+            name: String::new(),
+            cname_depth: 0,
+            ttl: MAX_TTL,
+            id: 0,
+            request: Vec::new(),
+            max_tries: servers.len() * attempts,
+            servers,
+            timeout,
+            tries: 0,
+            deadline: Duration::ZERO,
+        };
+        query.restart(name, qtype)?;
+        Ok(query)
+    }
+
+    /// Starts querying a new name from the first name server.
+    fn restart(&mut self, name: String, qtype: DnsRecordType) -> AxResult {
+        self.id = axhal::random::random_u64() as u16;
+        self.request = message::encode_query(self.id, &name, qtype.code())?;
+        self.name = name;
+        self.tries = 0;
+        self.send()
+    }
+
+    fn send(&mut self) -> AxResult {
+        let server = self.servers[self.tries % self.servers.len()];
+        debug!("dns query: {} (id {:#x}) to {}", self.name, self.id, server);
+        self.deadline = monotonic_time() + self.timeout;
+        match self.socket.send_to(&self.request, server) {
+            // retried on timeout
+            Ok(_) | Err(AxError::WouldBlock) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sends the query to the next name server.
+    fn retry(&mut self) -> AxResult {
+        self.tries += 1;
+        if self.tries >= self.max_tries {
+            return ax_err!(
+                ConnectionRefused,
+                "dns query: no response from name servers"
+            );
+        }
+        self.send()
+    }
+
+    fn poll(&mut self, qtype: DnsRecordType) -> AxResult<Resolved> {
+        SOCKET_SET.poll_interfaces();
+        let mut buf = [0; MAX_MESSAGE_LEN];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) if self.servers.contains(&from) => {
+                    if let Some(res) = self.handle_response(&buf[..len], qtype)? {
+                        return Ok(res);
+                    }
+                }
+                Ok(_) => {}
+                Err(AxError::WouldBlock) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        if monotonic_time() >= self.deadline {
+            self.retry()?;
+        }
+        Err(AxError::WouldBlock)
+    }
+
+    fn handle_response(&mut self, buf: &[u8], qtype: DnsRecordType) -> AxResult<Option<Resolved>> {
+        let Ok(resp) = message::decode_response(buf) else {
+            return Ok(None);
+        };
+        let expected = (self.name.as_str(), qtype.code());
+        if resp.id != self.id
+            || resp.question.as_ref().map(|(n, t)| (n.as_str(), *t)) != Some(expected)
+        {
+            return Ok(None);
+        }
+        if resp.truncated {
+            // the answer is incomplete, and can't be queried over TCP
+            warn!("dns query: truncated response for {}", self.name);
+            return ax_err!(Unsupported, "dns query: truncated response");
+        }
+        let negative_ttl = || {
+            let ttl = resp.negative_ttl().unwrap_or(DEFAULT_NEGATIVE_TTL);
+            ttl.min(MAX_NEGATIVE_TTL).min(self.ttl)
+        };
+        match resp.rcode {
+            RCODE_NOERROR => {}
+            RCODE_NXDOMAIN => {
+                return Ok(Some(Resolved {
+                    addrs: Vec::new(),
+                    ttl: negative_ttl(),
+                }));
+            }
+            rcode => {
+                warn!("dns query: {} failed with rcode {}", self.name, rcode);
+                self.retry()?;
+                return Ok(None);
+            }
+        }
+
+        // follow the CNAME chain in the answer section
+        let mut name = self.name.clone();
+        let mut ttl = self.ttl;
+        loop {
+            let mut addrs = Vec::new();
+            for rec in resp.answers.iter().filter(|rec| rec.name == name) {
+                let addr = match rec.data {
+                    RecordData::A(addr) => IpAddr::V4(addr),
+                    RecordData::Aaaa(addr) => IpAddr::V6(addr),
+                    _ => continue,
+                };
+                if qtype.matches(&addr) {
+                    addrs.push(addr);
+                    ttl = ttl.min(rec.ttl);
+                }
+            }
+            if !addrs.is_empty() {
+                return Ok(Some(Resolved { addrs, ttl }));
+            }
+
+            let cname = resp.answers.iter().find_map(|rec| match &rec.data {
+                RecordData::Cname(target) if rec.name == name => Some((target, rec.ttl)),
+                _ => None,
+            });
+            let Some((target, cname_ttl)) = cname else {
+                break;
+            };
+            self.cname_depth += 1;
+            if self.cname_depth > MAX_CNAME_DEPTH {
+                return ax_err!(InvalidData, "dns query: too many CNAMEs");
+            }
+            name = target.clone();
+            ttl = ttl.min(cname_ttl);
+        }
+
+        if name == self.name {
+            // the name exists but has no records of this type
+            return Ok(Some(Resolved {
+                addrs: Vec::new(),
+                ttl: negative_ttl(),
+            }));
+        }
+        // the server didn't resolve the target of the CNAME, query it again
+        self.ttl = ttl;
+        if let Some(addrs) = lookup_local(&name, qtype) {
+            return Ok(Some(Resolved { addrs, ttl }));
+        }
+        self.restart(name, qtype)?;
+        Ok(None)
+    }
+}
+
+enum QueryState {
+    Done(Option<AxResult<Vec<IpAddr>>>),
+    Pending(Box<PendingQuery>),
+}
+
+/// A DNS query which can be polled without blocking.
+///
+/// # Examples
+///
+/// ```ignore
+/// let mut query = DnsQuery::new("example.com", DnsRecordType::A)?;
+/// let addrs = loop {
+///     match query.poll() {
+///         Err(AxError::WouldBlock) => do_something_else(),
+///         res => break res?,
+///     }
+/// };
+/// ```
+pub struct DnsQuery {
+    name: String,
+    qtype: DnsRecordType,
+    state: QueryState,
+}
+
+impl DnsQuery {
+    /// Starts a query of the records of `qtype` for `name`.
+    ///
+    /// If the name is found in the hosts table or the cache, or is an IP
+    /// address literal, the query is finished immediately.
+    pub fn new(name: &str, qtype: DnsRecordType) -> AxResult<Self> {
+        let name = name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase();
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return ax_err!(InvalidInput, "dns query: invalid name");
+        }
+
+        let local = match name.parse::<IpAddr>() {
+            Ok(addr) => Some(if qtype.matches(&addr) {
+                alloc::vec![addr]
+            } else {
+                Vec::new()
+            }),
+            Err(_) => lookup_local(&name, qtype),
+        };
+        let state = match local {
+            Some(addrs) => QueryState::Done(Some(into_result(addrs))),
+            None => QueryState::Pending(Box::new(PendingQuery::start(name.clone(), qtype)?)),
+        };
+        Ok(Self { name, qtype, state })
+    }
+
+    /// Returns the queried name, normalized to lower case without the
+    /// trailing dot.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type of the queried records.
+    pub fn record_type(&self) -> DnsRecordType {
+        self.qtype
+    }
+
+    /// Polls the query.
+    ///
+    /// Returns [`Err(WouldBlock)`](AxError::WouldBlock) if the query is still
+    /// pending, or [`Err(NotFound)`](AxError::NotFound) if the name has no
+    /// records of the queried type. The result is returned only once, later
+    /// calls return [`Err(BadState)`](AxError::BadState).
+    pub fn poll(&mut self) -> AxResult<Vec<IpAddr>> {
+        let pending = match &mut self.state {
+            QueryState::Done(res) => {
+                return res
+                    .take()
+                    .unwrap_or_else(|| ax_err!(BadState, "dns query: already finished"));
+            }
+            QueryState::Pending(pending) => pending,
+        };
+        let res = match pending.poll(self.qtype) {
+            Err(AxError::WouldBlock) => return Err(AxError::WouldBlock),
+            Ok(Resolved { addrs, ttl }) => {
+                cache_insert(
+                    self.name.clone(),
+                    self.qtype,
+                    addrs.clone(),
+                    ttl.min(MAX_TTL),
+                );
+                into_result(addrs)
+            }
+            Err(e) => Err(e),
+        };
+        self.state = QueryState::Done(None);
+        res
+    }
+
+    /// Blocks until the query is finished and returns the result.
+    pub fn wait(mut self) -> AxResult<Vec<IpAddr>> {
+        loop {
+            match self.poll() {
+                Err(AxError::WouldBlock) => axtask::yield_now(),
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Queries the IPv4 addresses of a domain name.
+pub fn dns_query(name: &str) -> AxResult<Vec<IpAddr>> {
+    dns_lookup(name, DnsRecordType::A)
+}
+
+/// Queries the records of the given type of a domain name, blocks until the
+/// query is finished.
+pub fn dns_lookup(name: &str, qtype: DnsRecordType) -> AxResult<Vec<IpAddr>> {
+    DnsQuery::new(name, qtype)?.wait()
+}
+
+/// Returns the name servers in use.
+pub fn dns_servers() -> Vec<IpAddr> {
+    with_config(|config| config.servers.clone())
+}
+
+/// Replaces the name servers, only IPv4 servers are supported.
+///
+/// It overrides the servers in `/etc/resolv.conf` until the next
+/// [`reload_dns_config`].
+pub fn set_dns_servers(servers: &[IpAddr]) -> AxResult {
+    if servers.is_empty() || !servers.iter().all(IpAddr::is_ipv4) {
+        return ax_err!(InvalidInput, "set_dns_servers: invalid servers");
+    }
+    lock_config().servers = servers.to_vec();
+    Ok(())
+}
+
+/// Removes all the cached answers.
+pub fn flush_dns_cache() {
+    CACHE.lock().clear();
+}
+
+/// Reloads the hosts table and the name servers, and flushes the cache.
+pub fn reload_dns_config() {
+    let config = ResolverConfig::load();
+    *CONFIG.lock() = config;
+    flush_dns_cache();
+}
@@ -0,0 +1,246 @@
+use alloc::collections::BTreeSet;
+use core::net::IpAddr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{AxError, AxResult, ax_err, ax_err_type};
+use axio::PollState;
+use axsync::Mutex;
+use spin::RwLock;
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::icmp::{self, BindError, SendError};
+
+use super::addr::{from_core_ipaddr, into_core_ipaddr};
+use super::{SOCKET_SET, SocketSetWrapper};
+
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_HEADER_LEN: usize = 8;
+
+/// The identifiers the ICMP sockets are bound to.
+static BOUND_IDENTS: Mutex<BTreeSet<u16>> = Mutex::new(BTreeSet::new());
+
+/// An ICMP socket that provides POSIX-like APIs.
+///
+/// It behaves like the Linux "ping socket" (`SOCK_DGRAM` with `IPPROTO_ICMP`):
+/// the socket is bound to an identifier, the identifier field of each echo
+/// request sent is overwritten with it, and only the echo replies carrying the
+/// same identifier are received.
+///
+/// The data sent and received is a whole ICMP message, including the ICMP
+/// header. The checksum is always computed by the network stack.
+pub struct IcmpSocket {
+    handle: SocketHandle,
+    ident: RwLock<Option<u16>>,
+    nonblock: AtomicBool,
+}
+
+impl IcmpSocket {
+    /// Creates a new ICMP socket.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let socket = SocketSetWrapper::new_icmp_socket();
+        let handle = SOCKET_SET.add(socket);
+        Self {
+            handle,
+            ident: RwLock::new(None),
+            nonblock: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the identifier this socket is bound to, or
+    /// [`Err(NotConnected)`](AxError::NotConnected) if not bound.
+    pub fn ident(&self) -> AxResult<u16> {
+        self.ident.read().ok_or(AxError::NotConnected)
+    }
+
+    /// Returns whether this socket is in nonblocking mode.
+    #[inline]
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this ICMP socket into or out of nonblocking mode.
+    ///
+    /// This will result in `recv_from` and `send_to` operations becoming
+    /// nonblocking, i.e., immediately returning from their calls. If the IO
+    /// operation is successful, `Ok` is returned and no further action is
+    /// required. If the IO operation could not be completed and needs to be
+    /// retried, an error with kind [`Err(WouldBlock)`](AxError::WouldBlock) is
+    /// returned.
+    #[inline]
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    /// Binds an unbound socket to the given ICMP identifier.
+    ///
+    /// If the given identifier is 0, it generates one automatically. Returns
+    /// [`Err(AddrInUse)`](AxError::AddrInUse) if another socket is bound to
+    /// the identifier.
+    pub fn bind(&self, mut ident: u16) -> AxResult {
+        let mut self_ident = self.ident.write();
+        if self_ident.is_some() {
+            return ax_err!(InvalidInput, "socket bind() failed: already bound");
+        }
+        let mut bound_idents = BOUND_IDENTS.lock();
+        if ident == 0 {
+            ident = get_ephemeral_ident(&bound_idents)
+                .ok_or_else(|| ax_err_type!(AddrInUse, "socket bind() failed: no free ident"))?;
+        } else if bound_idents.contains(&ident) {
+            return ax_err!(AddrInUse, "socket bind() failed");
+        }
+
+        SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(self.handle, |socket| {
+            socket
+                .bind(icmp::Endpoint::Ident(ident))
+                .or_else(|e| match e {
+                    BindError::InvalidState => ax_err!(AlreadyExists, "socket bind() failed"),
+                    BindError::Unaddressable => ax_err!(InvalidInput, "socket bind() failed"),
+                })
+        })?;
+
+        bound_idents.insert(ident);
+        *self_ident = Some(ident);
+        debug!("ICMP socket {}: bound on ident {}", self.handle, ident);
+        Ok(())
+    }
+
+    /// Sends an ICMP message to the given address. On success, returns the
+    /// number of bytes written.
+    ///
+    /// The socket is bound to an automatically generated identifier if it is
+    /// not bound yet.
+    pub fn send_to(&self, buf: &[u8], remote_addr: IpAddr) -> AxResult<usize> {
+        if remote_addr.is_unspecified() {
+            return ax_err!(InvalidInput, "socket send_to() failed: invalid address");
+        }
+        if buf.len() < ICMP_HEADER_LEN {
+            return ax_err!(InvalidInput, "socket send_to() failed: message too short");
+        }
+        if self.ident.read().is_none() {
+            self.bind(0)?;
+        }
+        let ident = self.ident()?;
+        let remote_addr = from_core_ipaddr(remote_addr);
+
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(self.handle, |socket| {
+                if socket.can_send() {
+                    let packet = socket.send(buf.len(), remote_addr).map_err(|e| match e {
+                        SendError::BufferFull => AxError::WouldBlock,
+                        SendError::Unaddressable => {
+                            ax_err_type!(ConnectionRefused, "socket send_to() failed")
+                        }
+                    })?;
+                    packet.copy_from_slice(buf);
+                    if packet[0] == ICMP_ECHO_REQUEST {
+                        packet[4..6].copy_from_slice(&ident.to_be_bytes());
+                    }
+                    Ok(buf.len())
+                } else {
+                    // tx buffer is full
+                    Err(AxError::WouldBlock)
+                }
+            })
+        })
+    }
+
+    /// Receives a single ICMP message on the socket. On success, returns the
+    /// number of bytes read and the origin.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+        if self.ident.read().is_none() {
+            return ax_err!(NotConnected, "socket recv_from() failed");
+        }
+
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(self.handle, |socket| {
+                if socket.can_recv() {
+                    // data available
+                    let (len, addr) = socket
+                        .recv_slice(buf)
+                        .map_err(|_| ax_err_type!(BadState, "socket recv_from() failed"))?;
+                    Ok((len, into_core_ipaddr(addr)))
+                } else {
+                    // no more data
+                    Err(AxError::WouldBlock)
+                }
+            })
+        })
+    }
+
+    /// Close the socket.
+    pub fn shutdown(&self) -> AxResult {
+        // ICMP sockets are connectionless, nothing to do.
+        debug!("ICMP socket {}: shutting down", self.handle);
+        Ok(())
+    }
+
+    /// Whether the socket is readable or writable.
+    pub fn poll(&self) -> AxResult<PollState> {
+        if self.ident.read().is_none() {
+            return Ok(PollState {
+                readable: false,
+                writable: true,
+            });
+        }
+        SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(self.handle, |socket| {
+            Ok(PollState {
+                readable: socket.can_recv(),
+                writable: socket.can_send(),
+            })
+        })
+    }
+}
+
+/// Private methods
+impl IcmpSocket {
+    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        if self.is_nonblocking() {
+            f()
+        } else {
+            loop {
+                SOCKET_SET.poll_interfaces();
+                match f() {
+                    Ok(t) => return Ok(t),
+                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for IcmpSocket {
+    fn drop(&mut self) {
+        self.shutdown().ok();
+        SOCKET_SET.remove(self.handle);
+        if let Some(ident) = *self.ident.get_mut() {
+            BOUND_IDENTS.lock().remove(&ident);
+        }
+    }
+}
+
+/// Returns the next identifier not in `bound_idents`, or `None` if all of
+/// them are in use.
+fn get_ephemeral_ident(bound_idents: &BTreeSet<u16>) -> Option<u16> {
+    const IDENT_START: u16 = 0x4000;
+    const IDENT_END: u16 = 0xffff;
+    static CURR: Mutex<u16> = Mutex::new(IDENT_START);
+    let mut curr = CURR.lock();
+
+    for _ in IDENT_START..=IDENT_END {
+        let ident = *curr;
+        if *curr == IDENT_END {
+            *curr = IDENT_START;
+        } else {
+            *curr += 1;
+        }
+        if !bound_idents.contains(&ident) {
+            return Some(ident);
+        }
+    }
+    None
+}
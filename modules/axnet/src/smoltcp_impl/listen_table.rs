@@ -1,5 +1,6 @@
-use alloc::{boxed::Box, collections::VecDeque};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
 use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use axerrno::{AxError, AxResult, ax_err};
 use axsync::Mutex;
@@ -7,20 +8,66 @@ use smoltcp::iface::{SocketHandle, SocketSet};
 use smoltcp::socket::tcp::{self, State};
 use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
-use super::{LISTEN_QUEUE_SIZE, SOCKET_SET, SocketSetWrapper};
+use super::{LISTEN_QUEUE_SIZE, RxAction, SOCKET_SET, SocketSetWrapper};
 
 const PORT_NUM: usize = 65536;
 
+/// The maximum backlog of listening sockets, as `net.core.somaxconn` on Linux.
+static SOMAXCONN: AtomicUsize = AtomicUsize::new(4096);
+
+/// Returns the maximum backlog of listening TCP sockets.
+pub fn somaxconn() -> usize {
+    SOMAXCONN.load(Ordering::Relaxed)
+}
+
+/// Sets the maximum backlog of listening TCP sockets, the larger backlogs
+/// passed to later `listen` calls are truncated to it.
+pub fn set_somaxconn(somaxconn: usize) {
+    SOMAXCONN.store(somaxconn.max(1), Ordering::Relaxed);
+}
+
+/// Statistics of a listening TCP socket.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpListenStats {
+    /// The maximum number of established connections waiting to be accepted.
+    pub backlog: usize,
+    /// The number of established connections waiting to be accepted.
+    pub accept_queue_len: usize,
+    /// The number of half-open connections, i.e., SYN received but the
+    /// handshake is not completed.
+    pub syn_queue_len: usize,
+    /// The number of SYNs dropped because the accept queue is full.
+    pub accept_queue_overflows: u64,
+    /// The number of half-open connections dropped because the SYN queue is
+    /// full, which usually indicates a SYN flood.
+    pub syn_queue_overflows: u64,
+}
+
 struct ListenTableEntry {
     listen_endpoint: IpListenEndpoint,
     syn_queue: VecDeque<SocketHandle>,
+    backlog: usize,
+    rx_buf_len: usize,
+    tx_buf_len: usize,
+    accept_queue_overflows: u64,
+    syn_queue_overflows: u64,
 }
 
 impl ListenTableEntry {
-    pub fn new(listen_endpoint: IpListenEndpoint) -> Self {
+    pub fn new(
+        listen_endpoint: IpListenEndpoint,
+        backlog: usize,
+        rx_buf_len: usize,
+        tx_buf_len: usize,
+    ) -> Self {
         Self {
             listen_endpoint,
-            syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            syn_queue: VecDeque::with_capacity(backlog),
+            backlog,
+            rx_buf_len,
+            tx_buf_len,
+            accept_queue_overflows: 0,
+            syn_queue_overflows: 0,
         }
     }
 
@@ -57,16 +104,27 @@ impl ListenTable {
         Self { tcp }
     }
 
-    pub fn can_listen(&self, port: u16) -> bool {
-        self.tcp[port as usize].lock().is_none()
-    }
-
-    pub fn listen(&self, listen_endpoint: IpListenEndpoint) -> AxResult {
+    /// Starts listening on the given endpoint.
+    ///
+    /// At most `backlog` established connections can wait to be accepted, it
+    /// is clamped to `1..=somaxconn()`.
+    pub fn listen(
+        &self,
+        listen_endpoint: IpListenEndpoint,
+        backlog: usize,
+        rx_buf_len: usize,
+        tx_buf_len: usize,
+    ) -> AxResult {
         let port = listen_endpoint.port;
         assert_ne!(port, 0);
         let mut entry = self.tcp[port as usize].lock();
         if entry.is_none() {
-            *entry = Some(Box::new(ListenTableEntry::new(listen_endpoint)));
+            *entry = Some(Box::new(ListenTableEntry::new(
+                listen_endpoint,
+                backlog.clamp(1, somaxconn()),
+                rx_buf_len,
+                tx_buf_len,
+            )));
             Ok(())
         } else {
             ax_err!(AddrInUse, "socket listen() failed")
@@ -86,9 +144,57 @@ impl ListenTable {
         }
     }
 
+    pub fn stats(&self, port: u16) -> AxResult<TcpListenStats> {
+        if let Some(entry) = self.tcp[port as usize].lock().deref() {
+            let mut stats = TcpListenStats {
+                backlog: entry.backlog,
+                accept_queue_overflows: entry.accept_queue_overflows,
+                syn_queue_overflows: entry.syn_queue_overflows,
+                ..Default::default()
+            };
+            for &handle in &entry.syn_queue {
+                match SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| socket.state()) {
+                    State::SynReceived => stats.syn_queue_len += 1,
+                    state if is_established(state) => stats.accept_queue_len += 1,
+                    _ => {}
+                }
+            }
+            Ok(stats)
+        } else {
+            ax_err!(InvalidInput, "socket stats() failed: not listen")
+        }
+    }
+
+    /// Returns the endpoints being listened on, with the number of established
+    /// connections waiting to be accepted on each of them.
+    pub fn listeners(&self) -> Vec<(IpListenEndpoint, usize)> {
+        let mut listeners = Vec::new();
+        for entry in self.tcp.iter() {
+            if let Some(entry) = entry.lock().deref() {
+                let accept_queue_len = entry
+                    .syn_queue
+                    .iter()
+                    .filter(|&&handle| is_connected(handle))
+                    .count();
+                listeners.push((entry.listen_endpoint, accept_queue_len));
+            }
+        }
+        listeners
+    }
+
     pub fn accept(&self, port: u16) -> AxResult<(SocketHandle, (IpEndpoint, IpEndpoint))> {
         if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
             let syn_queue = &mut entry.syn_queue;
+            // connections closed before being accepted are dropped
+            syn_queue.retain(|&handle| {
+                let state =
+                    SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| socket.state());
+                let closed = matches!(state, State::Closed | State::TimeWait);
+                if closed {
+                    SOCKET_SET.remove(handle);
+                }
+                !closed
+            });
             let (idx, addr_tuple) = syn_queue
                 .iter()
                 .enumerate()
@@ -110,23 +216,54 @@ impl ListenTable {
         }
     }
 
+    /// Prepares a socket for the incoming SYN packet, and decides whether the
+    /// packet should be dropped.
     pub fn incoming_tcp_packet(
         &self,
         src: IpEndpoint,
         dst: IpEndpoint,
         sockets: &mut SocketSet<'_>,
-    ) {
+    ) -> RxAction {
         if let Some(entry) = self.tcp[dst.port as usize].lock().deref_mut() {
             if !entry.can_accept(dst.addr) {
                 // not listening on this address
-                return;
+                return RxAction::Process;
+            }
+
+            let (mut listening, mut half_open, mut established) = (0, 0, 0);
+            for &handle in &entry.syn_queue {
+                match sockets.get::<tcp::Socket>(handle).state() {
+                    State::Listen => listening += 1,
+                    State::SynReceived => half_open += 1,
+                    state if is_established(state) => established += 1,
+                    _ => {}
+                }
             }
-            if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
-                // SYN queue is full, drop the packet
-                warn!("SYN queue overflow!");
-                return;
+            if established >= entry.backlog {
+                // accept queue is full, drop the SYN as Linux does, the peer
+                // will retransmit it later.
+                debug!("TCP accept queue overflow on {}", entry.listen_endpoint);
+                entry.accept_queue_overflows += 1;
+                return RxAction::Drop;
             }
-            let mut socket = SocketSetWrapper::new_tcp_socket();
+            if listening > 0 {
+                // there is a socket ready for the connection
+                return RxAction::Process;
+            }
+            if half_open >= LISTEN_QUEUE_SIZE {
+                // SYN queue is full (SYN flood?), drop the oldest half-open
+                // connection to make room for the new one.
+                warn!("SYN queue overflow on {}", entry.listen_endpoint);
+                let idx = entry.syn_queue.iter().position(|&handle| {
+                    sockets.get::<tcp::Socket>(handle).state() == State::SynReceived
+                });
+                if let Some(handle) = idx.and_then(|idx| entry.syn_queue.remove(idx)) {
+                    sockets.remove(handle);
+                }
+                entry.syn_queue_overflows += 1;
+            }
+
+            let mut socket = SocketSetWrapper::new_tcp_socket(entry.rx_buf_len, entry.tx_buf_len);
             if socket.listen(entry.listen_endpoint).is_ok() {
                 let handle = sockets.add(socket);
                 debug!(
@@ -136,13 +273,19 @@ impl ListenTable {
                 entry.syn_queue.push_back(handle);
             }
         }
+        RxAction::Process
     }
 }
 
+/// Whether a connection waiting to be accepted is still established, the
+/// peer may have closed its side. Those closed before being accepted don't
+/// count in the backlog.
+fn is_established(state: State) -> bool {
+    matches!(state, State::Established | State::CloseWait)
+}
+
 fn is_connected(handle: SocketHandle) -> bool {
-    SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
-        !matches!(socket.state(), State::Listen | State::SynReceived)
-    })
+    SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| is_established(socket.state()))
 }
 
 fn get_addr_tuple(handle: SocketHandle) -> (IpEndpoint, IpEndpoint) {
@@ -1,12 +1,18 @@
 mod addr;
 mod bench;
+#[cfg(feature = "capture")]
+mod capture;
 mod dns;
+mod icmp;
 mod listen_table;
+mod port_manager;
+mod raw;
+pub mod stats;
 mod tcp;
 mod udp;
 
 use alloc::vec;
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::ops::DerefMut;
 
 use axdriver::prelude::*;
@@ -18,11 +24,23 @@ use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
 use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
 use smoltcp::socket::{self, AnySocket};
 use smoltcp::time::Instant;
-use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpProtocol, IpVersion};
 
 use self::listen_table::ListenTable;
-
-pub use self::dns::dns_query;
+use self::port_manager::PortBinding;
+use self::stats::InterfaceCounters;
+
+#[cfg(feature = "capture")]
+pub use self::capture::{
+    CONSOLE_PREFIX, CaptureConfig, CaptureMode, capture_to_pcap, start_capture, stop_capture,
+};
+pub use self::dns::{
+    DnsQuery, DnsRecordType, dns_lookup, dns_query, dns_servers, flush_dns_cache,
+    reload_dns_config, set_dns_servers,
+};
+pub use self::icmp::IcmpSocket;
+pub use self::listen_table::{TcpListenStats, set_somaxconn, somaxconn};
+pub use self::raw::RawSocket;
 pub use self::tcp::TcpSocket;
 pub use self::udp::UdpSocket;
 
@@ -37,9 +55,13 @@ macro_rules! env_or_default {
 
 const IP: &str = env_or_default!("AX_IP");
 const GATEWAY: &str = env_or_default!("AX_GW");
-const DNS_SEVER: &str = "8.8.8.8";
 const IP_PREFIX: u8 = 24;
 
+// used by the software NIC of the tests if `AX_IP` is not set, same as the
+// defaults in the Makefile
+const CHANNEL_NET_IP: &str = "10.0.2.15";
+const CHANNEL_NET_GATEWAY: &str = "10.0.2.2";
+
 const STANDARD_MTU: usize = 1500;
 
 const RANDOM_SEED: u64 = 0xA2CE_05A2_CE05_A2CE;
@@ -48,16 +70,28 @@ const TCP_RX_BUF_LEN: usize = 64 * 1024;
 const TCP_TX_BUF_LEN: usize = 64 * 1024;
 const UDP_RX_BUF_LEN: usize = 64 * 1024;
 const UDP_TX_BUF_LEN: usize = 64 * 1024;
+const ICMP_RX_BUF_LEN: usize = 16 * 1024;
+const ICMP_TX_BUF_LEN: usize = 16 * 1024;
+const RAW_RX_BUF_LEN: usize = 64 * 1024;
+const RAW_TX_BUF_LEN: usize = 64 * 1024;
+const MIN_SOCKET_BUF_LEN: usize = 2 * 1024;
+const MAX_SOCKET_BUF_LEN: usize = 4 * 1024 * 1024;
 const LISTEN_QUEUE_SIZE: usize = 512;
 
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
 static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
 static ETH0: LazyInit<InterfaceWrapper> = LazyInit::new();
 
-struct SocketSetWrapper<'a>(Mutex<SocketSet<'a>>);
+struct SocketSetWrapper<'a> {
+    sockets: Mutex<SocketSet<'a>>,
+    /// The TCP sockets closed by the user but still draining, e.g. in
+    /// `TIME_WAIT`, and the bindings of their ports.
+    closing: Mutex<Vec<(SocketHandle, Option<PortBinding>)>>,
+}
 
 struct DeviceWrapper {
     inner: RefCell<AxNetDevice>, // use `RefCell` is enough since it's wrapped in `Mutex` in `InterfaceWrapper`.
+    counters: InterfaceCounters,
 }
 
 struct InterfaceWrapper {
@@ -69,34 +103,57 @@ struct InterfaceWrapper {
 
 impl<'a> SocketSetWrapper<'a> {
     fn new() -> Self {
-        Self(Mutex::new(SocketSet::new(vec![])))
+        Self {
+            sockets: Mutex::new(SocketSet::new(vec![])),
+            closing: Mutex::new(Vec::new()),
+        }
     }
 
-    pub fn new_tcp_socket() -> socket::tcp::Socket<'a> {
-        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_RX_BUF_LEN]);
-        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_TX_BUF_LEN]);
+    pub fn new_tcp_socket(rx_buf_len: usize, tx_buf_len: usize) -> socket::tcp::Socket<'a> {
+        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; rx_buf_len]);
+        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; tx_buf_len]);
         socket::tcp::Socket::new(tcp_rx_buffer, tcp_tx_buffer)
     }
 
-    pub fn new_udp_socket() -> socket::udp::Socket<'a> {
-        let udp_rx_buffer = socket::udp::PacketBuffer::new(
+    pub fn new_udp_buffer(buf_len: usize) -> socket::udp::PacketBuffer<'a> {
+        socket::udp::PacketBuffer::new(
             vec![socket::udp::PacketMetadata::EMPTY; 8],
-            vec![0; UDP_RX_BUF_LEN],
+            vec![0; buf_len],
+        )
+    }
+
+    pub fn new_udp_socket(rx_buf_len: usize, tx_buf_len: usize) -> socket::udp::Socket<'a> {
+        let udp_rx_buffer = Self::new_udp_buffer(rx_buf_len);
+        let udp_tx_buffer = Self::new_udp_buffer(tx_buf_len);
+        socket::udp::Socket::new(udp_rx_buffer, udp_tx_buffer)
+    }
+
+    pub fn new_icmp_socket() -> socket::icmp::Socket<'a> {
+        let icmp_rx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; 8],
+            vec![0; ICMP_RX_BUF_LEN],
         );
-        let udp_tx_buffer = socket::udp::PacketBuffer::new(
-            vec![socket::udp::PacketMetadata::EMPTY; 8],
-            vec![0; UDP_TX_BUF_LEN],
+        let icmp_tx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; 8],
+            vec![0; ICMP_TX_BUF_LEN],
         );
-        socket::udp::Socket::new(udp_rx_buffer, udp_tx_buffer)
+        socket::icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer)
     }
 
-    pub fn new_dns_socket() -> socket::dns::Socket<'a> {
-        let server_addr = DNS_SEVER.parse().expect("invalid DNS server address");
-        socket::dns::Socket::new(&[server_addr], vec![])
+    pub fn new_raw_socket(protocol: IpProtocol) -> socket::raw::Socket<'a> {
+        let raw_rx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; 8],
+            vec![0; RAW_RX_BUF_LEN],
+        );
+        let raw_tx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; 8],
+            vec![0; RAW_TX_BUF_LEN],
+        );
+        socket::raw::Socket::new(IpVersion::Ipv4, protocol, raw_rx_buffer, raw_tx_buffer)
     }
 
     pub fn add<T: AnySocket<'a>>(&self, socket: T) -> SocketHandle {
-        let handle = self.0.lock().add(socket);
+        let handle = self.sockets.lock().add(socket);
         debug!("socket {}: created", handle);
         handle
     }
@@ -105,7 +162,7 @@ impl<'a> SocketSetWrapper<'a> {
     where
         F: FnOnce(&T) -> R,
     {
-        let set = self.0.lock();
+        let set = self.sockets.lock();
         let socket = set.get(handle);
         f(socket)
     }
@@ -114,19 +171,61 @@ impl<'a> SocketSetWrapper<'a> {
     where
         F: FnOnce(&mut T) -> R,
     {
-        let mut set = self.0.lock();
+        let mut set = self.sockets.lock();
         let socket = set.get_mut(handle);
         f(socket)
     }
 
     pub fn poll_interfaces(&self) {
-        ETH0.poll(&self.0);
+        ETH0.poll(&self.sockets);
+        self.reap_closed();
     }
 
     pub fn remove(&self, handle: SocketHandle) {
-        self.0.lock().remove(handle);
+        let mut sockets = self.sockets.lock();
+        sockets.remove(handle);
+        stats::prune_tcp_seqs(&sockets);
         debug!("socket {}: destroyed", handle);
     }
+
+    /// Removes a TCP socket once the connection is closed.
+    ///
+    /// The port stays bound by `binding` until then, so that it's not reused
+    /// by a new connection while the old one is still draining.
+    pub fn remove_tcp(&self, handle: SocketHandle, binding: Option<PortBinding>) {
+        let closed = self.with_socket::<socket::tcp::Socket, _, _>(handle, |socket| {
+            socket.state() == socket::tcp::State::Closed
+        });
+        if closed {
+            self.remove(handle);
+        } else {
+            debug!("socket {}: closing", handle);
+            self.closing.lock().push((handle, binding));
+        }
+    }
+
+    /// Removes the closing TCP sockets which have been closed.
+    fn reap_closed(&self) {
+        let mut closing = self.closing.lock();
+        if closing.is_empty() {
+            return;
+        }
+        let mut sockets = self.sockets.lock();
+        let len = closing.len();
+        closing.retain(|&(handle, _)| {
+            let socket = sockets.get::<socket::tcp::Socket>(handle);
+            if socket.state() == socket::tcp::State::Closed {
+                sockets.remove(handle);
+                debug!("socket {}: destroyed", handle);
+                false
+            } else {
+                true
+            }
+        });
+        if closing.len() < len {
+            stats::prune_tcp_seqs(&sockets);
+        }
+    }
 }
 
 impl InterfaceWrapper {
@@ -183,6 +282,7 @@ impl DeviceWrapper {
     fn new(inner: AxNetDevice) -> Self {
         Self {
             inner: RefCell::new(inner),
+            counters: InterfaceCounters::new(),
         }
     }
 }
@@ -212,11 +312,20 @@ impl Device for DeviceWrapper {
             Err(err) => {
                 if !matches!(err, DevError::Again) {
                     warn!("receive failed: {:?}", err);
+                    self.counters.record_rx_error();
                 }
                 return None;
             }
         };
-        Some((AxNetRxToken(&self.inner, rx_buf), AxNetTxToken(&self.inner)))
+        Some((
+            AxNetRxToken(
+                &self.inner,
+                rx_buf,
+                Cell::new(RxAction::Process),
+                &self.counters,
+            ),
+            AxNetTxToken(&self.inner, &self.counters),
+        ))
     }
 
     fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
@@ -226,7 +335,7 @@ impl Device for DeviceWrapper {
             return None;
         }
         if dev.can_transmit() {
-            Some(AxNetTxToken(&self.inner))
+            Some(AxNetTxToken(&self.inner, &self.counters))
         } else {
             None
         }
@@ -241,12 +350,27 @@ impl Device for DeviceWrapper {
     }
 }
 
-struct AxNetRxToken<'a>(&'a RefCell<AxNetDevice>, NetBufPtr);
-struct AxNetTxToken<'a>(&'a RefCell<AxNetDevice>);
+/// What to do with a received frame, decided before it's processed by smoltcp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RxAction {
+    /// Pass the frame to smoltcp.
+    Process,
+    /// Drop the frame.
+    Drop,
+}
+
+struct AxNetRxToken<'a>(
+    &'a RefCell<AxNetDevice>,
+    NetBufPtr,
+    Cell<RxAction>,
+    &'a InterfaceCounters,
+);
+struct AxNetTxToken<'a>(&'a RefCell<AxNetDevice>, &'a InterfaceCounters);
 
 impl RxToken for AxNetRxToken<'_> {
     fn preprocess(&self, sockets: &mut SocketSet<'_>) {
-        snoop_tcp_packet(self.1.packet(), sockets).ok();
+        let action = snoop_tcp_packet(self.1.packet(), sockets).unwrap_or(RxAction::Process);
+        self.2.set(action);
     }
 
     fn consume<R, F>(self, f: F) -> R
@@ -259,7 +383,16 @@ impl RxToken for AxNetRxToken<'_> {
             rx_buf.packet_len(),
             rx_buf.packet()
         );
-        let result = f(rx_buf.packet_mut());
+        #[cfg(feature = "capture")]
+        capture::capture_frame(rx_buf.packet());
+        let action = self.2.get();
+        let dropped = action == RxAction::Drop;
+        self.3.record_rx(rx_buf.packet_len(), dropped);
+        let result = match action {
+            RxAction::Process => f(rx_buf.packet_mut()),
+            // `f` must be called for the result, and smoltcp ignores empty frames
+            RxAction::Drop => f(&mut []),
+        };
         self.0.borrow_mut().recycle_rx_buffer(rx_buf).unwrap();
         result
     }
@@ -274,12 +407,26 @@ impl TxToken for AxNetTxToken<'_> {
         let mut tx_buf = dev.alloc_tx_buffer(len).unwrap();
         let ret = f(tx_buf.packet_mut());
         trace!("SEND {} bytes: {:02X?}", len, tx_buf.packet());
-        dev.transmit(tx_buf).unwrap();
+        #[cfg(feature = "capture")]
+        capture::capture_frame(tx_buf.packet());
+        self.1.record_tcp_tx(tx_buf.packet());
+        match dev.transmit(tx_buf) {
+            Ok(()) => self.1.record_tx(len),
+            Err(e) => {
+                warn!("transmit failed: {:?}", e);
+                self.1.record_tx_error();
+            }
+        }
         ret
     }
 }
 
-fn snoop_tcp_packet(buf: &[u8], sockets: &mut SocketSet<'_>) -> Result<(), smoltcp::wire::Error> {
+/// Inspects the incoming packet before it is processed by smoltcp, and
+/// decides what to do with it.
+fn snoop_tcp_packet(
+    buf: &[u8],
+    sockets: &mut SocketSet<'_>,
+) -> Result<RxAction, smoltcp::wire::Error> {
     use smoltcp::wire::{EthernetFrame, IpProtocol, Ipv4Packet, TcpPacket};
 
     let ether_frame = EthernetFrame::new_checked(buf)?;
@@ -292,10 +439,10 @@ fn snoop_tcp_packet(buf: &[u8], sockets: &mut SocketSet<'_>) -> Result<(), smolt
         let is_first = tcp_packet.syn() && !tcp_packet.ack();
         if is_first {
             // create a socket for the first incoming TCP packet, as the later accept() returns.
-            LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, sockets);
+            return Ok(LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, sockets));
         }
     }
-    Ok(())
+    Ok(RxAction::Process)
 }
 
 /// Poll the network stack.
@@ -317,11 +464,16 @@ pub fn bench_receive() {
 }
 
 pub(crate) fn init(net_dev: AxNetDevice) {
+    let (ip, gateway) = if IP.is_empty() && net_dev.device_name() == "channel-net" {
+        (CHANNEL_NET_IP, CHANNEL_NET_GATEWAY)
+    } else {
+        (IP, GATEWAY)
+    };
     let ether_addr = EthernetAddress(net_dev.mac_address().0);
     let eth0 = InterfaceWrapper::new("eth0", net_dev, ether_addr);
 
-    let ip = IP.parse().expect("invalid IP address");
-    let gateway = GATEWAY.parse().expect("invalid gateway IP address");
+    let ip = ip.parse().expect("invalid IP address");
+    let gateway = gateway.parse().expect("invalid gateway IP address");
     eth0.setup_ip_addr(ip, IP_PREFIX);
     eth0.setup_gateway(gateway);
 
@@ -333,4 +485,7 @@ pub(crate) fn init(net_dev: AxNetDevice) {
     info!("  ether:    {}", ETH0.ethernet_address());
     info!("  ip:       {}/{}", ip, IP_PREFIX);
     info!("  gateway:  {}", gateway);
+
+    #[cfg(feature = "procfs")]
+    stats::register_proc_files();
 }
@@ -0,0 +1,209 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axerrno::{AxResult, ax_err, ax_err_type};
+use axhal::random::random_u64;
+use axsync::Mutex;
+use smoltcp::wire::{IpAddress, IpListenEndpoint};
+
+const PORT_START: u16 = 0xc000;
+const PORT_END: u16 = 0xffff;
+
+static PORT_MANAGER: PortManager = PortManager::new();
+
+/// The transport protocol a port is bound for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// Socket options that control whether a port can be shared.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReuseFlags {
+    /// `SO_REUSEADDR`: the port can be shared with other sockets that also set
+    /// it, unless one of them is a TCP listener.
+    pub reuse_addr: bool,
+    /// `SO_REUSEPORT`: the port can be shared with other sockets that also set
+    /// it. TCP listeners still can't share a port, as incoming connections are
+    /// not balanced between them.
+    pub reuse_port: bool,
+}
+
+struct BindingEntry {
+    id: u64,
+    addr: Option<IpAddress>,
+    flags: ReuseFlags,
+    listening: bool,
+}
+
+impl BindingEntry {
+    /// Whether a new binding on `addr` with `flags` conflicts with this one.
+    fn conflicts_with(&self, addr: Option<IpAddress>, flags: ReuseFlags) -> bool {
+        let overlapped = match (self.addr, addr) {
+            (Some(a), Some(b)) => a == b,
+            _ => true, // the unspecified address overlaps all addresses
+        };
+        if !overlapped || (self.flags.reuse_port && flags.reuse_port) {
+            return false;
+        }
+        !(self.flags.reuse_addr && flags.reuse_addr && !self.listening)
+    }
+}
+
+struct PortManager {
+    bindings: Mutex<BTreeMap<(Protocol, u16), Vec<BindingEntry>>>,
+    next_id: AtomicU64,
+}
+
+impl PortManager {
+    const fn new() -> Self {
+        Self {
+            bindings: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    fn bind(
+        &self,
+        protocol: Protocol,
+        addr: Option<IpAddress>,
+        port: u16,
+        flags: ReuseFlags,
+    ) -> AxResult<PortBinding> {
+        let mut bindings = self.bindings.lock();
+        let ephemeral = port == 0;
+        let port = if !ephemeral {
+            let entries = bindings.get(&(protocol, port));
+            if entries.is_some_and(|e| e.iter().any(|e| e.conflicts_with(addr, flags))) {
+                return ax_err!(AddrInUse, "socket bind() failed: address in use");
+            }
+            port
+        } else {
+            // start from a random port, and choose the first one that is not
+            // used at all.
+            let num = (PORT_END - PORT_START) as u64 + 1;
+            let start = random_u64() % num;
+            (0..num)
+                .map(|i| PORT_START + ((start + i) % num) as u16)
+                .find(|&port| !bindings.contains_key(&(protocol, port)))
+                .ok_or_else(|| {
+                    warn!("no available ephemeral {:?} ports", protocol);
+                    ax_err_type!(AddrInUse, "no avaliable ports!")
+                })?
+        };
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        bindings
+            .entry((protocol, port))
+            .or_default()
+            .push(BindingEntry {
+                id,
+                addr,
+                flags,
+                listening: false,
+            });
+        Ok(PortBinding {
+            id,
+            protocol,
+            endpoint: IpListenEndpoint { addr, port },
+            ephemeral,
+        })
+    }
+
+    fn listen(&self, binding: &PortBinding) -> AxResult {
+        let key = (binding.protocol, binding.endpoint.port);
+        let mut bindings = self.bindings.lock();
+        let Some(entries) = bindings.get_mut(&key) else {
+            return Ok(());
+        };
+        // a listener cannot share the port by `SO_REUSEADDR`, or with another
+        // listener
+        let flags = entries
+            .iter()
+            .find(|e| e.id == binding.id)
+            .map_or(ReuseFlags::default(), |e| e.flags);
+        let shared = entries.iter().any(|e| {
+            e.id != binding.id
+                && (e.listening
+                    || e.conflicts_with(
+                        binding.endpoint.addr,
+                        ReuseFlags {
+                            reuse_addr: false,
+                            ..flags
+                        },
+                    ))
+        });
+        if shared {
+            return ax_err!(AddrInUse, "socket listen() failed: address in use");
+        }
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == binding.id) {
+            entry.listening = true;
+        }
+        Ok(())
+    }
+
+    fn release(&self, binding: &PortBinding) {
+        let key = (binding.protocol, binding.endpoint.port);
+        let mut bindings = self.bindings.lock();
+        if let Some(entries) = bindings.get_mut(&key) {
+            entries.retain(|e| e.id != binding.id);
+            if entries.is_empty() {
+                bindings.remove(&key);
+            }
+        }
+    }
+}
+
+/// A local address and port bound by a socket.
+///
+/// The port is released when it is dropped.
+pub struct PortBinding {
+    id: u64,
+    protocol: Protocol,
+    endpoint: IpListenEndpoint,
+    ephemeral: bool,
+}
+
+impl PortBinding {
+    /// Binds the given address and port for the protocol.
+    ///
+    /// If `port` is 0, an unused ephemeral port in `0xc000..=0xffff` is chosen
+    /// randomly. Returns [`AddrInUse`](axerrno::AxError::AddrInUse) if the
+    /// address conflicts with an existing binding.
+    pub fn bind(
+        protocol: Protocol,
+        addr: Option<IpAddress>,
+        port: u16,
+        flags: ReuseFlags,
+    ) -> AxResult<Self> {
+        PORT_MANAGER.bind(protocol, addr, port, flags)
+    }
+
+    /// Returns the bound endpoint, the address is `None` if it is unspecified.
+    #[inline]
+    pub fn endpoint(&self) -> IpListenEndpoint {
+        self.endpoint
+    }
+
+    /// Returns whether the port was chosen automatically.
+    #[inline]
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    /// Marks the binding as a TCP listener.
+    ///
+    /// Returns [`AddrInUse`](axerrno::AxError::AddrInUse) if the port is shared
+    /// with other sockets by `SO_REUSEADDR`, which is not allowed for listeners,
+    /// or with another listener.
+    pub fn listen(&self) -> AxResult {
+        PORT_MANAGER.listen(self)
+    }
+}
+
+impl Drop for PortBinding {
+    fn drop(&mut self) {
+        PORT_MANAGER.release(self);
+    }
+}
@@ -0,0 +1,172 @@
+use core::net::IpAddr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{AxError, AxResult, ax_err, ax_err_type};
+use axio::PollState;
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::raw;
+use smoltcp::wire::{IpAddress, IpProtocol, Ipv4Packet};
+
+use super::addr::into_core_ipaddr;
+use super::{SOCKET_SET, SocketSetWrapper};
+
+/// A raw IP socket that provides POSIX-like APIs.
+///
+/// A raw socket receives a copy of every incoming IPv4 packet carrying the
+/// given protocol, and sends packets of that protocol. Like a Linux raw socket
+/// with `IP_HDRINCL` set, the IPv4 header is included in the data both sent
+/// and received. The header checksum of outgoing packets is always filled in
+/// by the network stack.
+pub struct RawSocket {
+    handle: SocketHandle,
+    protocol: u8,
+    nonblock: AtomicBool,
+}
+
+impl RawSocket {
+    /// Creates a new raw socket for the given IP protocol number.
+    pub fn new(protocol: u8) -> Self {
+        let socket = SocketSetWrapper::new_raw_socket(IpProtocol::from(protocol));
+        let handle = SOCKET_SET.add(socket);
+        Self {
+            handle,
+            protocol,
+            nonblock: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the IP protocol number of this socket.
+    #[inline]
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    /// Returns whether this socket is in nonblocking mode.
+    #[inline]
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this raw socket into or out of nonblocking mode.
+    ///
+    /// This will result in `recv`, `recv_from` and `send` operations becoming
+    /// nonblocking, i.e., immediately returning from their calls. If the IO
+    /// operation is successful, `Ok` is returned and no further action is
+    /// required. If the IO operation could not be completed and needs to be
+    /// retried, an error with kind [`Err(WouldBlock)`](AxError::WouldBlock) is
+    /// returned.
+    #[inline]
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    /// Sends an IP packet on the socket. On success, returns the number of
+    /// bytes written.
+    ///
+    /// The buffer must start with an IPv4 header whose protocol field matches
+    /// the protocol of this socket, the destination is taken from the header.
+    pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        let packet = Ipv4Packet::new_checked(buf)
+            .map_err(|_| ax_err_type!(InvalidInput, "socket send() failed: malformed packet"))?;
+        if u8::from(packet.next_header()) != self.protocol {
+            return ax_err!(InvalidInput, "socket send() failed: protocol mismatch");
+        }
+
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<raw::Socket, _, _>(self.handle, |socket| {
+                if socket.can_send() {
+                    socket.send_slice(buf).map_err(|_| AxError::WouldBlock)?;
+                    Ok(buf.len())
+                } else {
+                    // tx buffer is full
+                    Err(AxError::WouldBlock)
+                }
+            })
+        })
+    }
+
+    /// Receives a single IP packet on the socket. On success, returns the
+    /// number of bytes read.
+    ///
+    /// If the packet is longer than the buffer, the rest of it is discarded.
+    pub fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.recv_impl(buf).map(|(len, _)| len)
+    }
+
+    /// Receives a single IP packet on the socket. On success, returns the
+    /// number of bytes read and the source address in the IP header.
+    ///
+    /// If the packet is longer than the buffer, the rest of it is discarded.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+        self.recv_impl(buf)
+            .map(|(len, src_addr)| (len, into_core_ipaddr(src_addr)))
+    }
+
+    /// Close the socket.
+    pub fn shutdown(&self) -> AxResult {
+        // raw sockets are connectionless, nothing to do.
+        debug!("raw socket {}: shutting down", self.handle);
+        Ok(())
+    }
+
+    /// Whether the socket is readable or writable.
+    pub fn poll(&self) -> AxResult<PollState> {
+        SOCKET_SET.with_socket_mut::<raw::Socket, _, _>(self.handle, |socket| {
+            Ok(PollState {
+                readable: socket.can_recv(),
+                writable: socket.can_send(),
+            })
+        })
+    }
+}
+
+/// Private methods
+impl RawSocket {
+    fn recv_impl(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddress)> {
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<raw::Socket, _, _>(self.handle, |socket| {
+                if socket.can_recv() {
+                    // data available, truncated like Linux if the buffer is short
+                    let packet = socket
+                        .recv()
+                        .map_err(|_| ax_err_type!(BadState, "socket recv() failed"))?;
+                    let src_addr = Ipv4Packet::new_checked(packet)
+                        .map(|packet| IpAddress::Ipv4(packet.src_addr()))
+                        .map_err(|_| ax_err_type!(BadState, "socket recv() failed"))?;
+                    let len = buf.len().min(packet.len());
+                    buf[..len].copy_from_slice(&packet[..len]);
+                    Ok((len, src_addr))
+                } else {
+                    // no more data
+                    Err(AxError::WouldBlock)
+                }
+            })
+        })
+    }
+
+    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        if self.is_nonblocking() {
+            f()
+        } else {
+            loop {
+                SOCKET_SET.poll_interfaces();
+                match f() {
+                    Ok(t) => return Ok(t),
+                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for RawSocket {
+    fn drop(&mut self) {
+        self.shutdown().ok();
+        SOCKET_SET.remove(self.handle);
+    }
+}
@@ -0,0 +1,471 @@
+//! Network statistics.
+//!
+//! It provides the counters of the network interfaces and a table of the
+//! TCP and UDP sockets. TCP retransmissions are counted by watching the
+//! sequence numbers of the segments transmitted on each connection. With the `procfs` feature, they are also rendered as
+//! `/proc/net/dev`, `/proc/net/tcp` and `/proc/net/udp` in the format of
+//! Linux, along with the tunable `/proc/sys/net/core/somaxconn`.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt::Write;
+use core::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axsync::Mutex;
+use smoltcp::iface::SocketSet;
+use smoltcp::socket::{Socket, tcp};
+use smoltcp::wire::{
+    EthernetFrame, EthernetProtocol, IpEndpoint, IpListenEndpoint, IpProtocol, Ipv4Packet,
+    TcpPacket, TcpSeqNumber,
+};
+
+use super::addr::{UNSPECIFIED_IP, into_core_sockaddr};
+use super::{ETH0, LISTEN_TABLE, SOCKET_SET};
+
+/// Counters of a network interface.
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceStats {
+    /// The name of the interface.
+    pub name: &'static str,
+    /// The number of frames received.
+    pub rx_packets: u64,
+    /// The number of bytes received.
+    pub rx_bytes: u64,
+    /// The number of errors reported by the device when receiving.
+    pub rx_errors: u64,
+    /// The number of received frames dropped before they are processed, e.g.
+    /// SYNs dropped because the accept queue is full.
+    pub rx_dropped: u64,
+    /// The number of frames transmitted.
+    pub tx_packets: u64,
+    /// The number of bytes transmitted.
+    pub tx_bytes: u64,
+    /// The number of errors reported by the device when transmitting.
+    pub tx_errors: u64,
+    /// The number of TCP segments retransmitted.
+    pub tcp_retransmits: u64,
+}
+
+/// The protocol of a socket in the socket table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketProtocol {
+    /// TCP.
+    Tcp,
+    /// UDP.
+    Udp,
+}
+
+/// The state of a socket in the socket table.
+///
+/// UDP sockets are always [`Closed`](SocketState::Closed), as Linux reports
+/// unconnected UDP sockets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketState {
+    Closed,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+}
+
+impl SocketState {
+    /// Returns the state number used by Linux in `/proc/net/tcp`.
+    pub const fn linux_code(self) -> u8 {
+        match self {
+            Self::Established => 0x01,
+            Self::SynSent => 0x02,
+            Self::SynReceived => 0x03,
+            Self::FinWait1 => 0x04,
+            Self::FinWait2 => 0x05,
+            Self::TimeWait => 0x06,
+            Self::Closed => 0x07,
+            Self::CloseWait => 0x08,
+            Self::LastAck => 0x09,
+            Self::Listen => 0x0a,
+            Self::Closing => 0x0b,
+        }
+    }
+}
+
+impl From<tcp::State> for SocketState {
+    fn from(state: tcp::State) -> Self {
+        match state {
+            tcp::State::Closed => Self::Closed,
+            tcp::State::Listen => Self::Listen,
+            tcp::State::SynSent => Self::SynSent,
+            tcp::State::SynReceived => Self::SynReceived,
+            tcp::State::Established => Self::Established,
+            tcp::State::FinWait1 => Self::FinWait1,
+            tcp::State::FinWait2 => Self::FinWait2,
+            tcp::State::CloseWait => Self::CloseWait,
+            tcp::State::Closing => Self::Closing,
+            tcp::State::LastAck => Self::LastAck,
+            tcp::State::TimeWait => Self::TimeWait,
+        }
+    }
+}
+
+/// An entry of the socket table.
+#[derive(Debug, Clone)]
+pub struct SocketStats {
+    /// The protocol of the socket.
+    pub protocol: SocketProtocol,
+    /// The local address, the IP is unspecified if the socket is bound on all
+    /// addresses.
+    pub local_addr: SocketAddr,
+    /// The remote address, or `0.0.0.0:0` if not connected.
+    pub remote_addr: SocketAddr,
+    /// The state of the socket.
+    pub state: SocketState,
+    /// The number of bytes in the receive queue. For listening sockets, it's
+    /// the number of connections waiting to be accepted.
+    pub recv_queue: usize,
+    /// The number of bytes in the send queue.
+    pub send_queue: usize,
+    /// The number of TCP segments retransmitted on the connection, always 0
+    /// for UDP sockets.
+    pub retransmits: u64,
+}
+
+/// The sequence numbers transmitted on a TCP connection.
+struct TcpTxSeq {
+    /// The initial sequence number, to tell a new connection on the same
+    /// addresses.
+    isn: TcpSeqNumber,
+    /// The sequence number following the data sent so far.
+    next: TcpSeqNumber,
+    retransmits: u64,
+}
+
+/// The TCP connections seen by the transmit path, by their local and remote
+/// endpoints.
+static TCP_TX_SEQS: Mutex<BTreeMap<(IpEndpoint, IpEndpoint), TcpTxSeq>> =
+    Mutex::new(BTreeMap::new());
+
+pub(crate) struct InterfaceCounters {
+    rx_packets: AtomicU64,
+    rx_bytes: AtomicU64,
+    rx_errors: AtomicU64,
+    rx_dropped: AtomicU64,
+    tx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+    tx_errors: AtomicU64,
+    tcp_retransmits: AtomicU64,
+}
+
+impl InterfaceCounters {
+    pub const fn new() -> Self {
+        Self {
+            rx_packets: AtomicU64::new(0),
+            rx_bytes: AtomicU64::new(0),
+            rx_errors: AtomicU64::new(0),
+            rx_dropped: AtomicU64::new(0),
+            tx_packets: AtomicU64::new(0),
+            tx_bytes: AtomicU64::new(0),
+            tx_errors: AtomicU64::new(0),
+            tcp_retransmits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_rx(&self, len: usize, dropped: bool) {
+        self.rx_packets.fetch_add(1, Ordering::Relaxed);
+        self.rx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+        if dropped {
+            self.rx_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_rx_error(&self) {
+        self.rx_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_tx(&self, len: usize) {
+        self.tx_packets.fetch_add(1, Ordering::Relaxed);
+        self.tx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_tx_error(&self) {
+        self.tx_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts the transmitted `frame` as a retransmission if it's a TCP
+    /// segment whose sequence numbers have been sent before.
+    pub fn record_tcp_tx(&self, frame: &[u8]) {
+        if let Some(true) = record_tcp_segment(frame) {
+            self.tcp_retransmits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self, name: &'static str) -> InterfaceStats {
+        InterfaceStats {
+            name,
+            rx_packets: self.rx_packets.load(Ordering::Relaxed),
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            rx_errors: self.rx_errors.load(Ordering::Relaxed),
+            rx_dropped: self.rx_dropped.load(Ordering::Relaxed),
+            tx_packets: self.tx_packets.load(Ordering::Relaxed),
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+            tx_errors: self.tx_errors.load(Ordering::Relaxed),
+            tcp_retransmits: self.tcp_retransmits.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Records the sequence numbers of a transmitted TCP segment, and returns
+/// whether it's a retransmission, or `None` if it's not a TCP segment.
+fn record_tcp_segment(frame: &[u8]) -> Option<bool> {
+    let frame = EthernetFrame::new_checked(frame).ok()?;
+    if frame.ethertype() != EthernetProtocol::Ipv4 {
+        return None;
+    }
+    let packet = Ipv4Packet::new_checked(frame.payload()).ok()?;
+    if packet.next_header() != IpProtocol::Tcp {
+        return None;
+    }
+    let segment = TcpPacket::new_checked(packet.payload()).ok()?;
+    let len = segment.payload().len() + segment.syn() as usize + segment.fin() as usize;
+    if len == 0 {
+        return Some(false); // pure ACKs and RSTs are not retransmitted
+    }
+    let local = IpEndpoint::new(packet.src_addr().into(), segment.src_port());
+    let remote = IpEndpoint::new(packet.dst_addr().into(), segment.dst_port());
+    let (seq, end) = (segment.seq_number(), segment.seq_number() + len);
+
+    let mut seqs = TCP_TX_SEQS.lock();
+    let entry = seqs.entry((local, remote)).or_insert(TcpTxSeq {
+        isn: seq,
+        next: seq,
+        retransmits: 0,
+    });
+    if segment.syn() && seq != entry.isn {
+        // a new connection on the same addresses
+        *entry = TcpTxSeq {
+            isn: seq,
+            next: seq,
+            retransmits: 0,
+        };
+    }
+    let retransmit = seq < entry.next;
+    if retransmit {
+        entry.retransmits += 1;
+    }
+    if end > entry.next {
+        entry.next = end;
+    }
+    Some(retransmit)
+}
+
+/// Forgets the TCP connections no longer in the socket set.
+pub(crate) fn prune_tcp_seqs(set: &SocketSet) {
+    let mut seqs = TCP_TX_SEQS.lock();
+    if seqs.is_empty() {
+        return;
+    }
+    let live: Vec<_> = set
+        .iter()
+        .filter_map(|(_, socket)| match socket {
+            Socket::Tcp(socket) => Some((socket.local_endpoint()?, socket.remote_endpoint()?)),
+            _ => None,
+        })
+        .collect();
+    seqs.retain(|key, _| live.contains(key));
+}
+
+/// Returns the number of TCP segments retransmitted on the connection.
+fn tcp_retransmits(local: IpEndpoint, remote: Option<IpEndpoint>) -> u64 {
+    remote
+        .and_then(|remote| {
+            let seqs = TCP_TX_SEQS.lock();
+            seqs.get(&(local, remote)).map(|seq| seq.retransmits)
+        })
+        .unwrap_or(0)
+}
+
+/// Returns the counters of all network interfaces.
+pub fn interface_stats() -> Vec<InterfaceStats> {
+    let counters = ETH0.dev.lock().counters.snapshot(ETH0.name());
+    alloc::vec![counters]
+}
+
+fn endpoint_to_sockaddr(endpoint: Option<IpEndpoint>) -> SocketAddr {
+    endpoint.map_or(
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)),
+        |ep| into_core_sockaddr(ep),
+    )
+}
+
+fn listen_endpoint_to_sockaddr(endpoint: IpListenEndpoint) -> SocketAddr {
+    into_core_sockaddr(IpEndpoint::new(
+        endpoint.addr.unwrap_or(UNSPECIFIED_IP),
+        endpoint.port,
+    ))
+}
+
+/// Returns the table of the TCP and UDP sockets.
+///
+/// Unbound sockets are not included.
+pub fn socket_stats() -> Vec<SocketStats> {
+    // listening TCP sockets are not in the socket set
+    let mut table: Vec<SocketStats> = LISTEN_TABLE
+        .listeners()
+        .into_iter()
+        .map(|(endpoint, accept_queue_len)| SocketStats {
+            protocol: SocketProtocol::Tcp,
+            local_addr: listen_endpoint_to_sockaddr(endpoint),
+            remote_addr: endpoint_to_sockaddr(None),
+            state: SocketState::Listen,
+            recv_queue: accept_queue_len,
+            send_queue: 0,
+            retransmits: 0,
+        })
+        .collect();
+
+    let set = SOCKET_SET.sockets.lock();
+    for (_, socket) in set.iter() {
+        match socket {
+            Socket::Tcp(socket) => {
+                // sockets in the SYN queue waiting for connections have no
+                // local endpoint
+                let Some(local) = socket.local_endpoint() else {
+                    continue;
+                };
+                table.push(SocketStats {
+                    protocol: SocketProtocol::Tcp,
+                    local_addr: into_core_sockaddr(local),
+                    remote_addr: endpoint_to_sockaddr(socket.remote_endpoint()),
+                    state: socket.state().into(),
+                    recv_queue: socket.recv_queue(),
+                    send_queue: socket.send_queue(),
+                    retransmits: tcp_retransmits(local, socket.remote_endpoint()),
+                });
+            }
+            Socket::Udp(socket) if socket.is_open() => {
+                table.push(SocketStats {
+                    protocol: SocketProtocol::Udp,
+                    local_addr: listen_endpoint_to_sockaddr(socket.endpoint()),
+                    remote_addr: endpoint_to_sockaddr(None),
+                    state: SocketState::Closed,
+                    recv_queue: socket.recv_queue(),
+                    send_queue: socket.send_queue(),
+                    retransmits: 0,
+                });
+            }
+            _ => {}
+        }
+    }
+    table
+}
+
+/// Renders the interface counters in the format of `/proc/net/dev`.
+pub fn render_net_dev() -> String {
+    let mut out = String::new();
+    out.push_str("Inter-|   Receive                                                |  Transmit\n");
+    out.push_str(concat!(
+        " face |bytes    packets errs drop fifo frame compressed multicast",
+        "|bytes    packets errs drop fifo colls carrier compressed\n"
+    ));
+    for s in interface_stats() {
+        writeln!(
+            out,
+            "{:>6}: {:>7} {:>7} {:>4} {:>4} {:>4} {:>5} {:>10} {:>9} {:>8} {:>7} {:>4} {:>4} {:>4} {:>5} {:>7} {:>10}",
+            s.name,
+            s.rx_bytes,
+            s.rx_packets,
+            s.rx_errors,
+            s.rx_dropped,
+            0,
+            0,
+            0,
+            0,
+            s.tx_bytes,
+            s.tx_packets,
+            s.tx_errors,
+            0,
+            0,
+            0,
+            0,
+            0
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Formats an address as in `/proc/net/tcp`, i.e., the IP in the native byte
+/// order followed by the port, both in hex.
+fn fmt_proc_addr(addr: SocketAddr) -> String {
+    let ip = match addr {
+        SocketAddr::V4(addr) => u32::from_ne_bytes(addr.ip().octets()),
+        SocketAddr::V6(_) => 0,
+    };
+    alloc::format!("{:08X}:{:04X}", ip, addr.port())
+}
+
+fn render_socket_table(protocol: SocketProtocol) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n",
+    );
+    let table = socket_stats();
+    for (i, s) in table.iter().filter(|s| s.protocol == protocol).enumerate() {
+        // timers and owners are not tracked
+        writeln!(
+            out,
+            "{:4}: {} {} {:02X} {:08X}:{:08X} 00:00000000 {:08X} {:5} {:8} {}",
+            i,
+            fmt_proc_addr(s.local_addr),
+            fmt_proc_addr(s.remote_addr),
+            s.state.linux_code(),
+            s.send_queue,
+            s.recv_queue,
+            s.retransmits,
+            0,
+            0,
+            0
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Renders the TCP sockets in the format of `/proc/net/tcp`.
+pub fn render_net_tcp() -> String {
+    render_socket_table(SocketProtocol::Tcp)
+}
+
+/// Renders the UDP sockets in the format of `/proc/net/udp`.
+pub fn render_net_udp() -> String {
+    render_socket_table(SocketProtocol::Udp)
+}
+
+#[cfg(feature = "procfs")]
+pub(crate) fn register_proc_files() {
+    for (path, read) in [
+        ("net/dev", render_net_dev as fn() -> String),
+        ("net/tcp", render_net_tcp),
+        ("net/udp", render_net_udp),
+    ] {
+        if let Err(e) = axfs::proc::register_file(path, read) {
+            warn!("failed to register /proc/{}: {:?}", path, e);
+        }
+    }
+    let read_somaxconn = || alloc::format!("{}\n", super::somaxconn());
+    let write_somaxconn = |value: &str| match value.trim().parse() {
+        Ok(n) if n > 0 => {
+            super::set_somaxconn(n);
+            Ok(())
+        }
+        _ => axerrno::ax_err!(InvalidInput),
+    };
+    if let Err(e) =
+        axfs::proc::register_tunable("sys/net/core/somaxconn", read_somaxconn, write_somaxconn)
+    {
+        warn!("failed to register /proc/sys/net/core/somaxconn: {:?}", e);
+    }
+}
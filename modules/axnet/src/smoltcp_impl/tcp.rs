@@ -1,8 +1,10 @@
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::time::Duration;
 
 use axerrno::{AxError, AxResult, ax_err, ax_err_type};
+use axhal::time::monotonic_time;
 use axio::PollState;
 use axsync::Mutex;
 
@@ -11,7 +13,11 @@ use smoltcp::socket::tcp::{self, ConnectError, State};
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use super::addr::{UNSPECIFIED_ENDPOINT, from_core_sockaddr, into_core_sockaddr, is_unspecified};
-use super::{ETH0, LISTEN_TABLE, SOCKET_SET, SocketSetWrapper};
+use super::port_manager::{PortBinding, Protocol, ReuseFlags};
+use super::{
+    ETH0, LISTEN_TABLE, MAX_SOCKET_BUF_LEN, MIN_SOCKET_BUF_LEN, SOCKET_SET, SocketSetWrapper,
+    TCP_RX_BUF_LEN, TCP_TX_BUF_LEN, TcpListenStats,
+};
 
 // State transitions:
 // CLOSED -(connect)-> BUSY -> CONNECTING -> CONNECTED -(shutdown)-> BUSY -> CLOSED
@@ -25,6 +31,51 @@ const STATE_CONNECTING: u8 = 2;
 const STATE_CONNECTED: u8 = 3;
 const STATE_LISTENING: u8 = 4;
 
+/// Socket options of a [`TcpSocket`].
+///
+/// They are kept even if the underlying socket is not created yet, and are
+/// applied once it is created.
+#[derive(Clone, Copy)]
+struct TcpOptions {
+    nodelay: bool,
+    reuse_addr: bool,
+    reuse_port: bool,
+    keep_alive: Option<Duration>,
+    linger: Option<Duration>,
+    recv_timeout: Option<Duration>,
+    send_timeout: Option<Duration>,
+    recv_buf_size: usize,
+    send_buf_size: usize,
+}
+
+impl TcpOptions {
+    const fn new() -> Self {
+        Self {
+            nodelay: false,
+            reuse_addr: false,
+            reuse_port: false,
+            keep_alive: None,
+            linger: None,
+            recv_timeout: None,
+            send_timeout: None,
+            recv_buf_size: TCP_RX_BUF_LEN,
+            send_buf_size: TCP_TX_BUF_LEN,
+        }
+    }
+
+    fn reuse_flags(&self) -> ReuseFlags {
+        ReuseFlags {
+            reuse_addr: self.reuse_addr,
+            reuse_port: self.reuse_port,
+        }
+    }
+
+    fn apply(&self, socket: &mut tcp::Socket) {
+        socket.set_nagle_enabled(!self.nodelay);
+        socket.set_keep_alive(self.keep_alive.map(into_smoltcp_duration));
+    }
+}
+
 /// A TCP socket that provides POSIX-like APIs.
 ///
 /// - [`connect`] is for TCP clients.
@@ -41,6 +92,8 @@ pub struct TcpSocket {
     local_addr: UnsafeCell<IpEndpoint>,
     peer_addr: UnsafeCell<IpEndpoint>,
     nonblock: AtomicBool,
+    opts: Mutex<TcpOptions>,
+    binding: UnsafeCell<Option<PortBinding>>,
 }
 
 unsafe impl Sync for TcpSocket {}
@@ -54,14 +107,30 @@ impl TcpSocket {
             local_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             peer_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             nonblock: AtomicBool::new(false),
+            opts: Mutex::new(TcpOptions::new()),
+            binding: UnsafeCell::new(None),
         }
     }
 
+    /// Creates a new TCP socket with the given sizes of the receive and send
+    /// buffers.
+    ///
+    /// The sizes are clamped to a reasonable range, and can be changed later by
+    /// [`set_recv_buffer_size`](Self::set_recv_buffer_size) and
+    /// [`set_send_buffer_size`](Self::set_send_buffer_size).
+    pub fn with_buffer_sizes(recv_buf_size: usize, send_buf_size: usize) -> Self {
+        let socket = Self::new();
+        socket.set_recv_buffer_size(recv_buf_size);
+        socket.set_send_buffer_size(send_buf_size);
+        socket
+    }
+
     /// Creates a new TCP socket that is already connected.
     const fn new_connected(
         handle: SocketHandle,
         local_addr: IpEndpoint,
         peer_addr: IpEndpoint,
+        opts: TcpOptions,
     ) -> Self {
         Self {
             state: AtomicU8::new(STATE_CONNECTED),
@@ -69,6 +138,8 @@ impl TcpSocket {
             local_addr: UnsafeCell::new(local_addr),
             peer_addr: UnsafeCell::new(peer_addr),
             nonblock: AtomicBool::new(false),
+            opts: Mutex::new(opts),
+            binding: UnsafeCell::new(None),
         }
     }
 
@@ -115,14 +186,179 @@ impl TcpSocket {
         self.nonblock.store(nonblocking, Ordering::Release);
     }
 
+    /// Returns whether the Nagle's algorithm is disabled on this socket.
+    #[inline]
+    pub fn nodelay(&self) -> bool {
+        self.opts.lock().nodelay
+    }
+
+    /// Enables or disables the Nagle's algorithm (`TCP_NODELAY`).
+    ///
+    /// If `nodelay` is `true`, segments are sent as soon as possible, even if
+    /// there is only a small amount of data.
+    pub fn set_nodelay(&self, nodelay: bool) {
+        self.opts.lock().nodelay = nodelay;
+        self.with_created_socket(|socket| socket.set_nagle_enabled(!nodelay));
+    }
+
+    /// Returns whether the local address can be reused (`SO_REUSEADDR`).
+    #[inline]
+    pub fn reuse_addr(&self) -> bool {
+        self.opts.lock().reuse_addr
+    }
+
+    /// Allows or disallows reusing the local address (`SO_REUSEADDR`).
+    ///
+    /// If set, the socket can be bound to a port which is bound by other
+    /// sockets that also set this option, unless one of them is listening.
+    /// It must be set before [`bind`](Self::bind).
+    pub fn set_reuse_addr(&self, reuse_addr: bool) {
+        self.opts.lock().reuse_addr = reuse_addr;
+    }
+
+    /// Returns whether the local port can be reused (`SO_REUSEPORT`).
+    #[inline]
+    pub fn reuse_port(&self) -> bool {
+        self.opts.lock().reuse_port
+    }
+
+    /// Allows or disallows reusing the local port (`SO_REUSEPORT`).
+    ///
+    /// If set, the socket can be bound to a port which is bound by other
+    /// sockets that also set this option. It must be set before
+    /// [`bind`](Self::bind). Note that only one of them can be listening.
+    pub fn set_reuse_port(&self, reuse_port: bool) {
+        self.opts.lock().reuse_port = reuse_port;
+    }
+
+    /// Returns the keep-alive interval, or `None` if keep-alive is disabled.
+    #[inline]
+    pub fn keep_alive(&self) -> Option<Duration> {
+        self.opts.lock().keep_alive
+    }
+
+    /// Enables or disables TCP keep-alive (`SO_KEEPALIVE`).
+    ///
+    /// If `interval` is `Some`, a keep-alive segment is sent every time the
+    /// connection has been idle for the given duration.
+    pub fn set_keep_alive(&self, interval: Option<Duration>) -> AxResult {
+        if interval.is_some_and(|d| d.is_zero()) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_keep_alive() failed: zero interval"
+            );
+        }
+        self.opts.lock().keep_alive = interval;
+        self.with_created_socket(|socket| {
+            socket.set_keep_alive(interval.map(into_smoltcp_duration))
+        });
+        Ok(())
+    }
+
+    /// Returns the linger timeout (`SO_LINGER`), or `None` if lingering is
+    /// disabled.
+    #[inline]
+    pub fn linger(&self) -> Option<Duration> {
+        self.opts.lock().linger
+    }
+
+    /// Sets the linger timeout (`SO_LINGER`).
+    ///
+    /// If it is `Some`, [`shutdown`](Self::shutdown) blocks until all the data
+    /// sent is acknowledged or the timeout expires. A zero timeout makes the
+    /// connection being reset (aborted) when closed, instead of performing the
+    /// normal FIN handshake.
+    pub fn set_linger(&self, linger: Option<Duration>) {
+        self.opts.lock().linger = linger;
+    }
+
+    /// Returns the timeout of receiving operations (`SO_RCVTIMEO`).
+    #[inline]
+    pub fn recv_timeout(&self) -> Option<Duration> {
+        self.opts.lock().recv_timeout
+    }
+
+    /// Sets the timeout of blocking receiving operations (`SO_RCVTIMEO`),
+    /// including [`recv`](Self::recv) and [`accept`](Self::accept).
+    ///
+    /// If the timeout expires, [`Err(WouldBlock)`](AxError::WouldBlock) is
+    /// returned. `None` means blocking indefinitely.
+    pub fn set_recv_timeout(&self, timeout: Option<Duration>) -> AxResult {
+        if timeout.is_some_and(|d| d.is_zero()) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_recv_timeout() failed: zero timeout"
+            );
+        }
+        self.opts.lock().recv_timeout = timeout;
+        Ok(())
+    }
+
+    /// Returns the timeout of sending operations (`SO_SNDTIMEO`).
+    #[inline]
+    pub fn send_timeout(&self) -> Option<Duration> {
+        self.opts.lock().send_timeout
+    }
+
+    /// Sets the timeout of blocking sending operations (`SO_SNDTIMEO`),
+    /// including [`send`](Self::send) and [`connect`](Self::connect).
+    ///
+    /// If the timeout expires, [`Err(WouldBlock)`](AxError::WouldBlock) is
+    /// returned. `None` means blocking indefinitely.
+    pub fn set_send_timeout(&self, timeout: Option<Duration>) -> AxResult {
+        if timeout.is_some_and(|d| d.is_zero()) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_send_timeout() failed: zero timeout"
+            );
+        }
+        self.opts.lock().send_timeout = timeout;
+        Ok(())
+    }
+
+    /// Returns the size of the receive buffer (`SO_RCVBUF`).
+    pub fn recv_buffer_size(&self) -> usize {
+        self.with_created_socket(|socket| socket.recv_capacity())
+            .unwrap_or_else(|| self.opts.lock().recv_buf_size)
+    }
+
+    /// Sets the size of the receive buffer (`SO_RCVBUF`).
+    ///
+    /// The size is clamped to a reasonable range. It only takes effect if the
+    /// socket is not connected or listening yet. Connections accepted by a
+    /// listening socket use its buffer sizes.
+    pub fn set_recv_buffer_size(&self, size: usize) {
+        self.opts.lock().recv_buf_size = size.clamp(MIN_SOCKET_BUF_LEN, MAX_SOCKET_BUF_LEN);
+    }
+
+    /// Returns the size of the send buffer (`SO_SNDBUF`).
+    pub fn send_buffer_size(&self) -> usize {
+        self.with_created_socket(|socket| socket.send_capacity())
+            .unwrap_or_else(|| self.opts.lock().send_buf_size)
+    }
+
+    /// Sets the size of the send buffer (`SO_SNDBUF`).
+    ///
+    /// The size is clamped to a reasonable range. It only takes effect if the
+    /// socket is not connected or listening yet. Connections accepted by a
+    /// listening socket use its buffer sizes.
+    pub fn set_send_buffer_size(&self, size: usize) {
+        self.opts.lock().send_buf_size = size.clamp(MIN_SOCKET_BUF_LEN, MAX_SOCKET_BUF_LEN);
+    }
+
     /// Connects to the given address and port.
     ///
     /// The local port is generated automatically.
     pub fn connect(&self, remote_addr: SocketAddr) -> AxResult {
         self.update_state(STATE_CLOSED, STATE_CONNECTING, || {
+            let opts = *self.opts.lock();
             // SAFETY: no other threads can read or write these fields.
-            let handle = unsafe { self.handle.get().read() }
-                .unwrap_or_else(|| SOCKET_SET.add(SocketSetWrapper::new_tcp_socket()));
+            let handle = unsafe { self.handle.get().read() }.unwrap_or_else(|| {
+                SOCKET_SET.add(SocketSetWrapper::new_tcp_socket(
+                    opts.recv_buf_size,
+                    opts.send_buf_size,
+                ))
+            });
 
             // TODO: check remote addr unreachable
             let remote_endpoint = from_core_sockaddr(remote_addr);
@@ -130,6 +366,7 @@ impl TcpSocket {
             let iface = &ETH0.iface;
             let (local_endpoint, remote_endpoint) = SOCKET_SET
                 .with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    opts.apply(socket);
                     socket
                         .connect(iface.lock().context(), remote_endpoint, bound_endpoint)
                         .or_else(|e| match e {
@@ -144,7 +381,8 @@ impl TcpSocket {
                         socket.local_endpoint().unwrap(),
                         socket.remote_endpoint().unwrap(),
                     ))
-                })?;
+                })
+                .inspect_err(|_| self.release_ephemeral_port())?;
             unsafe {
                 // SAFETY: no other threads can read or write these fields as we
                 // have changed the state to `BUSY`.
@@ -160,7 +398,7 @@ impl TcpSocket {
         if self.is_nonblocking() {
             Err(AxError::WouldBlock)
         } else {
-            self.block_on(|| {
+            self.block_on(self.send_timeout(), || {
                 let PollState { writable, .. } = self.poll_connect()?;
                 if !writable {
                     Err(AxError::WouldBlock)
@@ -175,24 +413,30 @@ impl TcpSocket {
 
     /// Binds an unbound socket to the given address and port.
     ///
-    /// If the given port is 0, it generates one automatically.
+    /// If the given port is 0, it generates one automatically. Returns
+    /// [`Err(AddrInUse)`](AxError::AddrInUse) if the address is used by other
+    /// sockets, see [`set_reuse_addr`](Self::set_reuse_addr) and
+    /// [`set_reuse_port`](Self::set_reuse_port).
     ///
     /// It's must be called before [`listen`](Self::listen) and
     /// [`accept`](Self::accept).
-    pub fn bind(&self, mut local_addr: SocketAddr) -> AxResult {
+    pub fn bind(&self, local_addr: SocketAddr) -> AxResult {
         self.update_state(STATE_CLOSED, STATE_CLOSED, || {
-            // TODO: check addr is available
-            if local_addr.port() == 0 {
-                local_addr.set_port(get_ephemeral_port()?);
-            }
-            // SAFETY: no other threads can read or write `self.local_addr` as we
-            // have changed the state to `BUSY`.
+            // SAFETY: no other threads can read or write `self.local_addr` and
+            // `self.binding` as we have changed the state to `BUSY`.
             unsafe {
-                let old = self.local_addr.get().read();
-                if old != UNSPECIFIED_ENDPOINT {
+                if (*self.binding.get()).is_some() {
                     return ax_err!(InvalidInput, "socket bind() failed: already bound");
                 }
-                self.local_addr.get().write(from_core_sockaddr(local_addr));
+                let local_endpoint = from_core_sockaddr(local_addr);
+                let addr = (!is_unspecified(local_endpoint.addr)).then_some(local_endpoint.addr);
+                let flags = self.opts.lock().reuse_flags();
+                let binding = PortBinding::bind(Protocol::Tcp, addr, local_endpoint.port, flags)?;
+                self.local_addr.get().write(IpEndpoint::new(
+                    local_endpoint.addr,
+                    binding.endpoint().port,
+                ));
+                *self.binding.get() = Some(binding);
             }
             Ok(())
         })
@@ -201,21 +445,43 @@ impl TcpSocket {
 
     /// Starts listening on the bound address and port.
     ///
+    /// At most `backlog` established connections can wait to be accepted, the
+    /// later SYNs are dropped until some of them are accepted.
+    ///
     /// It's must be called after [`bind`](Self::bind) and before
     /// [`accept`](Self::accept).
-    pub fn listen(&self) -> AxResult {
+    pub fn listen(&self, backlog: usize) -> AxResult {
         self.update_state(STATE_CLOSED, STATE_LISTENING, || {
             let bound_endpoint = self.bound_endpoint()?;
+            // SAFETY: `self.binding` is initialized by `bound_endpoint()`.
             unsafe {
+                (*self.binding.get()).as_ref().unwrap().listen()?;
                 (*self.local_addr.get()).port = bound_endpoint.port;
             }
-            LISTEN_TABLE.listen(bound_endpoint)?;
+            let opts = *self.opts.lock();
+            LISTEN_TABLE.listen(
+                bound_endpoint,
+                backlog,
+                opts.recv_buf_size,
+                opts.send_buf_size,
+            )?;
             debug!("TCP socket listening on {}", bound_endpoint);
             Ok(())
         })
         .unwrap_or(Ok(())) // ignore simultaneous `listen`s.
     }
 
+    /// Returns the statistics of the listening socket, e.g. the number of
+    /// pending connections and dropped SYNs.
+    pub fn listen_stats(&self) -> AxResult<TcpListenStats> {
+        if self.get_state() != STATE_LISTENING {
+            return ax_err!(InvalidInput, "socket listen_stats() failed: not listen");
+        }
+        // SAFETY: `self.local_addr` should be initialized in a listening socket.
+        let local_addr = unsafe { self.local_addr.get().read() };
+        LISTEN_TABLE.stats(local_addr.port)
+    }
+
     /// Accepts a new connection.
     ///
     /// This function will block the calling thread until a new TCP connection
@@ -229,26 +495,54 @@ impl TcpSocket {
 
         // SAFETY: `self.local_addr` should be initialized after `bind()`.
         let local_port = unsafe { self.local_addr.get().read().port };
-        self.block_on(|| {
+        self.block_on(self.recv_timeout(), || {
             let (handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port)?;
             debug!("TCP socket accepted a new connection {}", peer_addr);
-            Ok(TcpSocket::new_connected(handle, local_addr, peer_addr))
+            // accepted sockets inherit the options of the listener
+            let opts = *self.opts.lock();
+            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| opts.apply(socket));
+            Ok(TcpSocket::new_connected(
+                handle, local_addr, peer_addr, opts,
+            ))
         })
     }
 
     /// Close the connection.
+    ///
+    /// If the linger timeout is set, it blocks until all the data sent is
+    /// acknowledged or the timeout expires. See [`set_linger`](Self::set_linger).
     pub fn shutdown(&self) -> AxResult {
         // stream
         self.update_state(STATE_CONNECTED, STATE_CLOSED, || {
             // SAFETY: `self.handle` should be initialized in a connected socket, and
             // no other threads can read or write it.
             let handle = unsafe { self.handle.get().read().unwrap() };
+            let linger = self.linger();
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
-                debug!("TCP socket {}: shutting down", handle);
-                socket.close();
+                if linger == Some(Duration::ZERO) {
+                    debug!("TCP socket {}: aborting", handle);
+                    socket.abort();
+                } else {
+                    debug!("TCP socket {}: shutting down", handle);
+                    socket.close();
+                }
             });
+            // the port is released when the connection is closed, see `Drop`
             unsafe { self.local_addr.get().write(UNSPECIFIED_ENDPOINT) }; // clear bound address
             SOCKET_SET.poll_interfaces();
+            if let Some(timeout) = linger.filter(|t| !t.is_zero()) {
+                // wait for the remaining data to be acknowledged, ignore timeout
+                self.block_on(Some(timeout), || {
+                    SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
+                        if socket.send_queue() == 0 || !socket.is_active() {
+                            Ok(())
+                        } else {
+                            Err(AxError::WouldBlock)
+                        }
+                    })
+                })
+                .ok();
+            }
             Ok(())
         })
         .unwrap_or(Ok(()))?;
@@ -258,7 +552,10 @@ impl TcpSocket {
             // SAFETY: `self.local_addr` should be initialized in a listening socket,
             // and no other threads can read or write it.
             let local_port = unsafe { self.local_addr.get().read().port };
-            unsafe { self.local_addr.get().write(UNSPECIFIED_ENDPOINT) }; // clear bound address
+            unsafe {
+                self.local_addr.get().write(UNSPECIFIED_ENDPOINT); // clear bound address
+                *self.binding.get() = None; // release the port
+            }
             LISTEN_TABLE.unlisten(local_port);
             SOCKET_SET.poll_interfaces();
             Ok(())
@@ -271,6 +568,49 @@ impl TcpSocket {
 
     /// Receives data from the socket, stores it in the given buffer.
     pub fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.recv_impl(|socket| {
+            if !socket.may_recv() {
+                // connection closed
+                return Ok(0);
+            }
+            // copy both chunks if the data wraps around the ring buffer
+            socket
+                .recv_slice(buf)
+                .map_err(|_| ax_err_type!(BadState, "socket recv() failed"))
+        })
+    }
+
+    /// Receives data from the socket without copying it to an intermediate
+    /// buffer.
+    ///
+    /// The given function is called once with a contiguous chunk of the
+    /// received data, which may not be all the data available. It returns the
+    /// number of bytes consumed, which are then removed from the receive
+    /// buffer, and a value that is passed through to the caller. An empty chunk
+    /// means the connection has been closed by the peer.
+    pub fn recv_with<F, R>(&self, f: F) -> AxResult<R>
+    where
+        F: FnOnce(&[u8]) -> (usize, R),
+    {
+        let mut f = Some(f);
+        self.recv_impl(|socket| {
+            let f = f.take().unwrap();
+            if !socket.may_recv() {
+                // connection closed
+                return Ok(f(&[]).1);
+            }
+            socket
+                .recv(|data| f(data))
+                .map_err(|_| ax_err_type!(BadState, "socket recv() failed"))
+        })
+    }
+
+    /// Blocks until data is available or the connection is closed by the
+    /// peer, then calls `f` with the socket.
+    fn recv_impl<F, R>(&self, mut f: F) -> AxResult<R>
+    where
+        F: FnMut(&mut tcp::Socket) -> AxResult<R>,
+    {
         if self.is_connecting() {
             return Err(AxError::WouldBlock);
         } else if !self.is_connected() {
@@ -279,21 +619,14 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
+        self.block_on(self.recv_timeout(), || {
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_active() {
                     // not open
                     ax_err!(ConnectionRefused, "socket recv() failed")
-                } else if !socket.may_recv() {
-                    // connection closed
-                    Ok(0)
-                } else if socket.recv_queue() > 0 {
-                    // data available
-                    // TODO: use socket.recv(|buf| {...})
-                    let len = socket
-                        .recv_slice(buf)
-                        .map_err(|_| ax_err_type!(BadState, "socket recv() failed"))?;
-                    Ok(len)
+                } else if !socket.may_recv() || socket.recv_queue() > 0 {
+                    // connection closed or data available
+                    f(socket)
                 } else {
                     // no more data
                     Err(AxError::WouldBlock)
@@ -304,6 +637,39 @@ impl TcpSocket {
 
     /// Transmits data in the given buffer.
     pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        self.send_impl(|socket| {
+            // fill both chunks if the free space wraps around the ring buffer
+            socket
+                .send_slice(buf)
+                .map_err(|_| ax_err_type!(BadState, "socket send() failed"))
+        })
+    }
+
+    /// Transmits data by writing it directly into the send buffer.
+    ///
+    /// The given function is called once with a contiguous free chunk of the
+    /// send buffer, which may be smaller than the free space. It returns the
+    /// number of bytes written, which are then enqueued for sending, and a
+    /// value that is passed through to the caller.
+    pub fn send_with<F, R>(&self, f: F) -> AxResult<R>
+    where
+        F: FnOnce(&mut [u8]) -> (usize, R),
+    {
+        let mut f = Some(f);
+        self.send_impl(|socket| {
+            let f = f.take().unwrap();
+            socket
+                .send(|space| f(space))
+                .map_err(|_| ax_err_type!(BadState, "socket send() failed"))
+        })
+    }
+
+    /// Blocks until there is free space in the send buffer, then calls `f`
+    /// with the socket.
+    fn send_impl<F, R>(&self, mut f: F) -> AxResult<R>
+    where
+        F: FnMut(&mut tcp::Socket) -> AxResult<R>,
+    {
         if self.is_connecting() {
             return Err(AxError::WouldBlock);
         } else if !self.is_connected() {
@@ -312,18 +678,14 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
+        self.block_on(self.send_timeout(), || {
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_active() || !socket.may_send() {
                     // closed by remote
                     ax_err!(ConnectionReset, "socket send() failed")
                 } else if socket.can_send() {
                     // connected, and the tx buffer is not full
-                    // TODO: use socket.send(|buf| {...})
-                    let len = socket
-                        .send_slice(buf)
-                        .map_err(|_| ax_err_type!(BadState, "socket send() failed"))?;
-                    Ok(len)
+                    f(socket)
                 } else {
                     // tx buffer is full
                     Err(AxError::WouldBlock)
@@ -402,21 +764,48 @@ impl TcpSocket {
         self.get_state() == STATE_LISTENING
     }
 
+    /// Calls the given function with the underlying socket if it has been
+    /// created, i.e., the socket is connecting or connected.
+    fn with_created_socket<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut tcp::Socket) -> R,
+    {
+        match self.get_state() {
+            STATE_CONNECTING | STATE_CONNECTED => {
+                // SAFETY: `self.handle` should be initialized in a connecting or
+                // connected socket.
+                let handle = unsafe { self.handle.get().read().unwrap() };
+                Some(SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, f))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the bound local endpoint, binds to an ephemeral port if the
+    /// socket is not bound yet.
     fn bound_endpoint(&self) -> AxResult<IpListenEndpoint> {
-        // SAFETY: no other threads can read or write `self.local_addr`.
-        let local_addr = unsafe { self.local_addr.get().read() };
-        let port = if local_addr.port != 0 {
-            local_addr.port
-        } else {
-            get_ephemeral_port()?
-        };
-        assert_ne!(port, 0);
-        let addr = if !is_unspecified(local_addr.addr) {
-            Some(local_addr.addr)
-        } else {
-            None
-        };
-        Ok(IpListenEndpoint { addr, port })
+        // SAFETY: no other threads can read or write `self.binding`.
+        let binding = unsafe { &mut *self.binding.get() };
+        if let Some(binding) = binding {
+            return Ok(binding.endpoint());
+        }
+        let flags = self.opts.lock().reuse_flags();
+        let endpoint = binding
+            .insert(PortBinding::bind(Protocol::Tcp, None, 0, flags)?)
+            .endpoint();
+        assert_ne!(endpoint.port, 0);
+        Ok(endpoint)
+    }
+
+    /// Releases the port after `connect` fails, unless it's given explicitly
+    /// by `bind`, as Linux does.
+    fn release_ephemeral_port(&self) {
+        // SAFETY: no other threads can read or write `self.binding` before the
+        // state is changed from `BUSY` or `CONNECTING`.
+        let binding = unsafe { &mut *self.binding.get() };
+        if binding.as_ref().is_some_and(PortBinding::is_ephemeral) {
+            *binding = None;
+        }
     }
 
     fn poll_connect(&self) -> AxResult<PollState> {
@@ -439,6 +828,7 @@ impl TcpSocket {
                         self.local_addr.get().write(UNSPECIFIED_ENDPOINT);
                         self.peer_addr.get().write(UNSPECIFIED_ENDPOINT);
                     }
+                    self.release_ephemeral_port();
                     self.set_state(STATE_CLOSED); // connection failed
                     true
                 }
@@ -473,19 +863,26 @@ impl TcpSocket {
     ///
     /// If the socket is non-blocking, it calls the function once and returns
     /// immediately. Otherwise, it may call the function multiple times if it
-    /// returns [`Err(WouldBlock)`](AxError::WouldBlock).
-    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    /// returns [`Err(WouldBlock)`](AxError::WouldBlock), until the `timeout`
+    /// (if any) expires.
+    fn block_on<F, T>(&self, timeout: Option<Duration>, mut f: F) -> AxResult<T>
     where
         F: FnMut() -> AxResult<T>,
     {
         if self.is_nonblocking() {
             f()
         } else {
+            let deadline = timeout.map(|t| monotonic_time() + t);
             loop {
                 SOCKET_SET.poll_interfaces();
                 match f() {
                     Ok(t) => return Ok(t),
-                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(AxError::WouldBlock) => {
+                        if deadline.is_some_and(|ddl| monotonic_time() >= ddl) {
+                            return Err(AxError::WouldBlock);
+                        }
+                        axtask::yield_now()
+                    }
                     Err(e) => return Err(e),
                 }
             }
@@ -498,30 +895,11 @@ impl Drop for TcpSocket {
         self.shutdown().ok();
         // Safe because we have mut reference to `self`.
         if let Some(handle) = unsafe { self.handle.get().read() } {
-            SOCKET_SET.remove(handle);
+            SOCKET_SET.remove_tcp(handle, self.binding.get_mut().take());
         }
     }
 }
 
-fn get_ephemeral_port() -> AxResult<u16> {
-    const PORT_START: u16 = 0xc000;
-    const PORT_END: u16 = 0xffff;
-    static CURR: Mutex<u16> = Mutex::new(PORT_START);
-
-    let mut curr = CURR.lock();
-    let mut tries = 0;
-    // TODO: more robust
-    while tries <= PORT_END - PORT_START {
-        let port = *curr;
-        if *curr == PORT_END {
-            *curr = PORT_START;
-        } else {
-            *curr += 1;
-        }
-        if LISTEN_TABLE.can_listen(port) {
-            return Ok(port);
-        }
-        tries += 1;
-    }
-    ax_err!(AddrInUse, "no avaliable ports!")
+fn into_smoltcp_duration(d: Duration) -> smoltcp::time::Duration {
+    smoltcp::time::Duration::from_micros(d.as_micros() as u64)
 }
@@ -1,17 +1,33 @@
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
 
 use axerrno::{AxError, AxResult, ax_err, ax_err_type};
+use axhal::time::monotonic_time;
 use axio::PollState;
 use axsync::Mutex;
 use spin::RwLock;
 
 use smoltcp::iface::SocketHandle;
 use smoltcp::socket::udp::{self, BindError, SendError};
-use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
+use smoltcp::wire::IpEndpoint;
 
 use super::addr::{UNSPECIFIED_ENDPOINT, from_core_sockaddr, into_core_sockaddr, is_unspecified};
-use super::{SOCKET_SET, SocketSetWrapper};
+use super::port_manager::{PortBinding, Protocol, ReuseFlags};
+use super::{
+    MAX_SOCKET_BUF_LEN, MIN_SOCKET_BUF_LEN, SOCKET_SET, SocketSetWrapper, UDP_RX_BUF_LEN,
+    UDP_TX_BUF_LEN,
+};
+
+/// Socket options of a [`UdpSocket`].
+struct UdpOptions {
+    reuse_addr: bool,
+    reuse_port: bool,
+    recv_timeout: Option<Duration>,
+    send_timeout: Option<Duration>,
+    recv_buf_size: usize,
+    send_buf_size: usize,
+}
 
 /// A UDP socket that provides POSIX-like APIs.
 pub struct UdpSocket {
@@ -19,19 +35,42 @@ pub struct UdpSocket {
     local_addr: RwLock<Option<IpEndpoint>>,
     peer_addr: RwLock<Option<IpEndpoint>>,
     nonblock: AtomicBool,
+    opts: Mutex<UdpOptions>,
+    binding: Mutex<Option<PortBinding>>,
 }
 
 impl UdpSocket {
     /// Creates a new UDP socket.
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let socket = SocketSetWrapper::new_udp_socket();
+        Self::with_buffer_sizes(UDP_RX_BUF_LEN, UDP_TX_BUF_LEN)
+    }
+
+    /// Creates a new UDP socket with the given sizes of the receive and send
+    /// buffers.
+    ///
+    /// The sizes are clamped to a reasonable range, and can be changed later by
+    /// [`set_recv_buffer_size`](Self::set_recv_buffer_size) and
+    /// [`set_send_buffer_size`](Self::set_send_buffer_size).
+    pub fn with_buffer_sizes(recv_buf_size: usize, send_buf_size: usize) -> Self {
+        let recv_buf_size = recv_buf_size.clamp(MIN_SOCKET_BUF_LEN, MAX_SOCKET_BUF_LEN);
+        let send_buf_size = send_buf_size.clamp(MIN_SOCKET_BUF_LEN, MAX_SOCKET_BUF_LEN);
+        let socket = SocketSetWrapper::new_udp_socket(recv_buf_size, send_buf_size);
         let handle = SOCKET_SET.add(socket);
         Self {
             handle,
             local_addr: RwLock::new(None),
             peer_addr: RwLock::new(None),
             nonblock: AtomicBool::new(false),
+            opts: Mutex::new(UdpOptions {
+                reuse_addr: false,
+                reuse_port: false,
+                recv_timeout: None,
+                send_timeout: None,
+                recv_buf_size,
+                send_buf_size,
+            }),
+            binding: Mutex::new(None),
         }
     }
 
@@ -69,25 +108,143 @@ impl UdpSocket {
         self.nonblock.store(nonblocking, Ordering::Release);
     }
 
+    /// Returns whether the local address can be reused (`SO_REUSEADDR`).
+    #[inline]
+    pub fn reuse_addr(&self) -> bool {
+        self.opts.lock().reuse_addr
+    }
+
+    /// Allows or disallows reusing the local address (`SO_REUSEADDR`).
+    ///
+    /// If set, the socket can be bound to a port which is bound by other
+    /// sockets that also set this option. It must be set before
+    /// [`bind`](Self::bind).
+    pub fn set_reuse_addr(&self, reuse_addr: bool) {
+        self.opts.lock().reuse_addr = reuse_addr;
+    }
+
+    /// Returns whether the local port can be reused (`SO_REUSEPORT`).
+    #[inline]
+    pub fn reuse_port(&self) -> bool {
+        self.opts.lock().reuse_port
+    }
+
+    /// Allows or disallows reusing the local port (`SO_REUSEPORT`).
+    ///
+    /// It has the same effect as [`set_reuse_addr`](Self::set_reuse_addr) for
+    /// UDP sockets. Note that a datagram is only delivered to one of the
+    /// sockets sharing the port.
+    pub fn set_reuse_port(&self, reuse_port: bool) {
+        self.opts.lock().reuse_port = reuse_port;
+    }
+
+    /// Returns the timeout of receiving operations (`SO_RCVTIMEO`).
+    #[inline]
+    pub fn recv_timeout(&self) -> Option<Duration> {
+        self.opts.lock().recv_timeout
+    }
+
+    /// Sets the timeout of blocking receiving operations (`SO_RCVTIMEO`).
+    ///
+    /// If the timeout expires, [`Err(WouldBlock)`](AxError::WouldBlock) is
+    /// returned. `None` means blocking indefinitely.
+    pub fn set_recv_timeout(&self, timeout: Option<Duration>) -> AxResult {
+        if timeout.is_some_and(|d| d.is_zero()) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_recv_timeout() failed: zero timeout"
+            );
+        }
+        self.opts.lock().recv_timeout = timeout;
+        Ok(())
+    }
+
+    /// Returns the timeout of sending operations (`SO_SNDTIMEO`).
+    #[inline]
+    pub fn send_timeout(&self) -> Option<Duration> {
+        self.opts.lock().send_timeout
+    }
+
+    /// Sets the timeout of blocking sending operations (`SO_SNDTIMEO`).
+    ///
+    /// If the timeout expires, [`Err(WouldBlock)`](AxError::WouldBlock) is
+    /// returned. `None` means blocking indefinitely.
+    pub fn set_send_timeout(&self, timeout: Option<Duration>) -> AxResult {
+        if timeout.is_some_and(|d| d.is_zero()) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_send_timeout() failed: zero timeout"
+            );
+        }
+        self.opts.lock().send_timeout = timeout;
+        Ok(())
+    }
+
+    /// Returns the size of the receive buffer (`SO_RCVBUF`).
+    #[inline]
+    pub fn recv_buffer_size(&self) -> usize {
+        self.opts.lock().recv_buf_size
+    }
+
+    /// Sets the size of the receive buffer (`SO_RCVBUF`).
+    ///
+    /// The size is clamped to a reasonable range. Datagrams queued in the
+    /// buffer are kept, returns [`Err(ResourceBusy)`](AxError::ResourceBusy)
+    /// if they don't fit in the new buffer or datagrams are waiting to be sent.
+    pub fn set_recv_buffer_size(&self, size: usize) -> AxResult {
+        let mut opts = self.opts.lock();
+        let size = size.clamp(MIN_SOCKET_BUF_LEN, MAX_SOCKET_BUF_LEN);
+        self.resize_buffers(size, opts.send_buf_size)?;
+        opts.recv_buf_size = size;
+        Ok(())
+    }
+
+    /// Returns the size of the send buffer (`SO_SNDBUF`).
+    #[inline]
+    pub fn send_buffer_size(&self) -> usize {
+        self.opts.lock().send_buf_size
+    }
+
+    /// Sets the size of the send buffer (`SO_SNDBUF`).
+    ///
+    /// The size is clamped to a reasonable range. Returns
+    /// [`Err(ResourceBusy)`](AxError::ResourceBusy) if datagrams are waiting to
+    /// be sent, or the datagrams received don't fit in the receive buffer.
+    pub fn set_send_buffer_size(&self, size: usize) -> AxResult {
+        let mut opts = self.opts.lock();
+        let size = size.clamp(MIN_SOCKET_BUF_LEN, MAX_SOCKET_BUF_LEN);
+        self.resize_buffers(opts.recv_buf_size, size)?;
+        opts.send_buf_size = size;
+        Ok(())
+    }
+
     /// Binds an unbound socket to the given address and port.
     ///
+    /// If the given port is 0, it generates one automatically. Returns
+    /// [`Err(AddrInUse)`](AxError::AddrInUse) if the address is used by other
+    /// sockets, see [`set_reuse_addr`](Self::set_reuse_addr).
+    ///
     /// It's must be called before [`send_to`](Self::send_to) and
     /// [`recv_from`](Self::recv_from).
-    pub fn bind(&self, mut local_addr: SocketAddr) -> AxResult {
+    pub fn bind(&self, local_addr: SocketAddr) -> AxResult {
         let mut self_local_addr = self.local_addr.write();
 
-        if local_addr.port() == 0 {
-            local_addr.set_port(get_ephemeral_port()?);
-        }
         if self_local_addr.is_some() {
             return ax_err!(InvalidInput, "socket bind() failed: already bound");
         }
 
-        let local_endpoint = from_core_sockaddr(local_addr);
-        let endpoint = IpListenEndpoint {
-            addr: (!is_unspecified(local_endpoint.addr)).then_some(local_endpoint.addr),
-            port: local_endpoint.port,
+        let mut local_endpoint = from_core_sockaddr(local_addr);
+        let addr = (!is_unspecified(local_endpoint.addr)).then_some(local_endpoint.addr);
+        let flags = {
+            let opts = self.opts.lock();
+            ReuseFlags {
+                reuse_addr: opts.reuse_addr,
+                reuse_port: opts.reuse_port,
+            }
         };
+        let binding = PortBinding::bind(Protocol::Udp, addr, local_endpoint.port, flags)?;
+        let endpoint = binding.endpoint();
+        local_endpoint.port = endpoint.port;
         SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
             socket.bind(endpoint).or_else(|e| match e {
                 BindError::InvalidState => ax_err!(AlreadyExists, "socket bind() failed"),
@@ -96,6 +253,7 @@ impl UdpSocket {
         })?;
 
         *self_local_addr = Some(local_endpoint);
+        *self.binding.lock() = Some(binding);
         debug!("UDP socket {}: bound on {}", self.handle, endpoint);
         Ok(())
     }
@@ -211,7 +369,7 @@ impl UdpSocket {
             return ax_err!(NotConnected, "socket send() failed");
         }
 
-        self.block_on(|| {
+        self.block_on(self.send_timeout(), || {
             SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
                 if socket.can_send() {
                     socket
@@ -239,7 +397,7 @@ impl UdpSocket {
             return ax_err!(NotConnected, "socket send() failed");
         }
 
-        self.block_on(|| {
+        self.block_on(self.recv_timeout(), || {
             SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
                 if socket.can_recv() {
                     // data available
@@ -252,18 +410,51 @@ impl UdpSocket {
         })
     }
 
-    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    /// Replaces the underlying socket with a new one with the given buffer
+    /// sizes, and binds it to the same local endpoint. The datagrams received
+    /// are moved to the new one.
+    fn resize_buffers(&self, rx_buf_len: usize, tx_buf_len: usize) -> AxResult {
+        let local_addr = self.local_addr.read();
+        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+            // the datagrams to be sent can't be taken out of the socket
+            if socket.send_queue() > 0 || socket.recv_queue() > rx_buf_len {
+                return ax_err!(ResourceBusy, "socket buffers are in use");
+            }
+            let endpoint = socket.endpoint();
+            let mut rx_buffer = SocketSetWrapper::new_udp_buffer(rx_buf_len);
+            while let Ok((payload, meta)) = socket.recv() {
+                // it always fits, as the new buffer is empty and large enough
+                if let Ok(buf) = rx_buffer.enqueue(payload.len(), meta) {
+                    buf.copy_from_slice(payload);
+                }
+            }
+            *socket = udp::Socket::new(rx_buffer, SocketSetWrapper::new_udp_buffer(tx_buf_len));
+            if local_addr.is_some() {
+                // the endpoint was valid, rebinding cannot fail
+                socket.bind(endpoint).ok();
+            }
+            Ok(())
+        })
+    }
+
+    fn block_on<F, T>(&self, timeout: Option<Duration>, mut f: F) -> AxResult<T>
     where
         F: FnMut() -> AxResult<T>,
     {
         if self.is_nonblocking() {
             f()
         } else {
+            let deadline = timeout.map(|t| monotonic_time() + t);
             loop {
                 SOCKET_SET.poll_interfaces();
                 match f() {
                     Ok(t) => return Ok(t),
-                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(AxError::WouldBlock) => {
+                        if deadline.is_some_and(|ddl| monotonic_time() >= ddl) {
+                            return Err(AxError::WouldBlock);
+                        }
+                        axtask::yield_now()
+                    }
                     Err(e) => return Err(e),
                 }
             }
@@ -277,18 +468,3 @@ impl Drop for UdpSocket {
         SOCKET_SET.remove(self.handle);
     }
 }
-
-fn get_ephemeral_port() -> AxResult<u16> {
-    const PORT_START: u16 = 0xc000;
-    const PORT_END: u16 = 0xffff;
-    static CURR: Mutex<u16> = Mutex::new(PORT_START);
-    let mut curr = CURR.lock();
-
-    let port = *curr;
-    if *curr == PORT_END {
-        *curr = PORT_START;
-    } else {
-        *curr += 1;
-    }
-    Ok(port)
-}
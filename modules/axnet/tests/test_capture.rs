@@ -0,0 +1,118 @@
+#![cfg(feature = "capture")]
+
+mod test_common;
+
+use axnet::{CaptureConfig, CaptureMode, UdpSocket};
+use smoltcp::socket::udp;
+
+use test_common::{Peer, local_addr, peer_addr};
+
+const PCAP_HEADER_LEN: usize = 24;
+
+/// Splits a pcap file into the original lengths and the data of its records.
+fn pcap_records(pcap: &[u8]) -> Vec<(usize, Vec<u8>)> {
+    let field = |buf: &[u8], i: usize| u32::from_le_bytes(buf[i..i + 4].try_into().unwrap());
+    assert_eq!(field(pcap, 0), 0xa1b2_c3d4);
+    assert_eq!(field(pcap, 20), 1); // LINKTYPE_ETHERNET
+
+    let mut records = Vec::new();
+    let mut rest = &pcap[PCAP_HEADER_LEN..];
+    while !rest.is_empty() {
+        let (incl_len, orig_len) = (field(rest, 8) as usize, field(rest, 12) as usize);
+        records.push((orig_len, rest[16..16 + incl_len].to_vec()));
+        rest = &rest[16 + incl_len..];
+    }
+    records
+}
+
+/// Sends `payload` to the peer and waits for it to arrive.
+fn send_to_peer(peer: &mut Peer, socket: &UdpSocket, port: u16, payload: &[u8]) {
+    let handle = peer.udp_bind(port);
+    socket.send_to(payload, peer_addr(port)).unwrap();
+    let mut buf = [0; 64];
+    let n = peer.poll_until(|peer| {
+        let socket = peer.sockets.get_mut::<udp::Socket>(handle);
+        socket.recv_slice(&mut buf).ok().map(|(n, _)| n)
+    });
+    assert_eq!(&buf[..n], payload);
+    peer.sockets.remove(handle);
+}
+
+fn test_ring(peer: &mut Peer, socket: &UdpSocket) {
+    println!("test capturing frames into the ring buffer ...");
+    peer.take_frames();
+    axnet::start_capture(CaptureConfig::default());
+    send_to_peer(peer, socket, 50000, b"hello");
+    axnet::stop_capture();
+
+    // the frames sent to the peer are captured whole
+    let records = pcap_records(&axnet::capture_to_pcap());
+    let frames = peer.take_frames();
+    assert!(!frames.is_empty());
+    for frame in &frames {
+        assert!(records.contains(&(frame.len(), frame.clone())));
+    }
+
+    // nothing is captured after stopping
+    send_to_peer(peer, socket, 50001, b"world");
+    assert_eq!(pcap_records(&axnet::capture_to_pcap()), records);
+}
+
+fn test_snaplen(peer: &mut Peer, socket: &UdpSocket) {
+    println!("test truncating frames to the snaplen ...");
+    axnet::start_capture(CaptureConfig {
+        snaplen: 20,
+        ..Default::default()
+    });
+    send_to_peer(peer, socket, 50002, b"a longer payload");
+    axnet::stop_capture();
+
+    let records = pcap_records(&axnet::capture_to_pcap());
+    assert!(!records.is_empty());
+    for (orig_len, data) in &records {
+        assert_eq!(data.len(), 20.min(*orig_len));
+    }
+    assert!(records.iter().any(|(orig_len, _)| *orig_len > 20));
+}
+
+fn test_ring_full(peer: &mut Peer, socket: &UdpSocket) {
+    println!("test dropping the oldest frames when the ring is full ...");
+    axnet::start_capture(CaptureConfig {
+        ring_size: 100,
+        ..Default::default()
+    });
+    for port in 50003..50006 {
+        send_to_peer(peer, socket, port, b"payload");
+    }
+    axnet::stop_capture();
+
+    let records = pcap_records(&axnet::capture_to_pcap());
+    assert!(!records.is_empty());
+    assert!(records.iter().map(|(_, data)| data.len()).sum::<usize>() <= 100);
+}
+
+fn test_console(peer: &mut Peer, socket: &UdpSocket) {
+    println!("test streaming frames to the console ...");
+    axnet::start_capture(CaptureConfig {
+        mode: CaptureMode::Console,
+        ..Default::default()
+    });
+    send_to_peer(peer, socket, 50006, b"hello");
+    axnet::stop_capture();
+
+    // the frames are printed, not buffered
+    assert_eq!(axnet::capture_to_pcap().len(), PCAP_HEADER_LEN);
+}
+
+#[test]
+fn test_capture() {
+    let mut peer = test_common::init();
+    let socket = UdpSocket::new();
+    socket.set_nonblocking(true);
+    socket.bind(local_addr(7777)).unwrap();
+
+    test_ring(&mut peer, &socket);
+    test_snaplen(&mut peer, &socket);
+    test_ring_full(&mut peer, &socket);
+    test_console(&mut peer, &socket);
+}
@@ -0,0 +1,207 @@
+//! A peer network stack linked to axnet by a [`ChannelNetDev`].
+//!
+//! axnet takes the default address `10.0.2.15/24` of the [`ChannelNetDev`]
+//! (`AX_IP` must not be set), and the peer takes the address of the default
+//! gateway `10.0.2.2`.
+
+#![allow(dead_code)]
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use axdriver::{AxDeviceContainer, ChannelNetDev, NetChannel};
+use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
+use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::socket::{tcp, udp};
+use smoltcp::time::Instant;
+use smoltcp::wire::{
+    EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpListenEndpoint, Ipv4Address,
+};
+
+pub const LOCAL_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+pub const PEER_MAC: [u8; 6] = [0x52, 0x55, 0x0a, 0x00, 0x02, 0x02];
+pub const LOCAL_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 15);
+pub const PEER_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+
+/// The maximum number of rounds of [`Peer::poll_until`].
+const MAX_POLL_ROUNDS: usize = 5000;
+
+/// The device of the peer, which keeps the frames received from axnet.
+struct ChannelDevice(NetChannel, Vec<Vec<u8>>);
+
+struct ChannelRxToken(Vec<u8>);
+struct ChannelTxToken<'a>(&'a NetChannel);
+
+impl RxToken for ChannelRxToken {
+    fn preprocess(&self, _sockets: &mut SocketSet<'_>) {}
+
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.0)
+    }
+}
+
+impl TxToken for ChannelTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let ret = f(&mut frame);
+        self.0.send(&frame).expect("the queue of axnet is full");
+        ret
+    }
+}
+
+impl Device for ChannelDevice {
+    type RxToken<'a> = ChannelRxToken;
+    type TxToken<'a> = ChannelTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = self.0.recv()?;
+        self.1.push(frame.clone());
+        Some((ChannelRxToken(frame), ChannelTxToken(&self.0)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(ChannelTxToken(&self.0))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = 1514;
+        caps.medium = Medium::Ethernet;
+        caps
+    }
+}
+
+/// The peer stack. Its clock advances 1ms on each poll, while the clock of
+/// axnet is stopped on the host.
+pub struct Peer {
+    iface: Interface,
+    dev: ChannelDevice,
+    pub sockets: SocketSet<'static>,
+    now: i64,
+}
+
+impl Peer {
+    fn new(channel: NetChannel) -> Self {
+        let mut dev = ChannelDevice(channel, Vec::new());
+        let config = Config::new(HardwareAddress::Ethernet(EthernetAddress(PEER_MAC)));
+        let mut iface = Interface::new(config, &mut dev, Instant::ZERO);
+        iface.update_ip_addrs(|addrs| {
+            let ip = IpAddress::Ipv4(Ipv4Address(PEER_IP.octets()));
+            addrs.push(IpCidr::new(ip, 24)).unwrap();
+        });
+        Self {
+            iface,
+            dev,
+            sockets: SocketSet::new(vec![]),
+            now: 0,
+        }
+    }
+
+    /// The other end of the link, to inject frames directly.
+    pub fn channel(&self) -> &NetChannel {
+        &self.dev.0
+    }
+
+    /// Takes the frames received from axnet so far.
+    pub fn take_frames(&mut self) -> Vec<Vec<u8>> {
+        core::mem::take(&mut self.dev.1)
+    }
+
+    pub fn poll(&mut self) {
+        self.now += 1;
+        let timestamp = Instant::from_millis(self.now);
+        self.iface.poll(timestamp, &mut self.dev, &mut self.sockets);
+    }
+
+    /// Polls both stacks until `f` returns `Some`.
+    pub fn poll_until<T>(&mut self, mut f: impl FnMut(&mut Self) -> Option<T>) -> T {
+        for _ in 0..MAX_POLL_ROUNDS {
+            axnet::poll_interfaces();
+            self.poll();
+            if let Some(ret) = f(self) {
+                return ret;
+            }
+        }
+        panic!("timed out after {} rounds", MAX_POLL_ROUNDS);
+    }
+
+    pub fn tcp_connect(&mut self, local_port: u16, remote: SocketAddr) -> SocketHandle {
+        let mut socket = tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; 4096]),
+            tcp::SocketBuffer::new(vec![0; 4096]),
+        );
+        let SocketAddr::V4(remote) = remote else {
+            panic!("IPv6 is not supported");
+        };
+        let remote = (Ipv4Address(remote.ip().octets()), remote.port());
+        socket
+            .connect(self.iface.context(), remote, local_port)
+            .unwrap();
+        self.sockets.add(socket)
+    }
+
+    pub fn tcp_listen(&mut self, port: u16) -> SocketHandle {
+        let mut socket = tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; 4096]),
+            tcp::SocketBuffer::new(vec![0; 4096]),
+        );
+        socket.listen(port).unwrap();
+        self.sockets.add(socket)
+    }
+
+    pub fn udp_bind(&mut self, port: u16) -> SocketHandle {
+        self.udp_bind_endpoint(IpListenEndpoint { addr: None, port })
+    }
+
+    /// Binds a UDP socket to one of the addresses of the peer, which is also
+    /// the source address of the datagrams it sends.
+    pub fn udp_bind_addr(&mut self, addr: SocketAddr) -> SocketHandle {
+        let SocketAddr::V4(addr) = addr else {
+            panic!("IPv6 is not supported");
+        };
+        let ip = IpAddress::Ipv4(Ipv4Address(addr.ip().octets()));
+        self.udp_bind_endpoint(IpListenEndpoint {
+            addr: Some(ip),
+            port: addr.port(),
+        })
+    }
+
+    fn udp_bind_endpoint(&mut self, endpoint: IpListenEndpoint) -> SocketHandle {
+        let mut socket = udp::Socket::new(
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 8], vec![0; 4096]),
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 8], vec![0; 4096]),
+        );
+        socket.bind(endpoint).unwrap();
+        self.sockets.add(socket)
+    }
+
+    /// Adds another address to the peer, in the subnet of axnet.
+    pub fn add_ip(&mut self, ip: Ipv4Addr) {
+        self.iface.update_ip_addrs(|addrs| {
+            let ip = IpAddress::Ipv4(Ipv4Address(ip.octets()));
+            addrs.push(IpCidr::new(ip, 24)).unwrap();
+        });
+    }
+}
+
+pub fn local_addr(port: u16) -> SocketAddr {
+    SocketAddr::new(LOCAL_IP.into(), port)
+}
+
+pub fn peer_addr(port: u16) -> SocketAddr {
+    SocketAddr::new(PEER_IP.into(), port)
+}
+
+/// Initializes axnet with a [`ChannelNetDev`], and returns the peer linked to
+/// it.
+pub fn init() -> Peer {
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    let (dev, channel) = ChannelNetDev::new(LOCAL_MAC);
+    axnet::init_network(AxDeviceContainer::from_one(dev));
+    Peer::new(channel)
+}
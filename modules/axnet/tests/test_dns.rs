@@ -0,0 +1,154 @@
+mod test_common;
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use axerrno::AxError;
+use axnet::{DnsQuery, DnsRecordType};
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::udp;
+use smoltcp::wire::IpEndpoint;
+
+use test_common::{PEER_IP, Peer};
+
+/// The second address of the peer, taken by the first name server.
+const SERVER1_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 3);
+
+const RCODE_NXDOMAIN: u8 = 3;
+const RCODE_SERVFAIL: u8 = 2;
+
+struct Servers([SocketHandle; 2]);
+
+impl Servers {
+    fn new(peer: &mut Peer) -> Self {
+        peer.add_ip(SERVER1_IP);
+        let first = peer.udp_bind_addr(SocketAddr::new(SERVER1_IP.into(), 53));
+        let second = peer.udp_bind_addr(SocketAddr::new(PEER_IP.into(), 53));
+        axnet::set_dns_servers(&[SERVER1_IP.into(), PEER_IP.into()]).unwrap();
+        Self([first, second])
+    }
+
+    /// Takes a query received by one of the servers.
+    fn try_recv(&self, peer: &mut Peer) -> Option<(usize, Vec<u8>, IpEndpoint)> {
+        let mut buf = [0; 512];
+        self.0.iter().enumerate().find_map(|(i, &handle)| {
+            let socket = peer.sockets.get_mut::<udp::Socket>(handle);
+            let (n, meta) = socket.recv_slice(&mut buf).ok()?;
+            Some((i, buf[..n].to_vec(), meta.endpoint))
+        })
+    }
+
+    /// Polls `query` until one of the servers receives it, returns the index
+    /// of the server, the query message and its source.
+    fn recv(&self, peer: &mut Peer, query: &mut DnsQuery) -> (usize, Vec<u8>, IpEndpoint) {
+        peer.poll_until(|peer| {
+            assert_eq!(query.poll().unwrap_err(), AxError::WouldBlock);
+            self.try_recv(peer)
+        })
+    }
+
+    fn send(&self, peer: &mut Peer, server: usize, msg: &[u8], to: IpEndpoint) {
+        let socket = peer.sockets.get_mut::<udp::Socket>(self.0[server]);
+        socket.send_slice(msg, to).unwrap();
+    }
+}
+
+/// Builds the response to `query`, with an A record for each of `addrs`
+/// whose owner is the queried name.
+fn response(query: &[u8], rcode: u8, ttl: u32, addrs: &[Ipv4Addr]) -> Vec<u8> {
+    let mut msg = query.to_vec();
+    msg[2] |= 0x80; // QR
+    msg[3] = 0x80 | rcode; // RA
+    msg[6..8].copy_from_slice(&(addrs.len() as u16).to_be_bytes());
+    for addr in addrs {
+        msg.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1]); // the name, A, IN
+        msg.extend_from_slice(&ttl.to_be_bytes());
+        msg.extend_from_slice(&4u16.to_be_bytes());
+        msg.extend_from_slice(&addr.octets());
+    }
+    msg
+}
+
+/// Polls `query` until it's finished.
+fn wait(peer: &mut Peer, query: &mut DnsQuery) -> Result<Vec<IpAddr>, AxError> {
+    peer.poll_until(|_| match query.poll() {
+        Err(AxError::WouldBlock) => None,
+        res => Some(res),
+    })
+}
+
+/// Asserts that the servers receive nothing while polling for a while.
+fn assert_no_query(peer: &mut Peer, servers: &Servers) {
+    for _ in 0..100 {
+        axnet::poll_interfaces();
+        peer.poll();
+        assert!(servers.try_recv(peer).is_none());
+    }
+}
+
+fn test_failover(peer: &mut Peer, servers: &Servers) {
+    println!("test failing over to the next name server ...");
+    let mut query = DnsQuery::new("www.example.com", DnsRecordType::A).unwrap();
+    let (server, msg, from) = servers.recv(peer, &mut query);
+    assert_eq!(server, 0);
+    servers.send(peer, 0, &response(&msg, RCODE_SERVFAIL, 0, &[]), from);
+
+    // the same query, sent to the second server
+    let (server, retry, from) = servers.recv(peer, &mut query);
+    assert_eq!(server, 1);
+    assert_eq!(retry, msg);
+    let addr = Ipv4Addr::new(93, 184, 216, 34);
+    servers.send(peer, 1, &response(&msg, 0, 300, &[addr]), from);
+    assert_eq!(wait(peer, &mut query).unwrap(), [IpAddr::from(addr)]);
+}
+
+fn test_cache(peer: &mut Peer, servers: &Servers) {
+    println!("test answering from the cache ...");
+    // the answer of `test_failover`, for the normalized name
+    let mut query = DnsQuery::new("WWW.Example.com.", DnsRecordType::A).unwrap();
+    assert_eq!(query.name(), "www.example.com");
+    let addrs = query.poll().unwrap();
+    assert_eq!(addrs, [IpAddr::from(Ipv4Addr::new(93, 184, 216, 34))]);
+    assert_eq!(query.poll().unwrap_err(), AxError::BadState);
+    assert_no_query(peer, servers);
+
+    // negative answers are cached too
+    let mut query = DnsQuery::new("nx.example.com", DnsRecordType::A).unwrap();
+    let (server, msg, from) = servers.recv(peer, &mut query);
+    servers.send(peer, server, &response(&msg, RCODE_NXDOMAIN, 0, &[]), from);
+    assert_eq!(wait(peer, &mut query).unwrap_err(), AxError::NotFound);
+    let mut query = DnsQuery::new("nx.example.com", DnsRecordType::A).unwrap();
+    assert_eq!(query.poll().unwrap_err(), AxError::NotFound);
+    assert_no_query(peer, servers);
+
+    // the cache is per record type
+    let mut query = DnsQuery::new("www.example.com", DnsRecordType::Aaaa).unwrap();
+    let (server, msg, from) = servers.recv(peer, &mut query);
+    servers.send(peer, server, &response(&msg, 0, 300, &[]), from);
+    assert_eq!(wait(peer, &mut query).unwrap_err(), AxError::NotFound);
+
+    axnet::flush_dns_cache();
+    let mut query = DnsQuery::new("www.example.com", DnsRecordType::A).unwrap();
+    servers.recv(peer, &mut query);
+}
+
+fn test_ttl(peer: &mut Peer, servers: &Servers) {
+    println!("test expiring cached answers ...");
+    // the clock of axnet is stopped on the host, so an answer expires only if
+    // its TTL is zero
+    let addr = Ipv4Addr::new(10, 0, 0, 1);
+    for _ in 0..2 {
+        let mut query = DnsQuery::new("short.example.com", DnsRecordType::A).unwrap();
+        let (server, msg, from) = servers.recv(peer, &mut query);
+        servers.send(peer, server, &response(&msg, 0, 0, &[addr]), from);
+        assert_eq!(wait(peer, &mut query).unwrap(), [IpAddr::from(addr)]);
+    }
+}
+
+#[test]
+fn test_dns() {
+    let mut peer = test_common::init();
+    let servers = Servers::new(&mut peer);
+    test_failover(&mut peer, &servers);
+    test_cache(&mut peer, &servers);
+    test_ttl(&mut peer, &servers);
+}
@@ -0,0 +1,110 @@
+mod test_common;
+
+use axerrno::AxError;
+use axnet::{TcpSocket, UdpSocket};
+use smoltcp::socket::tcp;
+use smoltcp::wire::{EthernetFrame, EthernetProtocol, IpProtocol, Ipv4Packet, TcpPacket};
+
+use test_common::{Peer, local_addr, peer_addr};
+
+/// Returns the source port of the first TCP SYN in `frames`.
+fn syn_port(frames: &[Vec<u8>]) -> Option<u16> {
+    frames.iter().find_map(|frame| {
+        let frame = EthernetFrame::new_checked(&frame[..]).ok()?;
+        if frame.ethertype() != EthernetProtocol::Ipv4 {
+            return None;
+        }
+        let packet = Ipv4Packet::new_checked(frame.payload()).ok()?;
+        if packet.next_header() != IpProtocol::Tcp {
+            return None;
+        }
+        let segment = TcpPacket::new_checked(packet.payload()).ok()?;
+        segment.syn().then(|| segment.src_port())
+    })
+}
+
+fn test_reuse_port() {
+    println!("test sharing ports with SO_REUSEPORT ...");
+    let (a, b) = (UdpSocket::new(), UdpSocket::new());
+    a.set_reuse_port(true);
+    a.bind(local_addr(7000)).unwrap();
+    assert_eq!(b.bind(local_addr(7000)).err(), Some(AxError::AddrInUse));
+    let b = UdpSocket::new();
+    b.set_reuse_port(true);
+    b.bind(local_addr(7000)).unwrap();
+
+    // TCP listeners can't share a port
+    let (a, b) = (TcpSocket::new(), TcpSocket::new());
+    for socket in [&a, &b] {
+        socket.set_reuse_port(true);
+        socket.bind(local_addr(7000)).unwrap();
+    }
+    a.listen(8).unwrap();
+    assert_eq!(b.listen(8).err(), Some(AxError::AddrInUse));
+    drop(a);
+    b.listen(8).unwrap();
+}
+
+fn test_connect_refused(peer: &mut Peer) {
+    println!("test releasing the ephemeral port after connect() fails ...");
+    let socket = TcpSocket::new();
+    socket.set_nonblocking(true);
+    peer.take_frames();
+    assert_eq!(
+        socket.connect(peer_addr(9)).err(),
+        Some(AxError::WouldBlock)
+    );
+
+    // the peer isn't listening, and resets the connection
+    peer.poll_until(|_| socket.poll().unwrap().writable.then_some(()));
+    assert_eq!(socket.peer_addr().err(), Some(AxError::NotConnected));
+    let port = syn_port(&peer.take_frames()).expect("no SYN sent");
+    TcpSocket::new().bind(local_addr(port)).unwrap();
+
+    // a port given explicitly is kept
+    let socket = TcpSocket::new();
+    socket.set_nonblocking(true);
+    socket.bind(local_addr(7001)).unwrap();
+    assert_eq!(
+        socket.connect(peer_addr(9)).err(),
+        Some(AxError::WouldBlock)
+    );
+    peer.poll_until(|_| socket.poll().unwrap().writable.then_some(()));
+    let other = TcpSocket::new();
+    assert_eq!(other.bind(local_addr(7001)).err(), Some(AxError::AddrInUse));
+}
+
+fn test_close_draining(peer: &mut Peer) {
+    println!("test keeping the port of a closed connection until it's drained ...");
+    let handle = peer.tcp_listen(80);
+    let socket = TcpSocket::new();
+    socket.set_nonblocking(true);
+    socket.bind(local_addr(7002)).unwrap();
+    assert_eq!(
+        socket.connect(peer_addr(80)).err(),
+        Some(AxError::WouldBlock)
+    );
+    peer.poll_until(|_| socket.poll().unwrap().writable.then_some(()));
+    socket.peer_addr().unwrap();
+
+    // the connection is still in FIN_WAIT after close()
+    drop(socket);
+    peer.poll_until(|peer| {
+        let socket = peer.sockets.get::<tcp::Socket>(handle);
+        (socket.state() == tcp::State::CloseWait).then_some(())
+    });
+    let other = TcpSocket::new();
+    assert_eq!(other.bind(local_addr(7002)).err(), Some(AxError::AddrInUse));
+
+    // released once the connection is closed
+    peer.sockets.get_mut::<tcp::Socket>(handle).abort();
+    peer.poll_until(|_| TcpSocket::new().bind(local_addr(7002)).ok());
+}
+
+#[test]
+fn test_port() {
+    let mut peer = test_common::init();
+    test_reuse_port();
+    test_connect_refused(&mut peer);
+    test_close_draining(&mut peer);
+}
@@ -0,0 +1,89 @@
+mod test_common;
+
+use std::net::IpAddr;
+
+use axerrno::AxError;
+use axnet::{IcmpSocket, RawSocket};
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::wire::{
+    ETHERNET_HEADER_LEN, EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr,
+    IpProtocol, Ipv4Address, Ipv4Packet, Ipv4Repr,
+};
+
+use test_common::{LOCAL_IP, LOCAL_MAC, PEER_IP, PEER_MAC, Peer};
+
+/// An experimental protocol number, which axnet doesn't handle itself.
+const PROTOCOL: u8 = 253;
+
+/// Builds an Ethernet frame of an IP packet from the peer to axnet.
+fn ip_frame(payload: &[u8]) -> Vec<u8> {
+    let eth_repr = EthernetRepr {
+        src_addr: EthernetAddress(PEER_MAC),
+        dst_addr: EthernetAddress(LOCAL_MAC),
+        ethertype: EthernetProtocol::Ipv4,
+    };
+    let ip_repr = Ipv4Repr {
+        src_addr: Ipv4Address(PEER_IP.octets()),
+        dst_addr: Ipv4Address(LOCAL_IP.octets()),
+        next_header: IpProtocol::from(PROTOCOL),
+        payload_len: payload.len(),
+        hop_limit: 64,
+    };
+
+    let mut buf = vec![0; ETHERNET_HEADER_LEN + ip_repr.buffer_len() + ip_repr.payload_len];
+    let mut frame = EthernetFrame::new_unchecked(&mut buf);
+    eth_repr.emit(&mut frame);
+    let mut packet = Ipv4Packet::new_unchecked(frame.payload_mut());
+    ip_repr.emit(&mut packet, &ChecksumCapabilities::default());
+    packet.payload_mut().copy_from_slice(payload);
+    buf
+}
+
+fn test_raw_truncate(peer: &mut Peer) {
+    println!("test receiving on a raw socket with a short buffer ...");
+    let socket = RawSocket::new(PROTOCOL);
+    socket.set_nonblocking(true);
+    peer.channel().send(&ip_frame(b"first")).unwrap();
+    peer.channel().send(&ip_frame(b"second")).unwrap();
+
+    // the rest of the packet is discarded, even shorter than the IP header
+    let mut buf = [0; 8];
+    let (n, from) = peer.poll_until(|_| socket.recv_from(&mut buf).ok());
+    assert_eq!(n, buf.len());
+    assert_eq!(from, IpAddr::V4(PEER_IP));
+
+    // the next packet is received whole
+    let mut buf = [0; 64];
+    let n = peer.poll_until(|_| socket.recv(&mut buf).ok());
+    let packet = Ipv4Packet::new_checked(&buf[..n]).unwrap();
+    assert_eq!(packet.payload(), b"second");
+    assert_eq!(socket.recv(&mut buf).err(), Some(AxError::WouldBlock));
+}
+
+fn test_icmp_ident() {
+    println!("test binding ICMP identifiers ...");
+    let a = IcmpSocket::new();
+    a.bind(1234).unwrap();
+    let b = IcmpSocket::new();
+    assert_eq!(b.bind(1234).err(), Some(AxError::AddrInUse));
+
+    // generated identifiers are not in use
+    b.bind(0).unwrap();
+    let c = IcmpSocket::new();
+    c.bind(0).unwrap();
+    let idents = [a.ident().unwrap(), b.ident().unwrap(), c.ident().unwrap()];
+    assert_ne!(idents[0], idents[1]);
+    assert_ne!(idents[0], idents[2]);
+    assert_ne!(idents[1], idents[2]);
+
+    // the identifier is released when the socket is closed
+    drop(b);
+    IcmpSocket::new().bind(idents[1]).unwrap();
+}
+
+#[test]
+fn test_raw() {
+    let mut peer = test_common::init();
+    test_raw_truncate(&mut peer);
+    test_icmp_ident();
+}
@@ -0,0 +1,241 @@
+mod test_common;
+
+use std::net::SocketAddr;
+
+use axnet::stats::{self, InterfaceStats, SocketProtocol, SocketState};
+use axnet::{TcpSocket, UdpSocket};
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::udp;
+use smoltcp::wire::{
+    ETHERNET_HEADER_LEN, EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr,
+    IpProtocol, Ipv4Address, Ipv4Packet, Ipv4Repr, TcpControl, TcpPacket, TcpRepr, TcpSeqNumber,
+};
+
+use test_common::{LOCAL_IP, LOCAL_MAC, PEER_IP, PEER_MAC, Peer, local_addr, peer_addr};
+
+fn eth0() -> InterfaceStats {
+    stats::interface_stats().remove(0)
+}
+
+/// Formats an address as in `/proc/net/tcp`.
+fn proc_addr(addr: SocketAddr) -> String {
+    let SocketAddr::V4(addr) = addr else {
+        panic!("IPv6 is not supported");
+    };
+    format!(
+        "{:08X}:{:04X}",
+        u32::from_ne_bytes(addr.ip().octets()),
+        addr.port()
+    )
+}
+
+/// Returns the line of the socket at `local` in a rendered socket table.
+fn proc_line(table: &str, local: SocketAddr) -> Option<String> {
+    let local = proc_addr(local);
+    table
+        .lines()
+        .find(|line| line.split_whitespace().nth(1) == Some(&local))
+        .map(String::from)
+}
+
+/// Parses the TCP segment in an Ethernet frame.
+fn tcp_segment(frame: &[u8]) -> Option<TcpPacket<&[u8]>> {
+    let frame = EthernetFrame::new_checked(frame).ok()?;
+    if frame.ethertype() != EthernetProtocol::Ipv4 {
+        return None;
+    }
+    let packet = Ipv4Packet::new_checked(&frame.into_inner()[ETHERNET_HEADER_LEN..]).ok()?;
+    if packet.next_header() != IpProtocol::Tcp {
+        return None;
+    }
+    let (start, end) = (packet.header_len() as usize, packet.total_len() as usize);
+    TcpPacket::new_checked(&packet.into_inner()[start..end]).ok()
+}
+
+/// Builds an Ethernet frame of an empty ACK from the peer to axnet.
+fn ack_frame(src_port: u16, dst_port: u16, seq: TcpSeqNumber, ack: TcpSeqNumber) -> Vec<u8> {
+    let (src_addr, dst_addr) = (
+        Ipv4Address(PEER_IP.octets()),
+        Ipv4Address(LOCAL_IP.octets()),
+    );
+    let eth_repr = EthernetRepr {
+        src_addr: EthernetAddress(PEER_MAC),
+        dst_addr: EthernetAddress(LOCAL_MAC),
+        ethertype: EthernetProtocol::Ipv4,
+    };
+    let tcp_repr = TcpRepr {
+        src_port,
+        dst_port,
+        control: TcpControl::None,
+        seq_number: seq,
+        ack_number: Some(ack),
+        window_len: 4096,
+        window_scale: None,
+        max_seg_size: None,
+        sack_permitted: false,
+        sack_ranges: [None; 3],
+        payload: &[],
+    };
+    let ip_repr = Ipv4Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Tcp,
+        payload_len: tcp_repr.header_len(),
+        hop_limit: 64,
+    };
+
+    let caps = ChecksumCapabilities::default();
+    let mut buf = vec![0; ETHERNET_HEADER_LEN + ip_repr.buffer_len() + ip_repr.payload_len];
+    let mut frame = EthernetFrame::new_unchecked(&mut buf);
+    eth_repr.emit(&mut frame);
+    let mut packet = Ipv4Packet::new_unchecked(frame.payload_mut());
+    ip_repr.emit(&mut packet, &caps);
+    let mut segment = TcpPacket::new_unchecked(packet.payload_mut());
+    tcp_repr.emit(&mut segment, &src_addr.into(), &dst_addr.into(), &caps);
+    buf
+}
+
+fn test_counters(peer: &mut Peer) {
+    println!("test interface counters and the UDP socket table ...");
+    let socket = UdpSocket::new();
+    socket.set_nonblocking(true);
+    socket.bind(local_addr(7777)).unwrap();
+    let handle = peer.udp_bind(50000);
+
+    let before = eth0();
+    peer.sockets
+        .get_mut::<udp::Socket>(handle)
+        .send_slice(b"ping", (Ipv4Address(LOCAL_IP.octets()), 7777))
+        .unwrap();
+    let mut buf = [0; 16];
+    peer.poll_until(|_| socket.recv_from(&mut buf).ok());
+    socket.send_to(b"pong", peer_addr(50000)).unwrap();
+    peer.poll_until(|peer| {
+        let socket = peer.sockets.get_mut::<udp::Socket>(handle);
+        socket.recv_slice(&mut buf).ok()
+    });
+
+    // at least the datagrams, with the headers
+    let after = eth0();
+    assert_eq!(after.name, "eth0");
+    assert!(after.rx_packets > before.rx_packets);
+    assert!(after.rx_bytes >= before.rx_bytes + 42 + 4);
+    assert!(after.tx_packets > before.tx_packets);
+    assert!(after.tx_bytes >= before.tx_bytes + 42 + 4);
+    assert_eq!(after.rx_errors, 0);
+    assert_eq!(after.tx_errors, 0);
+
+    let dev = stats::render_net_dev();
+    let line = dev
+        .lines()
+        .find(|line| line.trim_start().starts_with("eth0:"));
+    let fields: Vec<&str> = line.unwrap().split_whitespace().collect();
+    assert_eq!(fields[1].parse::<u64>().unwrap(), after.rx_bytes);
+    assert_eq!(fields[2].parse::<u64>().unwrap(), after.rx_packets);
+    assert_eq!(fields[4].parse::<u64>().unwrap(), after.rx_dropped);
+    assert_eq!(fields[9].parse::<u64>().unwrap(), after.tx_bytes);
+    assert_eq!(fields[10].parse::<u64>().unwrap(), after.tx_packets);
+
+    let entry = stats::socket_stats()
+        .into_iter()
+        .find(|s| s.protocol == SocketProtocol::Udp && s.local_addr.port() == 7777)
+        .unwrap();
+    assert_eq!(entry.state, SocketState::Closed);
+    assert_eq!(entry.recv_queue, 0);
+    let line = proc_line(&stats::render_net_udp(), local_addr(7777)).unwrap();
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    assert_eq!(fields[2], "00000000:0000");
+    assert_eq!(fields[3], "07");
+}
+
+fn test_dropped(peer: &mut Peer) {
+    println!("test counting SYNs dropped by a full accept queue ...");
+    let listener = TcpSocket::new();
+    listener.set_nonblocking(true);
+    listener.bind(local_addr(6000)).unwrap();
+    listener.listen(1).unwrap();
+
+    let dropped = eth0().rx_dropped;
+    peer.tcp_connect(41000, local_addr(6000));
+    peer.poll_until(|_| listener.poll().unwrap().readable.then_some(()));
+    peer.tcp_connect(41001, local_addr(6000));
+    peer.poll_until(|_| (eth0().rx_dropped > dropped).then_some(()));
+
+    // the listener and the connection waiting to be accepted
+    let line = proc_line(&stats::render_net_tcp(), local_addr(6000)).unwrap();
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    assert_eq!(fields[3], "0A");
+    assert_eq!(fields[4], "00000000:00000001");
+    let table = stats::socket_stats();
+    assert!(table.iter().any(|s| {
+        s.local_addr == local_addr(6000)
+            && s.remote_addr == peer_addr(41000)
+            && s.state == SocketState::Established
+    }));
+}
+
+fn test_retransmits(peer: &mut Peer) {
+    println!("test counting TCP retransmissions ...");
+    peer.tcp_listen(81);
+    let stream = TcpSocket::new();
+    stream.set_nonblocking(true);
+    stream.bind(local_addr(7003)).unwrap();
+    stream.connect(peer_addr(81)).ok();
+    peer.poll_until(|_| stream.poll().unwrap().writable.then_some(()));
+    for _ in 0..10 {
+        axnet::poll_interfaces();
+        peer.poll();
+    }
+
+    // the next sequence number of the peer, acknowledged by axnet
+    let frames = peer.take_frames();
+    let peer_seq = frames
+        .iter()
+        .rev()
+        .filter_map(|frame| tcp_segment(frame))
+        .find(|segment| segment.src_port() == 7003)
+        .unwrap()
+        .ack_number();
+
+    // the segment is taken from the link before the peer stack receives it
+    assert_eq!(stream.send(b"lost").unwrap(), 4);
+    axnet::poll_interfaces();
+    let seq = std::iter::from_fn(|| peer.channel().recv())
+        .filter_map(|frame| tcp_segment(&frame).map(|s| (s.seq_number(), s.payload().len())))
+        .find(|&(_, len)| len == 4)
+        .expect("no data sent")
+        .0;
+
+    // three duplicate ACKs trigger a fast retransmission, the first one
+    // may be taken as a new ACK
+    let retransmits = eth0().tcp_retransmits;
+    for _ in 0..4 {
+        let frame = ack_frame(81, 7003, peer_seq, seq);
+        peer.channel().send(&frame).unwrap();
+        axnet::poll_interfaces();
+    }
+    let resent = std::iter::from_fn(|| peer.channel().recv())
+        .filter_map(|frame| tcp_segment(&frame).map(|s| (s.seq_number(), s.payload().len())))
+        .any(|seg| seg == (seq, 4));
+    assert!(resent);
+    assert_eq!(eth0().tcp_retransmits, retransmits + 1);
+
+    let entry = stats::socket_stats()
+        .into_iter()
+        .find(|s| s.local_addr == local_addr(7003))
+        .unwrap();
+    assert_eq!(entry.retransmits, 1);
+    let line = proc_line(&stats::render_net_tcp(), local_addr(7003)).unwrap();
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    assert_eq!(fields[2], proc_addr(peer_addr(81)));
+    assert_eq!(fields[3], "01");
+    assert_eq!(fields[6], "00000001");
+}
+
+#[test]
+fn test_stats() {
+    let mut peer = test_common::init();
+    test_counters(&mut peer);
+    test_dropped(&mut peer);
+    test_retransmits(&mut peer);
+}
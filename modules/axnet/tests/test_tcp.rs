@@ -0,0 +1,186 @@
+mod test_common;
+
+use std::time::Duration;
+
+use axerrno::AxError;
+use axnet::TcpSocket;
+use smoltcp::socket::tcp;
+
+use test_common::{Peer, local_addr, peer_addr};
+
+fn test_stream(peer: &mut Peer) {
+    println!("test TCP stream ...");
+    let listener = TcpSocket::new();
+    listener.set_nonblocking(true);
+    listener.bind(local_addr(5555)).unwrap();
+    listener.listen(8).unwrap();
+
+    let handle = peer.tcp_connect(40000, local_addr(5555));
+    let stream = peer.poll_until(|_| listener.accept().ok());
+    stream.set_nonblocking(true);
+    assert_eq!(stream.local_addr().unwrap(), local_addr(5555));
+    assert_eq!(stream.peer_addr().unwrap(), peer_addr(40000));
+
+    // axnet -> peer
+    assert_eq!(stream.send(b"hello").unwrap(), 5);
+    let mut buf = [0; 16];
+    let n = peer.poll_until(|peer| {
+        let socket = peer.sockets.get_mut::<tcp::Socket>(handle);
+        socket
+            .can_recv()
+            .then(|| socket.recv_slice(&mut buf).unwrap())
+    });
+    assert_eq!(&buf[..n], b"hello");
+
+    // peer -> axnet
+    let socket = peer.sockets.get_mut::<tcp::Socket>(handle);
+    assert_eq!(socket.send_slice(b"world").unwrap(), 5);
+    let n = peer.poll_until(|_| stream.recv(&mut buf).ok());
+    assert_eq!(&buf[..n], b"world");
+
+    // the peer closes the connection, axnet reads EOF
+    peer.sockets.get_mut::<tcp::Socket>(handle).close();
+    peer.poll_until(|_| stream.recv(&mut buf).ok().filter(|&n| n == 0));
+}
+
+fn test_listen_backlog(peer: &mut Peer) {
+    println!("test TCP listen backlog ...");
+    let listener = TcpSocket::new();
+    listener.set_nonblocking(true);
+    listener.bind(local_addr(6666)).unwrap();
+    listener.listen(1).unwrap();
+
+    let first = peer.tcp_connect(40001, local_addr(6666));
+    peer.poll_until(|_| listener.poll().unwrap().readable.then_some(()));
+
+    // the accept queue is full, the SYN is dropped
+    let second = peer.tcp_connect(40002, local_addr(6666));
+    let stats = peer.poll_until(|_| {
+        let stats = listener.listen_stats().unwrap();
+        (stats.accept_queue_overflows > 0).then_some(stats)
+    });
+    assert_eq!(stats.backlog, 1);
+    assert_eq!(stats.accept_queue_len, 1);
+    assert_eq!(
+        peer.sockets.get::<tcp::Socket>(second).state(),
+        tcp::State::SynSent
+    );
+
+    // accepting the first connection makes room for the retransmitted SYN
+    let stream = listener.accept().unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), peer_addr(40001));
+    assert!(peer.sockets.get::<tcp::Socket>(first).may_send());
+    let stream = peer.poll_until(|_| listener.accept().ok());
+    assert_eq!(stream.peer_addr().unwrap(), peer_addr(40002));
+}
+
+fn test_reset_before_accept(peer: &mut Peer) {
+    println!("test TCP connections reset before accept ...");
+    let listener = TcpSocket::new();
+    listener.set_nonblocking(true);
+    listener.bind(local_addr(6667)).unwrap();
+    listener.listen(1).unwrap();
+
+    let handle = peer.tcp_connect(40004, local_addr(6667));
+    peer.poll_until(|_| listener.poll().unwrap().readable.then_some(()));
+    assert_eq!(listener.listen_stats().unwrap().accept_queue_len, 1);
+
+    // the reset connection no longer takes the room in the backlog, and is
+    // not accepted
+    peer.sockets.get_mut::<tcp::Socket>(handle).abort();
+    peer.poll_until(|_| (listener.listen_stats().unwrap().accept_queue_len == 0).then_some(()));
+    peer.tcp_connect(40005, local_addr(6667));
+    let stream = peer.poll_until(|_| listener.accept().ok());
+    assert_eq!(stream.peer_addr().unwrap(), peer_addr(40005));
+}
+
+fn test_options(peer: &mut Peer) {
+    println!("test TCP options ...");
+    let listener = TcpSocket::new();
+    listener.set_nonblocking(true);
+    listener.bind(local_addr(5556)).unwrap();
+    listener.listen(8).unwrap();
+    peer.tcp_connect(40003, local_addr(5556));
+    let stream = peer.poll_until(|_| listener.accept().ok());
+
+    let interval = Duration::from_secs(60);
+    assert_eq!(stream.keep_alive(), None);
+    stream.set_keep_alive(Some(interval)).unwrap();
+    assert_eq!(stream.keep_alive(), Some(interval));
+    assert_eq!(
+        stream.set_keep_alive(Some(Duration::ZERO)).err(),
+        Some(AxError::InvalidInput)
+    );
+    stream.set_keep_alive(None).unwrap();
+    assert_eq!(stream.keep_alive(), None);
+
+    // the buffers of a connected socket can't be resized
+    let size = stream.recv_buffer_size();
+    stream.set_recv_buffer_size(size / 2);
+    assert_eq!(stream.recv_buffer_size(), size);
+}
+
+fn test_ring_wrap(peer: &mut Peer) {
+    println!("test TCP data wrapping around the ring buffers ...");
+    let listener = TcpSocket::new();
+    listener.set_nonblocking(true);
+    listener.set_recv_buffer_size(2048);
+    listener.set_send_buffer_size(2048);
+    listener.bind(local_addr(5557)).unwrap();
+    listener.listen(8).unwrap();
+    let handle = peer.tcp_connect(40006, local_addr(5557));
+    let stream = peer.poll_until(|_| listener.accept().ok());
+    stream.set_nonblocking(true);
+    assert_eq!(stream.recv_buffer_size(), 2048);
+
+    // move the read pointer to the middle of the receive buffer
+    let data: Vec<u8> = (0..1536).map(|i| i as u8).collect();
+    let socket = peer.sockets.get_mut::<tcp::Socket>(handle);
+    assert_eq!(socket.send_slice(&data).unwrap(), data.len());
+    let mut buf = [0; 2048];
+    let mut received = 0;
+    while received < data.len() {
+        received += peer.poll_until(|_| stream.recv(&mut buf[received..]).ok());
+    }
+    assert_eq!(&buf[..received], &data[..]);
+
+    // the data wrapping around is received in one call
+    let socket = peer.sockets.get_mut::<tcp::Socket>(handle);
+    assert_eq!(socket.send_slice(&data[..1024]).unwrap(), 1024);
+    peer.poll_until(|peer| {
+        let socket = peer.sockets.get::<tcp::Socket>(handle);
+        (socket.send_queue() == 0).then_some(())
+    });
+    assert_eq!(stream.recv(&mut buf).unwrap(), 1024);
+    assert_eq!(&buf[..1024], &data[..1024]);
+
+    // the same for the send buffer, once the peer has acknowledged the data
+    let mut sent = 0;
+    while sent < data.len() {
+        sent += peer.poll_until(|_| stream.send(&data[sent..]).ok());
+    }
+    let mut received = 0;
+    peer.poll_until(|peer| {
+        let socket = peer.sockets.get_mut::<tcp::Socket>(handle);
+        if socket.can_recv() {
+            received += socket.recv_slice(&mut buf).unwrap();
+        }
+        (received == data.len()).then_some(())
+    });
+    let mut rounds = 0;
+    peer.poll_until(|_| {
+        rounds += 1;
+        (rounds > 100).then_some(())
+    });
+    assert_eq!(stream.send(&data[..1024]).unwrap(), 1024);
+}
+
+#[test]
+fn test_tcp() {
+    let mut peer = test_common::init();
+    test_stream(&mut peer);
+    test_listen_backlog(&mut peer);
+    test_reset_before_accept(&mut peer);
+    test_options(&mut peer);
+    test_ring_wrap(&mut peer);
+}
@@ -0,0 +1,141 @@
+mod test_common;
+
+use axnet::UdpSocket;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::udp;
+use smoltcp::wire::{
+    ETHERNET_HEADER_LEN, EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr,
+    IpProtocol, Ipv4Address, Ipv4Packet, Ipv4Repr, UdpPacket, UdpRepr,
+};
+
+use test_common::{LOCAL_IP, LOCAL_MAC, PEER_IP, PEER_MAC, Peer, local_addr, peer_addr};
+
+/// Builds an Ethernet frame of a UDP datagram from the peer to axnet.
+fn udp_frame(src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+    let (src_addr, dst_addr) = (
+        Ipv4Address(PEER_IP.octets()),
+        Ipv4Address(LOCAL_IP.octets()),
+    );
+    let eth_repr = EthernetRepr {
+        src_addr: EthernetAddress(PEER_MAC),
+        dst_addr: EthernetAddress(LOCAL_MAC),
+        ethertype: EthernetProtocol::Ipv4,
+    };
+    let udp_repr = UdpRepr { src_port, dst_port };
+    let ip_repr = Ipv4Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Udp,
+        payload_len: udp_repr.header_len() + payload.len(),
+        hop_limit: 64,
+    };
+
+    let caps = ChecksumCapabilities::default();
+    let mut buf = vec![0; ETHERNET_HEADER_LEN + ip_repr.buffer_len() + ip_repr.payload_len];
+    let mut frame = EthernetFrame::new_unchecked(&mut buf);
+    eth_repr.emit(&mut frame);
+    let mut packet = Ipv4Packet::new_unchecked(frame.payload_mut());
+    ip_repr.emit(&mut packet, &caps);
+    let mut datagram = UdpPacket::new_unchecked(packet.payload_mut());
+    udp_repr.emit(
+        &mut datagram,
+        &src_addr.into(),
+        &dst_addr.into(),
+        payload.len(),
+        |buf| buf.copy_from_slice(payload),
+        &caps,
+    );
+    buf
+}
+
+/// Builds a little-endian pcap file with microsecond timestamps.
+fn pcap_file(frames: &[Vec<u8>]) -> Vec<u8> {
+    let mut pcap = Vec::new();
+    for field in [0xa1b2_c3d4u32, 0x0004_0002, 0, 0, 65535, 1] {
+        pcap.extend_from_slice(&field.to_le_bytes());
+    }
+    for (i, frame) in frames.iter().enumerate() {
+        let len = frame.len() as u32;
+        for field in [i as u32, 0, len, len] {
+            pcap.extend_from_slice(&field.to_le_bytes());
+        }
+        pcap.extend_from_slice(frame);
+    }
+    pcap
+}
+
+fn test_send_recv(peer: &mut Peer, socket: &UdpSocket) {
+    println!("test UDP send and receive ...");
+    let handle = peer.udp_bind(50000);
+
+    // peer -> axnet
+    peer.sockets
+        .get_mut::<udp::Socket>(handle)
+        .send_slice(b"ping", (Ipv4Address(LOCAL_IP.octets()), 7777))
+        .unwrap();
+    let mut buf = [0; 16];
+    let (n, from) = peer.poll_until(|_| socket.recv_from(&mut buf).ok());
+    assert_eq!(&buf[..n], b"ping");
+    assert_eq!(from, peer_addr(50000));
+
+    // axnet -> peer
+    assert_eq!(socket.send_to(b"pong", peer_addr(50000)).unwrap(), 4);
+    let (n, meta) = peer.poll_until(|peer| {
+        let socket = peer.sockets.get_mut::<udp::Socket>(handle);
+        socket.recv_slice(&mut buf).ok()
+    });
+    assert_eq!(&buf[..n], b"pong");
+    assert_eq!(meta.endpoint.port, 7777);
+}
+
+fn test_replay_pcap(peer: &mut Peer, socket: &UdpSocket) {
+    println!("test replaying a pcap file ...");
+    let frames = [
+        udp_frame(50001, 7777, b"first"),
+        udp_frame(50002, 7777, b"second"),
+    ];
+    assert_eq!(peer.channel().replay_pcap(&pcap_file(&frames)).unwrap(), 2);
+
+    let mut buf = [0; 16];
+    for (port, payload) in [(50001, &b"first"[..]), (50002, &b"second"[..])] {
+        let (n, from) = peer.poll_until(|_| socket.recv_from(&mut buf).ok());
+        assert_eq!(&buf[..n], payload);
+        assert_eq!(from, peer_addr(port));
+    }
+    assert_eq!(peer.channel().pending(), 0);
+}
+
+fn test_resize_buffers(peer: &mut Peer, socket: &UdpSocket) {
+    println!("test resizing the buffers with datagrams queued ...");
+    let handle = peer.udp_bind(50003);
+    for payload in [&b"one"[..], b"two"] {
+        peer.sockets
+            .get_mut::<udp::Socket>(handle)
+            .send_slice(payload, (Ipv4Address(LOCAL_IP.octets()), 7777))
+            .unwrap();
+    }
+    let mut buf = [0; 16];
+    peer.poll_until(|_| socket.peek_from(&mut buf).ok());
+
+    // the datagrams received are kept
+    let size = socket.recv_buffer_size() * 2;
+    socket.set_recv_buffer_size(size).unwrap();
+    assert_eq!(socket.recv_buffer_size(), size);
+    for payload in [&b"one"[..], b"two"] {
+        let (n, from) = peer.poll_until(|_| socket.recv_from(&mut buf).ok());
+        assert_eq!(&buf[..n], payload);
+        assert_eq!(from, peer_addr(50003));
+    }
+}
+
+#[test]
+fn test_udp() {
+    let mut peer = test_common::init();
+    let socket = UdpSocket::new();
+    socket.set_nonblocking(true);
+    socket.bind(local_addr(7777)).unwrap();
+
+    test_send_recv(&mut peer, &socket);
+    test_replay_pcap(&mut peer, &socket);
+    test_resize_buffers(&mut peer, &socket);
+}
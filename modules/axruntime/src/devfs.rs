@@ -0,0 +1,98 @@
+//! The console, random and framebuffer devices in `/dev`.
+
+use alloc::sync::Arc;
+
+use axerrno::AxResult;
+use axfs::devfs::{self, CharDevice};
+
+/// Registers the devices.
+pub(crate) fn init() {
+    let console: Arc<dyn CharDevice> = Arc::new(Console);
+    let random: Arc<dyn CharDevice> = Arc::new(Random);
+    let devs = [
+        ("console", console.clone()),
+        ("ttyS0", console),
+        ("random", random.clone()),
+        ("urandom", random),
+        #[cfg(feature = "display")]
+        ("fb0", Arc::new(Framebuffer) as Arc<dyn CharDevice>),
+    ];
+    for (name, dev) in devs {
+        if let Err(e) = devfs::register_char_device(name, dev) {
+            warn!("failed to register /dev/{}: {:?}", name, e);
+        }
+    }
+}
+
+/// The serial console, reading only the bytes already received.
+struct Console;
+
+impl CharDevice for Console {
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        Ok(axhal::console::read_bytes(buf))
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> AxResult<usize> {
+        axhal::console::write_bytes(buf);
+        Ok(buf.len())
+    }
+}
+
+/// The kernel CSPRNG, both `random` and `urandom` never block.
+struct Random;
+
+impl CharDevice for Random {
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        axhal::random::fill_bytes(buf);
+        Ok(buf.len())
+    }
+
+    /// The written data is discarded, it's not mixed into the entropy.
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> AxResult<usize> {
+        Ok(buf.len())
+    }
+}
+
+/// The framebuffer of the main display, which is flushed on each write.
+#[cfg(feature = "display")]
+struct Framebuffer;
+
+#[cfg(feature = "display")]
+impl Framebuffer {
+    /// Returns the address of the framebuffer memory, and the part of it
+    /// accessed at `offset` by at most `len` bytes.
+    ///
+    /// The memory is also accessed by the display driver and other users, so
+    /// it's only copied through raw pointers, without references to it.
+    fn memory(offset: u64, len: usize) -> (*mut u8, core::ops::Range<usize>) {
+        let info = axdisplay::framebuffer_info();
+        let start = info.fb_size.min(offset as usize);
+        let n = len.min(info.fb_size - start);
+        (info.fb_base_vaddr as *mut u8, start..start + n)
+    }
+}
+
+#[cfg(feature = "display")]
+impl CharDevice for Framebuffer {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let (fb, range) = Self::memory(offset, buf.len());
+        let n = range.len();
+        unsafe { core::ptr::copy_nonoverlapping(fb.add(range.start), buf.as_mut_ptr(), n) };
+        Ok(n)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        let (fb, range) = Self::memory(offset, buf.len());
+        let n = range.len();
+        if n == 0 && !buf.is_empty() {
+            return axerrno::ax_err!(StorageFull);
+        }
+        unsafe { core::ptr::copy_nonoverlapping(buf.as_ptr(), fb.add(range.start), n) };
+        axdisplay::framebuffer_flush();
+        Ok(n)
+    }
+
+    fn size(&self) -> u64 {
+        axdisplay::framebuffer_info().fb_size as u64
+    }
+}
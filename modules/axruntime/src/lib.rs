@@ -11,6 +11,10 @@
 //! - `multitask`: Enable multi-threading support.
 //! - `smp`: Enable SMP (symmetric multiprocessing) support.
 //! - `fs`: Enable filesystem support.
+//! - `devfs`: Add the console, random and framebuffer devices to `/dev`.
+//! - `procfs`: Generate kernel information in `/proc`, e.g. `/proc/meminfo`
+//!   and a directory for each task.
+//! - `sysfs`: Generate the probed devices, CPUs and the platform in `/sys`.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
 //!
@@ -21,6 +25,8 @@
 
 #[macro_use]
 extern crate axlog;
+#[cfg(any(feature = "devfs", feature = "procfs", feature = "sysfs"))]
+extern crate alloc;
 
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
@@ -28,6 +34,13 @@ mod lang_items;
 #[cfg(feature = "smp")]
 mod mp;
 
+#[cfg(feature = "devfs")]
+mod devfs;
+#[cfg(feature = "procfs")]
+mod procfs;
+#[cfg(feature = "sysfs")]
+mod sysfs;
+
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
 
@@ -129,6 +142,7 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     axlog::set_max_level(option_env!("AX_LOG").unwrap_or("")); // no effect if set `log-level-*` features
     info!("Logging is enabled.");
     info!("Primary CPU {} started, dtb = {:#x}.", cpu_id, dtb);
+    info!("Kernel command line: {:?}", axhal::cmdline::cmdline());
 
     info!("Found physcial memory regions:");
     for r in axhal::mem::memory_regions() {
@@ -160,12 +174,19 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
 
         #[cfg(feature = "fs")]
         axfs::init_filesystems(all_devices.block);
+        #[cfg(feature = "procfs")]
+        procfs::init();
+        #[cfg(feature = "sysfs")]
+        sysfs::init();
 
         #[cfg(feature = "net")]
         axnet::init_network(all_devices.net);
 
         #[cfg(feature = "display")]
         axdisplay::init_display(all_devices.display);
+
+        #[cfg(feature = "devfs")]
+        devfs::init();
     }
 
     #[cfg(feature = "smp")]
@@ -194,6 +215,11 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
 
     unsafe { main() };
 
+    #[cfg(feature = "fs")]
+    if let Err(e) = axfs::api::sync() {
+        warn!("failed to sync filesystems: {:?}", e);
+    }
+
     #[cfg(feature = "multitask")]
     axtask::exit(0);
     #[cfg(not(feature = "multitask"))]
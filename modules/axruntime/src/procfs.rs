@@ -0,0 +1,177 @@
+//! Kernel information in `/proc`.
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+use axfs::proc;
+
+/// Registers the generated files, and the per-task directories with the
+/// `multitask` feature.
+pub(crate) fn init() {
+    let files: &[(&str, proc::ProcReadFn)] = &[
+        ("cpuinfo", cpuinfo),
+        ("uptime", uptime),
+        #[cfg(feature = "alloc")]
+        ("meminfo", meminfo),
+        #[cfg(feature = "irq")]
+        ("interrupts", interrupts),
+    ];
+    for &(path, read) in files {
+        if let Err(e) = proc::register_file(path, read) {
+            warn!("failed to register /proc/{}: {:?}", path, e);
+        }
+    }
+
+    #[cfg(feature = "paging")]
+    if let Err(e) = proc::register_tunable(
+        "sys/vm/overcommit_memory",
+        read_overcommit_memory,
+        write_overcommit_memory,
+    ) {
+        warn!("failed to register /proc/sys/vm/overcommit_memory: {:?}", e);
+    }
+
+    #[cfg(feature = "multitask")]
+    if let Err(e) = proc::register_dir("", tasks::task_dirs) {
+        warn!("failed to register the task directories in /proc: {:?}", e);
+    }
+}
+
+fn cpuinfo() -> String {
+    let mut out = String::new();
+    for cpu_id in 0..axconfig::SMP {
+        out += &format!("processor\t: {}\n", cpu_id);
+        out += &format!("arch\t\t: {}\n", axconfig::ARCH);
+        out += &format!("platform\t: {}\n\n", axconfig::PLATFORM);
+    }
+    out
+}
+
+/// Generates the uptime, the idle time is not accounted.
+fn uptime() -> String {
+    let now = axhal::time::monotonic_time();
+    format!("{}.{:02} 0.00\n", now.as_secs(), now.subsec_millis() / 10)
+}
+
+#[cfg(feature = "alloc")]
+fn meminfo() -> String {
+    let allocator = axalloc::global_allocator();
+    let kb = |pages: usize| pages * axhal::mem::PAGE_SIZE_4K / 1024;
+    let free = kb(allocator.available_pages());
+    let mut out = String::new();
+    out += &format!(
+        "MemTotal:\t{:>8} kB\n",
+        kb(allocator.used_pages() + allocator.available_pages())
+    );
+    out += &format!("MemFree:\t{:>8} kB\n", free);
+    out += &format!("MemAvailable:\t{:>8} kB\n", free);
+    out += &format!("Slab:\t\t{:>8} kB\n", allocator.used_bytes() / 1024);
+    #[cfg(feature = "paging")]
+    {
+        out += &format!("Committed_AS:\t{:>8} kB\n", kb(axmm::committed_pages()));
+    }
+    out
+}
+
+/// Generates the number of times each IRQ occurred, on all CPUs.
+#[cfg(feature = "irq")]
+fn interrupts() -> String {
+    let mut out = format!("{:>4}  {:>10}\n", "", "TOTAL");
+    for (irq_num, count) in axhal::irq::irq_counts() {
+        out += &format!("{:>4}: {:>10}\n", irq_num, count);
+    }
+    out
+}
+
+#[cfg(feature = "paging")]
+fn read_overcommit_memory() -> String {
+    format!("{}\n", axmm::overcommit_policy() as u8)
+}
+
+#[cfg(feature = "paging")]
+fn write_overcommit_memory(value: &str) -> axerrno::AxResult {
+    let policy = value.trim().parse::<u8>().ok().map(TryInto::try_into);
+    match policy {
+        Some(Ok(policy)) => {
+            axmm::set_overcommit_policy(policy);
+            Ok(())
+        }
+        _ => axerrno::ax_err!(InvalidInput),
+    }
+}
+
+/// The directories of tasks, `/proc/<id>` and `/proc/self`.
+#[cfg(feature = "multitask")]
+mod tasks {
+    use alloc::{format, string::String, sync::Arc, vec::Vec};
+
+    use axfs::proc::ProcEntry;
+    use axtask::{AxTaskRef, TaskState};
+
+    use super::vec;
+
+    pub(super) fn task_dirs() -> Vec<(String, ProcEntry)> {
+        let mut dirs: Vec<_> = axtask::all_tasks()
+            .iter()
+            .map(|task| (format!("{}", task.id().as_u64()), task_dir(task)))
+            .collect();
+        if let Some(curr) = axtask::current_may_uninit() {
+            dirs.push(("self".into(), task_dir(curr.as_task_ref())));
+        }
+        dirs
+    }
+
+    /// The directory of the task, which doesn't keep it from being dropped.
+    fn task_dir(task: &AxTaskRef) -> ProcEntry {
+        let task = Arc::downgrade(task);
+        ProcEntry::dir(move || {
+            let files: [(&str, fn(&AxTaskRef) -> String); 2] =
+                [("stat", task_stat), ("status", task_status)];
+            files
+                .into_iter()
+                .map(|(name, render)| {
+                    let task = task.clone();
+                    let read = move || task.upgrade().map_or_else(String::new, |t| render(&t));
+                    (name.into(), ProcEntry::file(read))
+                })
+                .collect()
+        })
+    }
+
+    fn state_char(state: TaskState) -> char {
+        match state {
+            TaskState::Running | TaskState::Ready => 'R',
+            TaskState::Blocked => 'S',
+            TaskState::Exited => 'Z',
+        }
+    }
+
+    /// Generates the 52 fields of `/proc/<id>/stat`, the unknown ones are 0.
+    fn task_stat(task: &AxTaskRef) -> String {
+        let mut fields = vec!["0"; 52 - 3];
+        fields[20 - 4] = "1"; // num_threads
+        format!(
+            "{} ({}) {} {}\n",
+            task.id().as_u64(),
+            task.name(),
+            state_char(task.state()),
+            fields.join(" ")
+        )
+    }
+
+    fn task_status(task: &AxTaskRef) -> String {
+        let state = match task.state() {
+            TaskState::Running => "R (running)",
+            TaskState::Ready => "R (ready)",
+            TaskState::Blocked => "S (sleeping)",
+            TaskState::Exited => "Z (zombie)",
+        };
+        let id = task.id().as_u64();
+        format!(
+            "Name:\t{}\nState:\t{}\nTgid:\t{}\nPid:\t{}\nPPid:\t0\nThreads:\t1\n",
+            task.name(),
+            state,
+            id,
+            id
+        )
+    }
+}
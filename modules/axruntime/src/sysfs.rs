@@ -0,0 +1,175 @@
+//! Devices, CPUs and the platform in `/sys`.
+
+use alloc::{format, string::String, vec, vec::Vec};
+use core::fmt::Display;
+
+use axdriver::{DeviceBus, DeviceInfo, prelude::DeviceType};
+use axfs::sys::{self, SysEntry};
+
+/// Registers the generated files and directories.
+pub(crate) fn init() {
+    let files: &[(&str, sys::SysReadFn)] = &[
+        ("kernel/platform/arch", || format!("{}\n", axconfig::ARCH)),
+        ("kernel/platform/name", || {
+            format!("{}\n", axconfig::PLATFORM)
+        }),
+        ("kernel/platform/family", || {
+            format!("{}\n", axconfig::plat::FAMILY)
+        }),
+        ("kernel/mm/transparent_hugepage/enabled", || {
+            "always madvise [never]\n".into()
+        }),
+        (
+            "devices/system/clocksource/clocksource0/current_clocksource",
+            clock_source,
+        ),
+        (
+            "devices/system/clocksource/clocksource0/available_clocksource",
+            clock_source,
+        ),
+        ("devices/system/cpu/possible", || cpu_list(axconfig::SMP)),
+        ("devices/system/cpu/present", || cpu_list(axconfig::SMP)),
+        ("devices/system/cpu/online", || {
+            cpu_list(super::INITED_CPUS.load(core::sync::atomic::Ordering::Acquire))
+        }),
+    ];
+    for &(path, read) in files {
+        if let Err(e) = sys::register_file(path, read) {
+            warn!("failed to register /sys/{}: {:?}", path, e);
+        }
+    }
+
+    let dirs: &[(&str, sys::SysDirFn)] = &[
+        ("devices/system/cpu", cpu_dirs),
+        ("bus/pci/devices", pci_devices),
+        ("bus/platform/devices", platform_devices),
+        ("class/net", || class_devices(DeviceType::Net)),
+        ("class/block", || class_devices(DeviceType::Block)),
+        ("class/graphics", || class_devices(DeviceType::Display)),
+    ];
+    for &(path, entries) in dirs {
+        if let Err(e) = sys::register_dir(path, entries) {
+            warn!("failed to register /sys/{}: {:?}", path, e);
+        }
+    }
+}
+
+/// A file with the single value `value`.
+fn value_file(name: &str, value: impl Display) -> (String, SysEntry) {
+    let content = format!("{}\n", value);
+    (name.into(), SysEntry::file(move || content.clone()))
+}
+
+fn clock_source() -> String {
+    format!("{}\n", axhal::time::CLOCK_SOURCE)
+}
+
+/// Formats the IDs of the first `n` CPUs, e.g. `0-3`.
+fn cpu_list(n: usize) -> String {
+    match n {
+        0 => "\n".into(),
+        1 => "0\n".into(),
+        _ => format!("0-{}\n", n - 1),
+    }
+}
+
+/// Generates `cpu<N>/topology`, each CPU is a core of the only package.
+fn cpu_dirs() -> Vec<(String, SysEntry)> {
+    (0..axconfig::SMP)
+        .map(|cpu_id| {
+            let topology = SysEntry::dir(move || {
+                vec![
+                    value_file("core_id", cpu_id),
+                    value_file("physical_package_id", 0),
+                ]
+            });
+            let cpu = SysEntry::dir(move || vec![("topology".into(), topology.clone())]);
+            (format!("cpu{}", cpu_id), cpu)
+        })
+        .collect()
+}
+
+/// The directory of a probed device.
+fn device_dir(info: DeviceInfo) -> SysEntry {
+    SysEntry::dir(move || {
+        let mut files = vec![value_file("driver", &info.driver)];
+        match info.bus {
+            DeviceBus::Pci {
+                vendor_id,
+                device_id,
+                class,
+                ..
+            } => {
+                files.push(value_file("vendor", format!("{:#06x}", vendor_id)));
+                files.push(value_file("device", format!("{:#06x}", device_id)));
+                let class = format!("{:#04x}{:02x}{:02x}", class[0], class[1], class[2]);
+                files.push(value_file("class", class));
+            }
+            DeviceBus::Mmio { base, size } => {
+                let resource = format!("{:#018x} {:#018x}", base, base + size - 1);
+                files.push(value_file("resource", resource));
+            }
+            DeviceBus::Platform => {}
+        }
+        if let Some(irq) = info.irq {
+            files.push(value_file("irq", irq));
+        }
+        files
+    })
+}
+
+/// Generates the PCI devices, named by their addresses, e.g. `0000:00:01.0`.
+fn pci_devices() -> Vec<(String, SysEntry)> {
+    axdriver::devices()
+        .into_iter()
+        .filter_map(|info| match info.bus {
+            DeviceBus::Pci {
+                bus,
+                device,
+                function,
+                ..
+            } => {
+                let name = format!("0000:{:02x}:{:02x}.{:x}", bus, device, function);
+                Some((name, device_dir(info)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Generates the devices not on PCI, the MMIO ones are named by their base
+/// addresses, e.g. `10001000.mmio`, the others by their drivers and indexes,
+/// e.g. `ramdisk.0`.
+fn platform_devices() -> Vec<(String, SysEntry)> {
+    let mut index = 0;
+    let mut entries = Vec::new();
+    for info in axdriver::devices() {
+        let name = match info.bus {
+            DeviceBus::Mmio { base, .. } => format!("{:x}.mmio", base),
+            DeviceBus::Platform => {
+                index += 1;
+                format!("{}.{}", info.driver, index - 1)
+            }
+            DeviceBus::Pci { .. } => continue,
+        };
+        entries.push((name, device_dir(info)));
+    }
+    entries
+}
+
+/// Generates the devices of the category, named as they are used, e.g.
+/// `eth0` and `disk0`.
+fn class_devices(device_type: DeviceType) -> Vec<(String, SysEntry)> {
+    let prefix = match device_type {
+        DeviceType::Net => "eth",
+        DeviceType::Block => "disk",
+        DeviceType::Display => "fb",
+        _ => "dev",
+    };
+    axdriver::devices()
+        .into_iter()
+        .filter(|info| info.device_type == device_type)
+        .enumerate()
+        .map(|(i, info)| (format!("{}{}", prefix, i), device_dir(info)))
+        .collect()
+}
@@ -3,6 +3,7 @@
 use alloc::{
     string::String,
     sync::{Arc, Weak},
+    vec::Vec,
 };
 
 use kernel_guard::NoPreemptIrqSave;
@@ -114,6 +115,12 @@ pub fn spawn_task(task: TaskInner) -> AxTaskRef {
     task_ref
 }
 
+/// Returns all tasks, including the exited ones not dropped yet, in the
+/// order of their IDs.
+pub fn all_tasks() -> Vec<AxTaskRef> {
+    crate::task::all_tasks()
+}
+
 /// Spawns a new task with the given parameters.
 ///
 /// Returns the task reference.
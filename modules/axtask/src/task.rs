@@ -1,4 +1,4 @@
-use alloc::{boxed::Box, string::String, sync::Arc};
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use core::ops::Deref;
 use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU64, Ordering};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
@@ -14,7 +14,10 @@ use axhal::arch::TaskContext;
 use axhal::tls::TlsArea;
 
 use crate::task_ext::AxTaskExt;
-use crate::{AxCpuMask, AxTask, AxTaskRef, WaitQueue};
+use crate::{AxCpuMask, AxTask, AxTaskRef, WaitQueue, WeakAxTaskRef};
+
+/// The tasks not dropped yet, indexed by their IDs.
+static TASK_TABLE: SpinNoIrq<BTreeMap<u64, WeakAxTaskRef>> = SpinNoIrq::new(BTreeMap::new());
 
 /// A unique identifier for a thread.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -283,7 +286,11 @@ impl TaskInner {
     }
 
     pub(crate) fn into_arc(self) -> AxTaskRef {
-        Arc::new(AxTask::new(self))
+        let task = Arc::new(AxTask::new(self));
+        TASK_TABLE
+            .lock()
+            .insert(task.id().as_u64(), Arc::downgrade(&task));
+        task
     }
 
     /// Returns the task's current state.
@@ -454,9 +461,16 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
+        TASK_TABLE.lock().remove(&self.id.as_u64());
     }
 }
 
+/// Returns the tasks not dropped yet, in the order of their IDs.
+pub(crate) fn all_tasks() -> Vec<AxTaskRef> {
+    let table = TASK_TABLE.lock();
+    table.values().filter_map(|task| task.upgrade()).collect()
+}
+
 struct TaskStack {
     ptr: NonNull<u8>,
     layout: Layout,
@@ -0,0 +1,34 @@
+#!/bin/bash
+#
+# Run a local TLS 1.3 server for testing `axstd::net::TlsStream`.
+#
+# It generates a CA and a server certificate for "localhost", copies the CA
+# certificate into the FAT32 disk image as "/ca.pem", and serves HTTPS on port
+# 4433. In the QEMU user-net setup, the guest can reach it at 10.0.2.2:4433.
+#
+# Usage: tls-server.sh [disk.img]
+
+set -e
+
+disk=${1:-disk.img}
+dir=$(mktemp -d)
+trap 'rm -rf "$dir"' EXIT
+
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes \
+    -keyout "$dir/ca.key" -out "$dir/ca.pem" -days 30 -subj "/CN=ArceOS Test CA" \
+    -addext "basicConstraints=critical,CA:TRUE" 2>/dev/null
+openssl req -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes \
+    -keyout "$dir/server.key" -out "$dir/server.csr" -subj "/CN=localhost" 2>/dev/null
+openssl x509 -req -in "$dir/server.csr" -CA "$dir/ca.pem" -CAkey "$dir/ca.key" \
+    -CAcreateserial -out "$dir/server.pem" -days 30 \
+    -extfile <(printf "subjectAltName=DNS:localhost\nbasicConstraints=CA:FALSE") 2>/dev/null
+
+if [ -f "$disk" ]; then
+    mcopy -o -i "$disk" "$dir/ca.pem" ::/ca.pem
+    echo "Copied the CA certificate to $disk:/ca.pem"
+else
+    echo "Warning: disk image \"$disk\" not found, run \`make disk_img\` first"
+fi
+
+openssl s_server -accept 4433 -tls1_3 -www \
+    -cert "$dir/server.pem" -key "$dir/server.key"
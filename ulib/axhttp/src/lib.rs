@@ -0,0 +1,51 @@
+//! A simple HTTP/1.1 server.
+//!
+//! It parses requests with `Content-Length` or chunked bodies, keeps
+//! connections alive, dispatches requests by a [`Router`], and serves static
+//! files by [`StaticFiles`]. Each connection is handled in its own thread.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use axhttp::{Request, Response, Router, Server};
+//!
+//! let router = Router::new()
+//!     .get("/", |_req: Request| Response::html("<h1>Hello, ArceOS</h1>"))
+//!     .get("/users/:id", |req: Request| {
+//!         Response::text(format!("user {}\n", req.param("id").unwrap()))
+//!     });
+//! Server::bind("0.0.0.0:5555").unwrap().serve(router).unwrap();
+//! ```
+//!
+//! # Cargo Features
+//!
+//! - `axstd`: Build on [axstd] for ArceOS apps. Otherwise, it's built on the
+//!   Rust std, so the same app can also run on the host.
+//! - `fs`: Enable [`StaticFiles`].
+//!
+//! [axstd]: https://arceos-org.github.io/arceos/axstd/index.html
+
+#![cfg_attr(all(feature = "axstd", not(test)), no_std)]
+
+#[cfg(all(feature = "axstd", not(test)))]
+extern crate axstd as std;
+
+extern crate alloc;
+
+mod headers;
+mod request;
+mod response;
+mod router;
+mod server;
+
+#[cfg(feature = "fs")]
+mod static_files;
+
+pub use self::headers::Headers;
+pub use self::request::{Method, Request, Version};
+pub use self::response::{Body, Response, StatusCode};
+pub use self::router::{Handler, Router};
+pub use self::server::Server;
+
+#[cfg(feature = "fs")]
+pub use self::static_files::StaticFiles;
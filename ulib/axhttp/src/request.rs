@@ -0,0 +1,577 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::{fmt, str};
+use std::net::SocketAddr;
+
+use crate::headers::Headers;
+use crate::response::StatusCode;
+
+/// The maximum length of the request line and the header fields.
+const MAX_HEAD_LEN: usize = 8192;
+/// The maximum number of header fields.
+const MAX_HEADERS: usize = 64;
+/// The maximum length of a chunk-size line of a chunked body.
+const MAX_CHUNK_LINE_LEN: usize = 1024;
+
+/// HTTP request methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Options,
+    Patch,
+    Trace,
+    Connect,
+}
+
+impl Method {
+    /// Returns the name of the method, e.g. `"GET"`.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Options => "OPTIONS",
+            Self::Patch => "PATCH",
+            Self::Trace => "TRACE",
+            Self::Connect => "CONNECT",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "GET" => Self::Get,
+            "HEAD" => Self::Head,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "OPTIONS" => Self::Options,
+            "PATCH" => Self::Patch,
+            "TRACE" => Self::Trace,
+            "CONNECT" => Self::Connect,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// HTTP versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// HTTP/1.0
+    Http10,
+    /// HTTP/1.1
+    Http11,
+}
+
+/// Errors of malformed or unsupported requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ParseError {
+    Malformed,
+    UnknownMethod,
+    UnsupportedVersion,
+    UnsupportedEncoding,
+    HeadTooLarge,
+    BodyTooLarge,
+}
+
+impl ParseError {
+    /// The status code of the response to the request.
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::Malformed => StatusCode::BAD_REQUEST,
+            Self::UnknownMethod | Self::UnsupportedEncoding => StatusCode::NOT_IMPLEMENTED,
+            Self::UnsupportedVersion => StatusCode::HTTP_VERSION_NOT_SUPPORTED,
+            Self::HeadTooLarge => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            Self::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+}
+
+/// How the length of the request body is determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BodyKind {
+    None,
+    Length(usize),
+    Chunked,
+}
+
+/// The request line and the header fields.
+pub(crate) struct RequestHead {
+    pub method: Method,
+    pub target: String,
+    pub version: Version,
+    pub headers: Headers,
+}
+
+impl RequestHead {
+    pub fn body_kind(&self) -> Result<BodyKind, ParseError> {
+        if let Some(coding) = self.headers.get("Transfer-Encoding") {
+            // other codings (e.g. gzip) are not supported
+            return if coding.trim().eq_ignore_ascii_case("chunked") {
+                Ok(BodyKind::Chunked)
+            } else {
+                Err(ParseError::UnsupportedEncoding)
+            };
+        }
+        let mut len = None;
+        for value in self.headers.get_all("Content-Length") {
+            let value = value.parse().map_err(|_| ParseError::Malformed)?;
+            if len.is_some_and(|len| len != value) {
+                return Err(ParseError::Malformed);
+            }
+            len = Some(value);
+        }
+        Ok(len.map_or(BodyKind::None, BodyKind::Length))
+    }
+
+    /// Returns `true` if the client waits for `100 Continue` before sending
+    /// the body.
+    pub fn expects_continue(&self) -> bool {
+        self.version == Version::Http11
+            && self
+                .headers
+                .get("Expect")
+                .is_some_and(|v| v.eq_ignore_ascii_case("100-continue"))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Parses the request line and the header fields at the beginning of `buf`.
+///
+/// Returns `Ok(None)` if they are incomplete, otherwise returns them and their
+/// length including the empty line.
+pub(crate) fn parse_head(buf: &[u8]) -> Result<Option<(RequestHead, usize)>, ParseError> {
+    let Some(end) = find(buf, b"\r\n\r\n") else {
+        return if buf.len() > MAX_HEAD_LEN {
+            Err(ParseError::HeadTooLarge)
+        } else {
+            Ok(None)
+        };
+    };
+    if end > MAX_HEAD_LEN {
+        return Err(ParseError::HeadTooLarge);
+    }
+    let head = str::from_utf8(&buf[..end]).map_err(|_| ParseError::Malformed)?;
+    let mut lines = head.split("\r\n");
+
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(ParseError::Malformed);
+    };
+    let method = Method::parse(method).ok_or(ParseError::UnknownMethod)?;
+    if !target.starts_with('/') && target != "*" {
+        return Err(ParseError::Malformed);
+    }
+    let version = match version {
+        "HTTP/1.1" => Version::Http11,
+        "HTTP/1.0" => Version::Http10,
+        v if v.starts_with("HTTP/") => return Err(ParseError::UnsupportedVersion),
+        _ => return Err(ParseError::Malformed),
+    };
+
+    let mut headers = Headers::new();
+    for line in lines {
+        if headers.len() >= MAX_HEADERS {
+            return Err(ParseError::HeadTooLarge);
+        }
+        let (name, value) = line.split_once(':').ok_or(ParseError::Malformed)?;
+        // obsolete line folding and spaces before the colon are rejected
+        if name.is_empty() || name.contains([' ', '\t']) {
+            return Err(ParseError::Malformed);
+        }
+        headers.append(name, value.trim());
+    }
+
+    let head = RequestHead {
+        method,
+        target: target.to_string(),
+        version,
+        headers,
+    };
+    Ok(Some((head, end + 4)))
+}
+
+/// Parses a hexadecimal number, without the sign accepted by
+/// [`usize::from_str_radix`].
+fn parse_hex(s: &str) -> Option<usize> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    usize::from_str_radix(s, 16).ok()
+}
+
+/// The part of a chunked body to be decoded next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkState {
+    /// The chunk-size line.
+    Size,
+    /// The rest of the chunk data, of the given length.
+    Data(usize),
+    /// The line break after the chunk data.
+    DataEnd,
+    /// The trailer section, of which the given length has been decoded.
+    Trailer(usize),
+}
+
+/// Decodes a chunked body incrementally, the trailer fields are discarded.
+pub(crate) struct ChunkedDecoder {
+    body: Vec<u8>,
+    max_len: usize,
+    state: ChunkState,
+}
+
+impl ChunkedDecoder {
+    /// Creates a decoder of a body of at most `max_len` bytes.
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            body: Vec::new(),
+            max_len,
+            state: ChunkState::Size,
+        }
+    }
+
+    /// Decodes the data at the beginning of `buf`.
+    ///
+    /// Returns the length of the data consumed, which must not be passed
+    /// again, and the decoded body if it's complete.
+    pub fn decode(&mut self, buf: &[u8]) -> Result<(usize, Option<Vec<u8>>), ParseError> {
+        let mut pos = 0;
+        loop {
+            let rest = &buf[pos..];
+            match self.state {
+                ChunkState::Size => {
+                    let Some(line_len) = find(rest, b"\r\n") else {
+                        return if rest.len() > MAX_CHUNK_LINE_LEN {
+                            Err(ParseError::Malformed)
+                        } else {
+                            Ok((pos, None))
+                        };
+                    };
+                    if line_len > MAX_CHUNK_LINE_LEN {
+                        return Err(ParseError::Malformed);
+                    }
+                    let line =
+                        str::from_utf8(&rest[..line_len]).map_err(|_| ParseError::Malformed)?;
+                    // ignore chunk extensions
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    let size = parse_hex(size).ok_or(ParseError::Malformed)?;
+                    if size > self.max_len - self.body.len() {
+                        return Err(ParseError::BodyTooLarge);
+                    }
+                    pos += line_len + 2;
+                    self.state = match size {
+                        0 => ChunkState::Trailer(0),
+                        size => ChunkState::Data(size),
+                    };
+                }
+                ChunkState::Data(remaining) => {
+                    let len = remaining.min(rest.len());
+                    if len == 0 {
+                        return Ok((pos, None));
+                    }
+                    self.body.extend_from_slice(&rest[..len]);
+                    pos += len;
+                    self.state = match remaining - len {
+                        0 => ChunkState::DataEnd,
+                        remaining => ChunkState::Data(remaining),
+                    };
+                }
+                ChunkState::DataEnd => {
+                    if rest.len() < 2 {
+                        return Ok((pos, None));
+                    }
+                    if &rest[..2] != b"\r\n" {
+                        return Err(ParseError::Malformed);
+                    }
+                    pos += 2;
+                    self.state = ChunkState::Size;
+                }
+                ChunkState::Trailer(decoded) => {
+                    let Some(line_len) = find(rest, b"\r\n") else {
+                        return if decoded + rest.len() > MAX_HEAD_LEN {
+                            Err(ParseError::HeadTooLarge)
+                        } else {
+                            Ok((pos, None))
+                        };
+                    };
+                    pos += line_len + 2;
+                    // the trailer section ends with an empty line
+                    if line_len == 0 {
+                        self.state = ChunkState::Size;
+                        return Ok((pos, Some(core::mem::take(&mut self.body))));
+                    }
+                    let decoded = decoded + line_len + 2;
+                    if decoded > MAX_HEAD_LEN {
+                        return Err(ParseError::HeadTooLarge);
+                    }
+                    self.state = ChunkState::Trailer(decoded);
+                }
+            }
+        }
+    }
+}
+
+/// Decodes `%XX` escapes, and `+` as a space if `plus_as_space` is set (in
+/// query strings).
+///
+/// Returns `None` if there are invalid escapes or the result is not UTF-8.
+pub(crate) fn percent_decode(s: &str, plus_as_space: bool) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+                out.push(parse_hex(hex)? as u8);
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// An HTTP request.
+pub struct Request {
+    method: Method,
+    path: String,
+    query: Option<String>,
+    version: Version,
+    headers: Headers,
+    body: Vec<u8>,
+    params: Vec<(String, String)>,
+    peer_addr: SocketAddr,
+}
+
+impl Request {
+    pub(crate) fn new(head: RequestHead, body: Vec<u8>, peer_addr: SocketAddr) -> Self {
+        let (path, query) = match head.target.split_once('?') {
+            Some((path, query)) => (path.to_string(), Some(query.to_string())),
+            None => (head.target, None),
+        };
+        Self {
+            method: head.method,
+            path,
+            query,
+            version: head.version,
+            headers: head.headers,
+            body,
+            params: Vec::new(),
+            peer_addr,
+        }
+    }
+
+    /// Returns the request method.
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// Returns the path of the request target, without the query string.
+    ///
+    /// It's not percent-decoded.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the query string (without `?`), if any.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// Returns the percent-decoded value of a parameter in the query string.
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        self.query()?.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if percent_decode(key, true)? == name {
+                percent_decode(value, true)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the HTTP version of the request.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the header fields.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Returns the value of the first header field with the given name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Returns the body, decoded if it's chunked.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Takes the body out of the request.
+    pub fn take_body(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.body)
+    }
+
+    /// Returns the percent-decoded value of a parameter in the route pattern,
+    /// e.g. `id` in `/users/:id`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub(crate) fn set_params(&mut self, params: Vec<(String, String)>) {
+        self.params = params;
+    }
+
+    /// Returns the address of the client.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Returns `true` if the client wants to keep the connection open after
+    /// the response.
+    pub fn keep_alive(&self) -> bool {
+        match self.version {
+            Version::Http11 => !self.headers.has_token("Connection", "close"),
+            Version::Http10 => self.headers.has_token("Connection", "keep-alive"),
+        }
+    }
+}
+
+impl fmt::Debug for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("method", &self.method)
+            .field("path", &self.path)
+            .field("query", &self.query)
+            .field("version", &self.version)
+            .field("headers", &self.headers)
+            .field("body_len", &self.body.len())
+            .field("peer_addr", &self.peer_addr)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_requests() {
+        let buf = b"POST /users?id=1&name=a%20b HTTP/1.1\r\nHost: x\r\n\
+            Transfer-Encoding: chunked\r\n\r\n5;ext\r\nhello\r\n0\r\nX: y\r\n\r\nGET";
+        let (head, len) = parse_head(buf).unwrap().unwrap();
+        assert_eq!(head.method, Method::Post);
+        assert_eq!(head.headers.get("host"), Some("x"));
+        assert_eq!(head.body_kind(), Ok(BodyKind::Chunked));
+
+        let (body_len, body) = ChunkedDecoder::new(1024).decode(&buf[len..]).unwrap();
+        let body = body.unwrap();
+        assert_eq!(body, b"hello");
+        assert_eq!(&buf[len + body_len..], b"GET");
+        assert_eq!(
+            ChunkedDecoder::new(4).decode(&buf[len..]),
+            Err(ParseError::BodyTooLarge)
+        );
+
+        let req = Request::new(head, body, "127.0.0.1:80".parse().unwrap());
+        assert_eq!(req.path(), "/users");
+        assert_eq!(req.query_param("name").as_deref(), Some("a b"));
+        assert!(req.keep_alive());
+
+        // incomplete, malformed and unsupported requests
+        assert!(matches!(parse_head(b"GET / HTTP/1.1\r\n"), Ok(None)));
+        assert!(matches!(
+            parse_head(b"GET /  HTTP/1.1\r\n\r\n"),
+            Err(ParseError::Malformed)
+        ));
+        assert!(matches!(
+            parse_head(b"GET / HTTP/1.1\r\nBad : x\r\n\r\n"),
+            Err(ParseError::Malformed)
+        ));
+        assert!(matches!(
+            parse_head(b"BREW / HTTP/1.1\r\n\r\n"),
+            Err(ParseError::UnknownMethod)
+        ));
+        assert!(matches!(
+            parse_head(b"GET / HTTP/2.0\r\n\r\n"),
+            Err(ParseError::UnsupportedVersion)
+        ));
+        let (head, _) = parse_head(b"GET / HTTP/1.0\r\nContent-Length: 3\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(head.body_kind(), Ok(BodyKind::Length(3)));
+    }
+
+    #[test]
+    fn decode_chunked() {
+        // fed byte by byte, without decoding the consumed data again
+        let body = b"3\r\nabc\r\n10\r\n0123456789abcdef\r\n0\r\nX: y\r\n\r\n";
+        let mut decoder = ChunkedDecoder::new(1024);
+        let mut pending = Vec::new();
+        let mut decoded = None;
+        for (i, &b) in body.iter().enumerate() {
+            assert!(decoded.is_none(), "decoded before {}", i);
+            pending.push(b);
+            let (len, body) = decoder.decode(&pending).unwrap();
+            pending.drain(..len);
+            decoded = body;
+        }
+        assert!(pending.is_empty());
+        assert_eq!(decoded.as_deref(), Some(&b"abc0123456789abcdef"[..]));
+
+        // sizes with signs or overflowing are rejected
+        let mut decoder = ChunkedDecoder::new(1024);
+        assert_eq!(decoder.decode(b"+5\r\n"), Err(ParseError::Malformed));
+        let mut decoder = ChunkedDecoder::new(usize::MAX);
+        assert_eq!(decoder.decode(b"1\r\na\r\n"), Ok((6, None)));
+        assert_eq!(
+            decoder.decode(b"ffffffffffffffff\r\n"),
+            Err(ParseError::BodyTooLarge)
+        );
+        let mut decoder = ChunkedDecoder::new(1024);
+        assert_eq!(
+            decoder.decode(b"1ffffffffffffffff\r\n"),
+            Err(ParseError::Malformed)
+        );
+    }
+
+    #[test]
+    fn decode_percent() {
+        assert_eq!(percent_decode("a%20b+c", true).as_deref(), Some("a b c"));
+        assert_eq!(percent_decode("a+c", false).as_deref(), Some("a+c"));
+        assert_eq!(percent_decode("%+1", false), None);
+        assert_eq!(percent_decode("%2", false), None);
+        assert_eq!(percent_decode("%ff", false), None);
+    }
+}
@@ -0,0 +1,297 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use std::io::{self, Read, Write};
+
+use crate::headers::Headers;
+use crate::request::Version;
+
+/// HTTP status codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StatusCode(u16);
+
+impl StatusCode {
+    pub const CONTINUE: Self = Self(100);
+    pub const OK: Self = Self(200);
+    pub const CREATED: Self = Self(201);
+    pub const NO_CONTENT: Self = Self(204);
+    pub const MOVED_PERMANENTLY: Self = Self(301);
+    pub const FOUND: Self = Self(302);
+    pub const NOT_MODIFIED: Self = Self(304);
+    pub const BAD_REQUEST: Self = Self(400);
+    pub const FORBIDDEN: Self = Self(403);
+    pub const NOT_FOUND: Self = Self(404);
+    pub const METHOD_NOT_ALLOWED: Self = Self(405);
+    pub const REQUEST_TIMEOUT: Self = Self(408);
+    pub const PAYLOAD_TOO_LARGE: Self = Self(413);
+    pub const REQUEST_HEADER_FIELDS_TOO_LARGE: Self = Self(431);
+    pub const INTERNAL_SERVER_ERROR: Self = Self(500);
+    pub const NOT_IMPLEMENTED: Self = Self(501);
+    pub const SERVICE_UNAVAILABLE: Self = Self(503);
+    pub const HTTP_VERSION_NOT_SUPPORTED: Self = Self(505);
+
+    /// Creates a status code from a number in `100..=999`.
+    pub const fn new(code: u16) -> Option<Self> {
+        if matches!(code, 100..=999) {
+            Some(Self(code))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the status code as a number.
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    /// Returns the standard reason phrase, or an empty string for unknown
+    /// codes.
+    pub const fn reason_phrase(self) -> &'static str {
+        match self.0 {
+            100 => "Continue",
+            200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            301 => "Moved Permanently",
+            302 => "Found",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            503 => "Service Unavailable",
+            505 => "HTTP Version Not Supported",
+            _ => "",
+        }
+    }
+
+    /// Returns `true` if responses with this status have no body.
+    const fn has_no_body(self) -> bool {
+        matches!(self.0, 100..=199 | 204 | 304)
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.0, self.reason_phrase())
+    }
+}
+
+/// The body of a response.
+pub enum Body {
+    /// No body.
+    Empty,
+    /// A body in memory.
+    Bytes(Vec<u8>),
+    /// A body read from a reader. If the length is unknown, it's sent with
+    /// the chunked encoding.
+    Reader {
+        /// The source of the body.
+        reader: Box<dyn Read + Send>,
+        /// The length of the body, if known.
+        len: Option<u64>,
+    },
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("Empty"),
+            Self::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            Self::Reader { len, .. } => f.debug_struct("Reader").field("len", len).finish(),
+        }
+    }
+}
+
+/// An HTTP response.
+///
+/// The `Content-Length`, `Transfer-Encoding` and `Connection` fields are set
+/// by the server.
+#[derive(Debug)]
+pub struct Response {
+    status: StatusCode,
+    headers: Headers,
+    body: Body,
+}
+
+impl Response {
+    /// Creates a response without body.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            headers: Headers::new(),
+            body: Body::Empty,
+        }
+    }
+
+    /// Creates a `200 OK` response with the given body.
+    pub fn bytes(content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self::new(StatusCode::OK)
+            .with_header("Content-Type", content_type)
+            .with_body(Body::Bytes(body.into()))
+    }
+
+    /// Creates a `200 OK` response with a plain text body.
+    pub fn text(body: impl Into<String>) -> Self {
+        Self::bytes("text/plain; charset=utf-8", body.into())
+    }
+
+    /// Creates a `200 OK` response with an HTML body.
+    pub fn html(body: impl Into<String>) -> Self {
+        Self::bytes("text/html; charset=utf-8", body.into())
+    }
+
+    /// Creates a `200 OK` response whose body is read from `reader`.
+    ///
+    /// If `len` is `None`, the body is sent with the chunked encoding.
+    pub fn reader(
+        content_type: &str,
+        reader: impl Read + Send + 'static,
+        len: Option<u64>,
+    ) -> Self {
+        Self::new(StatusCode::OK)
+            .with_header("Content-Type", content_type)
+            .with_body(Body::Reader {
+                reader: Box::new(reader),
+                len,
+            })
+    }
+
+    /// Creates an error response, whose body is the status line in plain
+    /// text.
+    pub fn error(status: StatusCode) -> Self {
+        Self::text(format!("{}\n", status)).with_status(status)
+    }
+
+    /// Creates a `301 Moved Permanently` response redirecting to `location`.
+    pub fn redirect(location: &str) -> Self {
+        Self::new(StatusCode::MOVED_PERMANENTLY).with_header("Location", location)
+    }
+
+    /// Sets the status code.
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Sets a header field, replacing the existing ones with the same name.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Sets the body.
+    pub fn with_body(mut self, body: Body) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Returns the status code.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the header fields.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Returns the header fields for modification.
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
+    /// Returns the body.
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    /// Writes the response to the client.
+    ///
+    /// The body is omitted if `head_only` is set, i.e. the request method is
+    /// `HEAD`. Returns whether the connection can be kept open.
+    pub(crate) fn write_to<W: Write>(
+        self,
+        w: &mut W,
+        version: Version,
+        keep_alive: bool,
+        head_only: bool,
+    ) -> io::Result<bool> {
+        let Self {
+            status,
+            mut headers,
+            body,
+        } = self;
+        let len = match &body {
+            Body::Empty => Some(0),
+            Body::Bytes(bytes) => Some(bytes.len() as u64),
+            Body::Reader { len, .. } => *len,
+        };
+        // HTTP/1.0 clients don't know the chunked encoding, the end of the
+        // body is marked by closing the connection instead.
+        let chunked = len.is_none() && version == Version::Http11;
+        let keep_alive = keep_alive && (len.is_some() || chunked);
+
+        headers.remove("Content-Length");
+        headers.remove("Transfer-Encoding");
+        if !status.has_no_body() {
+            match len {
+                Some(len) => headers.insert("Content-Length", format!("{}", len)),
+                None if chunked => headers.insert("Transfer-Encoding", "chunked"),
+                None => {}
+            }
+        }
+        headers.insert(
+            "Connection",
+            if keep_alive { "keep-alive" } else { "close" },
+        );
+
+        let mut head = format!("HTTP/1.1 {}\r\n", status);
+        for (name, value) in headers.iter() {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+        head += "\r\n";
+        let mut head = head.into_bytes();
+
+        if head_only || status.has_no_body() {
+            w.write_all(&head)?;
+            return Ok(keep_alive);
+        }
+        match body {
+            Body::Empty => w.write_all(&head)?,
+            Body::Bytes(bytes) => {
+                // send small responses in one segment
+                head.extend_from_slice(&bytes);
+                w.write_all(&head)?;
+            }
+            Body::Reader { mut reader, .. } => {
+                w.write_all(&head)?;
+                let mut buf = [0; 4096];
+                loop {
+                    let n = reader.read(&mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    if chunked {
+                        w.write_all(format!("{:x}\r\n", n).as_bytes())?;
+                        w.write_all(&buf[..n])?;
+                        w.write_all(b"\r\n")?;
+                    } else {
+                        w.write_all(&buf[..n])?;
+                    }
+                }
+                if chunked {
+                    w.write_all(b"0\r\n\r\n")?;
+                }
+            }
+        }
+        Ok(keep_alive)
+    }
+}
@@ -0,0 +1,200 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::request::{Method, Request, percent_decode};
+use crate::response::{Response, StatusCode};
+
+/// A request handler.
+///
+/// It's implemented for closures like `|req: Request| Response::text("...")`.
+pub trait Handler: Send + Sync + 'static {
+    /// Handles a request and returns the response.
+    fn handle(&self, req: Request) -> Response;
+}
+
+impl<F> Handler for F
+where
+    F: Fn(Request) -> Response + Send + Sync + 'static,
+{
+    fn handle(&self, req: Request) -> Response {
+        self(req)
+    }
+}
+
+enum Segment {
+    /// Matches the same segment.
+    Literal(String),
+    /// `:name`, matches any non-empty segment.
+    Param(String),
+    /// `*name` or `*` (named `*`), matches the rest of the path. It must be
+    /// the last segment.
+    Rest(String),
+}
+
+struct Route {
+    method: Method,
+    segments: Vec<Segment>,
+    handler: Box<dyn Handler>,
+}
+
+impl Route {
+    /// Matches the segments of a request path, returns the parameters if
+    /// matched.
+    fn match_path(&self, path: &[&str]) -> Option<Vec<(String, String)>> {
+        let mut params = Vec::new();
+        for (i, seg) in self.segments.iter().enumerate() {
+            match seg {
+                Segment::Literal(lit) => {
+                    if *path.get(i)? != lit.as_str() {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    params.push((name.clone(), percent_decode(path.get(i)?, false)?));
+                }
+                Segment::Rest(name) => {
+                    let rest = path.get(i..).unwrap_or_default().join("/");
+                    params.push((name.clone(), percent_decode(&rest, false)?));
+                    return Some(params);
+                }
+            }
+        }
+        (path.len() == self.segments.len()).then_some(params)
+    }
+}
+
+/// Dispatches requests to handlers by the method and the path.
+///
+/// Routes are matched in the order they are added. A path pattern consists of
+/// segments separated by `/`, and each of them can be:
+///
+/// - a literal, e.g. `users`;
+/// - `:name`, matches any segment, its value is [`Request::param`]`("name")`;
+/// - `*name` or `*`, must be the last one, matches the rest of the path
+///   (possibly empty), its value is `param("name")` or `param("*")`.
+///
+/// `HEAD` requests are handled by `GET` routes if there are no `HEAD` routes
+/// for the path. If the path matches but the method doesn't, the response is
+/// `405 Method Not Allowed`, otherwise it's `404 Not Found` by default.
+pub struct Router {
+    routes: Vec<Route>,
+    not_found: Option<Box<dyn Handler>>,
+}
+
+impl Router {
+    /// Creates a router without routes.
+    pub fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            not_found: None,
+        }
+    }
+
+    /// Adds a route.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `*` is not in the last segment of the pattern.
+    pub fn route(mut self, method: Method, pattern: &str, handler: impl Handler) -> Self {
+        let segments: Vec<Segment> = pattern
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                if let Some(name) = s.strip_prefix(':') {
+                    Segment::Param(name.to_string())
+                } else if let Some(name) = s.strip_prefix('*') {
+                    Segment::Rest(if name.is_empty() { "*" } else { name }.to_string())
+                } else {
+                    Segment::Literal(s.to_string())
+                }
+            })
+            .collect();
+        let rest_pos = segments.iter().position(|s| matches!(s, Segment::Rest(_)));
+        assert!(
+            rest_pos.is_none_or(|pos| pos + 1 == segments.len()),
+            "`*` must be in the last segment: {:?}",
+            pattern
+        );
+        self.routes.push(Route {
+            method,
+            segments,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Adds a route for `GET` requests.
+    pub fn get(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::Get, pattern, handler)
+    }
+
+    /// Adds a route for `POST` requests.
+    pub fn post(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::Post, pattern, handler)
+    }
+
+    /// Adds a route for `PUT` requests.
+    pub fn put(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::Put, pattern, handler)
+    }
+
+    /// Adds a route for `DELETE` requests.
+    pub fn delete(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::Delete, pattern, handler)
+    }
+
+    /// Sets the handler of requests that match no routes.
+    pub fn not_found(mut self, handler: impl Handler) -> Self {
+        self.not_found = Some(Box::new(handler));
+        self
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Handler for Router {
+    fn handle(&self, mut req: Request) -> Response {
+        let path: Vec<&str> = req.path().split('/').filter(|s| !s.is_empty()).collect();
+        let mut matched = None;
+        let mut get_fallback = None;
+        let mut allowed = Vec::new();
+        for route in &self.routes {
+            let Some(params) = route.match_path(&path) else {
+                continue;
+            };
+            if route.method == req.method() {
+                matched = Some((route, params));
+                break;
+            }
+            if req.method() == Method::Head && route.method == Method::Get && get_fallback.is_none()
+            {
+                get_fallback = Some((route, params));
+            }
+            if !allowed.contains(&route.method) {
+                allowed.push(route.method);
+            }
+        }
+
+        if let Some((route, params)) = matched.or(get_fallback) {
+            req.set_params(params);
+            return route.handler.handle(req);
+        }
+        if !allowed.is_empty() {
+            if allowed.contains(&Method::Get) && !allowed.contains(&Method::Head) {
+                allowed.push(Method::Head);
+            }
+            let allow: Vec<&str> = allowed.iter().map(|m| m.as_str()).collect();
+            return Response::error(StatusCode::METHOD_NOT_ALLOWED)
+                .with_header("Allow", &allow.join(", "));
+        }
+        match &self.not_found {
+            Some(handler) => handler.handle(req),
+            None => Response::error(StatusCode::NOT_FOUND),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::time::Duration;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+
+use crate::request::{BodyKind, ChunkedDecoder, Method, ParseError, Request, Version, parse_head};
+use crate::response::Response;
+use crate::router::Handler;
+
+/// The default limit of the request body size.
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+/// The default time to wait for the next request on a kept-alive connection.
+const DEFAULT_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+struct Config {
+    keep_alive_timeout: Option<Duration>,
+    max_body_size: usize,
+}
+
+/// An HTTP server.
+pub struct Server {
+    listener: TcpListener,
+    config: Config,
+}
+
+impl Server {
+    /// Creates a server listening on the given address.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr)?))
+    }
+
+    /// Creates a server from a listening socket.
+    pub fn from_listener(listener: TcpListener) -> Self {
+        Self {
+            listener,
+            config: Config {
+                keep_alive_timeout: Some(DEFAULT_KEEP_ALIVE_TIMEOUT),
+                max_body_size: DEFAULT_MAX_BODY_SIZE,
+            },
+        }
+    }
+
+    /// Returns the local address that the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Sets how long to wait for data from clients before closing the
+    /// connection, 5 seconds by default. `None` means waiting forever.
+    pub fn keep_alive_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.keep_alive_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum size of request bodies, 1 MiB by default. Larger
+    /// requests are answered with `413 Payload Too Large`.
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.config.max_body_size = size;
+        self
+    }
+
+    /// Accepts connections and handles their requests by `handler` in new
+    /// threads.
+    ///
+    /// It only returns if accepting a connection fails. Errors on the
+    /// connections close them silently.
+    pub fn serve<H: Handler>(self, handler: H) -> io::Result<()> {
+        let handler = Arc::new(handler);
+        loop {
+            let (stream, peer_addr) = self.listener.accept()?;
+            let handler = handler.clone();
+            let config = self.config;
+            thread::spawn(move || {
+                let mut conn = Connection::new(stream, peer_addr, config);
+                let _ = conn.serve(&*handler);
+            });
+        }
+    }
+}
+
+enum ReadError {
+    Io(io::Error),
+    Parse(ParseError),
+    /// The connection is closed in the middle of a request.
+    Eof,
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ParseError> for ReadError {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    peer_addr: SocketAddr,
+    config: Config,
+    /// Received but not parsed data, may contain pipelined requests.
+    buf: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream, peer_addr: SocketAddr, config: Config) -> Self {
+        Self {
+            stream,
+            peer_addr,
+            config,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Receives more data into the buffer, returns `Err(Eof)` if the
+    /// connection is closed.
+    fn fill(&mut self) -> Result<(), ReadError> {
+        let mut chunk = [0; 4096];
+        let n = self.stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(ReadError::Eof);
+        }
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(())
+    }
+
+    /// Reads the next request, returns `Ok(None)` if the connection is closed
+    /// between requests.
+    fn read_request(&mut self) -> Result<Option<Request>, ReadError> {
+        let (head, head_len) = loop {
+            if let Some(head) = parse_head(&self.buf)? {
+                break head;
+            }
+            match self.fill() {
+                Err(ReadError::Eof) if self.buf.is_empty() => return Ok(None),
+                res => res?,
+            }
+        };
+        self.buf.drain(..head_len);
+
+        let body_kind = head.body_kind()?;
+        if matches!(body_kind, BodyKind::Length(len) if len > self.config.max_body_size) {
+            return Err(ParseError::BodyTooLarge.into());
+        }
+        if body_kind != BodyKind::None && head.expects_continue() {
+            self.stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        }
+        let body = match body_kind {
+            BodyKind::None => Vec::new(),
+            BodyKind::Length(len) => {
+                while self.buf.len() < len {
+                    self.fill()?;
+                }
+                self.buf.drain(..len).collect()
+            }
+            BodyKind::Chunked => {
+                let mut decoder = ChunkedDecoder::new(self.config.max_body_size);
+                loop {
+                    // the decoded data is dropped, not decoded again with the rest
+                    let (len, body) = decoder.decode(&self.buf)?;
+                    self.buf.drain(..len);
+                    if let Some(body) = body {
+                        break body;
+                    }
+                    self.fill()?;
+                }
+            }
+        };
+        Ok(Some(Request::new(head, body, self.peer_addr)))
+    }
+
+    fn serve(&mut self, handler: &dyn Handler) -> io::Result<()> {
+        self.stream
+            .set_read_timeout(self.config.keep_alive_timeout)?;
+        loop {
+            let req = match self.read_request() {
+                Ok(Some(req)) => req,
+                Ok(None) | Err(ReadError::Eof) => return Ok(()),
+                Err(ReadError::Io(e)) => return Err(e),
+                Err(ReadError::Parse(e)) => {
+                    // the rest of the stream can't be parsed, close it
+                    Response::error(e.status()).write_to(
+                        &mut self.stream,
+                        Version::Http11,
+                        false,
+                        false,
+                    )?;
+                    return Ok(());
+                }
+            };
+            let version = req.version();
+            let keep_alive = req.keep_alive();
+            let head_only = req.method() == Method::Head;
+            let resp = handler.handle(req);
+            if !resp.write_to(&mut self.stream, version, keep_alive, head_only)? {
+                return Ok(());
+            }
+        }
+    }
+}
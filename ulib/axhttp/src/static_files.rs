@@ -0,0 +1,112 @@
+use alloc::format;
+use alloc::string::String;
+use std::fs::{self, File};
+
+use crate::request::{Method, Request, percent_decode};
+use crate::response::{Response, StatusCode};
+use crate::router::Handler;
+
+/// A handler serving the files in a directory.
+///
+/// The path of the file is the `*` parameter of the route if any (e.g. the
+/// route is `/static/*`), otherwise it's the whole request path. Paths with
+/// `..` are rejected. For directories, the index file is served, and requests
+/// without a trailing `/` are redirected to the path with it.
+pub struct StaticFiles {
+    root: String,
+    index: Option<String>,
+}
+
+impl StaticFiles {
+    /// Creates a handler serving the files under `root`, with `index.html` as
+    /// the index file.
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: root.trim_end_matches('/').into(),
+            index: Some("index.html".into()),
+        }
+    }
+
+    /// Sets the index file of directories, or `None` to respond `404 Not
+    /// Found` to directories.
+    pub fn index_file(mut self, name: Option<&str>) -> Self {
+        self.index = name.map(Into::into);
+        self
+    }
+
+    /// Maps a relative path to a file path under the root.
+    fn file_path(&self, rel: &str) -> Option<String> {
+        let mut path = self.root.clone();
+        for comp in rel.split('/') {
+            match comp {
+                "" | "." => {}
+                ".." => return None,
+                comp => {
+                    path.push('/');
+                    path.push_str(comp);
+                }
+            }
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        Some(path)
+    }
+}
+
+/// Guesses the MIME type from the file extension.
+fn content_type(path: &str) -> &'static str {
+    let ext = path.rsplit_once('.').map_or("", |(_, ext)| ext);
+    match ext.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+impl Handler for StaticFiles {
+    fn handle(&self, req: Request) -> Response {
+        if !matches!(req.method(), Method::Get | Method::Head) {
+            return Response::error(StatusCode::METHOD_NOT_ALLOWED)
+                .with_header("Allow", "GET, HEAD");
+        }
+        let rel = match req.param("*") {
+            Some(rel) => Some(rel.into()),
+            None => percent_decode(req.path(), false),
+        };
+        let Some(mut path) = rel.and_then(|rel| self.file_path(&rel)) else {
+            return Response::error(StatusCode::FORBIDDEN);
+        };
+
+        let Ok(metadata) = fs::metadata(&path) else {
+            return Response::error(StatusCode::NOT_FOUND);
+        };
+        if metadata.is_dir() {
+            let Some(index) = &self.index else {
+                return Response::error(StatusCode::NOT_FOUND);
+            };
+            if !req.path().ends_with('/') {
+                // so that relative links in the index file work
+                return Response::redirect(&format!("{}/", req.path()));
+            }
+            path = format!("{}/{}", path.trim_end_matches('/'), index);
+        }
+
+        let Ok(file) = File::open(&path) else {
+            return Response::error(StatusCode::NOT_FOUND);
+        };
+        let len = file.metadata().map(|m| m.len()).ok();
+        Response::reader(content_type(&path), file, len)
+    }
+}
@@ -54,20 +54,6 @@ int usleep(unsigned useconds)
 
 #ifdef AX_CONFIG_FS
 
-// TODO:
-int access(const char *pathname, int mode)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-ssize_t readlink(const char *path, char *buf, size_t bufsiz)
-{
-    unimplemented();
-    return 0;
-}
-
 // TODO:
 int unlink(const char *pathname)
 {
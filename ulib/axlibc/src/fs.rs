@@ -1,7 +1,9 @@
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_ulong, c_void};
 
 use arceos_posix_api::{
-    sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat,
+    sys_access, sys_chmod, sys_chown, sys_fchmod, sys_flock, sys_fstat, sys_futimens, sys_getcwd,
+    sys_lseek, sys_lstat, sys_mkdir, sys_mount, sys_open, sys_readlink, sys_rename, sys_stat,
+    sys_symlink, sys_umask, sys_umount2, sys_utimensat,
 };
 
 use crate::{ctypes, utils::e};
@@ -51,6 +53,107 @@ pub unsafe extern "C" fn lstat(path: *const c_char, buf: *mut ctypes::stat) -> c
     e(sys_lstat(path, buf) as _)
 }
 
+/// Check whether the current user can access the file `path` with `mode`.
+///
+/// Return 0 if the access is allowed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn access(path: *const c_char, mode: c_int) -> c_int {
+    e(sys_access(path, mode))
+}
+
+/// Change the permissions of the file `path`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chmod(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_chmod(path, mode))
+}
+
+/// Change the permissions of the file opened as `fd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    e(sys_fchmod(fd, mode))
+}
+
+/// Change the owner and group of the file `path`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chown(
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+) -> c_int {
+    e(sys_chown(path, owner, group))
+}
+
+/// Change the access and modification times of the file `path` relative to
+/// the directory `dirfd`, to the current time if `times` is null.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    e(sys_utimensat(dirfd, path, times, flags))
+}
+
+/// Change the access and modification times of the file opened as `fd`, to
+/// the current time if `times` is null.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn futimens(fd: c_int, times: *const ctypes::timespec) -> c_int {
+    e(sys_futimens(fd, times))
+}
+
+/// Apply or remove a whole-file advisory lock on the file opened as `fd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn flock(fd: c_int, operation: c_int) -> c_int {
+    e(sys_flock(fd, operation))
+}
+
+/// Create a directory `path` with the permissions `mode`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mkdir(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_mkdir(path, mode))
+}
+
+/// Set the file mode creation mask, and return the previous one.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn umask(mask: ctypes::mode_t) -> ctypes::mode_t {
+    sys_umask(mask)
+}
+
+/// Create a symbolic link `linkpath` pointing to `target`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    e(sys_symlink(target, linkpath))
+}
+
+/// Read the target of the symbolic link `path` into `buf`.
+///
+/// Return the number of bytes placed in `buf`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: usize,
+) -> ctypes::ssize_t {
+    e(sys_readlink(path, buf, bufsiz) as _) as _
+}
+
 /// Get the path of the current directory.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
@@ -65,3 +168,33 @@ pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
 pub unsafe extern "C" fn rename(old: *const c_char, new: *const c_char) -> c_int {
     e(sys_rename(old, new))
 }
+
+/// Mount the filesystem `fstype` on the directory `target`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: c_ulong,
+    data: *const c_void,
+) -> c_int {
+    e(sys_mount(source, target, fstype, flags, data))
+}
+
+/// Unmount the filesystem mounted on `target`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn umount(target: *const c_char) -> c_int {
+    e(sys_umount2(target, 0))
+}
+
+/// Unmount the filesystem mounted on `target` with `flags`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn umount2(target: *const c_char, flags: c_int) -> c_int {
+    e(sys_umount2(target, flags))
+}
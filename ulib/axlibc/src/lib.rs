@@ -99,12 +99,15 @@ pub use self::strftime::strftime;
 pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3};
 
 #[cfg(feature = "fs")]
-pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, rename, stat};
+pub use self::fs::{
+    access, ax_open, chmod, chown, fchmod, flock, fstat, futimens, getcwd, lseek, lstat, mount,
+    readlink, rename, stat, symlink, umask, umount, umount2, utimensat,
+};
 
 #[cfg(feature = "net")]
 pub use self::net::{
     accept, bind, connect, freeaddrinfo, getaddrinfo, getpeername, getsockname, listen, recv,
-    recvfrom, send, sendto, shutdown, socket,
+    recvfrom, send, sendto, shutdown, socket, socketpair,
 };
 
 #[cfg(feature = "multitask")]
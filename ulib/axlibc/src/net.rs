@@ -1,7 +1,7 @@
 use arceos_posix_api::{
     sys_accept, sys_bind, sys_connect, sys_freeaddrinfo, sys_getaddrinfo, sys_getpeername,
-    sys_getsockname, sys_listen, sys_recv, sys_recvfrom, sys_send, sys_sendto, sys_shutdown,
-    sys_socket,
+    sys_getsockname, sys_getsockopt, sys_listen, sys_recv, sys_recvfrom, sys_send, sys_sendto,
+    sys_setsockopt, sys_shutdown, sys_socket, sys_socketpair,
 };
 use core::ffi::{c_char, c_int, c_void};
 
@@ -15,6 +15,19 @@ pub unsafe extern "C" fn socket(domain: c_int, socktype: c_int, protocol: c_int)
     e(sys_socket(domain, socktype, protocol))
 }
 
+/// Create a pair of connected sockets.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn socketpair(
+    domain: c_int,
+    socktype: c_int,
+    protocol: c_int,
+    sv: *mut c_int,
+) -> c_int {
+    e(sys_socketpair(domain, socktype, protocol, sv))
+}
+
 /// Bind a address to a socket.
 ///
 /// Return 0 if success.
@@ -135,6 +148,34 @@ pub unsafe extern "C" fn shutdown(
     e(sys_shutdown(socket_fd, flag))
 }
 
+/// Set options on a socket.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn setsockopt(
+    socket_fd: c_int,
+    level: c_int,
+    optname: c_int,
+    optval: *const c_void,
+    optlen: ctypes::socklen_t,
+) -> c_int {
+    e(sys_setsockopt(socket_fd, level, optname, optval, optlen))
+}
+
+/// Get options on a socket.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getsockopt(
+    socket_fd: c_int,
+    level: c_int,
+    optname: c_int,
+    optval: *mut c_void,
+    optlen: *mut ctypes::socklen_t,
+) -> c_int {
+    e(sys_getsockopt(socket_fd, level, optname, optval, optlen))
+}
+
 /// Query addresses for a domain name.
 ///
 /// Return address number if success.
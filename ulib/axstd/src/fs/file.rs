@@ -1,4 +1,5 @@
 use crate::io::{Result, SeekFrom, prelude::*};
+use crate::time::{SystemTime, UNIX_EPOCH};
 use core::fmt;
 
 use arceos_api::fs as api;
@@ -16,7 +17,7 @@ pub struct File {
 }
 
 /// Metadata information about a file.
-pub struct Metadata(api::AxFileAttr);
+pub struct Metadata(pub(super) api::AxFileAttr, pub(super) api::AxFileTimes);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -90,6 +91,12 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symbolic link, which is only
+    /// possible if it's returned by [`symlink_metadata`](super::symlink_metadata).
+    pub const fn is_symlink(&self) -> bool {
+        matches!(self.0.file_type(), FileType::SymLink)
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -110,6 +117,19 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the last modification time listed in this metadata.
+    pub fn modified(&self) -> Result<SystemTime> {
+        Ok(UNIX_EPOCH + self.1.modified)
+    }
+
+    /// Returns the last access time of this metadata.
+    ///
+    /// Like the `relatime` option of Linux, it's updated on reads only if
+    /// it's earlier than the modification time or a day old.
+    pub fn accessed(&self) -> Result<SystemTime> {
+        Ok(UNIX_EPOCH + self.1.accessed)
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -119,6 +139,7 @@ impl fmt::Debug for Metadata {
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
             .field("permissions", &self.permissions())
+            .field("modified", &self.modified())
             .finish_non_exhaustive()
     }
 }
@@ -160,7 +181,13 @@ impl File {
 
     /// Queries metadata about the underlying file.
     pub fn metadata(&self) -> Result<Metadata> {
-        api::ax_file_attr(&self.inner).map(Metadata)
+        let attr = api::ax_file_attr(&self.inner)?;
+        Ok(Metadata(attr, api::ax_file_times(&self.inner)?))
+    }
+
+    /// Changes the permissions on the underlying file.
+    pub fn set_permissions(&self, perm: Permissions) -> Result<()> {
+        api::ax_set_file_perm(&self.inner, perm)
     }
 }
 
@@ -32,6 +32,10 @@
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
 //!     - `net`: Enable networking support.
 //!     - `dns`: Enable DNS lookup support.
+//!     - `net-capture`: Enable packet capture (pcap) on the NIC.
+//!     - `net-tls`: Enable TLS support over TCP streams.
+//!     - `net-tls-getrandom`: Register the kernel random number generator as the
+//!       source of `getrandom`, which is used by the TLS crypto provider.
 //!     - `display`: Enable graphics support.
 //! - Device drivers
 //!     - `bus-mmio`: Use device tree to probe all MMIO devices.
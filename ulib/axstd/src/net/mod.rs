@@ -7,6 +7,7 @@
 //!
 //! * [`TcpListener`] and [`TcpStream`] provide functionality for communication over TCP
 //! * [`UdpSocket`] provides functionality for communication over UDP
+//! * [`TlsStream`] provides TLS 1.3 over a [`TcpStream`] (with the `net-tls` feature)
 //! * [`IpAddr`] represents IP addresses of either IPv4 or IPv6; [`Ipv4Addr`] and
 //!   [`Ipv6Addr`] are respectively IPv4 and IPv6 addresses
 //! * [`SocketAddr`] represents socket addresses of either IPv4 or IPv6; [`SocketAddrV4`]
@@ -18,11 +19,17 @@ mod socket_addr;
 mod tcp;
 mod udp;
 
+#[cfg(feature = "net-tls")]
+pub mod tls;
+
 pub use self::socket_addr::{IpAddr, Ipv4Addr, Ipv6Addr};
 pub use self::socket_addr::{SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
 pub use self::tcp::{TcpListener, TcpStream};
 pub use self::udp::UdpSocket;
 
+#[cfg(feature = "net-tls")]
+pub use self::tls::TlsStream;
+
 use crate::io;
 
 fn each_addr<A: ToSocketAddrs, F, T>(addr: A, mut f: F) -> io::Result<T>
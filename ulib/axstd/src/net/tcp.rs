@@ -1,5 +1,6 @@
 use super::{SocketAddr, ToSocketAddrs};
 use crate::io::{self, prelude::*};
+use core::time::Duration;
 
 use arceos_api::net::{self as api, AxTcpSocketHandle};
 
@@ -43,6 +44,81 @@ impl TcpStream {
     pub fn shutdown(&self) -> io::Result<()> {
         api::ax_tcp_shutdown(&self.0)
     }
+
+    /// Sets the read timeout to the timeout specified.
+    ///
+    /// If the value specified is [`None`], then [`read`] calls will block
+    /// indefinitely. An [`Err`] is returned if the zero [`Duration`] is
+    /// passed to this method.
+    ///
+    /// [`read`]: Read::read
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        api::ax_tcp_set_recv_timeout(&self.0, dur)
+    }
+
+    /// Returns the read timeout of this socket.
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        api::ax_tcp_recv_timeout(&self.0)
+    }
+
+    /// Sets the write timeout to the timeout specified.
+    ///
+    /// If the value specified is [`None`], then [`write`] calls will block
+    /// indefinitely. An [`Err`] is returned if the zero [`Duration`] is
+    /// passed to this method.
+    ///
+    /// [`write`]: Write::write
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        api::ax_tcp_set_send_timeout(&self.0, dur)
+    }
+
+    /// Returns the write timeout of this socket.
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        api::ax_tcp_send_timeout(&self.0)
+    }
+
+    /// Sets the value of the `TCP_NODELAY` option on this socket.
+    ///
+    /// If set, this option disables the Nagle algorithm. This means that
+    /// segments are always sent as soon as possible, even if there is only a
+    /// small amount of data.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        api::ax_tcp_set_nodelay(&self.0, nodelay)
+    }
+
+    /// Gets the value of the `TCP_NODELAY` option on this socket.
+    pub fn nodelay(&self) -> io::Result<bool> {
+        api::ax_tcp_nodelay(&self.0)
+    }
+
+    /// Enables TCP keep-alive (`SO_KEEPALIVE`) with the given interval, or
+    /// disables it if `None`.
+    ///
+    /// Keep-alive packets are sent after the connection is idle for the
+    /// interval.
+    pub fn set_keep_alive(&self, interval: Option<Duration>) -> io::Result<()> {
+        api::ax_tcp_set_keep_alive(&self.0, interval)
+    }
+
+    /// Gets the keep-alive interval of this socket, or `None` if keep-alive is
+    /// disabled.
+    pub fn keep_alive(&self) -> io::Result<Option<Duration>> {
+        api::ax_tcp_keep_alive(&self.0)
+    }
+
+    /// Sets the value of the `SO_LINGER` option on this socket.
+    ///
+    /// If set, [`shutdown`](Self::shutdown) blocks until the data sent is
+    /// acknowledged or the timeout expires. A zero timeout resets the
+    /// connection instead.
+    pub fn set_linger(&self, linger: Option<Duration>) -> io::Result<()> {
+        api::ax_tcp_set_linger(&self.0, linger)
+    }
+
+    /// Gets the value of the `SO_LINGER` option on this socket.
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        api::ax_tcp_linger(&self.0)
+    }
 }
 
 impl Read for TcpStream {
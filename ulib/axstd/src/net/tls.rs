@@ -0,0 +1,534 @@
+//! TLS 1.3 streams over TCP, based on [rustls] with the [ring] crypto
+//! provider.
+//!
+//! The cryptographic randomness comes from the kernel random number generator,
+//! and the certificate validity is checked against the wall clock time.
+//!
+//! [ring] generates the ephemeral keys with [getrandom], which has no source
+//! of randomness on bare metal. Enable the `net-tls-getrandom` feature to use
+//! the kernel random number generator, or register a custom one in the
+//! application with `getrandom::register_custom_getrandom!`.
+//!
+//! [rustls]: https://docs.rs/rustls
+//! [ring]: https://docs.rs/ring
+//! [getrandom]: https://docs.rs/getrandom
+
+use alloc::{collections::VecDeque, sync::Arc, vec, vec::Vec};
+
+use arceos_api::{sys::ax_fill_random, time::ax_wall_time};
+use axerrno::{AxError, ax_err, ax_err_type};
+use rustls::client::UnbufferedClientConnection;
+use rustls::crypto::{CryptoProvider, GetRandomFailed, SecureRandom};
+use rustls::pki_types::{ServerName, UnixTime};
+use rustls::server::UnbufferedServerConnection;
+use rustls::time_provider::TimeProvider;
+use rustls::unbuffered::{
+    AppDataRecord, ConnectionState, EncodeError, EncryptError, InsufficientSizeError,
+    UnbufferedStatus,
+};
+use rustls::{RootCertStore, version};
+
+use super::TcpStream;
+use crate::io::{self, prelude::*};
+
+pub use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+pub use rustls::{ClientConfig, ServerConfig};
+
+/// The initial size of the buffers for TLS records.
+///
+/// It's large enough to hold a full TLS record (16 KiB payload and overhead).
+const TLS_BUF_LEN: usize = 0x4800;
+
+#[derive(Debug)]
+struct AxSecureRandom;
+
+impl SecureRandom for AxSecureRandom {
+    fn fill(&self, buf: &mut [u8]) -> Result<(), GetRandomFailed> {
+        ax_fill_random(buf);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct AxTimeProvider;
+
+impl TimeProvider for AxTimeProvider {
+    fn current_time(&self) -> Option<UnixTime> {
+        Some(UnixTime::since_unix_epoch(ax_wall_time()))
+    }
+}
+
+// The crypto backend generates ephemeral keys with `getrandom`.
+#[cfg(feature = "net-tls-getrandom")]
+fn ax_getrandom(buf: &mut [u8]) -> Result<(), getrandom::Error> {
+    ax_fill_random(buf);
+    Ok(())
+}
+
+#[cfg(feature = "net-tls-getrandom")]
+getrandom::register_custom_getrandom!(ax_getrandom);
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(CryptoProvider {
+        secure_random: &AxSecureRandom,
+        ..rustls::crypto::ring::default_provider()
+    })
+}
+
+fn tls_err(_e: rustls::Error) -> AxError {
+    AxError::InvalidData
+}
+
+/// Creates a client configuration that trusts the given root certificates.
+pub fn client_config(root_certs: Vec<CertificateDer<'static>>) -> io::Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+    for cert in root_certs {
+        roots.add(cert).map_err(tls_err)?;
+    }
+    let config = ClientConfig::builder_with_details(crypto_provider(), Arc::new(AxTimeProvider))
+        .with_protocol_versions(&[&version::TLS13])
+        .map_err(tls_err)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// Creates a server configuration with the given certificate chain and its
+/// private key.
+pub fn server_config(
+    cert_chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> io::Result<Arc<ServerConfig>> {
+    let config = ServerConfig::builder_with_details(crypto_provider(), Arc::new(AxTimeProvider))
+        .with_protocol_versions(&[&version::TLS13])
+        .map_err(tls_err)?
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, key)
+        .map_err(tls_err)?;
+    Ok(Arc::new(config))
+}
+
+/// Loads all certificates from a PEM file.
+#[cfg(feature = "fs")]
+pub fn load_certs(path: &str) -> io::Result<Vec<CertificateDer<'static>>> {
+    use rustls::pki_types::pem::PemObject;
+
+    let pem = crate::fs::read(path)?;
+    let certs = CertificateDer::pem_slice_iter(&pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ax_err_type!(InvalidData, "invalid PEM certificate"))?;
+    if certs.is_empty() {
+        return ax_err!(InvalidData, "no certificates found");
+    }
+    Ok(certs)
+}
+
+/// Loads the first private key from a PEM file.
+#[cfg(feature = "fs")]
+pub fn load_private_key(path: &str) -> io::Result<PrivateKeyDer<'static>> {
+    use rustls::pki_types::pem::PemObject;
+
+    let pem = crate::fs::read(path)?;
+    PrivateKeyDer::from_pem_slice(&pem)
+        .map_err(|_| ax_err_type!(InvalidData, "invalid PEM private key"))
+}
+
+enum Connection {
+    Client(UnbufferedClientConnection),
+    Server(UnbufferedServerConnection),
+}
+
+#[derive(Clone, Copy)]
+enum Goal<'a> {
+    Handshake,
+    Read,
+    Write(&'a [u8]),
+    Close,
+}
+
+enum Progress {
+    /// Call `process_tls_records` again.
+    Continue,
+    /// More TLS records from the peer are required.
+    NeedInput,
+    /// The goal is reached, with the number of bytes written.
+    Done(usize),
+    /// The peer has sent `close_notify`.
+    PeerClosed,
+    /// The connection is closed.
+    Closed,
+}
+
+/// Buffers that are not borrowed by the connection state.
+struct Buffers {
+    outgoing: Vec<u8>,
+    outgoing_len: usize,
+    plaintext: VecDeque<u8>,
+}
+
+impl Buffers {
+    /// Appends TLS records to the outgoing buffer by `f`, growing the buffer
+    /// if it's not large enough.
+    fn encode_with<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+        required_size: impl Fn(&E) -> Option<usize>,
+    ) -> io::Result<()> {
+        loop {
+            match f(&mut self.outgoing[self.outgoing_len..]) {
+                Ok(n) => {
+                    self.outgoing_len += n;
+                    return Ok(());
+                }
+                Err(e) => match required_size(&e) {
+                    Some(size) => self.outgoing.resize(self.outgoing_len + size, 0),
+                    None => return Err(AxError::InvalidData),
+                },
+            }
+        }
+    }
+
+    fn transmit(&mut self, stream: &mut impl Write) -> io::Result<()> {
+        stream.write_all(&self.outgoing[..self.outgoing_len])?;
+        self.outgoing_len = 0;
+        Ok(())
+    }
+}
+
+/// Advances the connection by one state, returns the number of bytes to be
+/// discarded from the incoming buffer and the progress.
+fn advance<Data>(
+    status: UnbufferedStatus<'_, '_, Data>,
+    bufs: &mut Buffers,
+    stream: &mut impl Write,
+    goal: Goal<'_>,
+) -> io::Result<(usize, Progress)> {
+    let UnbufferedStatus { mut discard, state } = status;
+    let progress = match state.map_err(tls_err)? {
+        ConnectionState::ReadTraffic(mut state) => {
+            while let Some(record) = state.next_record() {
+                let AppDataRecord {
+                    discard: new_discard,
+                    payload,
+                } = record.map_err(tls_err)?;
+                discard += new_discard;
+                bufs.plaintext.extend(payload);
+            }
+            Progress::Continue
+        }
+        ConnectionState::EncodeTlsData(mut state) => {
+            bufs.encode_with(
+                |buf| state.encode(buf),
+                |e| match e {
+                    EncodeError::InsufficientSize(InsufficientSizeError { required_size }) => {
+                        Some(*required_size)
+                    }
+                    _ => None,
+                },
+            )?;
+            Progress::Continue
+        }
+        ConnectionState::TransmitTlsData(state) => {
+            bufs.transmit(stream)?;
+            state.done();
+            Progress::Continue
+        }
+        ConnectionState::BlockedHandshake => Progress::NeedInput,
+        ConnectionState::WriteTraffic(mut may_encrypt) => {
+            let required_size = |e: &EncryptError| match e {
+                EncryptError::InsufficientSize(InsufficientSizeError { required_size }) => {
+                    Some(*required_size)
+                }
+                _ => None,
+            };
+            match goal {
+                Goal::Handshake => Progress::Done(0),
+                Goal::Read if bufs.plaintext.is_empty() => Progress::NeedInput,
+                Goal::Read => Progress::Done(0),
+                Goal::Write(data) => {
+                    bufs.encode_with(|buf| may_encrypt.encrypt(data, buf), required_size)?;
+                    bufs.transmit(stream)?;
+                    Progress::Done(data.len())
+                }
+                Goal::Close => {
+                    bufs.encode_with(|buf| may_encrypt.queue_close_notify(buf), required_size)?;
+                    bufs.transmit(stream)?;
+                    Progress::Done(0)
+                }
+            }
+        }
+        ConnectionState::PeerClosed => Progress::PeerClosed,
+        ConnectionState::Closed => Progress::Closed,
+        // early data is not enabled
+        _ => return ax_err!(InvalidData, "unexpected TLS connection state"),
+    };
+    Ok((discard, progress))
+}
+
+/// A TLS stream over a [`TcpStream`], or another reliable byte stream.
+///
+/// Only TLS 1.3 is supported. Use [`client_config`] or [`server_config`] to
+/// create the configuration, and [`load_certs`] to load the certificates from
+/// the file system.
+///
+/// Reading returns [`UnexpectedEof`](AxError::UnexpectedEof) if the
+/// connection is closed without `close_notify`, since the data may have been
+/// truncated.
+pub struct TlsStream<S = TcpStream> {
+    stream: S,
+    conn: Connection,
+    incoming: Vec<u8>,
+    incoming_len: usize,
+    bufs: Buffers,
+}
+
+impl<S: Read + Write> TlsStream<S> {
+    fn new(stream: S, conn: Connection) -> Self {
+        Self {
+            stream,
+            conn,
+            incoming: vec![0; TLS_BUF_LEN],
+            incoming_len: 0,
+            bufs: Buffers {
+                outgoing: vec![0; TLS_BUF_LEN],
+                outgoing_len: 0,
+                plaintext: VecDeque::new(),
+            },
+        }
+    }
+
+    /// Performs the TLS handshake as a client on a connected stream.
+    ///
+    /// The certificate of the server is verified against `server_name`.
+    pub fn connect(stream: S, server_name: &str, config: Arc<ClientConfig>) -> io::Result<Self> {
+        let server_name = ServerName::try_from(server_name)
+            .map_err(|_| ax_err_type!(InvalidInput, "invalid server name"))?
+            .to_owned();
+        let conn = UnbufferedClientConnection::new(config, server_name).map_err(tls_err)?;
+        let mut tls = Self::new(stream, Connection::Client(conn));
+        tls.drive(Goal::Handshake)?;
+        Ok(tls)
+    }
+
+    /// Performs the TLS handshake as a server on an accepted stream.
+    pub fn accept(stream: S, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let conn = UnbufferedServerConnection::new(config).map_err(tls_err)?;
+        let mut tls = Self::new(stream, Connection::Server(conn));
+        tls.drive(Goal::Handshake)?;
+        Ok(tls)
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Sends `close_notify` to the peer, the underlying stream is kept open.
+    pub fn close_notify(&mut self) -> io::Result<()> {
+        self.drive(Goal::Close).map(|_| ())
+    }
+
+    /// Drives the connection until the goal is reached.
+    fn drive(&mut self, goal: Goal<'_>) -> io::Result<usize> {
+        loop {
+            if matches!(goal, Goal::Read) && !self.bufs.plaintext.is_empty() {
+                return Ok(0);
+            }
+
+            let incoming = &mut self.incoming[..self.incoming_len];
+            let (discard, progress) = match &mut self.conn {
+                Connection::Client(conn) => advance(
+                    conn.process_tls_records(incoming),
+                    &mut self.bufs,
+                    &mut self.stream,
+                    goal,
+                )?,
+                Connection::Server(conn) => advance(
+                    conn.process_tls_records(incoming),
+                    &mut self.bufs,
+                    &mut self.stream,
+                    goal,
+                )?,
+            };
+            if discard > 0 {
+                self.incoming.copy_within(discard..self.incoming_len, 0);
+                self.incoming_len -= discard;
+            }
+
+            match progress {
+                Progress::Continue => {}
+                Progress::NeedInput => {
+                    if self.incoming_len == self.incoming.len() {
+                        self.incoming.resize(self.incoming_len * 2, 0);
+                    }
+                    let n = self.stream.read(&mut self.incoming[self.incoming_len..])?;
+                    if n == 0 {
+                        // the connection is closed without `close_notify`
+                        return match goal {
+                            Goal::Close => Ok(0),
+                            _ => Err(AxError::UnexpectedEof),
+                        };
+                    }
+                    self.incoming_len += n;
+                }
+                Progress::Done(n) => return Ok(n),
+                Progress::PeerClosed | Progress::Closed => {
+                    return match goal {
+                        Goal::Read | Goal::Close => Ok(0),
+                        Goal::Handshake => Err(AxError::ConnectionReset),
+                        Goal::Write(_) => Err(AxError::NotConnected),
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl TlsStream<TcpStream> {
+    /// Sends `close_notify` to the peer and shuts down the TCP connection.
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        self.close_notify()?;
+        self.stream.shutdown()
+    }
+}
+
+impl<S: Read + Write> Read for TlsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.drive(Goal::Read)?;
+        let n = buf.len().min(self.bufs.plaintext.len());
+        for (dst, src) in buf.iter_mut().zip(self.bufs.plaintext.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl<S: Read + Write> Write for TlsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.drive(Goal::Write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // records are transmitted as soon as they are encrypted
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Condvar, Mutex};
+    use std::thread::{self, JoinHandle};
+
+    use rustls::pki_types::pem::PemObject;
+
+    use super::*;
+
+    /// One direction of an in-memory connection, with the data and whether
+    /// the writer is closed.
+    #[derive(Default)]
+    struct Pipe {
+        state: Mutex<(VecDeque<u8>, bool)>,
+        cond: Condvar,
+    }
+
+    /// An end of an in-memory connection, which is closed when dropped.
+    struct End {
+        rx: Arc<Pipe>,
+        tx: Arc<Pipe>,
+    }
+
+    fn pair() -> (End, End) {
+        let (a, b) = (Arc::new(Pipe::default()), Arc::new(Pipe::default()));
+        let a_end = End {
+            rx: a.clone(),
+            tx: b.clone(),
+        };
+        (a_end, End { rx: b, tx: a })
+    }
+
+    impl Read for End {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut state = self.rx.state.lock().unwrap();
+            while state.0.is_empty() && !state.1 {
+                state = self.rx.cond.wait(state).unwrap();
+            }
+            let n = buf.len().min(state.0.len());
+            for (dst, src) in buf.iter_mut().zip(state.0.drain(..n)) {
+                *dst = src;
+            }
+            Ok(n)
+        }
+    }
+
+    impl Write for End {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.tx.state.lock().unwrap().0.extend(buf);
+            self.tx.cond.notify_all();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Drop for End {
+        fn drop(&mut self) {
+            self.tx.state.lock().unwrap().1 = true;
+            self.tx.cond.notify_all();
+        }
+    }
+
+    /// Connects to a server running `server` in another thread.
+    fn connect(
+        server: impl FnOnce(TlsStream<End>) + Send + 'static,
+    ) -> (TlsStream<End>, JoinHandle<()>) {
+        let ca = CertificateDer::from_pem_slice(include_bytes!("../../resources/tls/ca.pem"));
+        let cert = CertificateDer::from_pem_slice(include_bytes!("../../resources/tls/server.pem"));
+        let key = PrivateKeyDer::from_pem_slice(include_bytes!("../../resources/tls/server.key"));
+        let client_config = client_config(vec![ca.unwrap()]).unwrap();
+        let server_config = server_config(vec![cert.unwrap()], key.unwrap()).unwrap();
+
+        let (client, server_end) = pair();
+        let server = thread::spawn(move || {
+            server(TlsStream::accept(server_end, server_config).unwrap());
+        });
+        let stream = TlsStream::connect(client, "localhost", client_config).unwrap();
+        (stream, server)
+    }
+
+    #[test]
+    fn close_notify() {
+        let (mut stream, server) = connect(|mut stream| {
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"ping");
+            stream.write_all(b"pong").unwrap();
+            stream.close_notify().unwrap();
+        });
+        stream.write_all(b"ping").unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"pong");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn truncated() {
+        let (mut stream, server) = connect(|mut stream| {
+            // closed without `close_notify`
+            stream.write_all(b"pong").unwrap();
+        });
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"pong");
+        assert_eq!(stream.read(&mut buf).err(), Some(AxError::UnexpectedEof));
+        server.join().unwrap();
+    }
+}
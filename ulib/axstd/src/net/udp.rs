@@ -1,5 +1,6 @@
 use super::{SocketAddr, ToSocketAddrs};
 use crate::io;
+use core::time::Duration;
 
 use arceos_api::net::{self as api, AxUdpSocketHandle};
 
@@ -35,6 +36,34 @@ impl UdpSocket {
         api::ax_udp_peer_addr(&self.0)
     }
 
+    /// Sets the read timeout to the timeout specified.
+    ///
+    /// If the value specified is [`None`], then receiving calls will block
+    /// indefinitely. An [`Err`] is returned if the zero [`Duration`] is
+    /// passed to this method.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        api::ax_udp_set_recv_timeout(&self.0, dur)
+    }
+
+    /// Returns the read timeout of this socket.
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        api::ax_udp_recv_timeout(&self.0)
+    }
+
+    /// Sets the write timeout to the timeout specified.
+    ///
+    /// If the value specified is [`None`], then sending calls will block
+    /// indefinitely. An [`Err`] is returned if the zero [`Duration`] is
+    /// passed to this method.
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        api::ax_udp_set_send_timeout(&self.0, dur)
+    }
+
+    /// Returns the write timeout of this socket.
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        api::ax_udp_send_timeout(&self.0)
+    }
+
     /// Receives a single datagram message on the socket. On success, returns
     /// the number of bytes read and the origin.
     pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {